@@ -1,3 +1,5 @@
+import datetime
+import decimal
 import enum
 from typing import Any
 from fastapi import FastAPI, HTTPException, status
@@ -26,7 +28,9 @@ async def healthcheck():
     return "OK"
 
 
-def execute_sql(sql: str) -> list[Any]:
+def execute_sql(
+    sql: str, binds: dict[str, Any]
+) -> tuple[list[str], list[int], list[Any]]:
     with oracledb.connect(
         user=settings.USERNAME,
         password=settings.PASSWORD,
@@ -34,8 +38,25 @@ def execute_sql(sql: str) -> list[Any]:
         mode=oracledb.SYSDBA,
     ) as conn:
         with conn.cursor() as cursor:
-            cursor.execute(sql)
-            return cursor.fetchmany()
+            cursor.execute(sql, binds)
+            columns = [column[0] for column in cursor.description]
+            # Found by the column type, any value of the column can be NULL
+            geometry_columns = [
+                n
+                for n, column in enumerate(cursor.description)
+                if is_geometry_type(column[1])
+            ]
+            rows = cursor.fetchmany()
+            # LOBs can only be read while the connection is open
+            return (
+                columns,
+                geometry_columns,
+                [tuple(read_lob(value) for value in row) for row in rows],
+            )
+
+
+def read_lob(value: Any) -> Any:
+    return value.read() if isinstance(value, oracledb.LOB) else value
 
 
 class Validation(enum.Enum):
@@ -43,20 +64,29 @@ class Validation(enum.Enum):
     EmptySet = 1
     TooManyElements = 2
     NotGeometryData = 3
+    NullGeometry = 4
+
 
+def is_geometry_type(type_code: Any) -> bool:
+    return (
+        isinstance(type_code, oracledb.DbObjectType)
+        and type_code.name == "SDO_GEOMETRY"
+    )
 
-def validate_data(data: list[Any]) -> Validation:
+
+def validate_data(geometry_columns: list[int], data: list[Any]) -> Validation:
     if len(data) == 0:
         return Validation.EmptySet
 
-    # Check column amount
-    if len(data[0]) > 1:
+    # Exactly one geometry column, the other columns are attributes
+    if len(geometry_columns) == 0:
+        return Validation.NotGeometryData
+
+    if len(geometry_columns) > 1:
         return Validation.TooManyElements
 
-    # Check if it is a geometry object
-    element = data[0][0]
-    if type(element) != oracledb.DbObject or element.type.name != "SDO_GEOMETRY":
-        return Validation.NotGeometryData
+    if any(row[geometry_columns[0]] is None for row in data):
+        return Validation.NullGeometry
 
     return Validation.Ok
 
@@ -67,25 +97,59 @@ class SdoGeometry(BaseModel):
     sdo_point: dict | None
     sdo_elem_info: list[float]
     sdo_ordinates: list[float]
-
-
-def convert_data(data: list[oracledb.DbObject]) -> list[SdoGeometry]:
-    # Get one sdo object from the tuple
-    sdo_objects = [row[0] for row in data]
-    return [
-        SdoGeometry(
-            sdo_gtype=sdo_object.SDO_GTYPE,
-            sdo_srid=sdo_object.SDO_SRID,
-            sdo_point=sdo_object.SDO_POINT,
-            sdo_elem_info=sdo_object.SDO_ELEM_INFO.aslist(),
-            sdo_ordinates=sdo_object.SDO_ORDINATES.aslist(),
+    attributes: dict[str, Any] = {}
+
+
+def convert_point(point: oracledb.DbObject | None) -> dict | None:
+    if point is None:
+        return None
+
+    return {"x": point.X, "y": point.Y, "z": point.Z}
+
+
+def convert_array(array: oracledb.DbObject | None) -> list[float]:
+    # Point geometries store their coordinates in SDO_POINT and leave arrays empty
+    return [] if array is None else array.aslist()
+
+
+def convert_value(value: Any) -> Any:
+    if isinstance(value, decimal.Decimal):
+        return float(value)
+    if isinstance(value, (datetime.date, datetime.datetime)):
+        return value.isoformat()
+    if isinstance(value, bytes):
+        return value.hex()
+    if value is None or isinstance(value, (str, int, float, bool)):
+        return value
+    return str(value)
+
+
+def convert_data(
+    columns: list[str], geometry_column: int, data: list[Any]
+) -> list[SdoGeometry]:
+    result = []
+    for row in data:
+        sdo_object = row[geometry_column]
+        result.append(
+            SdoGeometry(
+                sdo_gtype=sdo_object.SDO_GTYPE,
+                sdo_srid=sdo_object.SDO_SRID,
+                sdo_point=convert_point(sdo_object.SDO_POINT),
+                sdo_elem_info=convert_array(sdo_object.SDO_ELEM_INFO),
+                sdo_ordinates=convert_array(sdo_object.SDO_ORDINATES),
+                attributes={
+                    column: convert_value(value)
+                    for n, (column, value) in enumerate(zip(columns, row))
+                    if n != geometry_column
+                },
+            )
         )
-        for sdo_object in sdo_objects
-    ]
+    return result
 
 
 class GeometryRequest(BaseModel):
     sql: str
+    binds: dict[str, Any] = {}
 
 
 class Model400(BaseModel):
@@ -106,14 +170,14 @@ async def geometry(request: GeometryRequest) -> list[SdoGeometry]:
         )
 
     try:
-        data: list = execute_sql(request.sql)
+        columns, geometry_columns, data = execute_sql(request.sql, request.binds)
     except oracledb.DatabaseError as exc:
         raise HTTPException(
             status_code=status.HTTP_400_BAD_REQUEST,
             detail=f"Database error: {exc}",
         )
 
-    match validate_data(data):
+    match validate_data(geometry_columns, data):
         case Validation.EmptySet:
             raise HTTPException(
                 status_code=status.HTTP_400_BAD_REQUEST,
@@ -122,12 +186,17 @@ async def geometry(request: GeometryRequest) -> list[SdoGeometry]:
         case Validation.TooManyElements:
             raise HTTPException(
                 status_code=status.HTTP_400_BAD_REQUEST,
-                detail="Please, query only one geometry column",
+                detail="Please, query only one SDO_GEOMETRY column",
             )
         case Validation.NotGeometryData:
             raise HTTPException(
                 status_code=status.HTTP_400_BAD_REQUEST,
-                detail="Please, query ony column with SDO_GEOMETRY object",
+                detail="Please, query a column with SDO_GEOMETRY objects",
+            )
+        case Validation.NullGeometry:
+            raise HTTPException(
+                status_code=status.HTTP_400_BAD_REQUEST,
+                detail="Please, filter out rows with a NULL geometry",
             )
 
-    return convert_data(data)
+    return convert_data(columns, geometry_columns[0], data)
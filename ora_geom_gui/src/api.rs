@@ -1,10 +1,15 @@
 use std::{fmt::Display, io};
 
+use chrono::{DateTime, Local};
 use egui::{Color32, RichText};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::sdo_geometry::SdoGeometry;
+use crate::{
+    cache::CacheSettings,
+    feature::{as_number, attribute, Feature},
+    query::Bind,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct ApiBadRequest {
@@ -20,7 +25,7 @@ impl Display for ApiBadRequest {
 #[derive(thiserror::Error, Debug)]
 pub enum GeometryApiError {
     #[error("Failed fetching geometries: {0}")]
-    RequestFailed(#[from] ureq::Error),
+    RequestFailed(Box<ureq::Error>),
     #[error("Failed to convert JSON data")]
     JsonConversionFailed(#[from] io::Error),
     #[error("Url parsing failed")]
@@ -32,6 +37,35 @@ pub enum GeometryApiError {
     AsyncRequestFailed(#[from] reqwest::Error),
 }
 
+impl From<ureq::Error> for GeometryApiError {
+    fn from(err: ureq::Error) -> Self {
+        Self::RequestFailed(Box::new(err))
+    }
+}
+
+impl GeometryApiError {
+    /// Whether the backend could not be reached, rather than rejecting the query
+    pub fn is_unreachable(&self) -> bool {
+        match self {
+            Self::RequestFailed(err) => match &**err {
+                ureq::Error::Transport(transport) => matches!(
+                    transport.kind(),
+                    ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+                ),
+                ureq::Error::Status(status, _) => matches!(status, 502..=504),
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Features of a query, from the backend or from the response cache
+pub struct Fetched {
+    pub features: Vec<Feature>,
+    /// When the cached response was fetched, `None` when it came from the backend
+    pub stale_since: Option<DateTime<Local>>,
+}
+
 pub enum ApiHealth {
     Ok,
     Error(String),
@@ -40,12 +74,21 @@ pub enum ApiHealth {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GeometryApi {
     pub api_url: String,
+    #[serde(default)]
+    pub cache: CacheSettings,
+}
+
+impl Default for GeometryApi {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GeometryApi {
     pub fn new() -> Self {
         Self {
             api_url: String::from("http://localhost:8000"),
+            cache: CacheSettings::default(),
         }
     }
 
@@ -73,25 +116,69 @@ impl GeometryApi {
         }
     }
 
-    pub fn fetch_geometries(&self, sql: &str) -> Result<Vec<SdoGeometry>, GeometryApiError> {
+    /// Fetches features and caches the response, in offline mode the cached
+    /// response is served when the backend cannot be reached
+    pub fn fetch(&self, sql: &str, binds: &[Bind]) -> Result<Fetched, GeometryApiError> {
         let url = self.geometry_url()?;
-        let req = ureq::post(&url);
-        let response = req.send_json(ureq::json!({
-            "sql": sql.replace(";", "")
-        }));
-
-        if response.is_err() {
-            return match response.unwrap_err() {
-                ureq::Error::Status(400, r) => {
-                    let bad_request: ApiBadRequest = r.into_json()?;
-                    return Err(GeometryApiError::BadRequest(bad_request));
+        let request = request_body(sql, binds);
+        let key = cache_key(&url, &request);
+        let cache = self.cache.is_enabled.then(|| self.cache.cache());
+
+        match self.post(&url, &request) {
+            Ok(body) => {
+                let features: Vec<Feature> =
+                    serde_json::from_str(&body).map_err(io::Error::from)?;
+                if let Some(Err(e)) = cache.map(|c| c.put(&key, &body)) {
+                    tracing::error!("Failed caching the response: {}", e);
                 }
-                err @ _ => Err(GeometryApiError::RequestFailed(err)),
-            };
+                Ok(Fetched {
+                    features,
+                    stale_since: None,
+                })
+            }
+            Err(e) if e.is_unreachable() && self.cache.is_offline_mode => {
+                let Some(entry) = cache.and_then(|c| c.get(&key)) else {
+                    return Err(e);
+                };
+                let features: Vec<Feature> =
+                    serde_json::from_str(&entry.body).map_err(io::Error::from)?;
+                Ok(Fetched {
+                    features,
+                    stale_since: Some(entry.fetched_at),
+                })
+            }
+            Err(e) => Err(e),
         }
+    }
+
+    /// EPSG code that `SDO_CS` maps an Oracle SRID to, `None` when there is none
+    pub fn oracle_epsg(&self, srid: u32) -> Result<Option<u32>, GeometryApiError> {
+        let sql = "select sdo_geometry(2001, null, sdo_point_type(0, 0, null), null, null) geom, \
+                   sdo_cs.map_oracle_srid_to_epsg(:srid) epsg from dual";
+        let binds = [Bind {
+            name: ":srid".into(),
+            value: srid.to_string(),
+        }];
+        let features = self.fetch(sql, &binds)?.features;
+        Ok(features
+            .first()
+            .and_then(|f| attribute(&f.attributes, "epsg"))
+            .and_then(as_number)
+            .map(|epsg| epsg as u32))
+    }
+
+    /// Raw JSON the backend answers the request with
+    fn post(&self, url: &str, request: &serde_json::Value) -> Result<String, GeometryApiError> {
+        let response = match ureq::post(url).send_json(request) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(400, r)) => {
+                let bad_request: ApiBadRequest = r.into_json()?;
+                return Err(GeometryApiError::BadRequest(bad_request));
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-        let data: Vec<SdoGeometry> = response?.into_json()?;
-        Ok(data)
+        Ok(response.into_string()?)
     }
 
     pub fn connection_status(&self) -> RichText {
@@ -104,3 +191,89 @@ impl GeometryApi {
         }
     }
 }
+
+/// Identifies a response in the cache by the backend URL, SQL and binds
+fn cache_key(url: &str, request: &serde_json::Value) -> String {
+    serde_json::json!({ "url": url, "request": request }).to_string()
+}
+
+/// Body of a geometry request, binds without a name are left out
+fn request_body(sql: &str, binds: &[Bind]) -> serde_json::Value {
+    let binds = binds
+        .iter()
+        .filter(|b| !b.name.is_empty())
+        .map(|b| {
+            (
+                b.name.trim_start_matches(':').to_string(),
+                b.value.clone().into(),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+    ureq::json!({
+        "sql": sql.replace(";", ""),
+        "binds": binds,
+    })
+}
+
+/// Backend of tests, a local port that refuses connections
+#[cfg(test)]
+pub fn unreachable_url() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{cache_key, request_body, unreachable_url, GeometryApi},
+        cache::CacheSettings,
+        query::Bind,
+    };
+
+    #[test]
+    fn test_offline_mode_serves_cached_response() {
+        let directory = std::env::temp_dir().join(format!("ora_api_cache_{}", std::process::id()));
+        let mut api = GeometryApi {
+            api_url: unreachable_url(),
+            cache: CacheSettings {
+                directory: Some(directory.clone()),
+                ..Default::default()
+            },
+        };
+        let binds = vec![Bind {
+            name: ":class".into(),
+            value: "A".into(),
+        }];
+        let sql = "select geom from roads where class = :class";
+
+        assert!(api.fetch(sql, &binds).is_err());
+
+        let body = r#"[{"sdo_gtype": 2001.0, "sdo_srid": null,
+            "sdo_point": {"x": 1.0, "y": 2.0, "z": null},
+            "sdo_elem_info": [], "sdo_ordinates": []}]"#;
+        let key = cache_key(&api.geometry_url().unwrap(), &request_body(sql, &binds));
+        api.cache.cache().put(&key, body).unwrap();
+
+        let fetched = api.fetch(sql, &binds).unwrap();
+        assert_eq!(fetched.features.len(), 1);
+        assert!(fetched.stale_since.is_some());
+        // Other binds are another request
+        assert!(api.fetch(sql, &[]).is_err());
+
+        // A mistyped URL is reported, not taken for an unreachable backend
+        let mut mistyped = api.clone();
+        mistyped.api_url = "htp://localhost:8000".into();
+        let key = cache_key(
+            &mistyped.geometry_url().unwrap(),
+            &request_body(sql, &binds),
+        );
+        mistyped.cache.cache().put(&key, body).unwrap();
+        let error = mistyped.fetch(sql, &binds).err().unwrap();
+        assert!(!error.is_unreachable());
+
+        api.cache.is_offline_mode = false;
+        assert!(api.fetch(sql, &binds).is_err());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFY_APP;
+
+const CACHE_DIR: &str = "response_cache";
+const MEGABYTE: u64 = 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    #[error("Cache file error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Cache entry is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+fn is_enabled_default() -> bool {
+    true
+}
+
+fn max_megabytes_default() -> u64 {
+    256
+}
+
+/// How responses of the backend are kept on disk
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheSettings {
+    #[serde(default = "is_enabled_default")]
+    pub is_enabled: bool,
+    /// Serves cached responses when the backend cannot be reached
+    #[serde(default = "is_enabled_default")]
+    pub is_offline_mode: bool,
+    /// Oldest responses are removed once the cache grows beyond this
+    #[serde(default = "max_megabytes_default")]
+    pub max_megabytes: u64,
+    /// Directory of the cache, next to the configuration file when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            is_offline_mode: true,
+            max_megabytes: max_megabytes_default(),
+            directory: None,
+        }
+    }
+}
+
+impl CacheSettings {
+    pub fn cache(&self) -> ResponseCache {
+        let directory = self.directory.clone().unwrap_or_else(|| {
+            confy::get_configuration_file_path(CONFY_APP, CACHE_DIR)
+                .map(|path| path.with_extension(""))
+                .unwrap_or_else(|_| std::env::temp_dir().join(CONFY_APP).join(CACHE_DIR))
+        });
+        ResponseCache::new(directory, self.max_megabytes * MEGABYTE)
+    }
+}
+
+/// A raw response with the request it answers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    /// Backend URL, SQL and binds of the request
+    pub key: String,
+    pub fetched_at: DateTime<Local>,
+    pub body: String,
+}
+
+/// FNV-1a, unlike `DefaultHasher` it gives the same file names in every build
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Raw JSON responses stored as one file per request, the oldest go first when it is full
+pub struct ResponseCache {
+    directory: PathBuf,
+    max_bytes: u64,
+}
+
+impl ResponseCache {
+    pub fn new(directory: impl AsRef<Path>, max_bytes: u64) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            max_bytes,
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.json", fnv1a(key)))
+    }
+
+    /// Cached response of the request, `None` when it was never stored
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        let text = fs::read_to_string(self.path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&text).ok()?;
+        // Another request with the same hash
+        (entry.key == key).then_some(entry)
+    }
+
+    /// Stores the response and removes the oldest ones beyond the size limit
+    pub fn put(&self, key: &str, body: &str) -> Result<(), CacheError> {
+        fs::create_dir_all(&self.directory)?;
+        let entry = CacheEntry {
+            key: key.to_string(),
+            fetched_at: Local::now(),
+            body: body.to_string(),
+        };
+        fs::write(self.path(key), serde_json::to_string(&entry)?)?;
+        self.trim()
+    }
+
+    /// Cache files with their size, the oldest first
+    fn files(&self) -> Vec<(PathBuf, u64)> {
+        let Ok(dir) = fs::read_dir(&self.directory) else {
+            return vec![];
+        };
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = dir
+            .filter_map(Result::ok)
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|e| {
+                let metadata = e.metadata().ok()?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((e.path(), metadata.len(), modified))
+            })
+            .collect();
+        files.sort_by_key(|(_, _, modified)| *modified);
+        files
+            .into_iter()
+            .map(|(path, size, _)| (path, size))
+            .collect()
+    }
+
+    fn trim(&self) -> Result<(), CacheError> {
+        let files = self.files();
+        let mut size: u64 = files.iter().map(|(_, size)| size).sum();
+        for (path, file_size) in files {
+            if size <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= file_size;
+        }
+        Ok(())
+    }
+
+    /// Number of cached responses and their size in bytes
+    pub fn usage(&self) -> (usize, u64) {
+        let files = self.files();
+        (files.len(), files.iter().map(|(_, size)| size).sum())
+    }
+
+    /// Removes every cached response, returns how many there were
+    pub fn purge(&self) -> Result<usize, CacheError> {
+        let files = self.files();
+        for (path, _) in &files {
+            fs::remove_file(path)?;
+        }
+        Ok(files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::ResponseCache;
+
+    #[test]
+    fn test_cache_get_put_trim_and_purge() {
+        let directory = std::env::temp_dir().join(format!("ora_cache_{}", std::process::id()));
+        let cache = ResponseCache::new(&directory, 200);
+
+        assert!(cache.get("a").is_none());
+        cache.put("a", "[]").unwrap();
+        assert_eq!(cache.get("a").unwrap().body, "[]");
+        assert_eq!(cache.usage().0, 1);
+
+        // The oldest entry goes once the limit is exceeded
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put("b", &"x".repeat(100)).unwrap();
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+
+        assert_eq!(cache.purge().unwrap(), 1);
+        assert_eq!(cache.usage(), (0, 0));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    api::{GeometryApi, GeometryApiError},
+    feature::Feature,
+    formats::{Format, FormatError},
+    geometry_viewer::GeometryViewerConfig,
+    query::{Bind, Query},
+    raster::{PngRenderer, RasterError},
+    render::{Extent, POINT_SPAN},
+    sdo_geometry::SdoGeometry,
+    style::LayerStyle,
+    svg::SvgExporter,
+};
+
+/// Margin added around geometries when no extent is given
+const EXTENT_MARGIN: f64 = 0.05;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CliError {
+    #[error(transparent)]
+    Api(#[from] GeometryApiError),
+    #[error(transparent)]
+    Format(#[from] FormatError),
+    #[error(transparent)]
+    Raster(#[from] RasterError),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    InvalidArguments(String),
+    #[error("{0} geometries are not valid")]
+    InvalidGeometries(usize),
+}
+
+/// Oracle Geometry Viewer, runs the GUI when no command is given
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Send a query to the API and write the geometries to a file
+    Query {
+        /// Backend URL, the one from the config file is used by default
+        #[arg(long)]
+        api: Option<String>,
+        #[arg(long)]
+        sql: String,
+        /// Bind variable as `name=value`, can be repeated
+        #[arg(long = "bind", value_parser = parse_bind)]
+        binds: Vec<Bind>,
+        /// Use the cached response when the backend cannot be reached
+        #[arg(long)]
+        offline: bool,
+        /// Output file, `-` writes to the standard output
+        #[arg(long)]
+        out: PathBuf,
+        /// wkt, geojson or sdo-json, guessed from the output extension by default
+        #[arg(long)]
+        format: Option<Format>,
+    },
+    /// Convert geometries between WKT, GeoJSON and SDO JSON
+    Convert {
+        /// Input file, `-` reads from the standard input
+        #[arg(long)]
+        input: PathBuf,
+        /// Output file, `-` writes to the standard output
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(long)]
+        from: Option<Format>,
+        #[arg(long)]
+        to: Option<Format>,
+        /// SRID written to the output instead of the input one
+        #[arg(long)]
+        srid: Option<f32>,
+    },
+    /// Check geometries the way Oracle validates them, fails if any is invalid
+    Validate {
+        #[command(flatten)]
+        source: Source,
+    },
+    /// Render geometries into an SVG or a PNG file
+    Render {
+        #[command(flatten)]
+        source: Source,
+        /// Output file, the extension picks SVG or PNG
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(long, default_value_t = 1280)]
+        width: u32,
+        #[arg(long, default_value_t = 720)]
+        height: u32,
+        /// Drawn area as `min_x,min_y,max_x,max_y`, fits all geometries by default
+        #[arg(long, value_parser = parse_extent)]
+        extent: Option<Extent>,
+        /// Name of the layer shown in the legend
+        #[arg(long, default_value = "layer")]
+        name: String,
+        /// Draw axes
+        #[arg(long)]
+        axes: bool,
+        /// Draw a legend, SVG only
+        #[arg(long)]
+        legend: bool,
+        /// Draw a scale bar, in metres when the SRID is known
+        #[arg(long)]
+        scale_bar: bool,
+        /// Draw an arrow pointing north
+        #[arg(long)]
+        north_arrow: bool,
+    },
+}
+
+/// Geometries come either from a query or from a file
+#[derive(Args)]
+pub struct Source {
+    /// Backend URL, the one from the config file is used by default
+    #[arg(long)]
+    api: Option<String>,
+    #[arg(long, conflicts_with = "input", required_unless_present = "input")]
+    sql: Option<String>,
+    /// Bind variable as `name=value`, can be repeated
+    #[arg(long = "bind", value_parser = parse_bind, requires = "sql")]
+    binds: Vec<Bind>,
+    /// Use the cached response when the backend cannot be reached
+    #[arg(long, requires = "sql")]
+    offline: bool,
+    /// Input file, `-` reads from the standard input
+    #[arg(long)]
+    input: Option<PathBuf>,
+    /// Input format, guessed from the input extension by default
+    #[arg(long)]
+    format: Option<Format>,
+}
+
+impl Source {
+    fn load(&self) -> Result<Vec<SdoGeometry>, CliError> {
+        match (&self.sql, &self.input) {
+            (Some(sql), _) => fetch(&api(self.api.clone(), self.offline), sql, &self.binds),
+            (None, Some(input)) => {
+                let format = format_or_guess(self.format, input)?;
+                Ok(format.read(&read_input(input)?)?)
+            }
+            (None, None) => Err(CliError::InvalidArguments(String::from(
+                "Either --sql or --input must be given",
+            ))),
+        }
+    }
+}
+
+pub fn run(command: Command) -> Result<(), CliError> {
+    match command {
+        Command::Query {
+            api: url,
+            sql,
+            binds,
+            offline,
+            out,
+            format,
+        } => {
+            let format = format_or_guess(format, &out)?;
+            let geometries = fetch(&api(url, offline), &sql, &binds)?;
+            write_output(&out, &format.write(&geometries, None)?)?;
+            tracing::info!("Wrote {} geometries", geometries.len());
+        }
+        Command::Convert {
+            input,
+            out,
+            from,
+            to,
+            srid,
+        } => {
+            let from = format_or_guess(from, &input)?;
+            let to = format_or_guess(to, &out)?;
+            let geometries = from.read(&read_input(&input)?)?;
+            write_output(&out, &to.write(&geometries, srid)?)?;
+            tracing::info!("Converted {} geometries", geometries.len());
+        }
+        Command::Validate { source } => {
+            let geometries = source.load()?;
+            let mut invalid = 0;
+            for (n, geometry) in geometries.iter().enumerate() {
+                let errors = geometry.validation_errors();
+                if errors.is_empty() {
+                    println!("{n}: OK");
+                } else {
+                    invalid += 1;
+                    println!("{n}: {}", errors.join("; "));
+                }
+            }
+
+            if invalid > 0 {
+                return Err(CliError::InvalidGeometries(invalid));
+            }
+        }
+        Command::Render {
+            source,
+            out,
+            width,
+            height,
+            extent,
+            name,
+            axes,
+            legend,
+            scale_bar,
+            north_arrow,
+        } => {
+            let query = Query::new(
+                &name,
+                source.sql.clone().unwrap_or_default(),
+                source.binds.clone(),
+                LayerStyle::default(),
+                source.load()?.into_iter().map(Feature::from).collect(),
+            );
+            let extent = match extent {
+                Some(extent) => extent,
+                None => Extent::of_geometries(
+                    query
+                        .geometries
+                        .iter()
+                        .filter_map(|g| g.sdo_geometry.to_geo())
+                        .collect::<Vec<_>>()
+                        .iter(),
+                )
+                .map(|e| e.with_margin(EXTENT_MARGIN).with_point_span(POINT_SPAN))
+                .ok_or(CliError::InvalidArguments(String::from(
+                    "There are no geometries to render",
+                )))?,
+            };
+            let layers = [(name.as_str(), &query)];
+            let srid = query
+                .geometries
+                .first()
+                .and_then(|g| g.sdo_geometry.sdo_srid);
+
+            match extension(&out).as_str() {
+                "svg" => {
+                    let mut exporter = SvgExporter::new(extent);
+                    exporter.width = width;
+                    exporter.height = height;
+                    exporter.include_axes = axes;
+                    exporter.include_legend = legend;
+                    exporter.include_scale_bar = scale_bar;
+                    exporter.include_north_arrow = north_arrow;
+                    exporter.srid = srid;
+                    exporter.export(layers, &out)?;
+                }
+                "png" => {
+                    let mut renderer = PngRenderer::new(extent, width, height);
+                    renderer.include_axes = axes;
+                    renderer.include_scale_bar = scale_bar;
+                    renderer.include_north_arrow = north_arrow;
+                    renderer.srid = srid;
+                    renderer.export(layers, &out)?
+                }
+                other => {
+                    return Err(CliError::InvalidArguments(format!(
+                        "Cannot render into `{other}` files, use svg or png"
+                    )))
+                }
+            }
+            tracing::info!("Rendered {} geometries", query.geometries.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// API from the config file, cached responses are only served when `offline` is set
+fn api(url: Option<String>, offline: bool) -> GeometryApi {
+    let mut api = GeometryViewerConfig::load().api;
+    if let Some(url) = url {
+        api.api_url = url;
+    }
+    api.cache.is_offline_mode = offline;
+
+    api
+}
+
+/// Geometries of the query, a cached response is reported as a warning
+fn fetch(api: &GeometryApi, sql: &str, binds: &[Bind]) -> Result<Vec<SdoGeometry>, CliError> {
+    let fetched = api.fetch(sql, binds)?;
+    if let Some(time) = fetched.stale_since {
+        tracing::warn!(
+            "The backend is unreachable, using the response cached at {}",
+            time.format("%Y-%m-%d %H:%M")
+        );
+    }
+
+    Ok(fetched.features.into_iter().map(|f| f.geometry).collect())
+}
+
+fn format_or_guess(format: Option<Format>, path: &Path) -> Result<Format, CliError> {
+    match format {
+        Some(format) => Ok(format),
+        None if path == Path::new("-") => Err(CliError::InvalidArguments(String::from(
+            "Format must be given when reading or writing standard streams",
+        ))),
+        None => Ok(Format::from_path(path)?),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn read_input(path: &Path) -> io::Result<String> {
+    if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+
+    fs::read_to_string(path)
+}
+
+fn write_output(path: &Path, text: &str) -> io::Result<()> {
+    if path == Path::new("-") {
+        return io::stdout().write_all(text.as_bytes());
+    }
+
+    fs::write(path, text)
+}
+
+fn parse_bind(text: &str) -> Result<Bind, String> {
+    match text.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok(Bind {
+            name: name.trim().to_string(),
+            value: value.to_string(),
+        }),
+        _ => Err(String::from("Bind must be `name=value`")),
+    }
+}
+
+fn parse_extent(text: &str) -> Result<Extent, String> {
+    let values = text
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [min_x, min_y, max_x, max_y] if min_x < max_x && min_y < max_y => {
+            Ok(Extent::new([min_x, min_y], [max_x, max_y]))
+        }
+        _ => Err(String::from(
+            "Extent must be `min_x,min_y,max_x,max_y` with minimums below maximums",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::{
+        cli::{parse_bind, parse_extent, Cli, Command},
+        query::Bind,
+        render::Extent,
+    };
+
+    #[test]
+    fn test_parse_bind() {
+        assert_eq!(
+            parse_bind("class=RES"),
+            Ok(Bind {
+                name: "class".into(),
+                value: "RES".into()
+            })
+        );
+        assert!(parse_bind("=RES").is_err());
+        assert!(parse_bind("class").is_err());
+    }
+
+    #[test]
+    fn test_parse_extent() {
+        assert_eq!(
+            parse_extent("0, 1.5,10,20"),
+            Ok(Extent::new([0., 1.5], [10., 20.]))
+        );
+        assert!(parse_extent("10,0,0,10").is_err());
+        assert!(parse_extent("0,0,10").is_err());
+    }
+
+    #[test]
+    fn test_no_command_runs_gui() {
+        let cli = Cli::try_parse_from(["ora_geom_gui"]).unwrap();
+
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_render_requires_source() {
+        assert!(Cli::try_parse_from(["ora_geom_gui", "render", "--out", "a.svg"]).is_err());
+
+        let cli = Cli::try_parse_from([
+            "ora_geom_gui",
+            "render",
+            "--api",
+            "http://localhost:8000",
+            "--sql",
+            "select geom from parcels",
+            "--out",
+            "parcels.svg",
+        ])
+        .unwrap();
+
+        assert!(matches!(cli.command, Some(Command::Render { .. })));
+    }
+}
@@ -0,0 +1,603 @@
+use std::collections::{HashMap, VecDeque};
+
+use egui::{Color32, ComboBox, Context, DragValue, Grid, RichText, ScrollArea, Window};
+use geo::HausdorffDistance;
+use serde_json::Value;
+
+use crate::{
+    feature::{attribute, display, Feature},
+    layers::Layers,
+    location::{layer_combo, SpatialIndex},
+    operation::derived_sql,
+    query::{Geometry, Query},
+    render::Extent,
+    selection::{GeometryRef, Selection},
+    style::LayerStyle,
+    theme::{Categorized, Category, Theme},
+    undo::{Change, UndoStack},
+};
+
+/// Attribute of the comparison layer that holds the status of every geometry
+pub const STATUS_ATTRIBUTE: &str = "STATUS";
+
+/// How geometries of the two layers are paired
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Matching {
+    /// Same value of a key attribute, e.g. the primary key
+    #[default]
+    Key,
+    /// Same shape, every vertex within the tolerance of the other shape
+    Geometry,
+}
+
+impl Matching {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Matching::Key => "Key attribute",
+            Matching::Geometry => "Geometric equality",
+        }
+    }
+}
+
+/// What happened to a geometry between the layer before and the one after
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+impl Status {
+    pub const ALL: [Status; 4] = [
+        Status::Added,
+        Status::Removed,
+        Status::Changed,
+        Status::Unchanged,
+    ];
+
+    /// Value of the status attribute
+    pub fn label(&self) -> &'static str {
+        match self {
+            Status::Added => "ADDED",
+            Status::Removed => "REMOVED",
+            Status::Changed => "CHANGED",
+            Status::Unchanged => "UNCHANGED",
+        }
+    }
+
+    pub fn color(&self) -> Color32 {
+        match self {
+            Status::Added => Color32::from_rgb(0, 170, 0),
+            Status::Removed => Color32::from_rgb(220, 0, 0),
+            Status::Changed => Color32::from_rgb(255, 140, 0),
+            Status::Unchanged => Color32::GRAY,
+        }
+    }
+}
+
+/// A geometry of either layer, or a pair of matched ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub status: Status,
+    /// Row in the layer before
+    pub before: Option<usize>,
+    /// Row in the layer after
+    pub after: Option<usize>,
+    /// `geometry` and the names of the attributes that differ
+    pub changes: Vec<String>,
+}
+
+/// Whether every vertex of each shape is within the tolerance of the other shape
+fn is_same_shape(a: &Geometry, b: &Geometry, tolerance: f64) -> bool {
+    match (a.shape(), b.shape()) {
+        (Some(a), Some(b)) => a.hausdorff_distance(b) <= tolerance,
+        _ => a.sdo_geometry == b.sdo_geometry,
+    }
+}
+
+/// Names of the attributes whose values differ, compared by their text
+fn changed_attributes(a: &Geometry, b: &Geometry) -> Vec<String> {
+    let mut names: Vec<&String> = a.attributes.keys().collect();
+    for name in b.attributes.keys() {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name);
+        }
+    }
+    let text = |g: &Geometry, name: &str| attribute(&g.attributes, name).map(display);
+
+    names
+        .into_iter()
+        .filter(|name| text(a, name) != text(b, name))
+        .cloned()
+        .collect()
+}
+
+/// Text of the key of a geometry, `None` when it is missing or null
+fn key_of(geometry: &Geometry, key: &str) -> Option<String> {
+    attribute(&geometry.attributes, key)
+        .filter(|v| !v.is_null())
+        .map(display)
+}
+
+/// Pairs the shown geometries of both layers and classifies them.
+///
+/// Removed geometries come first in the order of the layer before, then the geometries
+/// of the layer after in their order. Geometries without a key value are never paired.
+pub fn compare(
+    before: &Query,
+    after: &Query,
+    matching: Matching,
+    key: &str,
+    tolerance: f64,
+) -> Vec<Difference> {
+    let shown = |query: &Query| -> Vec<usize> {
+        (0..query.geometries.len())
+            .filter(|&n| query.is_shown(n))
+            .collect()
+    };
+    let mut is_matched = vec![false; before.geometries.len()];
+    let mut pairs: Vec<(usize, Option<usize>)> = vec![];
+
+    match matching {
+        Matching::Key => {
+            let mut by_key: HashMap<String, VecDeque<usize>> = HashMap::new();
+            for n in shown(before) {
+                if let Some(value) = key_of(&before.geometries[n], key) {
+                    by_key.entry(value).or_default().push_back(n);
+                }
+            }
+            for n in shown(after) {
+                let found = key_of(&after.geometries[n], key)
+                    .and_then(|value| by_key.get_mut(&value)?.pop_front());
+                if let Some(m) = found {
+                    is_matched[m] = true;
+                }
+                pairs.push((n, found));
+            }
+        }
+        Matching::Geometry => {
+            let index = SpatialIndex::new(before);
+            for n in shown(after) {
+                let geometry = &after.geometries[n];
+                let found = geometry.extent().and_then(|extent| {
+                    let extent = Extent::new(
+                        [extent.min[0] - tolerance, extent.min[1] - tolerance],
+                        [extent.max[0] + tolerance, extent.max[1] + tolerance],
+                    );
+                    let mut candidates: Vec<usize> = index.candidates(&extent).collect();
+                    // The R-tree returns candidates in no particular order
+                    candidates.sort();
+                    candidates.into_iter().find(|&m| {
+                        !is_matched[m] && is_same_shape(&before.geometries[m], geometry, tolerance)
+                    })
+                });
+                if let Some(m) = found {
+                    is_matched[m] = true;
+                }
+                pairs.push((n, found));
+            }
+        }
+    }
+    let removed = shown(before)
+        .into_iter()
+        .filter(|&m| !is_matched[m])
+        .map(|m| Difference {
+            status: Status::Removed,
+            before: Some(m),
+            after: None,
+            changes: vec![],
+        });
+    let paired = pairs.into_iter().map(|(n, found)| {
+        let Some(m) = found else {
+            return Difference {
+                status: Status::Added,
+                before: None,
+                after: Some(n),
+                changes: vec![],
+            };
+        };
+        let (old, new) = (&before.geometries[m], &after.geometries[n]);
+        let mut changes = vec![];
+        if matching == Matching::Key && !is_same_shape(old, new, tolerance) {
+            changes.push(String::from("geometry"));
+        }
+        changes.extend(changed_attributes(old, new));
+        Difference {
+            status: match changes.is_empty() {
+                true => Status::Unchanged,
+                false => Status::Changed,
+            },
+            before: Some(m),
+            after: Some(n),
+            changes,
+        }
+    });
+
+    removed.chain(paired).collect()
+}
+
+/// Number of differences of every status, in the order of `Status::ALL`
+pub fn summary(differences: &[Difference]) -> [usize; 4] {
+    Status::ALL.map(|status| differences.iter().filter(|d| d.status == status).count())
+}
+
+/// Geometries of both layers with their status, removed ones keep their old shape
+pub fn difference_features(
+    differences: &[Difference],
+    before: &Query,
+    after: &Query,
+) -> Vec<Feature> {
+    differences
+        .iter()
+        .filter_map(|difference| {
+            let geometry = match difference.after {
+                Some(n) => &after.geometries[n],
+                None => &before.geometries[difference.before?],
+            };
+            let mut attributes = geometry.attributes.clone();
+            attributes.insert(
+                STATUS_ATTRIBUTE.to_string(),
+                Value::String(difference.status.label().to_string()),
+            );
+            Some(Feature {
+                geometry: geometry.sdo_geometry.clone(),
+                attributes,
+            })
+        })
+        .collect()
+}
+
+/// Theme colouring geometries of a comparison layer by their status
+pub fn status_theme() -> Theme {
+    Theme::Categorized(Categorized {
+        attribute: STATUS_ATTRIBUTE.to_string(),
+        categories: Status::ALL
+            .iter()
+            .map(|status| Category {
+                value: status.label().to_string(),
+                color: status.color(),
+            })
+            .collect(),
+    })
+}
+
+/// Layers and matching of the last comparison with its result
+pub struct Comparison {
+    pub before: String,
+    pub after: String,
+    pub matching: Matching,
+    pub key: String,
+    /// Largest distance between matching vertices, in display units
+    pub tolerance: f64,
+    /// Layer the differences were added as, its rows are in the order of `differences`
+    pub layer: String,
+    /// Generation of the layer's geometries, the rows no longer match once it changes
+    pub generation: u64,
+    pub differences: Vec<Difference>,
+    pub message: RichText,
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Self {
+            before: String::new(),
+            after: String::new(),
+            matching: Matching::Key,
+            key: String::new(),
+            tolerance: 0.,
+            layer: String::new(),
+            generation: 0,
+            differences: vec![],
+            message: RichText::new(""),
+        }
+    }
+}
+
+pub struct CompareWindow<'a> {
+    pub comparison: &'a mut Comparison,
+    pub layers: &'a mut Layers,
+    /// Clicking a changed geometry selects it in the comparison layer
+    pub selection: &'a mut Selection,
+    pub undo: &'a mut UndoStack,
+}
+
+impl<'a> CompareWindow<'a> {
+    pub fn new(
+        comparison: &'a mut Comparison,
+        layers: &'a mut Layers,
+        selection: &'a mut Selection,
+        undo: &'a mut UndoStack,
+    ) -> Self {
+        Self {
+            comparison,
+            layers,
+            selection,
+            undo,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Compare layers")
+            .open(is_active)
+            .resizable(true)
+            .show(ctx, |ui| {
+                Grid::new("compare_settings").num_columns(2).show(ui, |ui| {
+                    ui.label("Before:");
+                    layer_combo(
+                        ui,
+                        "compare_before",
+                        &mut self.comparison.before,
+                        self.layers,
+                    );
+                    ui.end_row();
+
+                    ui.label("After:");
+                    layer_combo(ui, "compare_after", &mut self.comparison.after, self.layers);
+                    ui.end_row();
+
+                    ui.label("Match by:");
+                    ui.horizontal(|ui| {
+                        for matching in [Matching::Key, Matching::Geometry] {
+                            ui.radio_value(
+                                &mut self.comparison.matching,
+                                matching,
+                                matching.label(),
+                            );
+                        }
+                    });
+                    ui.end_row();
+
+                    if self.comparison.matching == Matching::Key {
+                        ui.label("Key:");
+                        let attributes = self
+                            .layers
+                            .get(&self.comparison.after)
+                            .map(|l| l.query.attribute_names())
+                            .unwrap_or_default();
+                        ComboBox::from_id_source("compare_key")
+                            .selected_text(self.comparison.key.as_str())
+                            .show_ui(ui, |ui| {
+                                for name in attributes {
+                                    ui.selectable_value(
+                                        &mut self.comparison.key,
+                                        name.clone(),
+                                        name,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    }
+
+                    ui.label("Tolerance:");
+                    ui.add(
+                        DragValue::new(&mut self.comparison.tolerance)
+                            .speed(0.01)
+                            .clamp_range(0. ..=f64::MAX),
+                    )
+                    .on_hover_text(
+                        "Largest distance between vertices of equal shapes, in display units",
+                    );
+                    ui.end_row();
+                });
+
+                let is_ready = self.layers.contains(&self.comparison.before)
+                    && self.layers.contains(&self.comparison.after)
+                    && (self.comparison.matching == Matching::Geometry
+                        || !self.comparison.key.is_empty());
+                if ui
+                    .add_enabled(is_ready, egui::Button::new("Compare"))
+                    .on_hover_text("Add the differences as a new layer")
+                    .clicked()
+                {
+                    self.run();
+                }
+                ui.label(self.comparison.message.clone());
+
+                if self.comparison.differences.is_empty() {
+                    return;
+                }
+                ui.separator();
+                Grid::new("compare_summary").num_columns(2).show(ui, |ui| {
+                    for (status, count) in Status::ALL
+                        .iter()
+                        .zip(summary(&self.comparison.differences))
+                    {
+                        ui.colored_label(status.color(), status.label());
+                        ui.label(count.to_string());
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.label("Changed geometries:");
+                let layer = self.comparison.layer.clone();
+                let is_current = self
+                    .layers
+                    .get(&layer)
+                    .is_some_and(|l| l.query.generation() == self.comparison.generation);
+                if !is_current {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        format!("{layer} was renamed, removed or changed, compare again"),
+                    );
+                    return;
+                }
+                ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+                    let changed = self
+                        .comparison
+                        .differences
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, d)| d.status == Status::Changed);
+                    for (n, difference) in changed {
+                        let item = GeometryRef::new(&layer, n);
+                        let name = item
+                            .resolve(self.layers)
+                            .map_or(String::new(), |g| g.name.clone());
+                        let text = format!("{name}: {}", difference.changes.join(", "));
+                        if ui
+                            .selectable_label(self.selection.contains(&item), text)
+                            .clicked()
+                        {
+                            self.selection.select(item);
+                        }
+                    }
+                });
+            });
+    }
+
+    fn run(&mut self) {
+        let (Some(before), Some(after)) = (
+            self.layers.get(&self.comparison.before),
+            self.layers.get(&self.comparison.after),
+        ) else {
+            return;
+        };
+        let differences = compare(
+            &before.query,
+            &after.query,
+            self.comparison.matching,
+            &self.comparison.key,
+            self.comparison.tolerance,
+        );
+        let features = difference_features(&differences, &before.query, &after.query);
+
+        let name = self.layers.unique_name(&format!(
+            "{}_vs_{}",
+            self.comparison.before, self.comparison.after
+        ));
+        let mut query = Query::new(
+            &name,
+            // Only the status is selected, the layer is derived so it is never run again
+            derived_sql(&features, &[STATUS_ATTRIBUTE]),
+            vec![],
+            LayerStyle::with_color(Color32::GRAY),
+            features,
+        );
+        query.theme = status_theme();
+        query.is_derived = true;
+        query.apply_theme();
+        self.comparison.generation = query.generation();
+        self.undo.push(
+            format!("Add layer {name}"),
+            Change::RemoveLayer(name.clone()),
+        );
+        self.layers.push(name.clone(), query);
+
+        let [added, removed, changed, _] = summary(&differences);
+        self.comparison.message = RichText::new(format!(
+            "Created {name}: {added} added, {removed} removed, {changed} changed"
+        ))
+        .color(Color32::GREEN);
+        self.comparison.layer = name;
+        self.comparison.differences = differences;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        compare::{compare, difference_features, summary, Matching, Status},
+        feature::{attribute, Feature},
+        query::Query,
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+    };
+
+    fn square(min: f64, attributes: serde_json::Value) -> Feature {
+        Feature {
+            geometry: SdoGeometry {
+                sdo_gtype: 2003.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 1003., 3.],
+                sdo_ordinates: vec![min, min, min + 1., min + 1.],
+            },
+            attributes: serde_json::from_value(attributes).unwrap(),
+        }
+    }
+
+    fn query(name: &str, data: Vec<Feature>) -> Query {
+        Query::new(name, String::new(), vec![], LayerStyle::default(), data)
+    }
+
+    fn statuses(before: &Query, after: &Query, matching: Matching, tolerance: f64) -> Vec<Status> {
+        compare(before, after, matching, "ID", tolerance)
+            .into_iter()
+            .map(|d| d.status)
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_by_key() {
+        let before = query(
+            "old",
+            vec![
+                square(0., json!({"ID": 1, "CLASS": "RES"})),
+                square(10., json!({"ID": 2, "CLASS": "RES"})),
+                square(20., json!({"ID": 3, "CLASS": "RES"})),
+            ],
+        );
+        let after = query(
+            "new",
+            vec![
+                square(0., json!({"ID": 1, "CLASS": "RES"})),
+                square(10.5, json!({"ID": 2, "CLASS": "COM"})),
+                square(30., json!({"ID": 4, "CLASS": "RES"})),
+            ],
+        );
+
+        let differences = compare(&before, &after, Matching::Key, "ID", 0.);
+
+        assert_eq!(
+            differences.iter().map(|d| d.status).collect::<Vec<_>>(),
+            vec![
+                Status::Removed,
+                Status::Unchanged,
+                Status::Changed,
+                Status::Added
+            ]
+        );
+        assert_eq!(differences[0].before, Some(2));
+        assert_eq!(differences[2].changes, vec!["geometry", "CLASS"]);
+        assert_eq!(summary(&differences), [1, 1, 1, 1]);
+        // Within the tolerance only the attribute change is left
+        let differences = compare(&before, &after, Matching::Key, "ID", 1.);
+        assert_eq!(differences[2].changes, vec!["CLASS"]);
+
+        let features = difference_features(&differences, &before, &after);
+        let status = |n: usize| attribute(&features[n].attributes, "STATUS").cloned();
+        assert_eq!(status(0), Some(json!("REMOVED")));
+        assert_eq!(status(3), Some(json!("ADDED")));
+    }
+
+    #[test]
+    fn test_compare_by_geometry() {
+        let before = query(
+            "old",
+            vec![
+                square(0., json!({"CLASS": "RES"})),
+                square(10., json!({"CLASS": "RES"})),
+            ],
+        );
+        let after = query(
+            "new",
+            vec![
+                square(10.01, json!({"CLASS": "COM"})),
+                square(0., json!({"CLASS": "RES"})),
+            ],
+        );
+
+        assert_eq!(
+            statuses(&before, &after, Matching::Geometry, 0.),
+            vec![Status::Removed, Status::Added, Status::Unchanged]
+        );
+        assert_eq!(
+            statuses(&before, &after, Matching::Geometry, 0.1),
+            vec![Status::Changed, Status::Unchanged]
+        );
+    }
+}
@@ -0,0 +1,2 @@
+/// Application name that confy stores the configuration files under
+pub const CONFY_APP: &str = "oracle_geometry_viewer";
@@ -0,0 +1,773 @@
+use std::{
+    collections::BTreeMap,
+    f64::consts::{FRAC_PI_2, FRAC_PI_4},
+    sync::RwLock,
+};
+
+use geo::MapCoordsInPlace;
+
+use crate::sdo_geometry::SdoGeometry;
+
+/// Oracle SRIDs that have an EPSG equivalent under another code
+const ORACLE_SRIDS: [(u32, u32); 9] = [
+    (8307, 4326),
+    (8192, 4326),
+    (8265, 4269),
+    (8260, 4267),
+    (81989, 27700),
+    (3785, 3857),
+    (900913, 3857),
+    (102100, 3857),
+    (102113, 3857),
+];
+
+/// Built-in EPSG codes besides the UTM zones
+const EPSG_CODES: [u32; 17] = [
+    4326, 4269, 4267, 4258, 4283, 4167, 4230, 4277, 4314, 4322, 3857, 27700, 2154, 2180, 3006,
+    31467, 31468,
+];
+
+/// Northern and southern UTM zones on WGS 84
+const WGS84_UTM_NORTH: u32 = 32600;
+const WGS84_UTM_SOUTH: u32 = 32700;
+/// UTM zones on ETRS89 that cover Europe
+const ETRS89_UTM: u32 = 25800;
+const ETRS89_UTM_ZONES: std::ops::RangeInclusive<u32> = 28..=38;
+
+/// Other Oracle SRIDs, mapped to EPSG codes by `SDO_CS` in the database
+static ORACLE_ALIASES: RwLock<BTreeMap<u32, u32>> = RwLock::new(BTreeMap::new());
+
+/// Latitude beyond which Web Mercator is not defined
+const WEB_MERCATOR_MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum CrsError {
+    #[error("SRID {0} is not in the coordinate system registry")]
+    UnknownSrid(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipsoid {
+    pub a: f64,
+    pub inverse_flattening: f64,
+}
+
+impl Ellipsoid {
+    /// Squared eccentricity
+    fn e2(&self) -> f64 {
+        let f = 1. / self.inverse_flattening;
+        f * (2. - f)
+    }
+}
+
+const WGS84: Ellipsoid = Ellipsoid {
+    a: 6378137.,
+    inverse_flattening: 298.257223563,
+};
+const GRS80: Ellipsoid = Ellipsoid {
+    a: 6378137.,
+    inverse_flattening: 298.257222101,
+};
+const AIRY_1830: Ellipsoid = Ellipsoid {
+    a: 6377563.396,
+    inverse_flattening: 299.3249646,
+};
+const INTERNATIONAL_1924: Ellipsoid = Ellipsoid {
+    a: 6378388.,
+    inverse_flattening: 297.,
+};
+const BESSEL_1841: Ellipsoid = Ellipsoid {
+    a: 6377397.155,
+    inverse_flattening: 299.1528128,
+};
+const CLARKE_1866: Ellipsoid = Ellipsoid {
+    a: 6378206.4,
+    inverse_flattening: 294.978698214,
+};
+const WGS72_ELLIPSOID: Ellipsoid = Ellipsoid {
+    a: 6378135.,
+    inverse_flattening: 298.26,
+};
+
+/// Geodetic datum and its shift to WGS 84
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Datum {
+    pub name: &'static str,
+    pub ellipsoid: Ellipsoid,
+    /// Position vector Helmert parameters: translations in metres, rotations in arc seconds
+    /// and scale in ppm, all zero for datums that match WGS 84 within a metre
+    pub to_wgs84: [f64; 7],
+}
+
+const WGS84_DATUM: Datum = Datum {
+    name: "WGS 84",
+    ellipsoid: WGS84,
+    to_wgs84: [0.; 7],
+};
+
+fn grs80_datum(name: &'static str) -> Datum {
+    Datum {
+        name,
+        ellipsoid: GRS80,
+        to_wgs84: [0.; 7],
+    }
+}
+
+const OSGB36: Datum = Datum {
+    name: "OSGB36",
+    ellipsoid: AIRY_1830,
+    to_wgs84: [446.448, -125.157, 542.06, 0.1502, 0.247, 0.8421, -20.4894],
+};
+const ED50: Datum = Datum {
+    name: "ED50",
+    ellipsoid: INTERNATIONAL_1924,
+    to_wgs84: [-87., -98., -121., 0., 0., 0., 0.],
+};
+const DHDN: Datum = Datum {
+    name: "DHDN",
+    ellipsoid: BESSEL_1841,
+    to_wgs84: [598.1, 73.7, 418.2, 0.202, 0.045, -2.455, 6.7],
+};
+/// Shift of the conterminous United States
+const NAD27: Datum = Datum {
+    name: "NAD27",
+    ellipsoid: CLARKE_1866,
+    to_wgs84: [-8., 160., 176., 0., 0., 0., 0.],
+};
+const WGS72: Datum = Datum {
+    name: "WGS 72",
+    ellipsoid: WGS72_ELLIPSOID,
+    to_wgs84: [0., 0., 4.5, 0., 0., 0.554, 0.2263],
+};
+
+impl Datum {
+    fn is_wgs84(&self) -> bool {
+        self.to_wgs84 == [0.; 7]
+    }
+
+    /// Longitude and latitude in degrees on WGS 84
+    fn shift_to_wgs84(&self, lon_lat: [f64; 2]) -> [f64; 2] {
+        match self.is_wgs84() {
+            true => lon_lat,
+            false => {
+                let xyz = helmert(geocentric(&self.ellipsoid, lon_lat), self.to_wgs84);
+                geodetic(&WGS84, xyz)
+            }
+        }
+    }
+
+    fn shift_from_wgs84(&self, lon_lat: [f64; 2]) -> [f64; 2] {
+        match self.is_wgs84() {
+            true => lon_lat,
+            false => {
+                let inverse = self.to_wgs84.map(|p| -p);
+                geodetic(
+                    &self.ellipsoid,
+                    helmert(geocentric(&WGS84, lon_lat), inverse),
+                )
+            }
+        }
+    }
+}
+
+/// Earth-centred coordinates of a point on the ellipsoid
+fn geocentric(ellipsoid: &Ellipsoid, [lon, lat]: [f64; 2]) -> [f64; 3] {
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    let e2 = ellipsoid.e2();
+    let n = ellipsoid.a / (1. - e2 * lat.sin().powi(2)).sqrt();
+
+    [
+        n * lat.cos() * lon.cos(),
+        n * lat.cos() * lon.sin(),
+        n * (1. - e2) * lat.sin(),
+    ]
+}
+
+fn geodetic(ellipsoid: &Ellipsoid, [x, y, z]: [f64; 3]) -> [f64; 2] {
+    let e2 = ellipsoid.e2();
+    let p = x.hypot(y);
+    let mut lat = z.atan2(p * (1. - e2));
+    for _ in 0..5 {
+        let n = ellipsoid.a / (1. - e2 * lat.sin().powi(2)).sqrt();
+        lat = (z + e2 * n * lat.sin()).atan2(p);
+    }
+
+    [y.atan2(x).to_degrees(), lat.to_degrees()]
+}
+
+fn helmert([x, y, z]: [f64; 3], [tx, ty, tz, rx, ry, rz, s]: [f64; 7]) -> [f64; 3] {
+    let [rx, ry, rz] = [rx, ry, rz].map(|r| (r / 3600.).to_radians());
+    let scale = 1. + s * 1e-6;
+
+    [
+        tx + scale * (x - rz * y + ry * z),
+        ty + scale * (rz * x + y - rx * z),
+        tz + scale * (-ry * x + rx * y + z),
+    ]
+}
+
+/// Map projection, angles in degrees and distances in metres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Longitude and latitude in degrees
+    Geographic,
+    /// Spherical Mercator used by web maps
+    WebMercator,
+    TransverseMercator {
+        lat0: f64,
+        lon0: f64,
+        k0: f64,
+        false_easting: f64,
+        false_northing: f64,
+    },
+    /// Lambert conformal conic with two standard parallels
+    LambertConic {
+        lat0: f64,
+        lon0: f64,
+        lat1: f64,
+        lat2: f64,
+        false_easting: f64,
+        false_northing: f64,
+    },
+}
+
+impl Projection {
+    fn utm(zone: u32, is_south: bool) -> Self {
+        Projection::TransverseMercator {
+            lat0: 0.,
+            lon0: zone as f64 * 6. - 183.,
+            k0: 0.9996,
+            false_easting: 500000.,
+            false_northing: if is_south { 10000000. } else { 0. },
+        }
+    }
+
+    fn forward(&self, ellipsoid: &Ellipsoid, [lon, lat]: [f64; 2]) -> [f64; 2] {
+        match *self {
+            Projection::Geographic => [lon, lat],
+            Projection::WebMercator => {
+                let lat = lat
+                    .clamp(-WEB_MERCATOR_MAX_LATITUDE, WEB_MERCATOR_MAX_LATITUDE)
+                    .to_radians();
+                [
+                    ellipsoid.a * lon.to_radians(),
+                    ellipsoid.a * (FRAC_PI_4 + lat / 2.).tan().ln(),
+                ]
+            }
+            Projection::TransverseMercator {
+                lat0,
+                lon0,
+                k0,
+                false_easting,
+                false_northing,
+            } => {
+                let e2 = ellipsoid.e2();
+                let ep2 = e2 / (1. - e2);
+                let lat = lat.to_radians();
+                let n = ellipsoid.a / (1. - e2 * lat.sin().powi(2)).sqrt();
+                let t = lat.tan().powi(2);
+                let c = ep2 * lat.cos().powi(2);
+                let a = (lon - lon0).to_radians() * lat.cos();
+                let m = meridian_arc(ellipsoid, lat) - meridian_arc(ellipsoid, lat0.to_radians());
+
+                let x = k0
+                    * n
+                    * (a + (1. - t + c) * a.powi(3) / 6.
+                        + (5. - 18. * t + t * t + 72. * c - 58. * ep2) * a.powi(5) / 120.);
+                let y = k0
+                    * (m + n
+                        * lat.tan()
+                        * (a * a / 2.
+                            + (5. - t + 9. * c + 4. * c * c) * a.powi(4) / 24.
+                            + (61. - 58. * t + t * t + 600. * c - 330. * ep2) * a.powi(6) / 720.));
+                [x + false_easting, y + false_northing]
+            }
+            Projection::LambertConic {
+                lon0,
+                false_easting,
+                false_northing,
+                ..
+            } => {
+                let cone = Cone::new(ellipsoid, self);
+                let rho = cone.rho(lat.to_radians());
+                let theta = cone.n * (lon - lon0).to_radians();
+                [
+                    false_easting + rho * theta.sin(),
+                    false_northing + cone.rho0 - rho * theta.cos(),
+                ]
+            }
+        }
+    }
+
+    fn inverse(&self, ellipsoid: &Ellipsoid, [x, y]: [f64; 2]) -> [f64; 2] {
+        match *self {
+            Projection::Geographic => [x, y],
+            Projection::WebMercator => [
+                (x / ellipsoid.a).to_degrees(),
+                (2. * (y / ellipsoid.a).exp().atan() - FRAC_PI_2).to_degrees(),
+            ],
+            Projection::TransverseMercator {
+                lat0,
+                lon0,
+                k0,
+                false_easting,
+                false_northing,
+            } => {
+                let e2 = ellipsoid.e2();
+                let ep2 = e2 / (1. - e2);
+                let (x, y) = (x - false_easting, y - false_northing);
+
+                let m = meridian_arc(ellipsoid, lat0.to_radians()) + y / k0;
+                let mu = m
+                    / (ellipsoid.a * (1. - e2 / 4. - 3. * e2 * e2 / 64. - 5. * e2.powi(3) / 256.));
+                let e1 = (1. - (1. - e2).sqrt()) / (1. + (1. - e2).sqrt());
+                let lat1 = mu
+                    + (3. * e1 / 2. - 27. * e1.powi(3) / 32.) * (2. * mu).sin()
+                    + (21. * e1 * e1 / 16. - 55. * e1.powi(4) / 32.) * (4. * mu).sin()
+                    + 151. * e1.powi(3) / 96. * (6. * mu).sin()
+                    + 1097. * e1.powi(4) / 512. * (8. * mu).sin();
+
+                let c1 = ep2 * lat1.cos().powi(2);
+                let t1 = lat1.tan().powi(2);
+                let sin2 = 1. - e2 * lat1.sin().powi(2);
+                let n1 = ellipsoid.a / sin2.sqrt();
+                let r1 = ellipsoid.a * (1. - e2) / sin2.powf(1.5);
+                let d = x / (n1 * k0);
+
+                let lat = lat1
+                    - n1 * lat1.tan() / r1
+                        * (d * d / 2.
+                            - (5. + 3. * t1 + 10. * c1 - 4. * c1 * c1 - 9. * ep2) * d.powi(4)
+                                / 24.
+                            + (61. + 90. * t1 + 298. * c1 + 45. * t1 * t1
+                                - 252. * ep2
+                                - 3. * c1 * c1)
+                                * d.powi(6)
+                                / 720.);
+                let lon = (d - (1. + 2. * t1 + c1) * d.powi(3) / 6.
+                    + (5. - 2. * c1 + 28. * t1 - 3. * c1 * c1 + 8. * ep2 + 24. * t1 * t1)
+                        * d.powi(5)
+                        / 120.)
+                    / lat1.cos();
+                [lon0 + lon.to_degrees(), lat.to_degrees()]
+            }
+            Projection::LambertConic {
+                lon0,
+                false_easting,
+                false_northing,
+                ..
+            } => {
+                let cone = Cone::new(ellipsoid, self);
+                let e = ellipsoid.e2().sqrt();
+                let (dx, dy) = (x - false_easting, cone.rho0 - (y - false_northing));
+                let sign = cone.n.signum();
+                let rho = sign * dx.hypot(dy);
+                let t = (rho / (ellipsoid.a * cone.f)).powf(1. / cone.n);
+                let theta = (sign * dx).atan2(sign * dy);
+
+                let mut lat = FRAC_PI_2 - 2. * t.atan();
+                for _ in 0..10 {
+                    let factor = ((1. - e * lat.sin()) / (1. + e * lat.sin())).powf(e / 2.);
+                    lat = FRAC_PI_2 - 2. * (t * factor).atan();
+                }
+                [lon0 + (theta / cone.n).to_degrees(), lat.to_degrees()]
+            }
+        }
+    }
+}
+
+/// Distance along the meridian from the equator
+fn meridian_arc(ellipsoid: &Ellipsoid, lat: f64) -> f64 {
+    let e2 = ellipsoid.e2();
+    let (e4, e6) = (e2 * e2, e2.powi(3));
+
+    ellipsoid.a
+        * ((1. - e2 / 4. - 3. * e4 / 64. - 5. * e6 / 256.) * lat
+            - (3. * e2 / 8. + 3. * e4 / 32. + 45. * e6 / 1024.) * (2. * lat).sin()
+            + (15. * e4 / 256. + 45. * e6 / 1024.) * (4. * lat).sin()
+            - 35. * e6 / 3072. * (6. * lat).sin())
+}
+
+/// Constants of a Lambert conformal conic projection
+struct Cone {
+    a: f64,
+    e: f64,
+    n: f64,
+    f: f64,
+    rho0: f64,
+}
+
+impl Cone {
+    fn new(ellipsoid: &Ellipsoid, projection: &Projection) -> Self {
+        let Projection::LambertConic {
+            lat0, lat1, lat2, ..
+        } = *projection
+        else {
+            unreachable!("only conic projections have a cone")
+        };
+        let e = ellipsoid.e2().sqrt();
+        let m = |lat: f64| lat.cos() / (1. - e * e * lat.sin().powi(2)).sqrt();
+        let [lat0, lat1, lat2] = [lat0, lat1, lat2].map(f64::to_radians);
+
+        let (m1, m2) = (m(lat1), m(lat2));
+        let (t1, t2) = (cone_t(e, lat1), cone_t(e, lat2));
+        let n = match (lat1 - lat2).abs() < 1e-12 {
+            true => lat1.sin(),
+            false => (m1.ln() - m2.ln()) / (t1.ln() - t2.ln()),
+        };
+        let f = m1 / (n * t1.powf(n));
+        let mut cone = Self {
+            a: ellipsoid.a,
+            e,
+            n,
+            f,
+            rho0: 0.,
+        };
+        cone.rho0 = cone.rho(lat0);
+        cone
+    }
+
+    fn rho(&self, lat: f64) -> f64 {
+        self.a * self.f * cone_t(self.e, lat).powf(self.n)
+    }
+}
+
+fn cone_t(e: f64, lat: f64) -> f64 {
+    (FRAC_PI_4 - lat / 2.).tan() / ((1. - e * lat.sin()) / (1. + e * lat.sin())).powf(e / 2.)
+}
+
+/// Coordinate reference system of the built-in registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crs {
+    pub epsg: u32,
+    pub name: String,
+    pub datum: Datum,
+    pub projection: Projection,
+}
+
+impl Crs {
+    fn new(epsg: u32, name: &str, datum: Datum, projection: Projection) -> Self {
+        Self {
+            epsg,
+            name: name.to_string(),
+            datum,
+            projection,
+        }
+    }
+
+    pub fn is_geographic(&self) -> bool {
+        self.projection == Projection::Geographic
+    }
+
+    /// Unit of the coordinates
+    pub fn unit(&self) -> &'static str {
+        match self.is_geographic() {
+            true => "°",
+            false => "m",
+        }
+    }
+
+    /// Longitude and latitude in degrees on WGS 84
+    pub fn to_wgs84(&self, point: [f64; 2]) -> [f64; 2] {
+        let lon_lat = self.projection.inverse(&self.datum.ellipsoid, point);
+        self.datum.shift_to_wgs84(lon_lat)
+    }
+
+    pub fn from_wgs84(&self, lon_lat: [f64; 2]) -> [f64; 2] {
+        let lon_lat = self.datum.shift_from_wgs84(lon_lat);
+        self.projection.forward(&self.datum.ellipsoid, lon_lat)
+    }
+}
+
+impl std::fmt::Display for Crs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EPSG:{} {}", self.epsg, self.name)
+    }
+}
+
+/// EPSG code of an Oracle SRID, other SRIDs are EPSG codes already
+pub fn epsg_code(srid: u32) -> u32 {
+    ORACLE_SRIDS
+        .iter()
+        .find(|(oracle, _)| *oracle == srid)
+        .map(|(_, epsg)| *epsg)
+        .or_else(|| ORACLE_ALIASES.read().ok()?.get(&srid).copied())
+        .unwrap_or(srid)
+}
+
+/// Maps an Oracle SRID to the EPSG code the database reports for it
+pub fn register_oracle_srid(srid: u32, epsg: u32) {
+    if let Ok(mut aliases) = ORACLE_ALIASES.write() {
+        aliases.insert(srid, epsg);
+    }
+}
+
+/// Coordinate system of an Oracle or EPSG SRID
+pub fn lookup(srid: u32) -> Option<Crs> {
+    let epsg = epsg_code(srid);
+    let geographic = |name, datum| Some(Crs::new(epsg, name, datum, Projection::Geographic));
+    let transverse = |name, datum, lat0, lon0, k0, false_easting, false_northing| {
+        let projection = Projection::TransverseMercator {
+            lat0,
+            lon0,
+            k0,
+            false_easting,
+            false_northing,
+        };
+        Some(Crs::new(epsg, name, datum, projection))
+    };
+
+    match epsg {
+        4326 => geographic("WGS 84", WGS84_DATUM),
+        4269 => geographic("NAD83", grs80_datum("NAD83")),
+        4267 => geographic("NAD27", NAD27),
+        4258 => geographic("ETRS89", grs80_datum("ETRS89")),
+        4283 => geographic("GDA94", grs80_datum("GDA94")),
+        4167 => geographic("NZGD2000", grs80_datum("NZGD2000")),
+        4230 => geographic("ED50", ED50),
+        4277 => geographic("OSGB36", OSGB36),
+        4314 => geographic("DHDN", DHDN),
+        4322 => geographic("WGS 72", WGS72),
+        3857 => Some(Crs::new(
+            epsg,
+            "WGS 84 / Pseudo-Mercator",
+            WGS84_DATUM,
+            Projection::WebMercator,
+        )),
+        27700 => transverse(
+            "OSGB36 / British National Grid",
+            OSGB36,
+            49.,
+            -2.,
+            0.9996012717,
+            400000.,
+            -100000.,
+        ),
+        2180 => transverse(
+            "ETRS89 / Poland CS92",
+            grs80_datum("ETRS89"),
+            0.,
+            19.,
+            0.9993,
+            500000.,
+            -5300000.,
+        ),
+        3006 => transverse(
+            "SWEREF99 TM",
+            grs80_datum("SWEREF99"),
+            0.,
+            15.,
+            0.9996,
+            500000.,
+            0.,
+        ),
+        31467 => transverse(
+            "DHDN / 3-degree Gauss-Kruger zone 3",
+            DHDN,
+            0.,
+            9.,
+            1.,
+            3500000.,
+            0.,
+        ),
+        31468 => transverse(
+            "DHDN / 3-degree Gauss-Kruger zone 4",
+            DHDN,
+            0.,
+            12.,
+            1.,
+            4500000.,
+            0.,
+        ),
+        2154 => Some(Crs::new(
+            epsg,
+            "RGF93 / Lambert-93",
+            grs80_datum("RGF93"),
+            Projection::LambertConic {
+                lat0: 46.5,
+                lon0: 3.,
+                lat1: 49.,
+                lat2: 44.,
+                false_easting: 700000.,
+                false_northing: 6600000.,
+            },
+        )),
+        _ => utm(epsg),
+    }
+}
+
+fn utm(epsg: u32) -> Option<Crs> {
+    let (datum, zone, is_south) = match epsg {
+        32601..=32660 => (WGS84_DATUM, epsg - WGS84_UTM_NORTH, false),
+        32701..=32760 => (WGS84_DATUM, epsg - WGS84_UTM_SOUTH, true),
+        _ if ETRS89_UTM_ZONES.contains(&epsg.wrapping_sub(ETRS89_UTM)) => {
+            (grs80_datum("ETRS89"), epsg - ETRS89_UTM, false)
+        }
+        _ => return None,
+    };
+    let hemisphere = if is_south { "S" } else { "N" };
+    let name = format!("{} / UTM zone {zone}{hemisphere}", datum.name);
+
+    Some(Crs::new(
+        epsg,
+        &name,
+        datum,
+        Projection::utm(zone, is_south),
+    ))
+}
+
+/// Every coordinate system of the registry
+pub fn registry() -> Vec<Crs> {
+    let utm_zones = (1..=60)
+        .map(|zone| WGS84_UTM_NORTH + zone)
+        .chain((1..=60).map(|zone| WGS84_UTM_SOUTH + zone))
+        .chain(ETRS89_UTM_ZONES.map(|zone| ETRS89_UTM + zone));
+
+    EPSG_CODES
+        .into_iter()
+        .chain(utm_zones)
+        .filter_map(lookup)
+        .collect()
+}
+
+/// Name of the coordinate system of an SDO_SRID
+pub fn describe(srid: Option<f32>) -> String {
+    match srid {
+        None => "None".into(),
+        Some(srid) => match lookup(srid as u32) {
+            Some(crs) if crs.epsg == srid as u32 => crs.to_string(),
+            Some(crs) => format!("{srid} ({crs})"),
+            None => format!("{srid} (unknown)"),
+        },
+    }
+}
+
+/// Conversion of coordinates between two coordinate systems
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reprojection {
+    pub from: Crs,
+    pub to: Crs,
+    /// SRID given to reprojected SDO geometries
+    to_srid: u32,
+}
+
+impl Reprojection {
+    pub fn new(from: u32, to: u32) -> Result<Self, CrsError> {
+        Ok(Self {
+            from: lookup(from).ok_or(CrsError::UnknownSrid(from))?,
+            to: lookup(to).ok_or(CrsError::UnknownSrid(to))?,
+            to_srid: to,
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.from.epsg == self.to.epsg
+    }
+
+    pub fn point(&self, point: [f64; 2]) -> [f64; 2] {
+        match self.is_identity() {
+            true => point,
+            false => self.to.from_wgs84(self.from.to_wgs84(point)),
+        }
+    }
+
+    pub fn geometry(&self, geometry: &mut geo::Geometry) {
+        if !self.is_identity() {
+            geometry.map_coords_in_place(|c| self.point([c.x, c.y]).into());
+        }
+    }
+
+    /// Reprojected copy, arcs, rectangles and circles become vertices
+    pub fn sdo_geometry(&self, sdo_geometry: &SdoGeometry) -> Option<SdoGeometry> {
+        let mut geometry = sdo_geometry.to_geo()?;
+        self.geometry(&mut geometry);
+        Some(SdoGeometry::from_geo(&geometry, Some(self.to_srid as f32)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crs::{describe, lookup, register_oracle_srid, registry, Reprojection};
+
+    fn assert_close(a: [f64; 2], b: [f64; 2], tolerance: f64) {
+        assert!(
+            (a[0] - b[0]).abs() < tolerance && (a[1] - b[1]).abs() < tolerance,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn test_projections() {
+        // Example of the EPSG guidance note 7-2, without the datum shift
+        let bng = lookup(81989).unwrap();
+        let ellipsoid = bng.datum.ellipsoid;
+        let projected = bng.projection.forward(&ellipsoid, [0.5, 50.5]);
+        assert_close(projected, [577274.99, 69740.50], 0.01);
+        assert_close(
+            bng.projection.inverse(&ellipsoid, projected),
+            [0.5, 50.5],
+            1e-7,
+        );
+
+        let utm = lookup(32633).unwrap();
+        assert_eq!(utm.name, "WGS 84 / UTM zone 33N");
+        assert_close(utm.from_wgs84([15., 0.]), [500000., 0.], 1e-6);
+
+        let mercator = lookup(3857).unwrap();
+        assert_close(mercator.from_wgs84([180., 0.]), [20037508.3428, 0.], 1e-3);
+
+        let lambert = lookup(2154).unwrap();
+        assert_close(lambert.from_wgs84([3., 46.5]), [700000., 6600000.], 1e-6);
+        let paris = lambert.from_wgs84([2.35, 48.85]);
+        assert_close(lambert.to_wgs84(paris), [2.35, 48.85], 1e-9);
+    }
+
+    #[test]
+    fn test_reprojection_round_trip() {
+        let to_bng = Reprojection::new(8307, 27700).unwrap();
+        let to_wgs84 = Reprojection::new(27700, 8307).unwrap();
+        let london = [-0.1276, 51.5072];
+        let projected = to_bng.point(london);
+        // The datum shift moves the point by about a hundred metres
+        assert_close(projected, [530000., 180000.], 1000.);
+        assert_close(to_wgs84.point(projected), london, 1e-7);
+
+        assert!(Reprojection::new(8307, 4326).unwrap().is_identity());
+        assert!(Reprojection::new(8307, 1234).is_err());
+    }
+
+    #[test]
+    fn test_datum_shift() {
+        // DHDN 50°N 9°E on the central meridian of Gauss-Krüger zone 3, shifted with EPSG:1777
+        let gauss_kruger = lookup(31467).unwrap();
+        let ellipsoid = gauss_kruger.datum.ellipsoid;
+        let projected = gauss_kruger.projection.forward(&ellipsoid, [9., 50.]);
+        assert_close(projected, [3500000., 5540279.54], 0.01);
+        assert_close(
+            gauss_kruger.to_wgs84(projected),
+            [8.998_960_5, 49.998_853_1],
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let crs = registry();
+        assert!(crs.iter().any(|c| c.epsg == 25832));
+        assert!(crs.iter().all(|c| lookup(c.epsg).as_ref() == Some(c)));
+
+        assert_eq!(describe(Some(4326.)), "EPSG:4326 WGS 84");
+        assert_eq!(describe(Some(8307.)), "8307 (EPSG:4326 WGS 84)");
+        assert_eq!(describe(Some(1234.)), "1234 (unknown)");
+        assert_eq!(describe(Some(8260.)), "8260 (EPSG:4267 NAD27)");
+    }
+
+    #[test]
+    fn test_registered_oracle_srid() {
+        // A legacy code the registry does not know until the database maps it
+        assert!(lookup(82344).is_none());
+        register_oracle_srid(82344, 32632);
+        assert_eq!(lookup(82344).unwrap().epsg, 32632);
+        assert!(Reprojection::new(82344, 8307).is_ok());
+    }
+}
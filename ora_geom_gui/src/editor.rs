@@ -0,0 +1,1105 @@
+use egui::{Button, Color32, ComboBox, Context, DragValue, RichText, Stroke, TextEdit, Window};
+use egui_plot::{Line, LineStyle, MarkerShape, PlotUi, Points};
+use geo::{
+    Contains, Distance, Euclidean, Geometry, LineString, MultiLineString, MultiPoint, MultiPolygon,
+    Point, Polygon,
+};
+use wkt::ToWkt;
+
+use crate::{
+    crs::Reprojection,
+    formats::{Format, FormatError},
+    layers::Layers,
+    query::{Bind, InputQuery},
+    render,
+    sdo_geometry::{self, SdoGeometry},
+    selection::GeometryRef,
+    style::{Dash, LayerStyle},
+    undo::{Change, UndoStack},
+};
+
+/// Colour of the edited geometry and its vertices, distinct from the layer palette
+pub const EDIT_COLOR: Color32 = Color32::from_rgb(0, 190, 220);
+const DEFAULT_BIND: &str = "geom";
+
+/// What a click on the plot does while editing
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DrawTool {
+    Point,
+    Line,
+    Rectangle,
+    Circle,
+    #[default]
+    Polygon,
+    Hole,
+    Vertices,
+}
+
+impl DrawTool {
+    pub const ALL: [DrawTool; 7] = [
+        DrawTool::Point,
+        DrawTool::Line,
+        DrawTool::Rectangle,
+        DrawTool::Circle,
+        DrawTool::Polygon,
+        DrawTool::Hole,
+        DrawTool::Vertices,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DrawTool::Point => "Point",
+            DrawTool::Line => "Line",
+            DrawTool::Rectangle => "Rectangle",
+            DrawTool::Circle => "Circle",
+            DrawTool::Polygon => "Polygon",
+            DrawTool::Hole => "Hole",
+            DrawTool::Vertices => "Vertices",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            DrawTool::Point => "Click to add points",
+            DrawTool::Line => "Click to add vertices, double click or Enter finishes the line",
+            DrawTool::Rectangle => "Click two opposite corners",
+            DrawTool::Circle => "Click the center and then a point on the circumference",
+            DrawTool::Polygon => "Click to add vertices, click the first vertex or Enter closes it",
+            DrawTool::Hole => "Draw a ring inside a polygon to cut it out",
+            DrawTool::Vertices => {
+                "Drag vertices to move them, click an edge to insert one, right click or Delete removes one"
+            }
+        }
+    }
+
+    /// Tools that collect several clicked points before a shape is complete
+    fn is_path(&self) -> bool {
+        matches!(self, DrawTool::Line | DrawTool::Polygon | DrawTool::Hole)
+    }
+}
+
+/// A vertex by its part, its ring within a polygon and its position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexRef {
+    pub part: usize,
+    pub ring: usize,
+    pub index: usize,
+}
+
+impl VertexRef {
+    pub fn new(part: usize, ring: usize, index: usize) -> Self {
+        Self { part, ring, index }
+    }
+}
+
+/// Geometry that is drawn or edited, rings are stored without their closing vertex
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sketch {
+    Points(Vec<[f64; 2]>),
+    Lines(Vec<Vec<[f64; 2]>>),
+    /// Polygons made of an exterior ring followed by their holes
+    Polygons(Vec<Vec<Vec<[f64; 2]>>>),
+    /// Optimized rectangle given by two opposite corners
+    Rectangle([f64; 2], [f64; 2]),
+    Circle {
+        center: [f64; 2],
+        radius: f64,
+    },
+}
+
+impl Sketch {
+    /// Editable copy of a geometry, arcs become straight segments and collections are not supported
+    pub fn from_sdo(sdo_geometry: &SdoGeometry) -> Option<Self> {
+        if let [element] = sdo_geometry.elements()[..] {
+            let coordinates = sdo_geometry
+                .sdo_ordinates
+                .chunks_exact(sdo_geometry.dimensions())
+                .map(|c| [c[0], c[1]])
+                .collect::<Vec<_>>();
+            match (element.etype, element.interpretation, &coordinates[..]) {
+                (3 | 1003, 3, [a, b, ..]) => return Some(Sketch::Rectangle(*a, *b)),
+                (3 | 1003, 4, [a, b, c, ..]) => {
+                    if let Some((center, radius)) = sdo_geometry::circumcircle(*a, *b, *c) {
+                        return Some(Sketch::Circle { center, radius });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let sketch = match sdo_geometry.to_geo()? {
+            Geometry::Point(point) => Sketch::Points(vec![[point.x(), point.y()]]),
+            Geometry::MultiPoint(points) => {
+                Sketch::Points(points.iter().map(|p| [p.x(), p.y()]).collect())
+            }
+            Geometry::Line(line) => Sketch::Lines(vec![vec![
+                [line.start.x, line.start.y],
+                [line.end.x, line.end.y],
+            ]]),
+            Geometry::LineString(line) => Sketch::Lines(vec![vertices(&line)]),
+            Geometry::MultiLineString(lines) => Sketch::Lines(lines.iter().map(vertices).collect()),
+            Geometry::Polygon(polygon) => Sketch::Polygons(vec![rings(&polygon)]),
+            Geometry::MultiPolygon(polygons) => {
+                Sketch::Polygons(polygons.iter().map(rings).collect())
+            }
+            Geometry::Rect(rect) => {
+                Sketch::Rectangle([rect.min().x, rect.min().y], [rect.max().x, rect.max().y])
+            }
+            Geometry::Triangle(triangle) => Sketch::Polygons(vec![rings(&triangle.to_polygon())]),
+            Geometry::GeometryCollection(_) => return None,
+        };
+
+        Some(sketch)
+    }
+
+    /// Encodes the sketch, rectangles and circles keep their optimized Oracle form
+    pub fn to_sdo(&self, srid: Option<f32>) -> SdoGeometry {
+        let optimized = |interpretation: f32, sdo_ordinates: Vec<f64>| SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: srid,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., interpretation],
+            sdo_ordinates,
+        };
+
+        match self {
+            Sketch::Rectangle(a, b) => optimized(
+                3.,
+                vec![
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[0].max(b[0]),
+                    a[1].max(b[1]),
+                ],
+            ),
+            // Three points on the circumference, counterclockwise like an exterior ring
+            Sketch::Circle {
+                center: [x, y],
+                radius: r,
+            } => optimized(4., vec![*x, y - r, x + r, *y, *x, y + r]),
+            _ => SdoGeometry::from_geo(&self.to_geo(), srid),
+        }
+    }
+
+    fn to_geo(&self) -> Geometry {
+        let line = |points: &Vec<[f64; 2]>| LineString::from(points.clone());
+        let polygon = |rings: &Vec<Vec<[f64; 2]>>| {
+            let mut rings = rings.iter().map(|ring| {
+                let mut ring = line(ring);
+                ring.close();
+                ring
+            });
+            let exterior = rings.next().unwrap_or_else(|| LineString::new(vec![]));
+            Polygon::new(exterior, rings.collect())
+        };
+
+        match self {
+            Sketch::Points(points) if points.len() == 1 => Point::from(points[0]).into(),
+            Sketch::Points(points) => {
+                MultiPoint::new(points.iter().map(|p| Point::from(*p)).collect()).into()
+            }
+            Sketch::Lines(lines) if lines.len() == 1 => line(&lines[0]).into(),
+            Sketch::Lines(lines) => MultiLineString::new(lines.iter().map(line).collect()).into(),
+            Sketch::Polygons(polygons) if polygons.len() == 1 => polygon(&polygons[0]).into(),
+            Sketch::Polygons(polygons) => {
+                MultiPolygon::new(polygons.iter().map(polygon).collect()).into()
+            }
+            Sketch::Rectangle(a, b) => geo::Rect::new(*a, *b).to_polygon().into(),
+            Sketch::Circle { .. } => self
+                .to_sdo(None)
+                .to_geo()
+                .unwrap_or_else(|| MultiPoint::new(vec![]).into()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Sketch::Points(points) => points.is_empty(),
+            Sketch::Lines(lines) => lines.is_empty(),
+            Sketch::Polygons(polygons) => polygons.is_empty(),
+            Sketch::Rectangle(..) | Sketch::Circle { .. } => false,
+        }
+    }
+
+    /// Editable vertices, a circle has its center and a handle for the radius
+    pub fn vertices(&self) -> Vec<(VertexRef, [f64; 2])> {
+        match self {
+            Sketch::Points(points) => points
+                .iter()
+                .enumerate()
+                .map(|(part, p)| (VertexRef::new(part, 0, 0), *p))
+                .collect(),
+            Sketch::Lines(lines) => lines
+                .iter()
+                .enumerate()
+                .flat_map(|(part, line)| {
+                    line.iter()
+                        .enumerate()
+                        .map(move |(index, p)| (VertexRef::new(part, 0, index), *p))
+                })
+                .collect(),
+            Sketch::Polygons(polygons) => polygons
+                .iter()
+                .enumerate()
+                .flat_map(|(part, rings)| {
+                    rings.iter().enumerate().flat_map(move |(ring, points)| {
+                        points
+                            .iter()
+                            .enumerate()
+                            .map(move |(index, p)| (VertexRef::new(part, ring, index), *p))
+                    })
+                })
+                .collect(),
+            Sketch::Rectangle(a, b) => {
+                vec![(VertexRef::new(0, 0, 0), *a), (VertexRef::new(0, 0, 1), *b)]
+            }
+            Sketch::Circle { center, radius } => vec![
+                (VertexRef::new(0, 0, 0), *center),
+                (VertexRef::new(0, 0, 1), [center[0] + radius, center[1]]),
+            ],
+        }
+    }
+
+    /// Segments of lines and rings by the vertex they start at, rings include their closing segment
+    fn segments(&self) -> Vec<(VertexRef, [f64; 2], [f64; 2])> {
+        let mut segments = vec![];
+        let mut add = |part: usize, ring: usize, points: &[[f64; 2]], is_ring: bool| {
+            for (index, w) in points.windows(2).enumerate() {
+                segments.push((VertexRef::new(part, ring, index), w[0], w[1]));
+            }
+            if let (true, Some(first), Some(last)) = (is_ring, points.first(), points.last()) {
+                segments.push((VertexRef::new(part, ring, points.len() - 1), *last, *first));
+            }
+        };
+
+        match self {
+            Sketch::Lines(lines) => {
+                for (part, line) in lines.iter().enumerate() {
+                    add(part, 0, line, false);
+                }
+            }
+            Sketch::Polygons(polygons) => {
+                for (part, rings) in polygons.iter().enumerate() {
+                    for (ring, points) in rings.iter().enumerate() {
+                        add(part, ring, points, true);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        segments
+    }
+
+    pub fn nearest_vertex(&self, point: [f64; 2], tolerance: f64) -> Option<VertexRef> {
+        let distance = |p: [f64; 2]| (p[0] - point[0]).hypot(p[1] - point[1]);
+        self.vertices()
+            .into_iter()
+            .filter(|(_, p)| distance(*p) <= tolerance)
+            .min_by(|(_, a), (_, b)| distance(*a).total_cmp(&distance(*b)))
+            .map(|(v, _)| v)
+    }
+
+    /// Start of the line or ring segment closest to the point within `tolerance`
+    pub fn nearest_segment(&self, point: [f64; 2], tolerance: f64) -> Option<VertexRef> {
+        let point = Point::from(point);
+        let distance = |a: [f64; 2], b: [f64; 2]| {
+            Euclidean.distance(&point, &geo::Line::new(Point::from(a), Point::from(b)))
+        };
+        self.segments()
+            .into_iter()
+            .map(|(v, a, b)| (v, distance(a, b)))
+            .filter(|(_, d)| *d <= tolerance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(v, _)| v)
+    }
+
+    fn path_mut(&mut self, part: usize, ring: usize) -> Option<&mut Vec<[f64; 2]>> {
+        match self {
+            Sketch::Lines(lines) => lines.get_mut(part).filter(|_| ring == 0),
+            Sketch::Polygons(polygons) => polygons.get_mut(part)?.get_mut(ring),
+            _ => None,
+        }
+    }
+
+    pub fn move_vertex(&mut self, vertex: VertexRef, to: [f64; 2]) {
+        match self {
+            Sketch::Points(points) => {
+                if let Some(p) = points.get_mut(vertex.part) {
+                    *p = to;
+                }
+            }
+            Sketch::Rectangle(a, b) => match vertex.index {
+                0 => *a = to,
+                _ => *b = to,
+            },
+            Sketch::Circle { center, radius } => match vertex.index {
+                0 => *center = to,
+                _ => *radius = (to[0] - center[0]).hypot(to[1] - center[1]),
+            },
+            _ => {
+                if let Some(p) = self
+                    .path_mut(vertex.part, vertex.ring)
+                    .and_then(|path| path.get_mut(vertex.index))
+                {
+                    *p = to;
+                }
+            }
+        }
+    }
+
+    /// Inserts a vertex after the start of the segment
+    pub fn insert_vertex(&mut self, segment: VertexRef, point: [f64; 2]) {
+        if let Some(path) = self.path_mut(segment.part, segment.ring) {
+            path.insert((segment.index + 1).min(path.len()), point);
+        }
+    }
+
+    /// Removes a vertex, a line or ring that gets too short is removed entirely.
+    ///
+    /// Returns `false` for rectangles and circles, which have a fixed number of vertices.
+    pub fn delete_vertex(&mut self, vertex: VertexRef) -> bool {
+        let VertexRef { part, ring, index } = vertex;
+        match self {
+            Sketch::Points(points) if part < points.len() => {
+                points.remove(part);
+            }
+            Sketch::Lines(lines) if part < lines.len() => {
+                let line = &mut lines[part];
+                if line.len() > 2 {
+                    line.remove(index.min(line.len() - 1));
+                } else {
+                    lines.remove(part);
+                }
+            }
+            Sketch::Polygons(polygons) if part < polygons.len() => {
+                let rings = &mut polygons[part];
+                match rings.get_mut(ring) {
+                    Some(points) if points.len() > 3 => {
+                        points.remove(index.min(points.len() - 1));
+                    }
+                    Some(_) if ring > 0 => {
+                        rings.remove(ring);
+                    }
+                    Some(_) => {
+                        polygons.remove(part);
+                    }
+                    None => {}
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Cuts a ring out of the polygon around its first vertex, or the last polygon
+    fn add_hole(&mut self, ring: Vec<[f64; 2]>) -> bool {
+        let Sketch::Polygons(polygons) = self else {
+            return false;
+        };
+        let Some(first) = ring.first().copied() else {
+            return false;
+        };
+
+        let contains = |rings: &Vec<Vec<[f64; 2]>>| {
+            rings.first().is_some_and(|exterior| {
+                let mut exterior = LineString::from(exterior.clone());
+                exterior.close();
+                Polygon::new(exterior, vec![]).contains(&Point::from(first))
+            })
+        };
+        let index = polygons.iter().position(contains);
+        match index.or(polygons.len().checked_sub(1)) {
+            Some(n) => {
+                polygons[n].push(ring);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn vertices(line: &LineString) -> Vec<[f64; 2]> {
+    line.coords().map(|c| [c.x, c.y]).collect()
+}
+
+fn rings(polygon: &Polygon) -> Vec<Vec<[f64; 2]>> {
+    std::iter::once(polygon.exterior())
+        .chain(polygon.interiors())
+        .map(|ring| {
+            let mut points = vertices(ring);
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+            points
+        })
+        .collect()
+}
+
+/// Text the edited geometry is copied as
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Sql,
+    Wkt,
+    GeoJson,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] =
+        [OutputFormat::Sql, OutputFormat::Wkt, OutputFormat::GeoJson];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputFormat::Sql => "SDO_GEOMETRY constructor",
+            OutputFormat::Wkt => "WKT",
+            OutputFormat::GeoJson => "GeoJSON",
+        }
+    }
+
+    pub fn write(&self, geometry: &SdoGeometry) -> Result<String, FormatError> {
+        let geometries = std::slice::from_ref(geometry);
+        match self {
+            OutputFormat::Sql => Ok(geometry.to_sql()),
+            OutputFormat::Wkt => Ok(Format::Wkt.write(geometries, None)?.trim_end().to_string()),
+            OutputFormat::GeoJson => Format::GeoJson.write(geometries, None),
+        }
+    }
+}
+
+/// Pointer state of one frame on the plot
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlotInput {
+    /// Plot coordinates under the pointer
+    pub pointer: Option<[f64; 2]>,
+    /// Pointer snapped to a nearby vertex
+    pub snapped: Option<[f64; 2]>,
+    /// Distance in plot units within which vertices and edges are hit
+    pub tolerance: f64,
+    pub clicked: bool,
+    pub double_clicked: bool,
+    pub secondary_clicked: bool,
+    pub drag_started: bool,
+    pub dragged: bool,
+    pub drag_released: bool,
+}
+
+/// Geometry being drawn on the plot or edited from a layer
+pub struct Editor {
+    pub tool: DrawTool,
+    pub sketch: Option<Sketch>,
+    /// Clicked points of a shape that is not finished yet
+    pub pending: Vec<[f64; 2]>,
+    pub srid: Option<f32>,
+    /// Layer geometry the sketch was loaded from, applying the sketch replaces it
+    pub source: Option<GeometryRef>,
+    pub output: OutputFormat,
+    /// Bind variable the geometry is passed to the query as
+    pub bind_name: String,
+    pub message: RichText,
+    hovered: Option<VertexRef>,
+    dragged: Option<VertexRef>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            tool: DrawTool::default(),
+            sketch: None,
+            pending: vec![],
+            srid: None,
+            source: None,
+            output: OutputFormat::default(),
+            bind_name: DEFAULT_BIND.into(),
+            message: RichText::new(""),
+            hovered: None,
+            dragged: None,
+        }
+    }
+}
+
+impl Editor {
+    /// Starts over with an empty sketch
+    pub fn clear(&mut self) {
+        self.sketch = None;
+        self.pending.clear();
+        self.source = None;
+        self.hovered = None;
+        self.dragged = None;
+        self.message = RichText::new("");
+    }
+
+    /// Loads a layer geometry for vertex editing
+    pub fn edit(&mut self, item: GeometryRef, sdo_geometry: &SdoGeometry) -> bool {
+        let Some(sketch) = Sketch::from_sdo(sdo_geometry) else {
+            self.message = RichText::new("Collections can not be edited").color(Color32::RED);
+            return false;
+        };
+
+        self.clear();
+        self.sketch = Some(sketch);
+        self.srid = sdo_geometry.sdo_srid;
+        self.source = Some(item);
+        self.tool = DrawTool::Vertices;
+        true
+    }
+
+    /// Replaces the sketch, returns the previous one
+    pub fn set_sketch(&mut self, sketch: Option<Sketch>) -> Option<Sketch> {
+        self.hovered = None;
+        self.dragged = None;
+        std::mem::replace(&mut self.sketch, sketch)
+    }
+
+    pub fn to_sdo(&self) -> Option<SdoGeometry> {
+        self.sketch.as_ref().map(|sketch| sketch.to_sdo(self.srid))
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragged.is_some()
+    }
+
+    pub fn hovered(&self) -> Option<VertexRef> {
+        self.hovered
+    }
+
+    /// A vertex is under the pointer or being moved, so dragging must not pan the plot
+    pub fn wants_drag(&self) -> bool {
+        self.tool == DrawTool::Vertices && (self.hovered.is_some() || self.dragged.is_some())
+    }
+
+    /// Vertices the pointer snaps to besides the layers, without the one being moved
+    pub fn snap_vertices(&self) -> Vec<[f64; 2]> {
+        let sketch = self.sketch.iter().flat_map(|s| s.vertices());
+        sketch
+            .filter(|(v, _)| Some(*v) != self.dragged)
+            .map(|(_, p)| p)
+            .chain(self.pending.iter().copied())
+            .collect()
+    }
+
+    pub fn handle(&mut self, input: &PlotInput) {
+        if self.tool == DrawTool::Vertices {
+            self.edit_vertices(input);
+            return;
+        }
+
+        let Some(point) = input.snapped else {
+            return;
+        };
+        if input.clicked {
+            self.click(point);
+        }
+        if input.double_clicked && self.tool.is_path() {
+            self.finish();
+        }
+    }
+
+    fn click(&mut self, point: [f64; 2]) {
+        match self.tool {
+            DrawTool::Point => {
+                self.sketch = Some(match self.sketch.take() {
+                    Some(Sketch::Points(mut points)) => {
+                        points.push(point);
+                        Sketch::Points(points)
+                    }
+                    _ => Sketch::Points(vec![point]),
+                });
+            }
+            DrawTool::Line | DrawTool::Polygon | DrawTool::Hole => {
+                let closes = self.tool != DrawTool::Line
+                    && self.pending.len() >= 3
+                    && self.pending.first() == Some(&point);
+                if closes {
+                    self.finish();
+                } else if self.pending.last() != Some(&point) {
+                    self.pending.push(point);
+                }
+            }
+            DrawTool::Rectangle | DrawTool::Circle => match self.pending.first().copied() {
+                None => self.pending.push(point),
+                Some(first) => {
+                    self.pending.clear();
+                    if first != point {
+                        self.sketch = Some(match self.tool {
+                            DrawTool::Rectangle => Sketch::Rectangle(first, point),
+                            _ => Sketch::Circle {
+                                center: first,
+                                radius: (point[0] - first[0]).hypot(point[1] - first[1]),
+                            },
+                        });
+                    }
+                }
+            },
+            DrawTool::Vertices => {}
+        }
+    }
+
+    /// Adds the pending line or ring to the sketch, parts of the same kind are kept
+    pub fn finish(&mut self) {
+        let minimum = match self.tool {
+            DrawTool::Line => 2,
+            DrawTool::Polygon | DrawTool::Hole => 3,
+            _ => return,
+        };
+        if self.pending.len() < minimum {
+            return;
+        }
+
+        if self.tool == DrawTool::Hole {
+            let points = std::mem::take(&mut self.pending);
+            let is_added = self
+                .sketch
+                .as_mut()
+                .is_some_and(|sketch| sketch.add_hole(points.clone()));
+            if !is_added {
+                self.message =
+                    RichText::new("Holes are cut out of a drawn polygon").color(Color32::RED);
+                self.pending = points;
+            }
+            return;
+        }
+
+        let points = std::mem::take(&mut self.pending);
+        self.sketch = Some(match (self.tool, self.sketch.take()) {
+            (DrawTool::Line, Some(Sketch::Lines(mut lines))) => {
+                lines.push(points);
+                Sketch::Lines(lines)
+            }
+            (DrawTool::Line, _) => Sketch::Lines(vec![points]),
+            (_, Some(Sketch::Polygons(mut polygons))) => {
+                polygons.push(vec![points]);
+                Sketch::Polygons(polygons)
+            }
+            (_, _) => Sketch::Polygons(vec![vec![points]]),
+        });
+    }
+
+    /// Removes the last pending point
+    pub fn undo_point(&mut self) {
+        self.pending.pop();
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn delete_vertex(&mut self, vertex: VertexRef) {
+        let Some(sketch) = &mut self.sketch else {
+            return;
+        };
+        if !sketch.delete_vertex(vertex) {
+            self.message =
+                RichText::new("Rectangles and circles keep their vertices").color(Color32::RED);
+        }
+        if sketch.is_empty() {
+            self.sketch = None;
+        }
+        self.hovered = None;
+    }
+
+    fn edit_vertices(&mut self, input: &PlotInput) {
+        let Some(sketch) = &mut self.sketch else {
+            return;
+        };
+
+        if let Some(vertex) = self.dragged {
+            if let Some(point) = input.snapped.filter(|_| input.dragged) {
+                sketch.move_vertex(vertex, point);
+            }
+            if input.drag_released {
+                self.dragged = None;
+            }
+            return;
+        }
+        if input.drag_started && self.hovered.is_some() {
+            self.dragged = self.hovered;
+            return;
+        }
+
+        self.hovered = input
+            .pointer
+            .and_then(|p| sketch.nearest_vertex(p, input.tolerance));
+        match self.hovered {
+            Some(vertex) if input.secondary_clicked => self.delete_vertex(vertex),
+            None if input.clicked => {
+                let segment = input
+                    .pointer
+                    .and_then(|p| sketch.nearest_segment(p, input.tolerance));
+                if let (Some(segment), Some(point)) = (segment, input.snapped) {
+                    sketch.insert_vertex(segment, point);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draws the sketch with its vertices, the pending shape and the snapped pointer
+pub fn plot_editor(plot_ui: &mut PlotUi, editor: &Editor, pointer: Option<[f64; 2]>) {
+    let stroke = Stroke::new(2., EDIT_COLOR);
+    let style = LayerStyle {
+        marker_size: 4.,
+        ..LayerStyle::from(stroke)
+    };
+    let vertex_points = |points: Vec<[f64; 2]>, radius: f32, filled: bool| {
+        Points::new(points)
+            .shape(MarkerShape::Square)
+            .radius(radius)
+            .filled(filled)
+            .color(EDIT_COLOR)
+    };
+
+    if let Some(sketch) = &editor.sketch {
+        if let Some(geometry) = sketch.to_sdo(editor.srid).to_geo() {
+            render::plot_geometry(plot_ui, "Sketch", &geometry, &style);
+        }
+        let vertices = sketch.vertices();
+        plot_ui.points(vertex_points(
+            vertices.iter().map(|(_, p)| *p).collect(),
+            3.,
+            false,
+        ));
+        if let Some((_, p)) = vertices.iter().find(|(v, _)| Some(*v) == editor.hovered) {
+            plot_ui.points(vertex_points(vec![*p], 5., true));
+        }
+    }
+
+    plot_ui.line(Line::new(editor.pending.clone()).stroke(stroke));
+    plot_ui.points(vertex_points(editor.pending.clone(), 3., true));
+
+    let Some(pointer) = pointer else {
+        return;
+    };
+    if let Some(last) = editor.pending.last() {
+        let preview = match editor.tool {
+            DrawTool::Rectangle => Some(Sketch::Rectangle(*last, pointer)),
+            DrawTool::Circle => Some(Sketch::Circle {
+                center: *last,
+                radius: (pointer[0] - last[0]).hypot(pointer[1] - last[1]),
+            }),
+            _ => None,
+        };
+        match preview.and_then(|p| p.to_sdo(None).to_geo()) {
+            Some(geometry) => {
+                let dashed = LayerStyle {
+                    dash: Dash::Dashed,
+                    ..style
+                };
+                render::plot_geometry(plot_ui, "Sketch", &geometry, &dashed);
+            }
+            None => plot_ui.line(
+                Line::new(vec![*last, pointer])
+                    .stroke(stroke)
+                    .style(LineStyle::dashed_dense()),
+            ),
+        }
+    }
+    plot_ui.points(
+        Points::new(vec![pointer])
+            .shape(MarkerShape::Circle)
+            .radius(5.)
+            .filled(false)
+            .color(EDIT_COLOR),
+    );
+}
+
+pub struct EditorWindow<'a> {
+    pub editor: &'a mut Editor,
+    pub layers: &'a mut Layers,
+    pub input_query: &'a mut InputQuery,
+    pub show_query_window: &'a mut bool,
+    pub undo: &'a mut UndoStack,
+}
+
+impl<'a> EditorWindow<'a> {
+    pub fn new(
+        editor: &'a mut Editor,
+        layers: &'a mut Layers,
+        input_query: &'a mut InputQuery,
+        show_query_window: &'a mut bool,
+        undo: &'a mut UndoStack,
+    ) -> Self {
+        Self {
+            editor,
+            layers,
+            input_query,
+            show_query_window,
+            undo,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Editor")
+            .open(is_active)
+            .resizable(true)
+            .default_width(360.)
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for tool in DrawTool::ALL {
+                        ui.selectable_value(&mut self.editor.tool, tool, tool.label())
+                            .on_hover_text(tool.description());
+                    }
+                });
+                ui.label(self.editor.tool.description());
+                ui.label(
+                    RichText::new("Points snap to vertices. Backspace removes the last point, Escape cancels the shape.")
+                        .italics(),
+                );
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let mut has_srid = self.editor.srid.is_some();
+                    ui.checkbox(&mut has_srid, "SRID");
+                    let mut srid = self.editor.srid.unwrap_or_default();
+                    ui.add_enabled(has_srid, DragValue::new(&mut srid).speed(0.));
+                    self.editor.srid = has_srid.then_some(srid);
+                });
+
+                if let Some(source) = &self.editor.source {
+                    ui.label(format!("Editing row {} of {}", source.index, source.layer));
+                }
+
+                match self.editor.to_sdo() {
+                    Some(sdo_geometry) => self.output_ui(ui, &sdo_geometry),
+                    None => {
+                        ui.label("Draw a geometry on the plot, or edit one from a layer.");
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    let has_pending = !self.editor.pending.is_empty();
+                    if ui
+                        .add_enabled(has_pending, Button::new("Finish shape"))
+                        .clicked()
+                    {
+                        self.editor.finish();
+                    }
+                    if ui.button("New").clicked() {
+                        self.editor.clear();
+                    }
+                });
+                ui.label(self.editor.message.clone());
+            });
+    }
+
+    fn output_ui(&mut self, ui: &mut egui::Ui, sdo_geometry: &SdoGeometry) {
+        let errors = sdo_geometry.validation_errors();
+        if errors.is_empty() {
+            ui.label(RichText::new("Valid").color(Color32::GREEN));
+        } else {
+            ui.label(RichText::new(errors.join("\n")).color(Color32::RED));
+        }
+
+        ComboBox::from_label("Format")
+            .selected_text(self.editor.output.label())
+            .show_ui(ui, |ui| {
+                for format in OutputFormat::ALL {
+                    ui.selectable_value(&mut self.editor.output, format, format.label());
+                }
+            });
+        let text = self
+            .editor
+            .output
+            .write(sdo_geometry)
+            .unwrap_or_else(|e| e.to_string());
+        egui::ScrollArea::vertical()
+            .max_height(160.)
+            .show(ui, |ui| {
+                ui.add(
+                    TextEdit::multiline(&mut text.as_str())
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+        let is_valid = errors.is_empty();
+        ui.horizontal(|ui| {
+            if ui.button("Copy").clicked() {
+                ui.output_mut(|o| o.copied_text = text.clone());
+            }
+
+            if let Some(source) = self.editor.source.clone() {
+                if ui
+                    .add_enabled(is_valid, Button::new("Apply to layer"))
+                    .on_hover_text("Replace the geometry in its layer")
+                    .clicked()
+                {
+                    let layer = self.layers.get_mut(&source.layer);
+                    let previous = layer.as_ref().and_then(|l| {
+                        let geometry = l.query.geometries.get(source.index)?;
+                        Some(geometry.sdo_geometry.clone())
+                    });
+                    if let (Some(layer), Some(previous)) = (layer, previous) {
+                        // The sketch is in display coordinates, the layer keeps its own SRID
+                        let reprojection = sdo_geometry
+                            .sdo_srid
+                            .zip(previous.sdo_srid)
+                            .and_then(|(from, to)| Reprojection::new(from as u32, to as u32).ok());
+                        let sdo_geometry = reprojection
+                            .filter(|r| !r.is_identity())
+                            .and_then(|r| r.sdo_geometry(sdo_geometry))
+                            .unwrap_or_else(|| sdo_geometry.clone());
+                        layer
+                            .query
+                            .replace_geometry(source.index, sdo_geometry.clone());
+                        self.undo.push(
+                            format!("Edit geometry of {}", source.layer),
+                            Change::Geometry {
+                                layer: source.layer.clone(),
+                                index: source.index,
+                                sdo_geometry: previous,
+                                edited: sdo_geometry,
+                            },
+                        );
+                        self.editor.message = RichText::new(format!(
+                            "Updated row {} of {}",
+                            source.index, source.layer
+                        ));
+                    }
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Bind:");
+            ui.text_edit_singleline(&mut self.editor.bind_name);
+            let wkt = sdo_geometry.to_geo().map(|g| g.wkt_string());
+            let name = self
+                .editor
+                .bind_name
+                .trim()
+                .trim_start_matches(':')
+                .to_string();
+            if ui
+                .add_enabled(
+                    is_valid && wkt.is_some() && !name.is_empty(),
+                    Button::new("Use in query"),
+                )
+                .on_hover_text("Pass the geometry as WKT to a bind variable of the query")
+                .clicked()
+            {
+                let value = wkt.unwrap_or_default();
+                match self.input_query.binds.iter_mut().find(|b| b.name == name) {
+                    Some(bind) => bind.value = value,
+                    None => self.input_query.binds.push(Bind {
+                        name: name.clone(),
+                        value,
+                    }),
+                }
+                let srid = self
+                    .editor
+                    .srid
+                    .map_or("NULL".into(), |srid| srid.to_string());
+                self.input_query.message =
+                    RichText::new(format!("Use SDO_GEOMETRY(:{name}, {srid}) in the query"));
+                *self.show_query_window = true;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        editor::{DrawTool, Editor, PlotInput, Sketch, VertexRef},
+        sdo_geometry::SdoGeometry,
+    };
+
+    fn click(editor: &mut Editor, point: [f64; 2]) {
+        editor.handle(&PlotInput {
+            pointer: Some(point),
+            snapped: Some(point),
+            tolerance: 0.1,
+            clicked: true,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_draw_polygon_with_hole() {
+        let mut editor = Editor {
+            srid: Some(8307.),
+            ..Default::default()
+        };
+        for point in [[0., 0.], [10., 0.], [10., 10.], [0., 10.], [0., 0.]] {
+            click(&mut editor, point);
+        }
+        assert!(editor.pending.is_empty());
+
+        editor.tool = DrawTool::Hole;
+        for point in [[2., 2.], [4., 2.], [4., 4.]] {
+            click(&mut editor, point);
+        }
+        editor.finish();
+
+        let sdo_geometry = editor.to_sdo().unwrap();
+        assert_eq!(sdo_geometry.sdo_gtype, 2003.);
+        assert_eq!(sdo_geometry.sdo_srid, Some(8307.));
+        assert_eq!(
+            sdo_geometry.sdo_elem_info,
+            vec![1., 1003., 1., 11., 2003., 1.]
+        );
+        assert!(sdo_geometry.validation_errors().is_empty());
+    }
+
+    #[test]
+    fn test_rectangle_and_circle_keep_optimized_form() {
+        let mut editor = Editor {
+            tool: DrawTool::Rectangle,
+            ..Default::default()
+        };
+        click(&mut editor, [4., 5.]);
+        click(&mut editor, [0., 1.]);
+        let rectangle = editor.to_sdo().unwrap();
+        assert_eq!(rectangle.sdo_elem_info, vec![1., 1003., 3.]);
+        assert_eq!(rectangle.sdo_ordinates, vec![0., 1., 4., 5.]);
+
+        editor.tool = DrawTool::Circle;
+        click(&mut editor, [1., 1.]);
+        click(&mut editor, [1., 3.]);
+        let circle = editor.to_sdo().unwrap();
+        assert_eq!(circle.sdo_elem_info, vec![1., 1003., 4.]);
+        assert!(circle.validation_errors().is_empty());
+        assert_eq!(
+            Sketch::from_sdo(&circle),
+            Some(Sketch::Circle {
+                center: [1., 1.],
+                radius: 2.
+            })
+        );
+        assert_eq!(
+            Sketch::from_sdo(&rectangle),
+            Some(Sketch::Rectangle([0., 1.], [4., 5.]))
+        );
+    }
+
+    #[test]
+    fn test_edit_vertices() {
+        let line = SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 1.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10., 10.],
+        };
+        let mut sketch = Sketch::from_sdo(&line).unwrap();
+
+        let segment = sketch.nearest_segment([5., 0.5], 1.).unwrap();
+        assert_eq!(segment, VertexRef::new(0, 0, 0));
+        sketch.insert_vertex(segment, [5., 0.]);
+        sketch.move_vertex(VertexRef::new(0, 0, 2), [10., 1.]);
+        assert_eq!(
+            sketch,
+            Sketch::Lines(vec![vec![[0., 0.], [5., 0.], [10., 1.], [10., 10.]]])
+        );
+
+        assert!(sketch.delete_vertex(VertexRef::new(0, 0, 0)));
+        assert!(sketch.delete_vertex(VertexRef::new(0, 0, 0)));
+        assert_eq!(sketch, Sketch::Lines(vec![vec![[10., 1.], [10., 10.]]]));
+        // A line needs two vertices, deleting one more removes it
+        assert!(sketch.delete_vertex(VertexRef::new(0, 0, 0)));
+        assert!(sketch.is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use egui::{Button, Color32, Context, DragValue, Layout, RichText, Window};
+
+use crate::{
+    layers::Layers, measure, overlay::AxisFormat, raster::PngRenderer, render::Extent,
+    svg::SvgExporter,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Svg,
+    Png,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+        }
+    }
+}
+
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub include_axes: bool,
+    pub include_legend: bool,
+    pub include_scale_bar: bool,
+    pub include_north_arrow: bool,
+    pub message: RichText,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Svg,
+            path: "geometries.svg".into(),
+            width: 1280,
+            height: 720,
+            include_axes: true,
+            include_legend: true,
+            include_scale_bar: true,
+            include_north_arrow: false,
+            message: RichText::new(""),
+        }
+    }
+}
+
+impl ExportSettings {
+    /// Switches the format and keeps the file extension in sync with it
+    pub fn set_format(&mut self, format: ExportFormat) {
+        self.format = format;
+        let path = std::path::Path::new(&self.path).with_extension(format.extension());
+        self.path = path.to_string_lossy().to_string();
+    }
+}
+
+pub struct ExportWindow<'a> {
+    pub settings: &'a mut ExportSettings,
+    pub layers: &'a Layers,
+    pub plot_bounds: Option<Extent>,
+    /// Format of the plot axes, exported axes look the same
+    pub axis_format: AxisFormat,
+}
+
+impl<'a> ExportWindow<'a> {
+    pub fn new(
+        settings: &'a mut ExportSettings,
+        layers: &'a Layers,
+        plot_bounds: Option<Extent>,
+        axis_format: AxisFormat,
+    ) -> Self {
+        Self {
+            settings,
+            layers,
+            plot_bounds,
+            axis_format,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Export plot")
+            .open(is_active)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    let mut format = self.settings.format;
+                    ui.radio_value(&mut format, ExportFormat::Svg, "SVG");
+                    ui.radio_value(&mut format, ExportFormat::Png, "PNG");
+                    if format != self.settings.format {
+                        self.settings.set_format(format);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("File path:");
+                    ui.text_edit_singleline(&mut self.settings.path);
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Size:");
+                    ui.add(DragValue::new(&mut self.settings.width).clamp_range(16..=8192));
+                    ui.label("x");
+                    ui.add(DragValue::new(&mut self.settings.height).clamp_range(16..=8192));
+                });
+
+                ui.checkbox(&mut self.settings.include_axes, "Include axes");
+                // The PNG has no fonts for layer names, so the legend is only available in SVG
+                ui.add_enabled_ui(self.settings.format == ExportFormat::Svg, |ui| {
+                    ui.checkbox(&mut self.settings.include_legend, "Include legend");
+                });
+                ui.checkbox(&mut self.settings.include_scale_bar, "Include scale bar");
+                ui.checkbox(
+                    &mut self.settings.include_north_arrow,
+                    "Include north arrow",
+                );
+
+                ui.horizontal(|ui| {
+                    ui.label("Message:");
+                    ui.label(self.settings.message.clone());
+                });
+
+                ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                    let export_button = ui.add(Button::new("Export"));
+
+                    if export_button.clicked() {
+                        self.export();
+                    }
+                });
+            });
+    }
+
+    pub fn export(&mut self) {
+        let Some(extent) = self.plot_bounds else {
+            self.settings.message =
+                RichText::new("Plot has not been drawn yet").color(Color32::RED);
+            return;
+        };
+
+        let layers = self.layers.drawn();
+        let srid = measure::display_srid(self.layers);
+
+        let result = match self.settings.format {
+            ExportFormat::Svg => {
+                let mut exporter = SvgExporter::new(extent);
+                exporter.width = self.settings.width;
+                exporter.height = self.settings.height;
+                exporter.include_axes = self.settings.include_axes;
+                exporter.include_legend = self.settings.include_legend;
+                exporter.axis_format = self.axis_format;
+                exporter.include_scale_bar = self.settings.include_scale_bar;
+                exporter.include_north_arrow = self.settings.include_north_arrow;
+                exporter.srid = srid;
+                exporter
+                    .export(layers, &self.settings.path)
+                    .map_err(|e| e.to_string())
+            }
+            ExportFormat::Png => {
+                let mut renderer =
+                    PngRenderer::new(extent, self.settings.width, self.settings.height);
+                renderer.include_axes = self.settings.include_axes;
+                renderer.axis_format = self.axis_format;
+                renderer.include_scale_bar = self.settings.include_scale_bar;
+                renderer.include_north_arrow = self.settings.include_north_arrow;
+                renderer.srid = srid;
+                renderer
+                    .export(layers, &self.settings.path)
+                    .map_err(|e| e.to_string())
+            }
+        };
+
+        self.settings.message = match result {
+            Ok(()) => {
+                RichText::new(format!("Exported to {}", self.settings.path)).color(Color32::GREEN)
+            }
+            Err(e) => RichText::new(format!("Failed to export the plot: {e}")).color(Color32::RED),
+        };
+    }
+}
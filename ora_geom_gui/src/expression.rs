@@ -0,0 +1,664 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use serde_json::Value;
+
+use crate::feature::{as_number, display};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ExpressionError {
+    #[error("Unexpected character `{0}` at position {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("String starting at position {0} is not closed")]
+    UnterminatedString(usize),
+    #[error("Expected {0} but found `{1}`")]
+    Expected(&'static str, String),
+    #[error("Expression ended too early")]
+    UnexpectedEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// Parsed SQL-like expression, e.g. `area > 100 and class in ('RES', 'COM')`.
+///
+/// Names are looked up by the caller, so attributes and computed values like
+/// metrics can be mixed. Comparisons with `NULL` are `NULL` and `NOT`, `AND` and `OR`
+/// use three-valued logic, like in SQL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Name(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    Arithmetic(Box<Expr>, ArithmeticOp, Box<Expr>),
+    /// `%` matches any text and `_` a single character
+    Like {
+        value: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    In {
+        value: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    IsNull {
+        value: Box<Expr>,
+        negated: bool,
+    },
+}
+
+impl FromStr for Expr {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(token) => Err(ExpressionError::Expected("the end", token.to_string())),
+        }
+    }
+}
+
+impl Expr {
+    pub fn evaluate(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Name(name) => lookup(name).unwrap_or(Value::Null),
+            Expr::Negate(expr) => number(as_number(&expr.evaluate(lookup)).map(|n| -n)),
+            Expr::Not(expr) => match truth(&expr.evaluate(lookup)) {
+                Some(value) => Value::Bool(!value),
+                None => Value::Null,
+            },
+            Expr::And(left, right) => {
+                match (
+                    truth(&left.evaluate(lookup)),
+                    truth(&right.evaluate(lookup)),
+                ) {
+                    (Some(false), _) | (_, Some(false)) => Value::Bool(false),
+                    (Some(true), Some(true)) => Value::Bool(true),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Or(left, right) => {
+                match (
+                    truth(&left.evaluate(lookup)),
+                    truth(&right.evaluate(lookup)),
+                ) {
+                    (Some(true), _) | (_, Some(true)) => Value::Bool(true),
+                    (Some(false), Some(false)) => Value::Bool(false),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Compare(left, op, right) => {
+                let (left, right) = (left.evaluate(lookup), right.evaluate(lookup));
+                if left.is_null() || right.is_null() {
+                    return Value::Null;
+                }
+                let ordering = compare(&left, &right);
+                Value::Bool(ordering.is_some_and(|ordering| match op {
+                    CompareOp::Eq => ordering == Ordering::Equal,
+                    CompareOp::NotEq => ordering != Ordering::Equal,
+                    CompareOp::Less => ordering == Ordering::Less,
+                    CompareOp::LessEq => ordering != Ordering::Greater,
+                    CompareOp::Greater => ordering == Ordering::Greater,
+                    CompareOp::GreaterEq => ordering != Ordering::Less,
+                }))
+            }
+            Expr::Arithmetic(left, op, right) => {
+                let left = as_number(&left.evaluate(lookup));
+                let right = as_number(&right.evaluate(lookup));
+                number(left.zip(right).and_then(|(l, r)| match op {
+                    ArithmeticOp::Add => Some(l + r),
+                    ArithmeticOp::Subtract => Some(l - r),
+                    ArithmeticOp::Multiply => Some(l * r),
+                    ArithmeticOp::Divide => (r != 0.).then(|| l / r),
+                }))
+            }
+            Expr::Like {
+                value,
+                pattern,
+                negated,
+            } => match (value.evaluate(lookup), pattern.evaluate(lookup)) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (value, pattern) => {
+                    let chars = display(&value).chars().collect::<Vec<_>>();
+                    let pattern = display(&pattern).chars().collect::<Vec<_>>();
+                    Value::Bool(like(&chars, &pattern) != *negated)
+                }
+            },
+            Expr::In {
+                value,
+                list,
+                negated,
+            } => {
+                let value = value.evaluate(lookup);
+                if value.is_null() {
+                    return Value::Null;
+                }
+                let found = list
+                    .iter()
+                    .any(|item| compare(&value, &item.evaluate(lookup)) == Some(Ordering::Equal));
+                Value::Bool(found != *negated)
+            }
+            Expr::IsNull { value, negated } => {
+                Value::Bool(value.evaluate(lookup).is_null() != *negated)
+            }
+        }
+    }
+
+    /// Evaluates the expression as a condition, `NULL` does not match
+    pub fn matches(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> bool {
+        truth(&self.evaluate(lookup)).unwrap_or(false)
+    }
+
+    /// Names referenced by the expression
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.collect_names(&mut names);
+        names
+    }
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Name(name) => names.push(name),
+            Expr::Negate(expr) | Expr::Not(expr) => expr.collect_names(names),
+            Expr::And(left, right)
+            | Expr::Or(left, right)
+            | Expr::Compare(left, _, right)
+            | Expr::Arithmetic(left, _, right) => {
+                left.collect_names(names);
+                right.collect_names(names);
+            }
+            Expr::Like { value, pattern, .. } => {
+                value.collect_names(names);
+                pattern.collect_names(names);
+            }
+            Expr::In { value, list, .. } => {
+                value.collect_names(names);
+                for item in list {
+                    item.collect_names(names);
+                }
+            }
+            Expr::IsNull { value, .. } => value.collect_names(names),
+        }
+    }
+}
+
+fn number(value: Option<f64>) -> Value {
+    value
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(*b),
+        Value::Number(n) => Some(n.as_f64() != Some(0.)),
+        Value::String(s) => Some(!s.is_empty()),
+        _ => Some(true),
+    }
+}
+
+/// Numbers compare numerically, also against numeric strings, the rest compare as text
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if left.is_null() || right.is_null() {
+        return None;
+    }
+
+    let numeric = matches!(left, Value::Number(_)) || matches!(right, Value::Number(_));
+    match (as_number(left), as_number(right)) {
+        (Some(l), Some(r)) if numeric => l.partial_cmp(&r),
+        _ => Some(display(left).cmp(&display(right))),
+    }
+}
+
+/// Iterative wildcard matching, a `%` only ever backtracks to the last one seen
+fn like(text: &[char], pattern: &[char]) -> bool {
+    let (mut t, mut p) = (0, 0);
+    // Pattern position after the last `%` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(c) if *c == '_' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Name(String),
+    /// Keywords are stored in lower case
+    Keyword(&'static str),
+    Symbol(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Text(s) => write!(f, "'{s}'"),
+            Token::Name(s) => write!(f, "{s}"),
+            Token::Keyword(s) | Token::Symbol(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+const KEYWORDS: [&str; 9] = [
+    "and", "or", "not", "like", "in", "is", "null", "true", "false",
+];
+const SYMBOLS: [&str; 13] = [
+    "<=", ">=", "<>", "!=", "=", "<", ">", "+", "-", "*", "/", "(", ")",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExpressionError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ',' {
+            tokens.push(Token::Symbol(","));
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            // Single quotes are strings, double quotes are names with any characters
+            let start = i;
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ExpressionError::UnterminatedString(start)),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        value.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        value.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(match c {
+                '\'' => Token::Text(value),
+                _ => Token::Name(value),
+            });
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let literal = chars[start..i].iter().collect::<String>();
+            let value = literal
+                .parse()
+                .map_err(|_| ExpressionError::Expected("a number", literal.clone()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>();
+            let lower = word.to_lowercase();
+            match KEYWORDS.iter().find(|k| **k == lower) {
+                Some(keyword) => tokens.push(Token::Keyword(keyword)),
+                None => tokens.push(Token::Name(word)),
+            }
+        } else {
+            let rest = chars[i..].iter().take(2).collect::<String>();
+            match SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(symbol));
+                    i += symbol.len();
+                }
+                None => return Err(ExpressionError::UnexpectedCharacter(c, i)),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser, from the lowest precedence: `or`, `and`, `not`,
+/// comparisons, addition, multiplication, unary minus
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Keyword(k)) if *k == keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &'static str) -> Result<(), ExpressionError> {
+        if self.eat_symbol(symbol) {
+            return Ok(());
+        }
+
+        match self.peek() {
+            Some(token) => Err(ExpressionError::Expected(symbol, token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ExpressionError> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExpressionError> {
+        let mut expr = self.not()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, ExpressionError> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExpressionError> {
+        let left = self.additive()?;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            if !self.eat_keyword("null") {
+                return Err(self.unexpected("NULL"));
+            }
+            return Ok(Expr::IsNull {
+                value: Box::new(left),
+                negated,
+            });
+        }
+
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("like") {
+            return Ok(Expr::Like {
+                value: Box::new(left),
+                pattern: Box::new(self.additive()?),
+                negated,
+            });
+        }
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.additive()?];
+            while self.eat_symbol(",") {
+                list.push(self.additive()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::In {
+                value: Box::new(left),
+                list,
+                negated,
+            });
+        }
+        if negated {
+            return Err(self.unexpected("LIKE or IN"));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Symbol("=")) => CompareOp::Eq,
+            Some(Token::Symbol("!=" | "<>")) => CompareOp::NotEq,
+            Some(Token::Symbol("<")) => CompareOp::Less,
+            Some(Token::Symbol("<=")) => CompareOp::LessEq,
+            Some(Token::Symbol(">")) => CompareOp::Greater,
+            Some(Token::Symbol(">=")) => CompareOp::GreaterEq,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+
+        Ok(Expr::Compare(
+            Box::new(left),
+            op,
+            Box::new(self.additive()?),
+        ))
+    }
+
+    fn additive(&mut self) -> Result<Expr, ExpressionError> {
+        let mut expr = self.multiplicative()?;
+        loop {
+            let op = if self.eat_symbol("+") {
+                ArithmeticOp::Add
+            } else if self.eat_symbol("-") {
+                ArithmeticOp::Subtract
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Arithmetic(Box::new(expr), op, Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ExpressionError> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat_symbol("*") {
+                ArithmeticOp::Multiply
+            } else if self.eat_symbol("/") {
+                ArithmeticOp::Divide
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Arithmetic(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExpressionError> {
+        if self.eat_symbol("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExpressionError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(number(Some(n)))),
+            Some(Token::Text(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Name(name)) => Ok(Expr::Name(name)),
+            Some(Token::Keyword("null")) => Ok(Expr::Literal(Value::Null)),
+            Some(Token::Keyword("true")) => Ok(Expr::Literal(Value::Bool(true))),
+            Some(Token::Keyword("false")) => Ok(Expr::Literal(Value::Bool(false))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.or()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(token) => Err(ExpressionError::Expected("a value", token.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ExpressionError {
+        match self.peek() {
+            Some(token) => ExpressionError::Expected(expected, token.to_string()),
+            None => ExpressionError::UnexpectedEnd,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::expression::{like, Expr, ExpressionError};
+
+    fn lookup(name: &str) -> Option<Value> {
+        match name.to_lowercase().as_str() {
+            "area" => Some(json!(150.5)),
+            "class" => Some(json!("RES")),
+            "code" => Some(json!("042")),
+            "owner" => Some(Value::Null),
+            _ => None,
+        }
+    }
+
+    fn matches(text: &str) -> bool {
+        text.parse::<Expr>().unwrap().matches(&lookup)
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        assert!(matches("area > 100 and class = 'RES'"));
+        assert!(matches("AREA >= 150.5 AND NOT class <> 'RES'"));
+        assert!(!matches("area > 100 and class = 'COM'"));
+        assert!(matches("area < 100 or class = 'RES'"));
+        assert!(matches("area / 2 - 0.25 = 75"));
+        assert!(matches("-area < 0"));
+        // A numeric string compares as a number against numbers
+        assert!(matches("code = 42"));
+        assert!(matches("code = '042'"));
+    }
+
+    #[test]
+    fn test_nulls_like_and_in() {
+        assert!(!matches("owner = 'x'"));
+        assert!(!matches("owner <> 'x'"));
+        assert!(!matches("not owner = 'x'"));
+        assert!(!matches("not (owner = 'x' and class = 'RES')"));
+        assert!(matches("owner = 'x' or class = 'RES'"));
+        assert!(!matches("owner not like 'x%'"));
+        assert!(!matches("owner not in ('x')"));
+        assert!(matches("owner is null and class is not null"));
+        assert!(matches("missing is null"));
+        assert!(matches("class like 'R%'"));
+        assert!(matches("class like '_E_'"));
+        assert!(matches("class not like '%COM%'"));
+        assert!(matches("class in ('COM', 'RES')"));
+        assert!(matches("area not in (1, 2, 3)"));
+    }
+
+    #[test]
+    fn test_like() {
+        let chars = |text: &str| text.chars().collect::<Vec<_>>();
+        let like = |text: &str, pattern: &str| like(&chars(text), &chars(pattern));
+
+        assert!(like("", "%"));
+        assert!(like("abc", "a%c"));
+        assert!(like("abcbc", "%b_"));
+        assert!(like("a%c", "a%%"));
+        assert!(!like("abc", "a_"));
+        assert!(!like("abd", "%c%"));
+        // Backtracking would take exponential time on this one
+        assert!(!like(&"a".repeat(60), &format!("{}b", "%a".repeat(30))));
+    }
+
+    #[test]
+    fn test_names() {
+        let expr = "\"Land use\" = 'x' or area * 2 > width"
+            .parse::<Expr>()
+            .unwrap();
+
+        assert_eq!(expr.names(), vec!["Land use", "area", "width"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "class = 'RES".parse::<Expr>(),
+            Err(ExpressionError::UnterminatedString(8))
+        );
+        assert_eq!(
+            "area > ".parse::<Expr>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "area > 1 2".parse::<Expr>(),
+            Err(ExpressionError::Expected("the end", "2".into()))
+        );
+        assert!(matches!(
+            "area # 1".parse::<Expr>(),
+            Err(ExpressionError::UnexpectedCharacter('#', 5))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::sdo_geometry::SdoGeometry;
+
+/// Non-geometry columns of a row in the order of the query
+pub type Attributes = serde_json::Map<String, Value>;
+
+/// A row returned by the API: a geometry with the other columns of the query
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Feature {
+    #[serde(flatten)]
+    pub geometry: SdoGeometry,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    pub attributes: Attributes,
+}
+
+impl From<SdoGeometry> for Feature {
+    fn from(geometry: SdoGeometry) -> Self {
+        Self {
+            geometry,
+            attributes: Attributes::new(),
+        }
+    }
+}
+
+/// Looks up an attribute ignoring the case, Oracle returns column names in upper case
+pub fn attribute<'a>(attributes: &'a Attributes, name: &str) -> Option<&'a Value> {
+    attributes.get(name).or_else(|| {
+        attributes
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    })
+}
+
+/// Numeric value of numbers and numeric strings
+pub fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Text shown for a value, strings are not quoted
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("NULL"),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::feature::{as_number, attribute, display, Feature};
+
+    #[test]
+    fn test_feature_from_api_row() {
+        let feature: Feature = serde_json::from_value(json!({
+            "sdo_gtype": 2001.0,
+            "sdo_srid": null,
+            "sdo_point": {"x": 1.0, "y": 2.0, "z": null},
+            "sdo_elem_info": [],
+            "sdo_ordinates": [],
+            "attributes": {"ID": 7, "CLASS": "RES", "AREA": "12.5"}
+        }))
+        .unwrap();
+
+        assert_eq!(feature.geometry.sdo_gtype, 2001.);
+        assert_eq!(
+            feature.attributes.keys().collect::<Vec<_>>(),
+            vec!["ID", "CLASS", "AREA"]
+        );
+        assert_eq!(
+            attribute(&feature.attributes, "class").map(display),
+            Some("RES".into())
+        );
+        assert_eq!(
+            attribute(&feature.attributes, "area").and_then(as_number),
+            Some(12.5)
+        );
+    }
+
+    #[test]
+    fn test_feature_without_attributes() {
+        let feature: Feature = serde_json::from_value(json!({
+            "sdo_gtype": 2002.0,
+            "sdo_srid": 8307.0,
+            "sdo_point": null,
+            "sdo_elem_info": [1.0, 2.0, 1.0],
+            "sdo_ordinates": [0.0, 0.0, 1.0, 1.0]
+        }))
+        .unwrap();
+
+        assert!(feature.attributes.is_empty());
+        assert!(!serde_json::to_string(&feature)
+            .unwrap()
+            .contains("attributes"));
+    }
+}
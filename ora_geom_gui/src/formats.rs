@@ -0,0 +1,225 @@
+use std::{path::Path, str::FromStr};
+
+use geojson::{Feature, FeatureCollection, GeoJson};
+use wkt::{ToWkt, TryFromWkt};
+
+use crate::sdo_geometry::SdoGeometry;
+
+#[derive(thiserror::Error, Debug)]
+pub enum FormatError {
+    #[error("Unknown format: {0}")]
+    UnknownFormat(String),
+    #[error("Failed to parse WKT on line {0}: {1}")]
+    Wkt(usize, String),
+    #[error("Failed to parse GeoJSON: {0}")]
+    GeoJson(Box<geojson::Error>),
+    #[error("Failed to parse SDO JSON: {0}")]
+    SdoJson(#[from] serde_json::Error),
+    #[error("Geometry {0} has no drawable elements")]
+    EmptyGeometry(usize),
+}
+
+impl From<geojson::Error> for FormatError {
+    fn from(err: geojson::Error) -> Self {
+        Self::GeoJson(Box::new(err))
+    }
+}
+
+/// Text formats that geometries can be read from and written to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One geometry per line, optionally prefixed with `SRID=<srid>;`
+    Wkt,
+    /// A feature collection, a single feature or a bare geometry
+    GeoJson,
+    /// A JSON array in the same shape as the API responses
+    SdoJson,
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "wkt" => Ok(Format::Wkt),
+            "geojson" => Ok(Format::GeoJson),
+            "sdo-json" | "sdo" | "json" => Ok(Format::SdoJson),
+            _ => Err(FormatError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl Format {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, FormatError> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        match extension.to_lowercase().as_str() {
+            "wkt" | "txt" => Ok(Format::Wkt),
+            "geojson" => Ok(Format::GeoJson),
+            "json" => Ok(Format::SdoJson),
+            _ => Err(FormatError::UnknownFormat(extension)),
+        }
+    }
+
+    pub fn read(&self, text: &str) -> Result<Vec<SdoGeometry>, FormatError> {
+        match self {
+            Format::Wkt => read_wkt(text),
+            Format::GeoJson => read_geojson(text),
+            Format::SdoJson => Ok(serde_json::from_str(text)?),
+        }
+    }
+
+    /// Writes geometries, `srid` overrides their own SRID when it is set
+    pub fn write(
+        &self,
+        geometries: &[SdoGeometry],
+        srid: Option<f32>,
+    ) -> Result<String, FormatError> {
+        match self {
+            Format::Wkt => write_wkt(geometries, srid),
+            Format::GeoJson => write_geojson(geometries),
+            Format::SdoJson => {
+                let geometries = geometries
+                    .iter()
+                    .cloned()
+                    .map(|mut g| {
+                        g.sdo_srid = srid.or(g.sdo_srid);
+                        g
+                    })
+                    .collect::<Vec<_>>();
+                Ok(serde_json::to_string_pretty(&geometries)?)
+            }
+        }
+    }
+}
+
+fn read_wkt(text: &str) -> Result<Vec<SdoGeometry>, FormatError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            let line = line.trim();
+            let (srid, wkt) = match line.split_once(';') {
+                Some((prefix, wkt)) if prefix.to_uppercase().starts_with("SRID=") => {
+                    let srid = prefix[5..]
+                        .parse::<f32>()
+                        .map_err(|e| FormatError::Wkt(n + 1, e.to_string()))?;
+                    (Some(srid), wkt)
+                }
+                _ => (None, line),
+            };
+
+            let geometry = geo::Geometry::<f64>::try_from_wkt_str(wkt)
+                .map_err(|e| FormatError::Wkt(n + 1, e.to_string()))?;
+            Ok(SdoGeometry::from_geo(&geometry, srid))
+        })
+        .collect()
+}
+
+fn write_wkt(geometries: &[SdoGeometry], srid: Option<f32>) -> Result<String, FormatError> {
+    let mut lines = vec![];
+    for (n, sdo_geometry) in geometries.iter().enumerate() {
+        let geometry = sdo_geometry.to_geo().ok_or(FormatError::EmptyGeometry(n))?;
+        let wkt = geometry.wkt_string();
+
+        match srid.or(sdo_geometry.sdo_srid) {
+            Some(srid) => lines.push(format!("SRID={srid};{wkt}")),
+            None => lines.push(wkt),
+        }
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+fn read_geojson(text: &str) -> Result<Vec<SdoGeometry>, FormatError> {
+    let geometries = match text.parse::<GeoJson>()? {
+        GeoJson::Geometry(geometry) => vec![geometry],
+        GeoJson::Feature(feature) => feature.geometry.into_iter().collect(),
+        GeoJson::FeatureCollection(collection) => collection
+            .features
+            .into_iter()
+            .filter_map(|f| f.geometry)
+            .collect(),
+    };
+
+    geometries
+        .iter()
+        .map(|g| {
+            let geometry = geo::Geometry::<f64>::try_from(&g.value)?;
+            Ok(SdoGeometry::from_geo(&geometry, None))
+        })
+        .collect()
+}
+
+fn write_geojson(geometries: &[SdoGeometry]) -> Result<String, FormatError> {
+    let mut features = vec![];
+    for (n, sdo_geometry) in geometries.iter().enumerate() {
+        let geometry = sdo_geometry.to_geo().ok_or(FormatError::EmptyGeometry(n))?;
+        features.push(Feature {
+            geometry: Some(geojson::Geometry::new(geojson::Value::from(&geometry))),
+            ..Default::default()
+        });
+    }
+
+    let collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+
+    Ok(GeoJson::from(collection).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{formats::Format, sdo_geometry::SdoGeometry};
+
+    fn square() -> SdoGeometry {
+        SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: Some(3059.),
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 1.],
+            sdo_ordinates: vec![0., 0., 1., 0., 1., 1., 0., 1., 0., 0.],
+        }
+    }
+
+    #[test]
+    fn test_wkt_round_trip() {
+        let wkt = Format::Wkt.write(&[square()], None).unwrap();
+
+        assert_eq!(wkt, "SRID=3059;POLYGON((0 0,1 0,1 1,0 1,0 0))\n");
+        assert_eq!(Format::Wkt.read(&wkt).unwrap(), vec![square()]);
+    }
+
+    #[test]
+    fn test_geojson_round_trip() {
+        let geojson = Format::GeoJson.write(&[square()], None).unwrap();
+        let geometries = Format::GeoJson.read(&geojson).unwrap();
+
+        assert_eq!(geometries.len(), 1);
+        assert_eq!(geometries[0].sdo_ordinates, square().sdo_ordinates);
+        assert_eq!(geometries[0].sdo_elem_info, square().sdo_elem_info);
+    }
+
+    #[test]
+    fn test_sdo_json_srid_override() {
+        let json = Format::SdoJson.write(&[square()], Some(8307.)).unwrap();
+        let geometries = Format::SdoJson.read(&json).unwrap();
+
+        assert_eq!(geometries[0].sdo_srid, Some(8307.));
+    }
+
+    #[test]
+    fn test_wkt_parse_error_has_line() {
+        let error = Format::Wkt
+            .read("POINT(1 2)\n\nPOLYGON((0 0, 1")
+            .unwrap_err();
+
+        assert!(error.to_string().contains("line 3"));
+    }
+}
@@ -1,31 +1,64 @@
+use std::time::Duration;
+
 use eframe::App;
 use egui::{
-    ahash::HashMap, Align, Button, CollapsingHeader, Color32, Frame, Hyperlink, Layout, Response,
-    RichText, SidePanel, Ui, Visuals, Window,
+    collapsing_header::CollapsingState, epaint::RectShape, Align, Button, Color32, ComboBox,
+    CursorIcon, Frame, Hyperlink, Key, KeyboardShortcut, Label, Layout, Modifiers, PointerButton,
+    Rect, Response, RichText, Rounding, Sense, Shape, SidePanel, Ui, Visuals, Window,
 };
-use egui_plot::Plot;
+use egui_plot::{Plot, PlotBounds, PlotPoint, Text};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::GeometryApi,
-    query::{InputQuery, Query, QueryWindow},
+    compare::{CompareWindow, Comparison},
+    config::CONFY_APP,
+    crs::{self, Reprojection},
+    editor::{self, Editor, EditorWindow, PlotInput, Sketch},
+    export::{ExportFormat, ExportSettings, ExportWindow},
+    expression::Expr,
+    history::{QueryHistory, QueryLibrary},
+    inspector::InspectorWindow,
+    label::{self, LabelStyle},
+    layers::{Layer, LayerGroup, Layers, Solo},
+    location::{LocationQuery, LocationWindow},
+    lod::Viewport,
+    measure::{self, MeasureWindow, Measurement},
+    operation::{OperationSettings, OperationWindow},
+    overlay::{Axis, AxisFormat, Overlay, PainterCanvas},
+    query::{self, InputQuery, QueryWindow},
+    render::{self, Extent, ScreenTransform},
+    selection::{self, GeometryRef, Selection},
+    snap,
+    style::{self, LayerStyle},
+    table::{AttributeTable, AttributeTableWindow},
+    theme::{self, Theme},
+    undo::{Arrangement, Change, UndoStack, Visibility},
+    view::ViewHistory,
+    workspace::{WorkspaceSettings, WorkspaceWindow},
 };
 
 const PADDING: f32 = 15.0;
-const CONFY_APP: &'static str = "oracle_geometry_viewer";
-const CONFY_CONFIG: &'static str = "geometry_viewer_config";
+const CONFY_CONFIG: &str = "geometry_viewer_config";
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct GeometryViewerConfig {
     pub is_dark_mode: bool,
     pub api: GeometryApi,
+    #[serde(default)]
+    pub recent_workspaces: Vec<String>,
+    #[serde(default)]
+    pub overlay: Overlay,
 }
 
-impl Default for GeometryViewerConfig {
-    fn default() -> Self {
-        Self {
-            is_dark_mode: Default::default(),
-            api: GeometryApi::new(),
+impl GeometryViewerConfig {
+    pub fn load() -> Self {
+        confy::load(CONFY_APP, CONFY_CONFIG).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = confy::store(CONFY_APP, CONFY_CONFIG, self) {
+            tracing::error!("Failed saving app state: {}", e);
         }
     }
 }
@@ -34,22 +67,90 @@ pub struct GeometryViewer {
     pub config: GeometryViewerConfig,
     pub show_api_config_window: bool,
     pub show_query_window: bool,
+    pub show_export_window: bool,
+    pub show_workspace_window: bool,
     pub connection_status: RichText,
-    pub queries: HashMap<String, Query>,
+    /// Result of the last layer refresh
+    pub layer_message: RichText,
+    pub layers: Layers,
+    /// Name typed for a new layer group
+    pub new_group: String,
+    /// Text the geometries listed in the side panel are searched for
+    pub geometry_search: String,
+    /// Side panel entry that is being dragged
+    dragged: Option<Dragged>,
     pub input_query: InputQuery,
+    pub history: QueryHistory,
+    pub library: QueryLibrary,
+    pub export_settings: ExportSettings,
+    pub workspace_settings: WorkspaceSettings,
+    pub plot_bounds: Option<Extent>,
+    pub view: ViewHistory,
+    pub selection: Selection,
+    pub show_inspector_window: bool,
+    pub location: LocationQuery,
+    pub show_location_window: bool,
+    pub operation: OperationSettings,
+    pub show_operation_window: bool,
+    pub attribute_table: AttributeTable,
+    pub show_attribute_table_window: bool,
+    pub comparison: Comparison,
+    pub show_compare_window: bool,
+    pub tool: Tool,
+    pub measurement: Measurement,
+    pub editor: Editor,
+    pub undo: UndoStack,
+    /// Sketch at the end of the last frame, changes to it are recorded for undo
+    sketch_before: Option<Sketch>,
+    /// Count and size of the cached responses, read again after changes to the cache
+    cache_usage: Option<(usize, u64)>,
+}
+
+impl Default for GeometryViewer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GeometryViewer {
     pub fn new() -> Self {
-        let config = confy::load(CONFY_APP, CONFY_CONFIG).unwrap_or_default();
+        let config = GeometryViewerConfig::load();
 
         Self {
             config,
             show_api_config_window: true,
             show_query_window: false,
+            show_export_window: false,
+            show_workspace_window: false,
             connection_status: RichText::new("No test done").color(Color32::LIGHT_YELLOW),
-            queries: HashMap::default(),
+            layer_message: RichText::new(""),
+            layers: Layers::default(),
+            new_group: String::new(),
+            geometry_search: String::new(),
+            dragged: None,
             input_query: InputQuery::default(),
+            history: QueryHistory::load(),
+            library: QueryLibrary::load(),
+            export_settings: ExportSettings::default(),
+            workspace_settings: WorkspaceSettings::default(),
+            plot_bounds: None,
+            view: ViewHistory::default(),
+            selection: Selection::default(),
+            show_inspector_window: false,
+            location: LocationQuery::default(),
+            show_location_window: false,
+            operation: OperationSettings::default(),
+            show_operation_window: false,
+            attribute_table: AttributeTable::default(),
+            show_attribute_table_window: false,
+            comparison: Comparison::default(),
+            show_compare_window: false,
+            tool: Tool::default(),
+            measurement: Measurement::default(),
+            editor: Editor::default(),
+            undo: UndoStack::default(),
+            sketch_before: None,
+            cache_usage: None,
         }
     }
 
@@ -97,99 +198,614 @@ impl GeometryViewer {
 
         ui.add_space(PADDING);
 
-        ui.with_layout(Layout::left_to_right(egui::Align::Min), |ui| {
-            let btn_emoji: RichText;
-            let btn_description: RichText;
+        ui.with_layout(
+            Layout::left_to_right(egui::Align::Min).with_main_wrap(true),
+            |ui| {
+                let btn_emoji: RichText;
+                let btn_description: RichText;
 
-            if self.config.is_dark_mode {
-                btn_emoji = RichText::new("🔆").text_style(egui::TextStyle::Body);
-                btn_description =
-                    RichText::new("Switch to light mode").text_style(egui::TextStyle::Body);
-            } else {
-                btn_emoji = RichText::new("🌙").text_style(egui::TextStyle::Body);
-                btn_description =
-                    RichText::new("Switch to dark mode").text_style(egui::TextStyle::Body);
-            }
+                if self.config.is_dark_mode {
+                    btn_emoji = RichText::new("🔆").text_style(egui::TextStyle::Body);
+                    btn_description =
+                        RichText::new("Switch to light mode").text_style(egui::TextStyle::Body);
+                } else {
+                    btn_emoji = RichText::new("🌙").text_style(egui::TextStyle::Body);
+                    btn_description =
+                        RichText::new("Switch to dark mode").text_style(egui::TextStyle::Body);
+                }
 
-            let theme_btn = ui
-                .add(Button::new(btn_emoji))
-                .on_hover_text(btn_description);
+                let theme_btn = ui
+                    .add(Button::new(btn_emoji))
+                    .on_hover_text(btn_description);
 
-            if theme_btn.clicked() {
-                self.config.is_dark_mode = !self.config.is_dark_mode;
-                self.save_config();
-            }
+                if theme_btn.clicked() {
+                    self.config.is_dark_mode = !self.config.is_dark_mode;
+                    self.save_config();
+                }
 
-            let api_url_button = ui.add(Button::new("API configuration"));
+                let api_url_button = ui.add(Button::new("API configuration"));
 
-            if api_url_button.clicked() {
-                self.show_api_config_window = true;
-            }
+                if api_url_button.clicked() {
+                    self.show_api_config_window = true;
+                }
 
-            let geometry_button = ui.add(Button::new("Add geometry"));
+                let geometry_button = ui.add(Button::new("Add geometry"));
 
-            if geometry_button.clicked() {
-                self.show_query_window = true;
-            }
-        });
+                if geometry_button.clicked() {
+                    self.input_query.editing = None;
+                    self.show_query_window = true;
+                }
+
+                ui.menu_button("Export", |ui| {
+                    if ui.button("Export as SVG").clicked() {
+                        self.export_settings.set_format(ExportFormat::Svg);
+                        self.show_export_window = true;
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Export as PNG").clicked() {
+                        self.export_settings.set_format(ExportFormat::Png);
+                        self.show_export_window = true;
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Workspace", |ui| {
+                    if ui.button("Save or open workspace").clicked() {
+                        self.show_workspace_window = true;
+                        ui.close_menu();
+                    }
+
+                    ui.add_enabled_ui(!self.config.recent_workspaces.is_empty(), |ui| {
+                        ui.menu_button("Recent workspaces", |ui| {
+                            for path in self.config.recent_workspaces.clone() {
+                                if ui.button(&path).clicked() {
+                                    WorkspaceWindow::new(
+                                        &mut self.workspace_settings,
+                                        &mut self.layers,
+                                        &mut self.config,
+                                        self.plot_bounds,
+                                        &mut self.view,
+                                        &mut self.undo,
+                                    )
+                                    .open(&path);
+                                    self.show_workspace_window = true;
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                });
+
+                ui.menu_button("View", |ui| {
+                    self.view_menu(ui);
+                });
+
+                ui.menu_button("Select", |ui| {
+                    if ui
+                        .button("Select by location")
+                        .on_hover_text("Select geometries by their relationship to another layer")
+                        .clicked()
+                    {
+                        self.show_location_window = true;
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .button("Geometry operations")
+                        .on_hover_text("Union, intersection, difference, buffer or convex hull of the selection into a new layer")
+                        .clicked()
+                    {
+                        self.show_operation_window = true;
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .button("Compare layers")
+                        .on_hover_text("Added, removed and changed geometries between two layers")
+                        .clicked()
+                    {
+                        self.show_compare_window = true;
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .add_enabled(!self.selection.is_empty(), Button::new("Clear selection"))
+                        .clicked()
+                    {
+                        self.selection.clear();
+                        ui.close_menu();
+                    }
+                });
+            },
+        );
 
         ui.add_space(PADDING);
 
+        self.history_list(ui);
         self.geometry_list(ui);
     }
 
+    /// Undo and redo buttons with the recorded operations, clicking one goes back or forward to it
+    fn history_list(&mut self, ui: &mut Ui) {
+        let mut steps: isize = 0;
+
+        ui.collapsing("History", |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.undo.can_undo(), Button::new("⟲ Undo"))
+                    .on_hover_text("Ctrl+Z")
+                    .clicked()
+                {
+                    steps = -1;
+                }
+                if ui
+                    .add_enabled(self.undo.can_redo(), Button::new("⟳ Redo"))
+                    .on_hover_text("Ctrl+Shift+Z")
+                    .clicked()
+                {
+                    steps = 1;
+                }
+            });
+
+            egui::ScrollArea::vertical()
+                .id_source("undo_history")
+                .max_height(150.)
+                .show(ui, |ui| {
+                    let done = self.undo.done().collect::<Vec<_>>();
+                    for (n, label) in done.iter().enumerate() {
+                        let is_current = n + 1 == done.len();
+                        if ui.selectable_label(is_current, *label).clicked() {
+                            steps = n as isize + 1 - done.len() as isize;
+                        }
+                    }
+                    for (n, label) in self.undo.undone().enumerate() {
+                        let label = RichText::new(label).weak();
+                        if ui.selectable_label(false, label).clicked() {
+                            steps = n as isize + 1;
+                        }
+                    }
+                    if done.is_empty() && !self.undo.can_redo() {
+                        ui.label("Nothing to undo");
+                    }
+                });
+        });
+
+        for _ in steps..0 {
+            self.undo();
+        }
+        for _ in 0..steps {
+            self.redo();
+        }
+    }
+
+    pub fn undo(&mut self) {
+        self.undo.undo(&mut self.layers, &mut self.editor);
+        self.sketch_before = self.editor.sketch.clone();
+    }
+
+    pub fn redo(&mut self) {
+        self.undo.redo(&mut self.layers, &mut self.editor);
+        self.sketch_before = self.editor.sketch.clone();
+    }
+
     pub fn geometry_list(&mut self, ui: &mut Ui) {
         let scroll = egui::ScrollArea::vertical().auto_shrink([false, true]);
-        let mut to_remove: Option<String> = None;
+        let mut action: Option<LayerAction> = None;
+        let mut targets: Vec<(Rect, DropTarget)> = vec![];
+
+        if !self.layer_message.text().is_empty() {
+            ui.label(self.layer_message.clone());
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Layers:");
+            if ui.button("Show all").clicked() {
+                self.change_visibility(|layers| layers.set_all_visible(true));
+            }
+            if ui.button("Hide all").clicked() {
+                self.change_visibility(|layers| layers.set_all_visible(false));
+            }
+            if ui.button("Invert").clicked() {
+                self.change_visibility(Layers::invert_visibility);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_group)
+                    .hint_text("group name")
+                    .desired_width(150.),
+            );
+            if ui.button("New group").clicked() {
+                let before = Arrangement::of(&self.layers);
+                if self.layers.add_group(&self.new_group) {
+                    self.undo.push_arrangement(before, &self.layers);
+                    self.new_group.clear();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.geometry_search)
+                    .hint_text("search geometries")
+                    .desired_width(150.),
+            )
+            .on_hover_text("Lists only geometries whose name or an attribute contains the text");
+            if ui
+                .add_enabled(!self.geometry_search.is_empty(), Button::new("Clear"))
+                .clicked()
+            {
+                self.geometry_search.clear();
+            }
+        });
+
         scroll.show(ui, |ui| {
-            for (name, query) in self.queries.iter_mut() {
-                CollapsingHeader::new(name).show(ui, |ui| {
-                    egui::stroke_ui(ui, &mut query.stroke, "Curve Stroke");
-                    ui.collapsing("Geometries", |ui| {
-                        for geometry in query.geometries.iter_mut() {
-                            ui.horizontal_wrapped(|ui| {
-                                ui.checkbox(&mut geometry.is_active, geometry.name.clone());
-                            });
-                        }
+            let groups = self.layers.groups.clone();
+            let solo = self.layers.solo.clone();
+            let options = LayerListOptions {
+                view_width: self.plot_bounds.map(|bounds| bounds.width()),
+                search: &self.geometry_search,
+            };
 
-                        let toggle_button = ui.add(Button::new("Show all"));
-                        if toggle_button.clicked() {
-                            for geometry in query.geometries.iter_mut() {
-                                geometry.is_active = true;
-                            }
-                        }
-                    });
-                    let delete_button = ui.add(Button::new(
-                        RichText::new("Delete objects").color(Color32::RED),
-                    ));
+            for (group, range) in self.layers.blocks() {
+                if group.is_empty() {
+                    for n in range {
+                        let layer = &mut self.layers.layers[n];
+                        let rect = layer_ui(ui, n, layer, &groups, &solo, &options, &mut action);
+                        targets.push((rect, DropTarget::Layer(n)));
+                    }
+                    continue;
+                }
 
-                    if delete_button.clicked() {
-                        to_remove = Some(name.clone());
+                let Some(layer_group) = groups.iter().find(|g| g.name == group) else {
+                    continue;
+                };
+                let layers = &mut self.layers.layers[range.clone()];
+                let mut members = vec![];
+                let (header, header_action) = group_ui(ui, layer_group, &solo, |ui| {
+                    for (layer, n) in layers.iter_mut().zip(range) {
+                        let rect = layer_ui(ui, n, layer, &groups, &solo, &options, &mut action);
+                        members.push((rect, DropTarget::Layer(n)));
                     }
                 });
+                targets.push((header, DropTarget::Group(group.clone())));
+                targets.extend(members);
+                if header_action.is_some() {
+                    action = header_action;
+                }
+            }
+
+            let empty_groups = self.layers.empty_groups().cloned().collect::<Vec<_>>();
+            for layer_group in empty_groups {
+                let (header, header_action) = group_ui(ui, &layer_group, &solo, |ui| {
+                    ui.label("Drop layers here");
+                });
+                targets.push((header, DropTarget::Group(layer_group.name.clone())));
+                if header_action.is_some() {
+                    action = header_action;
+                }
             }
+
+            let (_, end) = ui.allocate_space(egui::vec2(ui.available_width(), PADDING));
+            targets.push((end, DropTarget::End));
         });
 
-        if let Some(name) = to_remove {
-            // From https://github.com/emilk/egui/blob/master/crates/egui_demo_lib/src/demo/misc_demo_window.rs#L463-L474
-            self.queries = std::mem::take(&mut self.queries)
-                .into_iter()
-                .filter(|(n, _q)| *n != name)
-                .collect();
+        self.drag_and_drop(ui, &mut action, &targets);
+
+        match action {
+            Some(LayerAction::Edit(name)) => {
+                if let Some(layer) = self.layers.get(&name) {
+                    self.input_query.edit(&name, &layer.query);
+                    self.show_query_window = true;
+                }
+            }
+            Some(LayerAction::Refresh(name)) => self.refresh_layer(&name),
+            Some(LayerAction::Remove(name)) => {
+                if let Some((n, layer)) = self.layers.position(&name).zip(self.layers.remove(&name))
+                {
+                    self.undo.push(
+                        format!("Delete layer {name}"),
+                        Change::InsertLayer(n, Box::new(layer)),
+                    );
+                }
+            }
+            Some(LayerAction::SetGroup(n, group)) => {
+                self.arrange(|layers| layers.set_group(n, &group))
+            }
+            Some(LayerAction::RemoveGroup(group)) => {
+                self.arrange(|layers| layers.remove_group(&group))
+            }
+            Some(LayerAction::Solo(solo)) => {
+                self.change_visibility(|layers| layers.toggle_solo(solo))
+            }
+            Some(LayerAction::ShowLayer(name, is_visible)) => self.change_visibility(|layers| {
+                if let Some(layer) = layers.get_mut(&name) {
+                    layer.is_visible = is_visible;
+                }
+            }),
+            Some(LayerAction::ShowGroup(name, is_visible)) => self.change_visibility(|layers| {
+                if let Some(group) = layers.group_mut(&name) {
+                    group.is_visible = is_visible;
+                }
+            }),
+            Some(LayerAction::ShowGeometries(name, active)) => self.change_visibility(|layers| {
+                let Some(layer) = layers.get_mut(&name) else {
+                    return;
+                };
+                for (n, is_active) in active {
+                    layer.query.set_active(n, is_active);
+                }
+            }),
+            Some(LayerAction::Restyled(name, revert)) => self.undo.push_merged(
+                format!("Style of {name}"),
+                &format!("style {name}"),
+                *revert,
+                ui.input(|i| i.time),
+            ),
+            Some(LayerAction::Drag(dragged)) => self.dragged = Some(dragged),
+            Some(LayerAction::Zoom(extent)) => self.view.zoom_to(extent),
+            Some(LayerAction::Inspect(item)) => {
+                self.selection.select(item);
+                self.show_inspector_window = true;
+            }
+            Some(LayerAction::LookUpSrid(srid)) => self.look_up_srid(srid),
+            Some(LayerAction::OpenTable(name)) => {
+                self.attribute_table.open(&name);
+                self.show_attribute_table_window = true;
+            }
+            Some(LayerAction::EditGeometry(item)) => {
+                if let Some(geometry) = item.resolve(&self.layers) {
+                    let sdo_geometry = geometry.display_sdo_geometry().into_owned();
+                    if self.editor.edit(item, &sdo_geometry) {
+                        self.tool = Tool::Edit;
+                    }
+                }
+            }
+            None => {}
         }
     }
 
-    pub fn save_config(&mut self) {
-        if let Err(e) = confy::store(
-            CONFY_APP,
-            CONFY_CONFIG,
-            GeometryViewerConfig {
-                is_dark_mode: self.config.is_dark_mode,
-                api: self.config.api.clone(),
-            },
-        ) {
-            tracing::error!("Failed saving app state: {}", e);
+    /// Changes the layer order or groups and records it for undo
+    fn arrange(&mut self, change: impl FnOnce(&mut Layers)) {
+        let before = Arrangement::of(&self.layers);
+        change(&mut self.layers);
+        self.undo.push_arrangement(before, &self.layers);
+    }
+
+    /// Changes the visibility of layers, groups or geometries and records it for undo
+    fn change_visibility(&mut self, change: impl FnOnce(&mut Layers)) {
+        let before = Visibility::of(&self.layers);
+        change(&mut self.layers);
+        self.undo.push_visibility(before, &self.layers);
+    }
+
+    /// Shows where the dragged entry would go and moves it once the pointer is released
+    fn drag_and_drop(
+        &mut self,
+        ui: &mut Ui,
+        action: &mut Option<LayerAction>,
+        targets: &[(Rect, DropTarget)],
+    ) {
+        let Some(dragged) = self.dragged.clone() else {
+            return;
+        };
+
+        ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        let target = ui.input(|i| i.pointer.interact_pos()).and_then(|pos| {
+            targets
+                .iter()
+                .find(|(rect, _)| rect.y_range().contains(pos.y))
+        });
+
+        if let Some((rect, _)) = target {
+            ui.painter()
+                .hline(rect.x_range(), rect.top(), ui.visuals().selection.stroke);
+        }
+
+        if ui.input(|i| i.pointer.any_down()) {
+            return;
+        }
+
+        self.dragged = None;
+        let Some((_, target)) = target else {
+            return;
+        };
+
+        // A dragged entry takes the place of the target, an action of the same frame is dropped
+        *action = None;
+        let before = Arrangement::of(&self.layers);
+        let first_of = |group: &str| self.layers.layers.iter().position(|l| l.group == group);
+        match (dragged, target) {
+            (Dragged::Layer(from), DropTarget::Layer(to)) => {
+                let group = self.layers.layers[*to].group.clone();
+                self.layers.move_layer(from, *to, &group);
+            }
+            (Dragged::Layer(from), DropTarget::Group(group)) => {
+                let to = first_of(group).unwrap_or(self.layers.len());
+                self.layers.move_layer(from, to, group);
+            }
+            (Dragged::Layer(from), DropTarget::End) => {
+                self.layers.move_layer(from, self.layers.len(), "");
+            }
+            (Dragged::Group(group), DropTarget::Layer(to)) => self.layers.move_group(&group, *to),
+            (Dragged::Group(group), DropTarget::Group(other)) => {
+                let to = first_of(other).unwrap_or(self.layers.len());
+                self.layers.move_group(&group, to);
+            }
+            (Dragged::Group(group), DropTarget::End) => {
+                self.layers.move_group(&group, self.layers.len())
+            }
         }
+        self.undo.push_arrangement(before, &self.layers);
+    }
+
+    fn view_menu(&mut self, ui: &mut Ui) {
+        if ui
+            .add_enabled(self.view.can_go_back(), Button::new("Back"))
+            .on_hover_text("Alt + ←")
+            .clicked()
+        {
+            self.view.go_back();
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(self.view.can_go_forward(), Button::new("Forward"))
+            .on_hover_text("Alt + →")
+            .clicked()
+        {
+            self.view.go_forward();
+            ui.close_menu();
+        }
+
+        ui.separator();
+
+        let all = self
+            .layers
+            .drawn()
+            .filter_map(|(_, query)| query.extent())
+            .reduce(|a, b| a.union(&b));
+        let clicked = ui
+            .add_enabled(all.is_some(), Button::new("Zoom to all layers"))
+            .clicked();
+        if let Some(extent) = all.filter(|_| clicked) {
+            self.view.zoom_to(extent);
+            ui.close_menu();
+        }
+
+        let selected = self.selection.extent(&self.layers);
+        let clicked = ui
+            .add_enabled(selected.is_some(), Button::new("Zoom to selection"))
+            .clicked();
+        if let Some(extent) = selected.filter(|_| clicked) {
+            self.view.zoom_to(extent);
+            ui.close_menu();
+        }
+
+        ui.menu_button("Axis labels", |ui| {
+            for format in AxisFormat::ALL {
+                let overlay = &mut self.config.overlay;
+                if ui
+                    .radio_value(&mut overlay.axis_format, format, format.label())
+                    .clicked()
+                {
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.checkbox(&mut self.config.overlay.scale_bar, "Scale bar");
+        ui.checkbox(&mut self.config.overlay.north_arrow, "North arrow");
+
+        ui.menu_button("Display CRS", |ui| {
+            let mut srid = self.layers.display_srid;
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    ui.selectable_value(&mut srid, None, "Native coordinates")
+                        .on_hover_text("Draw coordinates as they are, without reprojection");
+                    for crs in crs::registry() {
+                        ui.selectable_value(&mut srid, Some(crs.epsg), crs.to_string());
+                    }
+                });
+            if srid != self.layers.display_srid {
+                self.set_display_srid(srid);
+                ui.close_menu();
+            }
+        });
+
+        if ui.button("Inspector").clicked() {
+            self.show_inspector_window = true;
+            ui.close_menu();
+        }
+    }
+
+    /// Reprojects the layers and the sketch, previous views and measurements are in other units
+    pub fn set_display_srid(&mut self, srid: Option<u32>) {
+        let reprojection = self
+            .editor
+            .srid
+            .zip(srid)
+            .and_then(|(from, to)| Reprojection::new(from as u32, to).ok());
+        if let (Some(reprojection), Some(sdo)) = (reprojection, self.editor.to_sdo()) {
+            let sketch = reprojection
+                .sdo_geometry(&sdo)
+                .and_then(|sdo| Sketch::from_sdo(&sdo));
+            if sketch.is_some() {
+                self.editor.set_sketch(sketch);
+                self.editor.srid = srid.map(|srid| srid as f32);
+                self.sketch_before = self.editor.sketch.clone();
+            }
+        }
+
+        self.layers.display_srid = srid;
+        self.layers.reproject();
+        self.measurement = Measurement::default();
+        self.view = ViewHistory::default();
+        let all = self
+            .layers
+            .drawn()
+            .filter_map(|(_, query)| query.extent())
+            .reduce(|a, b| a.union(&b));
+        if let Some(extent) = all {
+            self.view.zoom_to(extent);
+        }
+    }
+
+    /// Fetches the layer again with its current query, keeping its style and visibility
+    pub fn refresh_layer(&mut self, name: &str) {
+        let Some(query) = self.layers.get_mut(name).map(|l| &mut l.query) else {
+            return;
+        };
+        if query.is_derived {
+            self.layer_message =
+                RichText::new(format!("{name} is derived and cannot be refreshed"))
+                    .color(Color32::YELLOW);
+            return;
+        }
+
+        let data = query::fetch_recorded(
+            &self.config.api,
+            &mut self.history,
+            name,
+            &query.sql,
+            &query.binds,
+        );
+
+        self.layer_message = match data {
+            Ok(fetched) => {
+                query.stale_since = fetched.stale_since;
+                query.refresh(name, fetched.features);
+                query::fetched_message(&format!("Refreshed {name}"), fetched.stale_since)
+            }
+            Err(e) => RichText::new(format!("Failed to refresh {name}: {e}")).color(Color32::RED),
+        };
+    }
+
+    /// Maps an Oracle SRID the registry does not know with `SDO_CS` of the database
+    fn look_up_srid(&mut self, srid: u32) {
+        self.layer_message = match self.config.api.oracle_epsg(srid) {
+            Ok(Some(epsg)) if crs::lookup(epsg).is_some() => {
+                crs::register_oracle_srid(srid, epsg);
+                self.layers.reset_display();
+                RichText::new(format!("SRID {}", crs::describe(Some(srid as f32))))
+                    .color(Color32::GREEN)
+            }
+            Ok(Some(epsg)) => RichText::new(format!(
+                "SRID {srid} is EPSG:{epsg}, which is not in the CRS registry"
+            ))
+            .color(Color32::YELLOW),
+            Ok(None) => RichText::new(format!("The database has no EPSG code for SRID {srid}"))
+                .color(Color32::YELLOW),
+            Err(e) => {
+                RichText::new(format!("Failed to look up SRID {srid}: {e}")).color(Color32::RED)
+            }
+        };
+    }
+
+    pub fn save_config(&mut self) {
+        self.config.save();
     }
 
     pub fn render_api_config(&mut self, ctx: &egui::Context) {
@@ -227,64 +843,782 @@ impl GeometryViewer {
                     ui.label("Connection status:");
                     ui.label(self.connection_status.clone());
                 });
+
+                let cache = ui.collapsing("Response cache", |ui| self.cache_settings(ui));
+                if cache.body_returned.is_none() {
+                    // Read the usage again when the section is opened
+                    self.cache_usage = None;
+                }
+            });
+    }
+
+    /// Cache of query responses on disk, used when the backend cannot be reached
+    fn cache_settings(&mut self, ui: &mut Ui) {
+        let settings = &mut self.config.api.cache;
+        let mut is_changed = ui
+            .checkbox(&mut settings.is_enabled, "Cache responses on disk")
+            .changed();
+        ui.add_enabled_ui(settings.is_enabled, |ui| {
+            is_changed |= ui
+                .checkbox(&mut settings.is_offline_mode, "Offline mode")
+                .on_hover_text("Show cached results when the backend is unreachable")
+                .changed();
+            ui.horizontal(|ui| {
+                ui.label("Size limit:");
+                is_changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut settings.max_megabytes)
+                            .clamp_range(1..=65536)
+                            .suffix(" MB"),
+                    )
+                    .changed();
             });
+        });
+
+        let cache = settings.cache();
+        let (count, size) = *self.cache_usage.get_or_insert_with(|| cache.usage());
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{count} responses, {:.1} MB in {}",
+                size as f64 / (1024. * 1024.),
+                cache.directory().display()
+            ));
+            if ui.small_button("↻").on_hover_text("Read again").clicked() {
+                self.cache_usage = None;
+            }
+        });
+        if ui
+            .add_enabled(count > 0, Button::new("Purge cache"))
+            .clicked()
+        {
+            self.cache_usage = None;
+            self.layer_message = match cache.purge() {
+                Ok(n) => {
+                    RichText::new(format!("Removed {n} cached responses")).color(Color32::GREEN)
+                }
+                Err(e) => {
+                    RichText::new(format!("Failed to purge the cache: {e}")).color(Color32::RED)
+                }
+            };
+        }
+
+        if is_changed {
+            self.cache_usage = None;
+            self.save_config();
+        }
     }
 
     pub fn geometry_content(&mut self, ui: &mut Ui) -> Response {
-        let plot = Plot::new("oracle_geometry").y_axis_width(3).data_aspect(1.);
+        // The plot background and the layers go below the plot items,
+        // their shapes are reserved now and set once the transform is known
+        let background = ui.painter().add(Shape::Noop);
+        let layer_shapes = ui.painter().add(Shape::Noop);
+        let srid = measure::display_srid(&self.layers);
+        let axis_format = self.config.overlay.axis_format.resolve(srid);
+        let plot = Plot::new("oracle_geometry")
+            .x_axis_formatter(move |value, _, range| {
+                axis_format.format(value, Axis::X, range.end() - range.start())
+            })
+            .y_axis_formatter(move |value, _, range| {
+                axis_format.format(value, Axis::Y, range.end() - range.start())
+            })
+            .y_axis_width(3)
+            .data_aspect(1.)
+            .show_background(false)
+            .allow_double_click_reset(self.tool == Tool::Select)
+            .allow_drag(!(self.tool == Tool::Edit && self.editor.wants_drag()));
+        let mut pointer: Option<[f64; 2]> = None;
+        let mut snapped: Option<[f64; 2]> = None;
 
-        plot.show(ui, |plot_ui| {
-            for query in self.queries.values() {
-                for geometry in query.geometries.iter() {
-                    if !geometry.is_active {
-                        continue;
-                    }
+        let mut viewport: Option<Viewport> = None;
 
-                    if geometry.sdo_geometry.is_polygon() {
-                        if let Some(poly) = geometry.sdo_geometry.create_polygon(query.stroke) {
-                            plot_ui.polygon(poly.name(geometry.name.clone()))
-                        }
-                    }
+        let plot_response = plot.show(ui, |plot_ui| {
+            let requested = self.view.take_requested();
+            if let Some(view) = requested {
+                plot_ui.set_plot_bounds(PlotBounds::from_min_max(view.min, view.max));
+            }
+            // Pans and zooms are applied after the items are added, so the viewport
+            // is that of the last frame unless a view was requested
+            let drawn_viewport = Viewport::of_transform(plot_ui.transform(), requested);
+            viewport = Some(drawn_viewport);
 
-                    if geometry.sdo_geometry.is_circle() {
-                        if let Some(circle) = geometry.sdo_geometry.create_circle(query.stroke) {
-                            plot_ui.line(circle.name(geometry.name.clone()))
-                        }
-                    }
+            // The layers are painted below the plot items, their extent still counts
+            // for the automatic bounds of the plot
+            let drawn = self
+                .layers
+                .drawn()
+                .filter_map(|(_, query)| query.extent())
+                .reduce(|a, b| a.union(&b));
+            if let Some(drawn) = drawn {
+                for corner in [drawn.min, drawn.max] {
+                    plot_ui.text(Text::new(PlotPoint::new(corner[0], corner[1]), ""));
+                }
+            }
 
-                    if geometry.sdo_geometry.is_line() {
-                        if let Some(line) = geometry.sdo_geometry.create_line(query.stroke) {
-                            plot_ui.line(line.name(geometry.name.clone()))
-                        }
+            for (item, geometry) in self.selection.geometries(&self.layers) {
+                let Some(layer) = self.layers.get(&item.layer) else {
+                    continue;
+                };
+                if let Some(drawn) = drawn_viewport.drawn(geometry) {
+                    let style = selection::highlighted(&layer.query.geometry_style(item.index));
+                    render::plot_geometry(plot_ui, &geometry.name, drawn.shape(), &style);
+                }
+            }
+
+            pointer = plot_ui.pointer_coordinate().map(|p| [p.x, p.y]);
+            let tolerance = snap::SNAP_TOLERANCE / plot_ui.transform().dpos_dvalue_x().abs();
+            match self.tool {
+                Tool::Select => {}
+                Tool::Measure => {
+                    snapped = pointer.map(|p| {
+                        snap::snap_to_vertex(&self.layers, &self.measurement.points, p, tolerance)
+                            .unwrap_or(p)
+                    });
+                    measure::plot_measurement(plot_ui, &self.measurement, snapped);
+                }
+                Tool::Edit => {
+                    let extra = self.editor.snap_vertices();
+                    snapped = pointer.map(|p| {
+                        snap::snap_to_vertex(&self.layers, &extra, p, tolerance).unwrap_or(p)
+                    });
+                    editor::plot_editor(plot_ui, &self.editor, snapped);
+                }
+            }
+        });
+
+        // The transform of the response already has the bounds set on this frame
+        let transform = plot_response.transform;
+        let view = Extent::from_plot_bounds(transform.bounds());
+        self.plot_bounds = Some(view);
+        if self.view.observe(view, ui.input(|i| i.time)) {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+        // Draws again when the view moved out of what was culled or simplified for
+        let final_viewport = Viewport::of_transform(&transform, None);
+        if !viewport.is_some_and(|v| v.covers(&final_viewport)) {
+            ui.ctx().request_repaint();
+        }
+
+        let response = &plot_response.response;
+        if self.tool == Tool::Measure {
+            if let Some(point) = snapped.filter(|_| response.clicked()) {
+                if self.measurement.points.is_empty() || self.measurement.is_closed {
+                    self.measurement.srid = measure::display_srid(&self.layers);
+                }
+                self.measurement.add(point);
+            }
+        } else if self.tool == Tool::Edit {
+            let input = PlotInput {
+                pointer,
+                snapped,
+                tolerance: snap::SNAP_TOLERANCE / transform.dpos_dvalue_x().abs(),
+                clicked: response.clicked(),
+                double_clicked: response.double_clicked(),
+                secondary_clicked: response.secondary_clicked(),
+                drag_started: response.drag_started_by(PointerButton::Primary),
+                dragged: response.dragged_by(PointerButton::Primary),
+                drag_released: response.drag_released_by(PointerButton::Primary),
+            };
+            // A new sketch takes the units of the layers it is drawn over
+            if input.clicked && self.editor.sketch.is_none() && self.editor.pending.is_empty() {
+                self.editor.srid = measure::display_srid(&self.layers).or(self.editor.srid);
+            }
+            self.editor.handle(&input);
+        } else if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            let point = transform.value_from_position(pos);
+            let tolerance = selection::HIT_TOLERANCE / transform.dpos_dvalue_x().abs();
+            let hit = selection::hit_test(&self.layers, [point.x, point.y], tolerance);
+            match (hit, ui.input(|i| i.modifiers.shift)) {
+                (Some(item), true) => self.selection.toggle(item),
+                (Some(item), false) => self.selection.select(item),
+                (None, true) => {}
+                (None, false) => self.selection.clear(),
+            }
+            if !self.selection.is_empty() {
+                self.show_inspector_window = true;
+            }
+        }
+
+        let frame = *plot_response.transform.frame();
+        let painter = ui.painter().with_clip_rect(frame);
+        painter.set(
+            background,
+            RectShape::new(
+                frame,
+                Rounding::same(2.),
+                ui.visuals().extreme_bg_color,
+                ui.visuals().widgets.noninteractive.bg_stroke,
+            ),
+        );
+        let screen = ScreenTransform::new(
+            view,
+            [frame.min.x as f64, frame.min.y as f64],
+            [frame.width() as f64, frame.height() as f64],
+        );
+        // Every layer is filled and outlined before the layers above it
+        painter.set(
+            layer_shapes,
+            self.layers
+                .drawn()
+                .flat_map(|(_, query)| render::layer_shapes(&screen, &final_viewport, query))
+                .collect::<Vec<_>>(),
+        );
+        label::draw_labels(&painter, &plot_response.transform, &self.layers);
+        self.config.overlay.draw(
+            &mut PainterCanvas { painter: &painter },
+            &screen,
+            srid,
+            ui.visuals().text_color(),
+        );
+
+        plot_response.response
+    }
+}
+
+/// What a click on the plot does
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Tool {
+    #[default]
+    Select,
+    Measure,
+    Edit,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::Select, Tool::Measure, Tool::Edit];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Select => "👆 Select",
+            Tool::Measure => "📏 Measure",
+            Tool::Edit => "✏ Edit",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Tool::Select => "Click geometries to inspect them",
+            Tool::Measure => "Measure distances, bearings and areas",
+            Tool::Edit => "Draw new geometries or move, insert and delete vertices",
+        }
+    }
+}
+
+/// Side panel entry that is being dragged
+#[derive(Clone)]
+enum Dragged {
+    Layer(usize),
+    Group(String),
+}
+
+/// Side panel entry that a dragged one is dropped on
+enum DropTarget {
+    Layer(usize),
+    Group(String),
+    End,
+}
+
+/// Settings every layer of the side panel is drawn with
+struct LayerListOptions<'a> {
+    /// Width of the plot in data units, labels depend on it
+    view_width: Option<f64>,
+    /// Only geometries containing this text are listed
+    search: &'a str,
+}
+
+/// Change to the layers requested from the side panel, applied after it is drawn
+enum LayerAction {
+    Edit(String),
+    Refresh(String),
+    Remove(String),
+    SetGroup(usize, String),
+    RemoveGroup(String),
+    Solo(Solo),
+    Drag(Dragged),
+    Zoom(Extent),
+    Inspect(GeometryRef),
+    EditGeometry(GeometryRef),
+    OpenTable(String),
+    LookUpSrid(u32),
+    ShowLayer(String, bool),
+    ShowGroup(String, bool),
+    /// Whether the listed geometries of the layer are checked
+    ShowGeometries(String, Vec<(usize, bool)>),
+    /// Style of the layer was changed in place, recorded by the change that reverts it
+    Restyled(String, Box<Change>),
+}
+
+fn drag_handle(ui: &mut Ui) -> Response {
+    ui.add(Label::new("☰").sense(Sense::drag()))
+        .on_hover_cursor(CursorIcon::Grab)
+        .on_hover_text("Drag to reorder")
+}
+
+/// Collapsible layer with a drag handle, visibility and solo in its header, returns its area
+fn layer_ui(
+    ui: &mut Ui,
+    index: usize,
+    layer: &mut Layer,
+    groups: &[LayerGroup],
+    solo: &Option<Solo>,
+    options: &LayerListOptions,
+    action: &mut Option<LayerAction>,
+) -> Rect {
+    let name = layer.name.clone();
+    let id = ui.make_persistent_id(("layer", &name));
+
+    ui.vertical(|ui| {
+        CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if drag_handle(ui).drag_started() {
+                    *action = Some(LayerAction::Drag(Dragged::Layer(index)));
+                }
+                let mut is_visible = layer.is_visible;
+                if ui
+                    .checkbox(&mut is_visible, "")
+                    .on_hover_text("Show the layer")
+                    .changed()
+                {
+                    *action = Some(LayerAction::ShowLayer(name.clone(), is_visible));
+                }
+                let is_solo = *solo == Some(Solo::Layer(name.clone()));
+                if ui
+                    .selectable_label(is_solo, "S")
+                    .on_hover_text("Show only this layer")
+                    .clicked()
+                {
+                    *action = Some(LayerAction::Solo(Solo::Layer(name.clone())));
+                }
+                ui.label(&name);
+                if let Some(time) = layer.query.stale_since {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        format!("⚠ stale since {}", time.format("%Y-%m-%d %H:%M")),
+                    )
+                    .on_hover_text(
+                        "The backend was unreachable, the geometries come from the cache. \
+                         Refresh the layer once it is back.",
+                    );
+                }
+                if layer.query.geometries.iter().any(|g| !g.is_reprojectable()) {
+                    ui.colored_label(Color32::YELLOW, "⚠ not reprojected")
+                        .on_hover_text(
+                            "Geometries without an SRID of the CRS registry cannot be drawn \
+                             in the display CRS and are hidden",
+                        );
+                }
+                if !options.search.trim().is_empty() {
+                    let found = layer
+                        .query
+                        .geometries
+                        .iter()
+                        .enumerate()
+                        .filter(|(n, g)| {
+                            layer.query.matches_filter(*n) && g.contains_text(options.search)
+                        })
+                        .count();
+                    ui.weak(format!("({found} found)"));
+                }
+            })
+            .body(|ui| layer_body(ui, index, layer, groups, options, action));
+    })
+    .response
+    .rect
+}
+
+fn layer_body(
+    ui: &mut Ui,
+    index: usize,
+    layer: &mut Layer,
+    groups: &[LayerGroup],
+    options: &LayerListOptions,
+    action: &mut Option<LayerAction>,
+) {
+    let name = layer.name.clone();
+    let query = &mut layer.query;
+
+    ui.horizontal(|ui| {
+        ui.label("Group:");
+        let mut group = layer.group.clone();
+        let selected = match group.is_empty() {
+            true => "None".to_string(),
+            false => group.clone(),
+        };
+        ComboBox::from_id_source(("layer_group", &name))
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut group, String::new(), "None");
+                for g in groups {
+                    ui.selectable_value(&mut group, g.name.clone(), &g.name);
+                }
+            });
+        if group != layer.group {
+            *action = Some(LayerAction::SetGroup(index, group));
+        }
+    });
+    let srid = query
+        .geometries
+        .first()
+        .and_then(|g| g.sdo_geometry.sdo_srid);
+    let is_known = srid.is_some_and(|srid| crs::lookup(srid as u32).is_some());
+    ui.horizontal(|ui| {
+        ui.label(format!("SRID: {}", crs::describe(srid)))
+            .on_hover_text(match is_known {
+                true => "Reprojected to the display CRS",
+                false => "Not in the CRS registry, only drawn with native coordinates",
+            });
+        if let Some(srid) = srid.filter(|_| !is_known) {
+            if ui
+                .small_button("Look up")
+                .on_hover_text("Map the Oracle SRID to an EPSG code with SDO_CS of the database")
+                .clicked()
+            {
+                *action = Some(LayerAction::LookUpSrid(srid as u32));
+            }
+        }
+    });
+    // Only the open sections are copied to record what they change
+    ui.collapsing("Style", |ui| {
+        let style = query.style;
+        style::style_ui(ui, &mut query.style, &name);
+        if query.style != style {
+            *action = Some(restyled(
+                &name,
+                style,
+                query.theme.clone(),
+                query.label.clone(),
+            ));
+        }
+    });
+    ui.collapsing("Thematic styling", |ui| {
+        let attributes = query.attribute_names();
+        let theme = query.theme.clone();
+        if theme::theme_ui(ui, &mut query.theme, &attributes, &query.geometries, &name) {
+            query.apply_theme();
+            *action = Some(restyled(&name, query.style, theme, query.label.clone()));
+        }
+    });
+    theme::legend_ui(ui, &query.theme);
+    ui.collapsing("Labels", |ui| {
+        let attributes = query.attribute_names();
+        let label = query.label.clone();
+        label::label_ui(ui, &mut query.label, &attributes, options.view_width, &name);
+        if query.label != label {
+            *action = Some(restyled(&name, query.style, query.theme.clone(), label));
+        }
+    });
+    ui.collapsing("Filter", |ui| {
+        let response = ui
+            .add(
+                egui::TextEdit::singleline(&mut query.filter)
+                    .hint_text("area > 100 and class = 'RES'"),
+            )
+            .on_hover_text(
+                "Hides geometries that do not match. Attributes can be used \
+                 together with name, area, length, perimeter and valid",
+            );
+        if response.changed() {
+            query.apply_filter();
+        }
+        if !query.filter.trim().is_empty() {
+            match query.filter.parse::<Expr>() {
+                Ok(_) => {
+                    let matching = (0..query.geometries.len())
+                        .filter(|&n| query.matches_filter(n))
+                        .count();
+                    ui.label(format!(
+                        "{matching} of {} geometries match",
+                        query.geometries.len()
+                    ));
+                }
+                Err(e) => {
+                    ui.colored_label(Color32::RED, e.to_string());
+                }
+            }
+        }
+    });
+    ui.collapsing("Geometries", |ui| {
+        let listed: Vec<usize> = (0..query.geometries.len())
+            .filter(|&n| {
+                query.matches_filter(n) && query.geometries[n].contains_text(options.search)
+            })
+            .collect();
+        if listed.len() < query.geometries.len() {
+            ui.weak(format!(
+                "{} of {} geometries listed",
+                listed.len(),
+                query.geometries.len()
+            ));
+        }
+        for &n in &listed {
+            let geometry = &query.geometries[n];
+            ui.horizontal_wrapped(|ui| {
+                let mut is_active = geometry.is_active;
+                if ui.checkbox(&mut is_active, geometry.name.clone()).changed() {
+                    *action = Some(LayerAction::ShowGeometries(
+                        name.clone(),
+                        vec![(n, is_active)],
+                    ));
+                }
+                if let Some(extent) = geometry.extent() {
+                    if ui.small_button("🔍").on_hover_text("Zoom to").clicked() {
+                        *action = Some(LayerAction::Zoom(extent));
                     }
                 }
+                if ui.small_button("ℹ").on_hover_text("Inspect").clicked() {
+                    *action = Some(LayerAction::Inspect(GeometryRef::new(&name, n)));
+                }
+                if ui
+                    .small_button("✏")
+                    .on_hover_text("Edit vertices")
+                    .clicked()
+                {
+                    *action = Some(LayerAction::EditGeometry(GeometryRef::new(&name, n)));
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let active: Option<fn(bool) -> bool> = if ui.add(Button::new("Show all")).clicked() {
+                Some(|_| true)
+            } else if ui.add(Button::new("Hide all")).clicked() {
+                Some(|_| false)
+            } else if ui.add(Button::new("Invert")).clicked() {
+                Some(|is_active| !is_active)
+            } else {
+                None
+            };
+            if let Some(active) = active {
+                let active = listed
+                    .iter()
+                    .map(|&n| (n, active(query.geometries[n].is_active)))
+                    .collect();
+                *action = Some(LayerAction::ShowGeometries(name.clone(), active));
+            }
+        });
+    });
+
+    ui.horizontal(|ui| {
+        if let Some(extent) = query.extent() {
+            if ui
+                .button("Zoom to")
+                .on_hover_text("Show all visible geometries of this layer")
+                .clicked()
+            {
+                *action = Some(LayerAction::Zoom(extent));
+            }
+        }
+
+        if ui
+            .button("Table")
+            .on_hover_text("List the geometries with their metrics and attributes")
+            .clicked()
+        {
+            *action = Some(LayerAction::OpenTable(name.clone()));
+        }
+
+        if ui
+            .button("Edit")
+            .on_hover_text("Change the query of this layer")
+            .clicked()
+        {
+            *action = Some(LayerAction::Edit(name.clone()));
+        }
+
+        let refresh_hint = match query.is_derived {
+            true => "Created from other layers, its query cannot fetch it again",
+            false => "Fetch the geometries again",
+        };
+        if ui
+            .add_enabled(!query.is_derived, Button::new("Refresh"))
+            .on_hover_text(refresh_hint)
+            .on_disabled_hover_text(refresh_hint)
+            .clicked()
+        {
+            *action = Some(LayerAction::Refresh(name.clone()));
+        }
+
+        let delete_button = ui.add(Button::new(
+            RichText::new("Delete objects").color(Color32::RED),
+        ));
+
+        if delete_button.clicked() {
+            *action = Some(LayerAction::Remove(name.clone()));
+        }
+    });
+}
+
+/// Action recording a style change of the layer by the style it replaced
+fn restyled(name: &str, style: LayerStyle, theme: Theme, label: LabelStyle) -> LayerAction {
+    LayerAction::Restyled(
+        name.to_string(),
+        Box::new(Change::Style {
+            layer: name.to_string(),
+            style,
+            theme,
+            label,
+        }),
+    )
+}
+
+/// Collapsible group of layers, returns the area of its header and the action it requested
+fn group_ui(
+    ui: &mut Ui,
+    group: &LayerGroup,
+    solo: &Option<Solo>,
+    add_layers: impl FnOnce(&mut Ui),
+) -> (Rect, Option<LayerAction>) {
+    let mut action = None;
+    let id = ui.make_persistent_id(("layer_group", &group.name));
+
+    let (_, header, _) = CollapsingState::load_with_default_open(ui.ctx(), id, true)
+        .show_header(ui, |ui| {
+            if drag_handle(ui).drag_started() {
+                action = Some(LayerAction::Drag(Dragged::Group(group.name.clone())));
+            }
+            let mut is_visible = group.is_visible;
+            if ui
+                .checkbox(&mut is_visible, "")
+                .on_hover_text("Show the group")
+                .changed()
+            {
+                action = Some(LayerAction::ShowGroup(group.name.clone(), is_visible));
+            }
+            let is_solo = *solo == Some(Solo::Group(group.name.clone()));
+            if ui
+                .selectable_label(is_solo, "S")
+                .on_hover_text("Show only this group")
+                .clicked()
+            {
+                action = Some(LayerAction::Solo(Solo::Group(group.name.clone())));
+            }
+            ui.label(RichText::new(format!("📁 {}", group.name)).strong());
+            if ui
+                .small_button("🗑")
+                .on_hover_text("Delete the group, its layers are kept")
+                .clicked()
+            {
+                action = Some(LayerAction::RemoveGroup(group.name.clone()));
             }
-            plot_ui.pointer_coordinate();
-            plot_ui.pointer_coordinate_drag_delta();
-            plot_ui.plot_bounds();
-            plot_ui.response().hovered();
         })
-        .response
-    }
+        .body(add_layers);
+
+    (header.response.rect, action)
 }
 
 const BOARD_PANEL_WIDTH: f32 = 300.0;
 
 impl App for GeometryViewer {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // New and refreshed layers are drawn in the display SRID as well
+        self.layers.reproject();
+
         if self.config.is_dark_mode {
             ctx.set_visuals(Visuals::dark());
         } else {
             ctx.set_visuals(Visuals::light());
         }
 
+        // Text fields have their own undo
+        if ctx.memory(|m| m.focus().is_none()) {
+            let redo = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+            let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+            if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+                self.redo();
+            }
+            if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+                self.undo();
+            }
+        }
+
         if self.show_api_config_window {
             self.render_api_config(ctx);
         }
 
         if self.show_query_window {
-            QueryWindow::new(&mut self.queries, &mut self.input_query, &self.config.api)
-                .show(ctx, &mut self.show_query_window);
+            QueryWindow::new(
+                &mut self.layers,
+                &mut self.input_query,
+                &self.config.api,
+                &mut self.history,
+                &mut self.library,
+                &mut self.undo,
+            )
+            .show(ctx, &mut self.show_query_window);
+        }
+
+        if self.show_workspace_window {
+            WorkspaceWindow::new(
+                &mut self.workspace_settings,
+                &mut self.layers,
+                &mut self.config,
+                self.plot_bounds,
+                &mut self.view,
+                &mut self.undo,
+            )
+            .show(ctx, &mut self.show_workspace_window);
+        }
+
+        if ctx.input(|i| i.modifiers.alt && i.key_pressed(Key::ArrowLeft)) {
+            self.view.go_back();
+        }
+        if ctx.input(|i| i.modifiers.alt && i.key_pressed(Key::ArrowRight)) {
+            self.view.go_forward();
+        }
+
+        self.selection.retain_existing(&self.layers);
+        if self.show_inspector_window {
+            InspectorWindow::new(
+                &mut self.selection,
+                &self.layers,
+                &mut self.view,
+                &mut self.editor,
+                &mut self.tool,
+            )
+            .show(ctx, &mut self.show_inspector_window);
+        }
+
+        if self.show_location_window {
+            LocationWindow::new(&mut self.location, &self.layers, &mut self.selection)
+                .show(ctx, &mut self.show_location_window);
+        }
+
+        if self.show_operation_window {
+            OperationWindow::new(
+                &mut self.operation,
+                &mut self.layers,
+                &self.selection,
+                &mut self.undo,
+            )
+            .show(ctx, &mut self.show_operation_window);
+        }
+
+        if self.show_compare_window {
+            CompareWindow::new(
+                &mut self.comparison,
+                &mut self.layers,
+                &mut self.selection,
+                &mut self.undo,
+            )
+            .show(ctx, &mut self.show_compare_window);
+        }
+
+        if self.show_attribute_table_window {
+            AttributeTableWindow::new(&mut self.attribute_table, &self.layers, &mut self.selection)
+                .show(ctx, &mut self.show_attribute_table_window);
+        }
+
+        if self.show_export_window {
+            ExportWindow::new(
+                &mut self.export_settings,
+                &self.layers,
+                self.plot_bounds,
+                self.config.overlay.axis_format,
+            )
+            .show(ctx, &mut self.show_export_window);
         }
 
         SidePanel::new(egui::panel::Side::Left, "side_panel")
@@ -293,8 +1627,80 @@ impl App for GeometryViewer {
             .resizable(false)
             .show(ctx, |ui| self.side_panel(ui));
 
+        if self.tool == Tool::Measure {
+            let mut is_open = true;
+            MeasureWindow::new(&mut self.measurement).show(ctx, &mut is_open);
+            if !is_open {
+                self.tool = Tool::Select;
+            }
+
+            // Keys go to text fields when one has the focus
+            if ctx.memory(|m| m.focus().is_none()) {
+                if ctx.input(|i| i.key_pressed(Key::Backspace)) {
+                    self.measurement.undo();
+                }
+                if ctx.input(|i| i.key_pressed(Key::Escape)) {
+                    self.measurement.clear();
+                }
+            }
+        }
+
+        if self.tool == Tool::Edit {
+            let mut is_open = true;
+            EditorWindow::new(
+                &mut self.editor,
+                &mut self.layers,
+                &mut self.input_query,
+                &mut self.show_query_window,
+                &mut self.undo,
+            )
+            .show(ctx, &mut is_open);
+            if !is_open {
+                self.tool = Tool::Select;
+            }
+
+            if ctx.memory(|m| m.focus().is_none()) {
+                if ctx.input(|i| i.key_pressed(Key::Enter)) {
+                    self.editor.finish();
+                }
+                if ctx.input(|i| i.key_pressed(Key::Backspace)) {
+                    self.editor.undo_point();
+                }
+                if ctx.input(|i| i.key_pressed(Key::Escape)) {
+                    self.editor.cancel();
+                }
+                if let Some(vertex) = self
+                    .editor
+                    .hovered()
+                    .filter(|_| ctx.input(|i| i.key_pressed(Key::Delete)))
+                {
+                    self.editor.delete_vertex(vertex);
+                }
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for tool in Tool::ALL {
+                    ui.selectable_value(&mut self.tool, tool, tool.label())
+                        .on_hover_text(tool.description());
+                }
+            });
             Frame::canvas(ui.style()).show(ui, |ui| self.geometry_content(ui));
         });
+
+        if self.editor.sketch != self.sketch_before {
+            let before = std::mem::replace(&mut self.sketch_before, self.editor.sketch.clone());
+            let change = Change::Sketch(before);
+            match self.editor.is_dragging() {
+                true => self.undo.push_merged(
+                    "Move vertex",
+                    "move vertex",
+                    change,
+                    ctx.input(|i| i.time),
+                ),
+                false => self.undo.push("Edit sketch", change),
+            }
+        }
     }
 }
@@ -0,0 +1,295 @@
+use std::{collections::BTreeSet, path::Path, time::Duration};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::CONFY_APP, query::Bind};
+
+const HISTORY_CONFIG: &str = "query_history";
+const LIBRARY_CONFIG: &str = "query_library";
+const MAX_HISTORY_ENTRIES: usize = 500;
+
+fn matches(search: &str, fields: &[&str]) -> bool {
+    let search = search.trim().to_lowercase();
+    search.is_empty() || fields.iter().any(|f| f.to_lowercase().contains(&search))
+}
+
+/// One submitted query.
+///
+/// Tables have to go after plain values in TOML, so `binds` is the last field.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub api_url: String,
+    pub name: String,
+    pub sql: String,
+    pub row_count: usize,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub binds: Vec<Bind>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        api_url: &str,
+        name: &str,
+        sql: &str,
+        binds: &[Bind],
+        duration: Duration,
+        result: Result<usize, String>,
+    ) -> Self {
+        let (row_count, error) = match result {
+            Ok(row_count) => (row_count, None),
+            Err(e) => (0, Some(e)),
+        };
+
+        Self {
+            timestamp: Local::now(),
+            api_url: api_url.to_string(),
+            name: name.to_string(),
+            sql: sql.to_string(),
+            row_count,
+            duration_ms: duration.as_millis() as u64,
+            error,
+            binds: binds.to_vec(),
+        }
+    }
+
+    pub fn matches(&self, search: &str) -> bool {
+        matches(
+            search,
+            &[
+                &self.name,
+                &self.sql,
+                &self.api_url,
+                self.error.as_deref().unwrap_or_default(),
+            ],
+        )
+    }
+}
+
+/// Submitted queries with the newest first
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct QueryHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl QueryHistory {
+    pub fn load() -> Self {
+        confy::load(CONFY_APP, HISTORY_CONFIG).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = confy::store(CONFY_APP, HISTORY_CONFIG, self) {
+            tracing::error!("Failed saving query history: {}", e);
+        }
+    }
+
+    pub fn load_path(path: impl AsRef<Path>) -> Result<Self, confy::ConfyError> {
+        confy::load_path(path)
+    }
+
+    pub fn save_path(&self, path: impl AsRef<Path>) -> Result<(), confy::ConfyError> {
+        confy::store_path(path, self)
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_HISTORY_ENTRIES);
+    }
+
+    pub fn search<'a>(&'a self, search: &'a str) -> impl Iterator<Item = &'a HistoryEntry> {
+        self.entries.iter().filter(move |e| e.matches(search))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SavedQuery {
+    pub name: String,
+    pub folder: String,
+    pub description: String,
+    pub sql: String,
+    #[serde(default)]
+    pub binds: Vec<Bind>,
+}
+
+impl SavedQuery {
+    pub fn matches(&self, search: &str) -> bool {
+        matches(
+            search,
+            &[&self.name, &self.folder, &self.description, &self.sql],
+        )
+    }
+}
+
+/// Named queries grouped into folders, a name is unique inside its folder
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct QueryLibrary {
+    pub queries: Vec<SavedQuery>,
+}
+
+impl QueryLibrary {
+    pub fn load() -> Self {
+        confy::load(CONFY_APP, LIBRARY_CONFIG).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = confy::store(CONFY_APP, LIBRARY_CONFIG, self) {
+            tracing::error!("Failed saving query library: {}", e);
+        }
+    }
+
+    pub fn load_path(path: impl AsRef<Path>) -> Result<Self, confy::ConfyError> {
+        confy::load_path(path)
+    }
+
+    pub fn save_path(&self, path: impl AsRef<Path>) -> Result<(), confy::ConfyError> {
+        confy::store_path(path, self)
+    }
+
+    /// Adds the query or replaces the one with the same folder and name
+    pub fn upsert(&mut self, query: SavedQuery) {
+        match self
+            .queries
+            .iter_mut()
+            .find(|q| q.folder == query.folder && q.name == query.name)
+        {
+            Some(existing) => *existing = query,
+            None => self.queries.push(query),
+        }
+    }
+
+    pub fn remove(&mut self, folder: &str, name: &str) {
+        self.queries
+            .retain(|q| !(q.folder == folder && q.name == name));
+    }
+
+    pub fn folders(&self) -> BTreeSet<&str> {
+        self.queries.iter().map(|q| q.folder.as_str()).collect()
+    }
+
+    pub fn search<'a>(
+        &'a self,
+        folder: &'a str,
+        search: &'a str,
+    ) -> impl Iterator<Item = &'a SavedQuery> {
+        self.queries
+            .iter()
+            .filter(move |q| q.folder == folder && q.matches(search))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        history::{HistoryEntry, QueryHistory, QueryLibrary, SavedQuery},
+        query::Bind,
+    };
+
+    fn binds() -> Vec<Bind> {
+        vec![Bind {
+            name: "class".into(),
+            value: "RES".into(),
+        }]
+    }
+
+    #[test]
+    fn test_history_newest_first_and_search() {
+        let mut history = QueryHistory::default();
+        history.push(HistoryEntry::new(
+            "http://localhost:8000",
+            "parcels",
+            "select geom from parcels",
+            &[],
+            Duration::from_millis(120),
+            Ok(10),
+        ));
+        history.push(HistoryEntry::new(
+            "http://localhost:8000",
+            "roads",
+            "select geom from roads",
+            &[],
+            Duration::from_millis(50),
+            Err("ORA-00942: table or view does not exist".into()),
+        ));
+
+        assert_eq!(history.entries[0].name, "roads");
+        assert_eq!(history.entries[0].row_count, 0);
+        assert_eq!(history.search("PARCELS").count(), 1);
+        assert_eq!(history.search("ora-00942").count(), 1);
+        assert_eq!(history.search("").count(), 2);
+    }
+
+    #[test]
+    fn test_history_persistence() {
+        let directory = std::env::temp_dir().join(format!("ora_history_{}", std::process::id()));
+        let path = directory.join("history.toml");
+        let mut history = QueryHistory::default();
+        history.push(HistoryEntry::new(
+            "http://localhost:8000",
+            "parcels",
+            "select geom from parcels where class = :class",
+            &binds(),
+            Duration::from_millis(120),
+            Ok(10),
+        ));
+        history.push(HistoryEntry::new(
+            "http://localhost:8000",
+            "roads",
+            "select geom from roads",
+            &[],
+            Duration::from_millis(50),
+            Err("Request failed".into()),
+        ));
+
+        history.save_path(&path).unwrap();
+        let loaded = QueryHistory::load_path(&path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(loaded, history);
+    }
+
+    #[test]
+    fn test_library_upsert_and_persistence() {
+        let directory = std::env::temp_dir().join(format!("ora_library_{}", std::process::id()));
+        let path = directory.join("library.toml");
+        let mut library = QueryLibrary::default();
+        let saved = SavedQuery {
+            name: "residential".into(),
+            folder: "parcels".into(),
+            description: "Residential parcels".into(),
+            sql: "select geom from parcels where class = :class".into(),
+            binds: binds(),
+        };
+        library.upsert(saved.clone());
+        library.upsert(SavedQuery {
+            description: "Only residential parcels".into(),
+            ..saved
+        });
+        library.upsert(SavedQuery {
+            name: "all".into(),
+            folder: "roads".into(),
+            sql: "select geom from roads".into(),
+            ..Default::default()
+        });
+
+        assert_eq!(library.queries.len(), 2);
+        assert_eq!(
+            library.folders().into_iter().collect::<Vec<_>>(),
+            vec!["parcels", "roads"]
+        );
+        assert_eq!(library.search("parcels", "only").count(), 1);
+
+        library.save_path(&path).unwrap();
+        let loaded = QueryLibrary::load_path(&path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(loaded, library);
+
+        library.remove("roads", "all");
+        assert_eq!(library.queries.len(), 1);
+    }
+}
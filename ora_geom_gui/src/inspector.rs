@@ -0,0 +1,276 @@
+use egui::{CollapsingHeader, Color32, Context, Grid, RichText, Ui, Window};
+use geo::CoordsIter;
+
+use crate::{
+    crs,
+    editor::Editor,
+    feature::display,
+    geometry_viewer::Tool,
+    layers::Layers,
+    query::Geometry,
+    render::Extent,
+    selection::{GeometryRef, Selection},
+    svg::format_tick,
+    view::ViewHistory,
+};
+
+/// Vertices listed with native and display coordinates
+const MAX_LISTED_VERTICES: usize = 200;
+
+/// Change requested from the inspector, applied after the window is drawn
+enum InspectorAction {
+    Zoom(Extent),
+    Deselect(GeometryRef),
+    Edit(GeometryRef),
+}
+
+pub struct InspectorWindow<'a> {
+    pub selection: &'a mut Selection,
+    pub layers: &'a Layers,
+    pub view: &'a mut ViewHistory,
+    pub editor: &'a mut Editor,
+    pub tool: &'a mut Tool,
+}
+
+impl<'a> InspectorWindow<'a> {
+    pub fn new(
+        selection: &'a mut Selection,
+        layers: &'a Layers,
+        view: &'a mut ViewHistory,
+        editor: &'a mut Editor,
+        tool: &'a mut Tool,
+    ) -> Self {
+        Self {
+            selection,
+            layers,
+            view,
+            editor,
+            tool,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Inspector")
+            .open(is_active)
+            .resizable(true)
+            .default_width(320.)
+            .show(ctx, |ui| {
+                let mut action: Option<InspectorAction> = None;
+
+                if self.selection.is_empty() {
+                    ui.label("Click a geometry on the plot to inspect it.");
+                    ui.label("Shift + click adds it to the selection or removes it.");
+                    return;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} selected", self.selection.items.len()));
+                    let extent = self.selection.extent(self.layers);
+                    if ui
+                        .add_enabled(extent.is_some(), egui::Button::new("Zoom to selection"))
+                        .clicked()
+                    {
+                        action = extent.map(InspectorAction::Zoom);
+                    }
+                    if ui.button("Clear selection").clicked() {
+                        self.selection.clear();
+                    }
+                });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (item, geometry) in self.selection.geometries(self.layers) {
+                        CollapsingHeader::new(format!("{}: {}", item.layer, geometry.name))
+                            .id_source(("inspector", &item.layer, item.index))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                geometry_details(ui, geometry);
+
+                                ui.horizontal(|ui| {
+                                    let extent = geometry.extent();
+                                    if ui
+                                        .add_enabled(extent.is_some(), egui::Button::new("Zoom to"))
+                                        .clicked()
+                                    {
+                                        action = extent.map(InspectorAction::Zoom);
+                                    }
+                                    if ui.button("Deselect").clicked() {
+                                        action = Some(InspectorAction::Deselect(item.clone()));
+                                    }
+                                    if ui
+                                        .button("Edit")
+                                        .on_hover_text("Move, insert and delete vertices")
+                                        .clicked()
+                                    {
+                                        action = Some(InspectorAction::Edit(item.clone()));
+                                    }
+                                });
+                            });
+                    }
+                });
+
+                match action {
+                    Some(InspectorAction::Zoom(extent)) => self.view.zoom_to(extent),
+                    Some(InspectorAction::Deselect(item)) => self.selection.toggle(item),
+                    Some(InspectorAction::Edit(item)) => {
+                        if let Some(geometry) = item.resolve(self.layers) {
+                            if self.editor.edit(item, &geometry.display_sdo_geometry()) {
+                                *self.tool = Tool::Edit;
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            });
+    }
+}
+
+fn geometry_details(ui: &mut Ui, geometry: &Geometry) {
+    let sdo = &geometry.sdo_geometry;
+    let reprojection = geometry.reprojection();
+
+    Grid::new(("inspector_geometry", &geometry.name))
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("SDO_GTYPE:");
+            ui.label(sdo.sdo_gtype.to_string());
+            ui.end_row();
+
+            ui.label("SDO_SRID:");
+            ui.label(crs::describe(sdo.sdo_srid));
+            ui.end_row();
+
+            if let Some(reprojection) = &reprojection {
+                ui.label("Display CRS:");
+                ui.label(reprojection.to.to_string());
+                ui.end_row();
+            }
+
+            ui.label("Elements:");
+            ui.label(sdo.elements().len().to_string());
+            ui.end_row();
+
+            ui.label("Ordinates:");
+            ui.label(sdo.sdo_ordinates.len().to_string());
+            ui.end_row();
+
+            if let Some(extent) = geometry.metrics().mbr {
+                ui.label("Min:");
+                ui.label(format_point(extent.min));
+                ui.end_row();
+
+                ui.label("Max:");
+                ui.label(format_point(extent.max));
+                ui.end_row();
+            }
+
+            if let Some(extent) = geometry.extent().filter(|_| reprojection.is_some()) {
+                ui.label("Display min:");
+                ui.label(format_point(extent.min));
+                ui.end_row();
+
+                ui.label("Display max:");
+                ui.label(format_point(extent.max));
+                ui.end_row();
+            }
+
+            ui.label("Validity:");
+            let errors = sdo.validation_errors();
+            if errors.is_empty() {
+                ui.label(RichText::new("Valid").color(Color32::GREEN));
+            } else {
+                ui.label(RichText::new(errors.join("\n")).color(Color32::RED));
+            }
+            ui.end_row();
+        });
+
+    let metrics = geometry.metrics();
+    CollapsingHeader::new("Metrics")
+        .id_source(("inspector_metrics", &geometry.name))
+        .default_open(true)
+        .show(ui, |ui| {
+            Grid::new(("inspector_metrics_grid", &geometry.name))
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Metric:");
+                    ui.label(metrics.metric.label());
+                    ui.end_row();
+
+                    if sdo.is_polygon() {
+                        ui.label("Area:");
+                        ui.label(metrics.metric.format_area(metrics.area));
+                        ui.end_row();
+
+                        ui.label("Perimeter:");
+                        ui.label(metrics.metric.format_length(metrics.perimeter));
+                        ui.end_row();
+                    }
+
+                    if metrics.length > 0. || sdo.is_line() {
+                        ui.label("Length:");
+                        ui.label(metrics.metric.format_length(metrics.length));
+                        ui.end_row();
+                    }
+
+                    if let Some(centroid) = metrics.centroid {
+                        ui.label("Centroid:");
+                        ui.label(format_point(centroid));
+                        ui.end_row();
+                    }
+
+                    if let Some(point) = metrics.interior_point {
+                        ui.label("Interior point:");
+                        ui.label(format_point(point));
+                        ui.end_row();
+                    }
+                });
+        });
+
+    if let (Some(native), Some(shape)) = (sdo.to_geo(), geometry.shape()) {
+        ui.collapsing("Coordinates", |ui| {
+            Grid::new(("inspector_coordinates", &geometry.name))
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Native");
+                    ui.strong("Display");
+                    ui.end_row();
+
+                    let vertices = native.coords_iter().zip(shape.coords_iter());
+                    for (n, (a, b)) in vertices.take(MAX_LISTED_VERTICES).enumerate() {
+                        ui.label(n.to_string());
+                        ui.label(format_point([a.x, a.y]));
+                        ui.label(format_point([b.x, b.y]));
+                        ui.end_row();
+                    }
+                });
+            let count = native.coords_count();
+            if count > MAX_LISTED_VERTICES {
+                ui.weak(format!("{} more vertices", count - MAX_LISTED_VERTICES));
+            }
+        });
+    }
+
+    if !geometry.attributes.is_empty() {
+        ui.collapsing("Attributes", |ui| {
+            Grid::new(("inspector_attributes", &geometry.name))
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (name, value) in &geometry.attributes {
+                        ui.label(name);
+                        ui.label(display(value));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+fn format_point([x, y]: [f64; 2]) -> String {
+    format!("{}, {}", format_tick(x), format_tick(y))
+}
@@ -0,0 +1,308 @@
+use egui::{Color32, DragValue, FontId, Grid, Painter, Rect, Ui, Vec2};
+use egui_plot::{PlotPoint, PlotTransform};
+use geo::{Euclidean, Geometry, InteriorPoint, InterpolateLine, Length, LineString};
+use serde::{Deserialize, Serialize};
+
+use crate::{feature, layers::Layers, query};
+
+/// Labels drawn at most on one frame, the rest would not fit without overlapping anyway
+const MAX_LABELS: usize = 500;
+/// Free space kept around every label in pixels
+const LABEL_PADDING: f32 = 2.;
+/// Halo width in pixels
+const HALO_WIDTH: f32 = 1.;
+
+/// How the geometries of a layer are labelled
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LabelStyle {
+    pub is_enabled: bool,
+    /// Attribute shown as the label, the geometry name when it is empty
+    pub attribute: String,
+    pub font_size: f32,
+    pub color: Color32,
+    /// Outline that keeps the text readable over geometries
+    pub halo: bool,
+    pub halo_color: Color32,
+    /// Labels appear once the plot shows at most this width in coordinate units, 0 always shows them
+    pub max_view_width: f64,
+}
+
+impl Default for LabelStyle {
+    fn default() -> Self {
+        Self {
+            is_enabled: false,
+            attribute: String::new(),
+            font_size: 12.,
+            color: Color32::BLACK,
+            halo: true,
+            halo_color: Color32::WHITE,
+            max_view_width: 0.,
+        }
+    }
+}
+
+impl LabelStyle {
+    pub fn is_shown(&self, view_width: f64) -> bool {
+        self.is_enabled && (self.max_view_width <= 0. || view_width <= self.max_view_width)
+    }
+
+    /// Value of the labelled attribute, or the geometry name
+    pub fn text(&self, geometry: &query::Geometry) -> Option<String> {
+        if self.attribute.is_empty() {
+            return Some(geometry.name.clone());
+        }
+        feature::attribute(&geometry.attributes, &self.attribute)
+            .filter(|value| !value.is_null())
+            .map(feature::display)
+    }
+}
+
+/// Where a geometry is labelled: a point inside polygons, the middle of lines and the point itself
+pub fn anchor(geometry: &Geometry) -> Option<[f64; 2]> {
+    let midpoint = |line: &LineString| Euclidean.point_at_ratio_from_start(line, 0.5);
+    let point = match geometry {
+        Geometry::LineString(line) => midpoint(line),
+        // The longest line is the most visible one
+        Geometry::MultiLineString(lines) => lines
+            .iter()
+            .max_by(|a, b| Euclidean.length(*a).total_cmp(&Euclidean.length(*b)))
+            .and_then(midpoint),
+        Geometry::Line(line) => Some(Euclidean.point_at_ratio_from_start(line, 0.5)),
+        other => other.interior_point(),
+    };
+
+    point.map(|p| [p.x(), p.y()])
+}
+
+/// Screen areas taken by labels, a label is only drawn where it overlaps none of them
+#[derive(Debug, Default)]
+pub struct LabelPlacer {
+    placed: Vec<Rect>,
+}
+
+impl LabelPlacer {
+    /// Takes the area when it is free
+    pub fn place(&mut self, rect: Rect) -> bool {
+        if self.placed.iter().any(|r| r.intersects(rect)) {
+            return false;
+        }
+        self.placed.push(rect);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.placed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placed.is_empty()
+    }
+}
+
+/// Draws labels of the drawn layers, upper layers and later rows win over the ones below
+pub fn draw_labels(painter: &Painter, transform: &PlotTransform, layers: &Layers) {
+    let bounds = transform.bounds();
+    let view_width = bounds.width();
+    let mut placer = LabelPlacer::default();
+
+    let labelled = layers
+        .layers
+        .iter()
+        .filter(|layer| layers.is_drawn(layer) && layer.query.label.is_shown(view_width));
+    for layer in labelled {
+        let style = &layer.query.label;
+        let font = FontId::proportional(style.font_size);
+
+        for geometry in layer.query.active_geometries().rev() {
+            if placer.len() >= MAX_LABELS {
+                return;
+            }
+            let Some([x, y]) = geometry.label_point() else {
+                continue;
+            };
+            if !(bounds.min()[0]..=bounds.max()[0]).contains(&x)
+                || !(bounds.min()[1]..=bounds.max()[1]).contains(&y)
+            {
+                continue;
+            }
+            let Some(text) = style.text(geometry).filter(|t| !t.is_empty()) else {
+                continue;
+            };
+
+            let galley = painter.layout_no_wrap(text, font.clone(), style.color);
+            let center = transform.position_from_point(&PlotPoint::new(x, y));
+            let rect = Rect::from_center_size(center, galley.size());
+            if !placer.place(rect.expand(LABEL_PADDING)) {
+                continue;
+            }
+
+            if style.halo {
+                for dx in [-HALO_WIDTH, 0., HALO_WIDTH] {
+                    for dy in [-HALO_WIDTH, 0., HALO_WIDTH] {
+                        let offset = Vec2::new(dx, dy);
+                        if offset != Vec2::ZERO {
+                            painter.galley_with_color(
+                                rect.min + offset,
+                                galley.clone(),
+                                style.halo_color,
+                            );
+                        }
+                    }
+                }
+            }
+            painter.galley_with_color(rect.min, galley, style.color);
+        }
+    }
+}
+
+/// Editor for the labels of a layer, `view_width` is the current plot width for the threshold
+pub fn label_ui(
+    ui: &mut Ui,
+    style: &mut LabelStyle,
+    attributes: &[String],
+    view_width: Option<f64>,
+    id: &str,
+) {
+    ui.checkbox(&mut style.is_enabled, "Show labels");
+
+    ui.add_enabled_ui(style.is_enabled, |ui| {
+        Grid::new(("layer_labels", id))
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Text:");
+                let selected = match style.attribute.is_empty() {
+                    true => "Geometry name",
+                    false => style.attribute.as_str(),
+                };
+                egui::ComboBox::from_id_source(("label_attribute", id))
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut style.attribute, String::new(), "Geometry name");
+                        for name in attributes {
+                            ui.selectable_value(&mut style.attribute, name.clone(), name);
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Font:");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut style.font_size)
+                            .speed(0.2)
+                            .clamp_range(6.0..=48.0),
+                    )
+                    .on_hover_text("Size");
+                    ui.color_edit_button_srgba(&mut style.color);
+                });
+                ui.end_row();
+
+                ui.label("Halo:");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut style.halo, "");
+                    ui.color_edit_button_srgba(&mut style.halo_color);
+                });
+                ui.end_row();
+
+                ui.label("Show below width:");
+                ui.horizontal(|ui| {
+                    let speed = style.max_view_width.max(1.) * 0.01;
+                    ui.add(
+                        DragValue::new(&mut style.max_view_width)
+                            .speed(speed)
+                            .clamp_range(0.0..=f64::MAX),
+                    )
+                    .on_hover_text("Plot width in coordinate units, 0 shows labels at any zoom");
+                    if let Some(width) = view_width {
+                        if ui
+                            .small_button("Current")
+                            .on_hover_text("Show labels from the current zoom on")
+                            .clicked()
+                        {
+                            style.max_view_width = width;
+                        }
+                    }
+                });
+                ui.end_row();
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2, Rect};
+    use geo::{line_string, point, polygon, Contains, Geometry, MultiLineString};
+    use serde_json::json;
+
+    use crate::{
+        label::{anchor, LabelPlacer, LabelStyle},
+        query,
+        sdo_geometry::SdoGeometry,
+    };
+
+    #[test]
+    fn test_anchor() {
+        let point: Geometry = point!(x: 1., y: 2.).into();
+        assert_eq!(anchor(&point), Some([1., 2.]));
+
+        let line: Geometry = line_string![(x: 0., y: 0.), (x: 10., y: 0.), (x: 10., y: 10.)].into();
+        assert_eq!(anchor(&line), Some([10., 0.]));
+
+        let lines: Geometry = MultiLineString::new(vec![
+            line_string![(x: 0., y: 0.), (x: 1., y: 0.)],
+            line_string![(x: 0., y: 5.), (x: 0., y: 9.)],
+        ])
+        .into();
+        assert_eq!(anchor(&lines), Some([0., 7.]));
+
+        // The centroid of a U shape is outside of it
+        let u_shape: Geometry = polygon![
+            (x: 0., y: 0.),
+            (x: 3., y: 0.),
+            (x: 3., y: 3.),
+            (x: 2., y: 3.),
+            (x: 2., y: 1.),
+            (x: 1., y: 1.),
+            (x: 1., y: 3.),
+            (x: 0., y: 3.),
+        ]
+        .into();
+        let anchor = anchor(&u_shape).unwrap();
+        assert!(u_shape.contains(&geo::Point::from(anchor)));
+    }
+
+    #[test]
+    fn test_label_text_and_threshold() {
+        let mut geometry = query::Geometry::new(
+            "parcels_0".into(),
+            SdoGeometry::from_geo(&geo::Point::new(0., 0.).into(), None),
+            Default::default(),
+        );
+        geometry
+            .attributes
+            .insert("PARCEL_NO".into(), json!("12/3"));
+
+        let mut style = LabelStyle::default();
+        assert_eq!(style.text(&geometry), Some("parcels_0".into()));
+        style.attribute = "parcel_no".into();
+        assert_eq!(style.text(&geometry), Some("12/3".into()));
+
+        assert!(!style.is_shown(10.));
+        style.is_enabled = true;
+        assert!(style.is_shown(1e9));
+        style.max_view_width = 100.;
+        assert!(style.is_shown(100.));
+        assert!(!style.is_shown(100.5));
+    }
+
+    #[test]
+    fn test_labels_do_not_overlap() {
+        let mut placer = LabelPlacer::default();
+        let label = |x: f32| Rect::from_min_size(pos2(x, 0.), vec2(20., 10.));
+
+        assert!(placer.place(label(0.)));
+        assert!(!placer.place(label(15.)));
+        assert!(placer.place(label(25.)));
+        assert_eq!(placer.len(), 2);
+    }
+}
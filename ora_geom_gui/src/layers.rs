@@ -0,0 +1,422 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::query::Query;
+
+/// A query result shown in the side panel and on the plot
+pub struct Layer {
+    pub name: String,
+    /// Name of the group the layer belongs to, empty when it is not grouped
+    pub group: String,
+    pub is_visible: bool,
+    pub query: Query,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LayerGroup {
+    pub name: String,
+    pub is_visible: bool,
+}
+
+/// Layer or group that is drawn alone, regardless of the visibility of the others
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Solo {
+    Layer(String),
+    Group(String),
+}
+
+/// Layers in the order of the side panel, the first one is drawn on top.
+///
+/// Layers of a group are always next to each other, so a group can be shown as a folder.
+#[derive(Default)]
+pub struct Layers {
+    pub layers: Vec<Layer>,
+    pub groups: Vec<LayerGroup>,
+    pub solo: Option<Solo>,
+    /// SRID every layer is drawn in, `None` draws the coordinates as they are
+    pub display_srid: Option<u32>,
+}
+
+impl Layers {
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
+
+    pub fn queries(&self) -> impl Iterator<Item = &Query> {
+        self.layers.iter().map(|l| &l.query)
+    }
+
+    /// Adds an ungrouped visible layer on top of the others
+    pub fn push(&mut self, name: String, query: Query) {
+        self.layers.insert(
+            0,
+            Layer {
+                name,
+                group: String::new(),
+                is_visible: true,
+                query,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Layer> {
+        if self.solo == Some(Solo::Layer(name.to_string())) {
+            self.solo = None;
+        }
+        let n = self.position(name)?;
+        Some(self.layers.remove(n))
+    }
+
+    /// Appends a number to the name when a layer with it already exists
+    pub fn unique_name(&self, name: &str) -> String {
+        let name = if name.is_empty() { "query" } else { name };
+        (1..)
+            .map(|n| match n {
+                1 => name.to_string(),
+                n => format!("{name}_{n}"),
+            })
+            .find(|candidate| !self.contains(candidate))
+            .unwrap_or_default()
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) {
+        if let Some(layer) = self.get_mut(name) {
+            layer.name = new_name.to_string();
+        }
+        if self.solo == Some(Solo::Layer(name.to_string())) {
+            self.solo = Some(Solo::Layer(new_name.to_string()));
+        }
+    }
+
+    pub fn group(&self, name: &str) -> Option<&LayerGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut LayerGroup> {
+        self.groups.iter_mut().find(|g| g.name == name)
+    }
+
+    /// Creates an empty visible group, returns `false` when the name is empty or taken
+    pub fn add_group(&mut self, name: &str) -> bool {
+        if name.is_empty() || self.group(name).is_some() {
+            return false;
+        }
+
+        self.groups.push(LayerGroup {
+            name: name.to_string(),
+            is_visible: true,
+        });
+        true
+    }
+
+    /// Deletes the group, its layers stay where they are without a group
+    pub fn remove_group(&mut self, name: &str) {
+        self.groups.retain(|g| g.name != name);
+        for layer in self.layers.iter_mut().filter(|l| l.group == name) {
+            layer.group.clear();
+        }
+        if self.solo == Some(Solo::Group(name.to_string())) {
+            self.solo = None;
+        }
+    }
+
+    /// Puts the layer into a group, it becomes the last layer of the group
+    pub fn set_group(&mut self, index: usize, group: &str) {
+        let to = match self.layers.iter().rposition(|l| l.group == group) {
+            Some(last) if !group.is_empty() => last + 1,
+            _ => index,
+        };
+        self.move_layer(index, to, group);
+    }
+
+    /// Moves a layer before the layer at `to` and puts it into `group`.
+    ///
+    /// `to` is an index before the move, `len()` moves the layer to the bottom.
+    pub fn move_layer(&mut self, from: usize, to: usize, group: &str) {
+        if from >= self.layers.len() {
+            return;
+        }
+
+        let mut layer = self.layers.remove(from);
+        layer.group = group.to_string();
+        let to = if to > from { to - 1 } else { to };
+        self.layers.insert(to.min(self.layers.len()), layer);
+        self.regroup();
+    }
+
+    /// Moves all layers of a group before the layer at `to`, or before its group when it has one
+    pub fn move_group(&mut self, group: &str, to: usize) {
+        if group.is_empty() {
+            return;
+        }
+
+        let to = match self.layers.get(to) {
+            Some(target) if target.group == group => return,
+            Some(target) if !target.group.is_empty() => self
+                .layers
+                .iter()
+                .position(|l| l.group == target.group)
+                .unwrap_or(to),
+            _ => to,
+        };
+        let before = self.layers[..to.min(self.layers.len())]
+            .iter()
+            .filter(|l| l.group != group)
+            .count();
+
+        let (members, mut others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.layers)
+            .into_iter()
+            .partition(|l| l.group == group);
+        others.splice(before..before, members);
+        self.layers = others;
+    }
+
+    /// Pulls the layers of every group up to the first layer of that group
+    fn regroup(&mut self) {
+        let keys: Vec<usize> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(n, layer)| match layer.group.is_empty() {
+                true => n,
+                false => self
+                    .layers
+                    .iter()
+                    .position(|l| l.group == layer.group)
+                    .unwrap_or(n),
+            })
+            .collect();
+
+        let mut layers: Vec<(usize, Layer)> = keys
+            .into_iter()
+            .zip(std::mem::take(&mut self.layers))
+            .collect();
+        layers.sort_by_key(|(key, _)| *key);
+        self.layers = layers.into_iter().map(|(_, layer)| layer).collect();
+    }
+
+    /// Consecutive layers with the same group, ungrouped layers have an empty group name
+    pub fn blocks(&self) -> Vec<(String, Range<usize>)> {
+        let mut blocks: Vec<(String, Range<usize>)> = vec![];
+        for (n, layer) in self.layers.iter().enumerate() {
+            match blocks.last_mut() {
+                Some((group, range)) if *group == layer.group => range.end = n + 1,
+                _ => blocks.push((layer.group.clone(), n..n + 1)),
+            }
+        }
+
+        blocks
+    }
+
+    /// Groups without layers
+    pub fn empty_groups(&self) -> impl Iterator<Item = &LayerGroup> {
+        self.groups
+            .iter()
+            .filter(|g| !self.layers.iter().any(|l| l.group == g.name))
+    }
+
+    /// Solos the layer or group, or turns solo off when it is already soloed
+    pub fn toggle_solo(&mut self, solo: Solo) {
+        self.solo = match self.solo == Some(solo.clone()) {
+            true => None,
+            false => Some(solo),
+        };
+    }
+
+    pub fn set_all_visible(&mut self, is_visible: bool) {
+        for layer in self.layers.iter_mut() {
+            layer.is_visible = is_visible;
+        }
+        for group in self.groups.iter_mut() {
+            group.is_visible = is_visible;
+        }
+    }
+
+    pub fn invert_visibility(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.is_visible = !layer.is_visible;
+        }
+    }
+
+    /// A soloed layer is drawn even when hidden, a soloed group draws its visible layers
+    pub fn is_drawn(&self, layer: &Layer) -> bool {
+        let is_group_visible = || self.group(&layer.group).is_none_or(|g| g.is_visible);
+        match &self.solo {
+            Some(Solo::Layer(name)) => layer.name == *name,
+            Some(Solo::Group(group)) => layer.group == *group && layer.is_visible,
+            None => layer.is_visible && is_group_visible(),
+        }
+    }
+
+    /// Reprojects geometries whose display SRID differs, new geometries are reprojected by the next call
+    pub fn reproject(&mut self) {
+        for layer in &mut self.layers {
+            layer.query.set_display_srid(self.display_srid);
+        }
+    }
+
+    /// Reprojects every shape again, after an SRID was added to the registry
+    pub fn reset_display(&mut self) {
+        for layer in &mut self.layers {
+            layer.query.reset_display();
+        }
+    }
+
+    /// Drawn layers from the bottom to the top
+    pub fn drawn(&self) -> impl Iterator<Item = (&str, &Query)> {
+        self.layers
+            .iter()
+            .rev()
+            .filter(|l| self.is_drawn(l))
+            .map(|l| (l.name.as_str(), &l.query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{point, Geometry};
+
+    use crate::{
+        feature::Feature,
+        layers::{Layers, Solo},
+        query::Query,
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+    };
+
+    fn layers(names: &[&str]) -> Layers {
+        let mut layers = Layers::default();
+        for name in names.iter().rev() {
+            layers.push(
+                name.to_string(),
+                Query::new(name, String::new(), vec![], LayerStyle::default(), vec![]),
+            );
+        }
+        layers
+    }
+
+    fn names(layers: &Layers) -> Vec<&str> {
+        layers.layers.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_move_layers_and_groups() {
+        let mut layers = layers(&["a", "b", "c", "d"]);
+        assert_eq!(names(&layers), vec!["a", "b", "c", "d"]);
+        assert_eq!(
+            layers.drawn().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["d", "c", "b", "a"]
+        );
+
+        layers.move_layer(0, 3, "");
+        assert_eq!(names(&layers), vec!["b", "c", "a", "d"]);
+
+        // Grouped layers are kept together
+        layers.add_group("roads");
+        layers.set_group(0, "roads");
+        layers.set_group(3, "roads");
+        assert_eq!(names(&layers), vec!["b", "d", "c", "a"]);
+        assert_eq!(
+            layers.blocks(),
+            vec![("roads".to_string(), 0..2), (String::new(), 2..4)]
+        );
+
+        layers.move_group("roads", 4);
+        assert_eq!(names(&layers), vec!["c", "a", "b", "d"]);
+
+        // Dropping a layer into the middle of a group joins it
+        layers.move_layer(0, 3, "roads");
+        assert_eq!(names(&layers), vec!["a", "b", "c", "d"]);
+
+        layers.remove_group("roads");
+        assert!(layers.layers.iter().all(|l| l.group.is_empty()));
+    }
+
+    #[test]
+    fn test_visibility_and_solo() {
+        let mut layers = layers(&["a", "b", "c"]);
+        layers.add_group("group");
+        layers.set_group(1, "group");
+        layers.set_group(2, "group");
+        let drawn = |layers: &Layers| {
+            layers
+                .drawn()
+                .map(|(n, _)| n.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        layers.group_mut("group").unwrap().is_visible = false;
+        assert_eq!(drawn(&layers), vec!["a"]);
+
+        layers.toggle_solo(Solo::Group("group".into()));
+        layers.get_mut("c").unwrap().is_visible = false;
+        assert_eq!(drawn(&layers), vec!["b"]);
+
+        layers.toggle_solo(Solo::Layer("c".into()));
+        assert_eq!(drawn(&layers), vec!["c"]);
+        layers.toggle_solo(Solo::Layer("c".into()));
+        assert_eq!(layers.solo, None);
+
+        layers.set_all_visible(true);
+        layers.invert_visibility();
+        layers.get_mut("a").unwrap().is_visible = true;
+        assert_eq!(drawn(&layers), vec!["a"]);
+    }
+
+    #[test]
+    fn test_reproject() {
+        let point = |srid| {
+            let point: Geometry = point!(x: 180., y: 0.).into();
+            Feature::from(SdoGeometry::from_geo(&point, srid))
+        };
+        let data = vec![point(Some(8307.)), point(Some(1234.)), point(None)];
+        let mut layers = Layers::default();
+        layers.push(
+            "points".into(),
+            Query::new("points", String::new(), vec![], LayerStyle::default(), data),
+        );
+        let x = |layers: &Layers| {
+            layers.layers[0]
+                .query
+                .geometries
+                .iter()
+                .map(|g| g.extent().map(|e| e.min[0].round()))
+                .collect::<Vec<_>>()
+        };
+
+        layers.display_srid = Some(3857);
+        layers.reproject();
+        // Unknown and missing SRIDs are not drawn in other units
+        assert_eq!(x(&layers), vec![Some(20037508.), None, None]);
+
+        layers.display_srid = None;
+        layers.reproject();
+        assert_eq!(x(&layers), vec![Some(180.); 3]);
+        assert!(layers.layers[0]
+            .query
+            .geometries
+            .iter()
+            .all(|g| g.is_reprojectable()));
+    }
+}
@@ -0,0 +1,343 @@
+use egui::{Color32, ComboBox, Context, RichText, Ui, Window};
+use geo::{relate::IntersectionMatrix, Relate};
+use rstar::{
+    primitives::{GeomWithData, Rectangle},
+    RTree, AABB,
+};
+
+use crate::{
+    layers::Layers,
+    query::Query,
+    render::Extent,
+    selection::{GeometryRef, Selection},
+};
+
+/// Relationship a geometry must have with the geometries of the other layer
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Predicate {
+    #[default]
+    Intersects,
+    Contains,
+    Within,
+    Touches,
+    Overlaps,
+    Disjoint,
+    Equals,
+}
+
+impl Predicate {
+    pub const ALL: [Predicate; 7] = [
+        Predicate::Intersects,
+        Predicate::Contains,
+        Predicate::Within,
+        Predicate::Touches,
+        Predicate::Overlaps,
+        Predicate::Disjoint,
+        Predicate::Equals,
+    ];
+
+    /// Verb completing "geometries that ... the other layer"
+    pub fn label(&self) -> &'static str {
+        match self {
+            Predicate::Intersects => "intersect",
+            Predicate::Contains => "contain",
+            Predicate::Within => "are within",
+            Predicate::Touches => "touch",
+            Predicate::Overlaps => "overlap",
+            Predicate::Disjoint => "are disjoint from",
+            Predicate::Equals => "are equal to",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Predicate::Intersects => "Share at least one point",
+            Predicate::Contains => "The other geometry lies inside, not only on the boundary",
+            Predicate::Within => "Lie inside the other geometry, not only on its boundary",
+            Predicate::Touches => "Meet only at their boundaries",
+            Predicate::Overlaps => "Share part of their interior, of the same dimension",
+            Predicate::Disjoint => "Share no point with any geometry of the other layer",
+            Predicate::Equals => "Cover the same points, regardless of the vertex order",
+        }
+    }
+
+    pub fn holds(&self, matrix: &IntersectionMatrix) -> bool {
+        match self {
+            Predicate::Intersects => matrix.is_intersects(),
+            Predicate::Contains => matrix.is_contains(),
+            Predicate::Within => matrix.is_within(),
+            Predicate::Touches => matrix.is_touches(),
+            Predicate::Overlaps => matrix.is_overlaps(),
+            Predicate::Disjoint => matrix.is_disjoint(),
+            Predicate::Equals => matrix.is_equal_topo(),
+        }
+    }
+}
+
+/// R-tree of the bounding boxes of the active geometries of a layer, by row number
+pub struct SpatialIndex {
+    tree: RTree<GeomWithData<Rectangle<[f64; 2]>, usize>>,
+}
+
+impl SpatialIndex {
+    pub fn new(query: &Query) -> Self {
+        let boxes = query
+            .geometries
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| query.is_shown(*n))
+            .filter_map(|(n, g)| {
+                let extent = g.extent()?;
+                Some(GeomWithData::new(
+                    Rectangle::from_corners(extent.min, extent.max),
+                    n,
+                ))
+            })
+            .collect();
+
+        Self {
+            tree: RTree::bulk_load(boxes),
+        }
+    }
+
+    /// Rows whose bounding box touches the extent
+    pub fn candidates(&self, extent: &Extent) -> impl Iterator<Item = usize> + '_ {
+        self.tree
+            .locate_in_envelope_intersecting(&AABB::from_corners(extent.min, extent.max))
+            .map(|entry| entry.data)
+    }
+}
+
+/// Rows of the target layer that have the relationship with a geometry of the reference layer.
+///
+/// Disjoint geometries have to be disjoint from every geometry of the reference layer.
+/// Shapes are compared in display coordinates, so layers in different SRIDs can be related
+/// once a display CRS is chosen. A geometry is never compared with itself.
+pub fn select_by_location(target: &Query, reference: &Query, predicate: Predicate) -> Vec<usize> {
+    let index = SpatialIndex::new(reference);
+    let is_same_layer = std::ptr::eq(target, reference);
+
+    target
+        .geometries
+        .iter()
+        .enumerate()
+        .filter(|(n, _)| target.is_shown(*n))
+        .filter_map(|(n, geometry)| {
+            let shape = geometry.shape()?;
+            let extent = geometry.extent()?;
+            let mut matrices = index
+                .candidates(&extent)
+                .filter(|&m| !(is_same_layer && m == n))
+                .filter_map(|m| reference.geometries[m].shape())
+                .map(|other| shape.relate(other));
+
+            let is_selected = match predicate {
+                // Geometries whose boxes do not meet are disjoint anyway
+                Predicate::Disjoint => matrices.all(|m| m.is_disjoint()),
+                _ => matrices.any(|m| predicate.holds(&m)),
+            };
+            is_selected.then_some(n)
+        })
+        .collect()
+}
+
+/// Layers and relationship of the last "Select by location"
+#[derive(Default)]
+pub struct LocationQuery {
+    pub target: String,
+    pub reference: String,
+    pub predicate: Predicate,
+    /// Adds to the current selection instead of replacing it
+    pub is_added: bool,
+    pub message: RichText,
+}
+
+pub struct LocationWindow<'a> {
+    pub location: &'a mut LocationQuery,
+    pub layers: &'a Layers,
+    pub selection: &'a mut Selection,
+}
+
+impl<'a> LocationWindow<'a> {
+    pub fn new(
+        location: &'a mut LocationQuery,
+        layers: &'a Layers,
+        selection: &'a mut Selection,
+    ) -> Self {
+        Self {
+            location,
+            layers,
+            selection,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Select by location")
+            .open(is_active)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Select geometries of");
+                    layer_combo(
+                        ui,
+                        "location_target",
+                        &mut self.location.target,
+                        self.layers,
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("that");
+                    ComboBox::from_id_source("location_predicate")
+                        .selected_text(self.location.predicate.label())
+                        .show_ui(ui, |ui| {
+                            for predicate in Predicate::ALL {
+                                ui.selectable_value(
+                                    &mut self.location.predicate,
+                                    predicate,
+                                    predicate.label(),
+                                )
+                                .on_hover_text(predicate.description());
+                            }
+                        });
+                    ui.label("geometries of");
+                    layer_combo(
+                        ui,
+                        "location_reference",
+                        &mut self.location.reference,
+                        self.layers,
+                    );
+                });
+                ui.checkbox(&mut self.location.is_added, "Add to the current selection");
+
+                let layers = self
+                    .layers
+                    .get(&self.location.target)
+                    .zip(self.layers.get(&self.location.reference));
+                let select = ui
+                    .add_enabled(layers.is_some(), egui::Button::new("Select"))
+                    .clicked();
+                if let Some((target, reference)) = layers.filter(|_| select) {
+                    let rows = select_by_location(
+                        &target.query,
+                        &reference.query,
+                        self.location.predicate,
+                    );
+                    if !self.location.is_added {
+                        self.selection.clear();
+                    }
+                    for n in &rows {
+                        let item = GeometryRef::new(&target.name, *n);
+                        if !self.selection.contains(&item) {
+                            self.selection.items.push(item);
+                        }
+                    }
+                    self.location.message = match rows.len() {
+                        0 => RichText::new("No geometry matched").color(Color32::LIGHT_YELLOW),
+                        n => {
+                            RichText::new(format!("{n} geometries selected")).color(Color32::GREEN)
+                        }
+                    };
+                }
+
+                ui.label(self.location.message.clone());
+            });
+    }
+}
+
+/// Picks one of the layers by name
+pub fn layer_combo(ui: &mut Ui, id: &str, name: &mut String, layers: &Layers) {
+    ComboBox::from_id_source(id)
+        .selected_text(name.as_str())
+        .show_ui(ui, |ui| {
+            for layer in &layers.layers {
+                ui.selectable_value(name, layer.name.clone(), &layer.name);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        feature::Feature,
+        location::{select_by_location, Predicate, SpatialIndex},
+        query::Query,
+        render::Extent,
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+    };
+
+    fn rectangle(min: [f64; 2], max: [f64; 2]) -> Feature {
+        Feature::from(SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3.],
+            sdo_ordinates: vec![min[0], min[1], max[0], max[1]],
+        })
+    }
+
+    fn query(name: &str, data: Vec<Feature>) -> Query {
+        Query::new(name, String::new(), vec![], LayerStyle::default(), data)
+    }
+
+    #[test]
+    fn test_spatial_index_candidates() {
+        let parcels = query(
+            "parcels",
+            vec![
+                rectangle([0., 0.], [10., 10.]),
+                rectangle([10., 0.], [20., 10.]),
+                rectangle([50., 50.], [60., 60.]),
+            ],
+        );
+        let index = SpatialIndex::new(&parcels);
+
+        let mut rows: Vec<usize> = index
+            .candidates(&Extent::new([5., 5.], [15., 6.]))
+            .collect();
+        rows.sort();
+
+        assert_eq!(rows, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_select_by_location() {
+        let parcels = query(
+            "parcels",
+            vec![
+                rectangle([0., 0.], [10., 10.]),
+                rectangle([10., 0.], [20., 10.]),
+            ],
+        );
+        let buildings = query(
+            "buildings",
+            vec![
+                // Inside the first parcel
+                rectangle([2., 2.], [4., 4.]),
+                // Across the shared parcel boundary
+                rectangle([8., 2.], [12., 4.]),
+                // Against the outer edge of the second parcel
+                rectangle([20., 2.], [22., 4.]),
+                // Far away
+                rectangle([40., 40.], [42., 42.]),
+            ],
+        );
+
+        let select = |predicate| select_by_location(&buildings, &parcels, predicate);
+
+        assert_eq!(select(Predicate::Intersects), vec![0, 1, 2]);
+        assert_eq!(select(Predicate::Within), vec![0]);
+        assert_eq!(select(Predicate::Touches), vec![2]);
+        assert_eq!(select(Predicate::Overlaps), vec![1]);
+        assert_eq!(select(Predicate::Disjoint), vec![3]);
+        assert_eq!(
+            select_by_location(&parcels, &buildings, Predicate::Contains),
+            vec![0]
+        );
+        // Parcels touch each other but not themselves
+        assert_eq!(
+            select_by_location(&parcels, &parcels, Predicate::Touches),
+            vec![0, 1]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::{Arc, Mutex};
+
+use egui_plot::PlotTransform;
+use geo::{Geometry, Simplify};
+
+use crate::{query, render::Extent, triangulation::Triangulation};
+
+/// Largest distance in pixels between a simplified line and the real one
+pub const PIXEL_TOLERANCE: f64 = 0.5;
+/// Geometries with fewer vertices are always drawn in full
+pub const MIN_SIMPLIFIED_VERTICES: usize = 256;
+/// Zoom bands whose simplified shapes are kept for every geometry
+const CACHED_BANDS: usize = 4;
+/// Fraction of the view added on every side, so panning does not show missing geometries
+const CULL_MARGIN: f64 = 0.5;
+
+/// Zoom band of a scale, every band has twice the data units per pixel of the previous one
+pub fn zoom_band(units_per_pixel: f64) -> i32 {
+    units_per_pixel.max(f64::MIN_POSITIVE).log2().floor() as i32
+}
+
+/// Tolerance in data units of a band, it stays within `PIXEL_TOLERANCE` over the whole band
+pub fn band_tolerance(band: i32) -> f64 {
+    PIXEL_TOLERANCE * 2f64.powi(band)
+}
+
+/// Douglas–Peucker simplification of lines and polygon rings, rings keep at least 4 vertices
+pub fn simplify(shape: &Geometry, epsilon: f64) -> Geometry {
+    match shape {
+        Geometry::LineString(line) => Geometry::LineString(line.simplify(epsilon)),
+        Geometry::MultiLineString(lines) => Geometry::MultiLineString(lines.simplify(epsilon)),
+        Geometry::Polygon(polygon) => Geometry::Polygon(polygon.simplify(epsilon)),
+        Geometry::MultiPolygon(polygons) => Geometry::MultiPolygon(polygons.simplify(epsilon)),
+        Geometry::GeometryCollection(collection) => {
+            Geometry::GeometryCollection(collection.iter().map(|g| simplify(g, epsilon)).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Shape drawn at one zoom band with the triangles of its fill
+pub struct Simplified {
+    pub shape: Geometry,
+    pub triangulation: Triangulation,
+}
+
+impl Simplified {
+    pub fn new(shape: &Geometry, band: i32) -> Self {
+        let shape = simplify(shape, band_tolerance(band));
+        let triangulation = Triangulation::of_geometry(&shape);
+        Self {
+            shape,
+            triangulation,
+        }
+    }
+}
+
+/// Simplified shapes of a geometry for the zoom bands used last
+#[derive(Default)]
+pub struct LodCache {
+    bands: Mutex<Vec<(i32, Arc<Simplified>)>>,
+}
+
+impl LodCache {
+    pub fn get_or_insert(
+        &self,
+        band: i32,
+        simplify: impl FnOnce() -> Simplified,
+    ) -> Arc<Simplified> {
+        let mut bands = self.bands.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, simplified)) = bands.iter().find(|(b, _)| *b == band) {
+            return simplified.clone();
+        }
+
+        let simplified = Arc::new(simplify());
+        if bands.len() >= CACHED_BANDS {
+            bands.remove(0);
+        }
+        bands.push((band, simplified.clone()));
+        simplified
+    }
+}
+
+/// What is drawn for a geometry: its full shape or a simplified one
+pub enum Drawn<'a> {
+    Full(&'a Geometry, &'a Triangulation),
+    Simplified(Arc<Simplified>),
+}
+
+impl Drawn<'_> {
+    pub fn shape(&self) -> &Geometry {
+        match self {
+            Drawn::Full(shape, _) => shape,
+            Drawn::Simplified(simplified) => &simplified.shape,
+        }
+    }
+
+    pub fn triangulation(&self) -> &Triangulation {
+        match self {
+            Drawn::Full(_, triangulation) => triangulation,
+            Drawn::Simplified(simplified) => &simplified.triangulation,
+        }
+    }
+}
+
+/// Part of the plot geometries are drawn for and the zoom band they are simplified for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub visible: Extent,
+    /// Visible extent with a margin, geometries outside of it are culled
+    pub extent: Extent,
+    pub band: i32,
+}
+
+impl Viewport {
+    pub fn new(visible: Extent, units_per_pixel: f64) -> Self {
+        Self {
+            visible,
+            extent: visible.with_margin(CULL_MARGIN),
+            band: zoom_band(units_per_pixel),
+        }
+    }
+
+    /// Viewport of the plot, or of the view it is about to be moved to
+    pub fn of_transform(transform: &PlotTransform, requested: Option<Extent>) -> Self {
+        let visible = requested.unwrap_or(Extent::from_plot_bounds(transform.bounds()));
+        let frame = transform.frame();
+        // The plot keeps the aspect ratio, so the larger scale of both axes is used
+        let units_per_pixel = (visible.width() / frame.width().max(1.) as f64)
+            .max(visible.height() / frame.height().max(1.) as f64);
+        Self::new(visible, units_per_pixel)
+    }
+
+    /// Whether the geometries drawn for this viewport are still right for the other one
+    pub fn covers(&self, other: &Viewport) -> bool {
+        self.band == other.band && self.extent.contains(&other.visible)
+    }
+
+    /// Shape to draw for the geometry, `None` when it is outside of the viewport
+    pub fn drawn<'a>(&self, geometry: &'a query::Geometry) -> Option<Drawn<'a>> {
+        if !self.extent.intersects(&geometry.extent()?) {
+            return None;
+        }
+        self.simplified(geometry)
+    }
+
+    /// What is drawn for a geometry in the zoom band, wherever it is
+    pub fn simplified<'a>(&self, geometry: &'a query::Geometry) -> Option<Drawn<'a>> {
+        match geometry.simplified(self.band) {
+            Some(simplified) => Some(Drawn::Simplified(simplified)),
+            None => Some(Drawn::Full(geometry.shape()?, geometry.triangulation())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{CoordsIter, Geometry, LineString};
+
+    use crate::{
+        lod::{band_tolerance, simplify, zoom_band, Viewport},
+        render::Extent,
+    };
+
+    #[test]
+    fn test_zoom_band() {
+        assert_eq!(zoom_band(1.), 0);
+        assert_eq!(zoom_band(3.), 1);
+        assert_eq!(zoom_band(0.3), -2);
+        // The tolerance never exceeds half a pixel within the band
+        assert!(band_tolerance(zoom_band(3.)) <= 0.5 * 3.);
+    }
+
+    #[test]
+    fn test_simplify_keeps_shape_within_tolerance() {
+        // Zigzag with a 0.1 amplitude along a straight line
+        let line = LineString::from(
+            (0..=1000)
+                .map(|i| (i as f64, if i % 2 == 0 { 0. } else { 0.1 }))
+                .collect::<Vec<_>>(),
+        );
+        let shape = Geometry::LineString(line);
+
+        let coarse = simplify(&shape, 0.5);
+        let fine = simplify(&shape, 0.01);
+
+        assert_eq!(coarse.coords_count(), 2);
+        assert_eq!(fine.coords_count(), shape.coords_count());
+    }
+
+    #[test]
+    fn test_viewport_covers() {
+        let viewport = Viewport::new(Extent::new([0., 0.], [100., 100.]), 1.);
+        let panned = Viewport::new(Extent::new([30., 0.], [130., 100.]), 1.);
+        let far = Viewport::new(Extent::new([300., 0.], [400., 100.]), 1.);
+        let zoomed_out = Viewport::new(Extent::new([0., 0.], [100., 100.]), 4.);
+
+        assert!(viewport.covers(&panned));
+        assert!(!viewport.covers(&far));
+        assert!(!viewport.covers(&zoomed_out));
+        assert!(viewport
+            .extent
+            .intersects(&Extent::new([-20., -20.], [-10., -10.])));
+        assert!(!viewport
+            .extent
+            .intersects(&Extent::new([200., 0.], [210., 10.])));
+    }
+}
@@ -1,8 +1,42 @@
 pub mod api;
+pub mod cache;
+pub mod cli;
+pub mod compare;
+pub mod config;
+pub mod crs;
+pub mod editor;
+pub mod export;
+pub mod expression;
+pub mod feature;
+pub mod formats;
 pub mod geometry_viewer;
+pub mod history;
+pub mod inspector;
+pub mod label;
+pub mod layers;
+pub mod location;
+pub mod lod;
+pub mod measure;
+pub mod metrics;
+pub mod operation;
+pub mod overlay;
 pub mod query;
+pub mod raster;
+pub mod render;
 pub mod sdo_geometry;
+pub mod selection;
+pub mod snap;
+pub mod style;
+pub mod svg;
+pub mod table;
+pub mod theme;
+pub mod triangulation;
+pub mod undo;
+pub mod view;
+pub mod workspace;
 
+use clap::Parser;
+use cli::Cli;
 use eframe::egui;
 use egui::Visuals;
 use geometry_viewer::GeometryViewer;
@@ -12,9 +46,18 @@ use tracing_subscriber::FmtSubscriber;
 fn main() -> Result<(), eframe::Error> {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
+        .with_writer(std::io::stderr)
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
+    if let Some(command) = Cli::parse().command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(1280.0, 720.0)),
         ..Default::default()
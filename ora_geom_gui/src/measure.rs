@@ -0,0 +1,373 @@
+use egui::{Button, Color32, Context, Grid, RichText, Stroke, Window};
+use egui_plot::{Line, LineStyle, MarkerShape, PlotUi, Points};
+use geo::{Area, Bearing, Distance, Geodesic, GeodesicArea, LineString, Point, Polygon};
+
+use crate::{
+    crs::{self, Crs},
+    layers::Layers,
+};
+
+/// Colour of measured paths, distinct from the layer palette
+pub const MEASURE_COLOR: Color32 = Color32::from_rgb(230, 60, 160);
+
+/// How distances and areas are computed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Metric {
+    /// Cartesian coordinates in the units of the SRID
+    #[default]
+    Planar,
+    /// Longitude and latitude on the WGS 84 ellipsoid, in metres
+    Geodesic,
+}
+
+impl Metric {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Metric::Planar => "planar, in coordinate units",
+            Metric::Geodesic => "geodesic, in metres",
+        }
+    }
+
+    pub fn distance(&self, a: [f64; 2], b: [f64; 2]) -> f64 {
+        match self {
+            Metric::Planar => (b[0] - a[0]).hypot(b[1] - a[1]),
+            Metric::Geodesic => Geodesic.distance(Point::from(a), Point::from(b)),
+        }
+    }
+
+    /// Degrees clockwise from north, or from the y axis for planar coordinates
+    pub fn bearing(&self, a: [f64; 2], b: [f64; 2]) -> f64 {
+        match self {
+            Metric::Planar => (b[0] - a[0])
+                .atan2(b[1] - a[1])
+                .to_degrees()
+                .rem_euclid(360.),
+            Metric::Geodesic => Geodesic
+                .bearing(Point::from(a), Point::from(b))
+                .rem_euclid(360.),
+        }
+    }
+
+    pub fn area(&self, polygon: &Polygon) -> f64 {
+        match self {
+            Metric::Planar => polygon.unsigned_area(),
+            Metric::Geodesic => polygon.geodesic_area_unsigned(),
+        }
+    }
+
+    pub fn format_length(&self, length: f64) -> String {
+        match self {
+            Metric::Planar => format!("{length:.3}"),
+            Metric::Geodesic if length >= 1000. => format!("{:.3} km", length / 1000.),
+            Metric::Geodesic => format!("{length:.2} m"),
+        }
+    }
+
+    pub fn format_area(&self, area: f64) -> String {
+        match self {
+            Metric::Planar => format!("{area:.3}"),
+            Metric::Geodesic if area >= 1e6 => format!("{:.3} km²", area / 1e6),
+            Metric::Geodesic => format!("{area:.1} m²"),
+        }
+    }
+}
+
+/// The display SRID, or the SRID of the topmost drawn layer, plot coordinates are in it
+pub fn display_srid(layers: &Layers) -> Option<f32> {
+    if let Some(srid) = layers.display_srid {
+        return Some(srid as f32);
+    }
+    layers
+        .drawn()
+        .last()
+        .and_then(|(_, query)| query.geometries.first())
+        .and_then(|g| g.sdo_geometry.sdo_srid)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub length: f64,
+    pub bearing: f64,
+    /// Length of the path up to the end of this segment
+    pub cumulative: f64,
+}
+
+/// Points clicked on the plot, a closed measurement is a polygon
+#[derive(Debug, Default)]
+pub struct Measurement {
+    pub points: Vec<[f64; 2]>,
+    pub is_closed: bool,
+    pub srid: Option<f32>,
+}
+
+impl Measurement {
+    /// Geodesic whenever the coordinates can be converted to WGS 84, projected ones included
+    pub fn metric(&self) -> Metric {
+        match self.crs() {
+            Some(_) => Metric::Geodesic,
+            None => Metric::Planar,
+        }
+    }
+
+    fn crs(&self) -> Option<Crs> {
+        self.srid.and_then(|srid| crs::lookup(srid as u32))
+    }
+
+    /// Points in the coordinates the metric works in, longitude and latitude when geodesic
+    fn measured(&self, points: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+        match self.crs() {
+            Some(crs) => points.into_iter().map(|p| crs.to_wgs84(p)).collect(),
+            None => points,
+        }
+    }
+
+    /// Adds a point, clicking the first point again closes a path of three or more points
+    pub fn add(&mut self, point: [f64; 2]) {
+        if self.is_closed {
+            self.points.clear();
+            self.is_closed = false;
+        }
+
+        if self.points.len() >= 3 && self.points.first() == Some(&point) {
+            self.is_closed = true;
+        } else if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+    }
+
+    /// Opens a closed polygon, or removes the last point of a path
+    pub fn undo(&mut self) {
+        if self.is_closed {
+            self.is_closed = false;
+        } else {
+            self.points.pop();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.is_closed = false;
+    }
+
+    /// Vertices in drawing order, a closed measurement ends with its first point
+    pub fn path(&self) -> Vec<[f64; 2]> {
+        let mut path = self.points.clone();
+        if self.is_closed {
+            path.extend(self.points.first());
+        }
+        path
+    }
+
+    pub fn segments(&self) -> Vec<Segment> {
+        let metric = self.metric();
+        let mut cumulative = 0.;
+        self.measured(self.path())
+            .windows(2)
+            .map(|w| {
+                let length = metric.distance(w[0], w[1]);
+                cumulative += length;
+                Segment {
+                    length,
+                    bearing: metric.bearing(w[0], w[1]),
+                    cumulative,
+                }
+            })
+            .collect()
+    }
+
+    pub fn length(&self) -> f64 {
+        self.segments().last().map_or(0., |s| s.cumulative)
+    }
+
+    pub fn polygon(&self) -> Option<Polygon> {
+        self.is_closed
+            .then(|| Polygon::new(LineString::from(self.points.clone()), vec![]))
+    }
+
+    pub fn area(&self) -> Option<f64> {
+        let ring = LineString::from(self.measured(self.points.clone()));
+        self.is_closed
+            .then(|| self.metric().area(&Polygon::new(ring, vec![])))
+    }
+}
+
+/// Draws the measured path and a dashed line to the pointer while it is open
+pub fn plot_measurement(
+    plot_ui: &mut PlotUi,
+    measurement: &Measurement,
+    pointer: Option<[f64; 2]>,
+) {
+    let stroke = Stroke::new(2., MEASURE_COLOR);
+    plot_ui.line(Line::new(measurement.path()).stroke(stroke));
+    plot_ui.points(
+        Points::new(measurement.points.clone())
+            .radius(3.)
+            .filled(true)
+            .color(MEASURE_COLOR),
+    );
+
+    let Some(pointer) = pointer else {
+        return;
+    };
+    if let Some(last) = measurement.points.last().filter(|_| !measurement.is_closed) {
+        plot_ui.line(
+            Line::new(vec![*last, pointer])
+                .stroke(stroke)
+                .style(LineStyle::dashed_dense()),
+        );
+    }
+    plot_ui.points(
+        Points::new(vec![pointer])
+            .shape(MarkerShape::Circle)
+            .radius(5.)
+            .filled(false)
+            .color(MEASURE_COLOR),
+    );
+}
+
+pub struct MeasureWindow<'a> {
+    pub measurement: &'a mut Measurement,
+}
+
+impl<'a> MeasureWindow<'a> {
+    pub fn new(measurement: &'a mut Measurement) -> Self {
+        Self { measurement }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Measure")
+            .open(is_active)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Click the plot to add points, click the first point to close an area.");
+                ui.label("Points snap to vertices. Backspace undoes, Escape clears.");
+                ui.separator();
+
+                let metric = self.measurement.metric();
+                let srid = self
+                    .measurement
+                    .srid
+                    .map_or("no SRID".into(), |srid| format!("SRID {srid}"));
+                ui.label(RichText::new(format!("{srid}, {}", metric.label())).italics());
+
+                let segments = self.measurement.segments();
+                if !segments.is_empty() {
+                    Grid::new("measure_segments")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("#");
+                            ui.strong("Length");
+                            ui.strong("Bearing");
+                            ui.strong("Total");
+                            ui.end_row();
+
+                            for (n, segment) in segments.iter().enumerate() {
+                                ui.label((n + 1).to_string());
+                                ui.label(metric.format_length(segment.length));
+                                ui.label(format!("{:.1}°", segment.bearing));
+                                ui.label(metric.format_length(segment.cumulative));
+                                ui.end_row();
+                            }
+                        });
+                }
+
+                if let Some(area) = self.measurement.area() {
+                    ui.separator();
+                    Grid::new("measure_area").num_columns(2).show(ui, |ui| {
+                        ui.label("Area:");
+                        ui.label(metric.format_area(area));
+                        ui.end_row();
+
+                        ui.label("Perimeter:");
+                        ui.label(metric.format_length(self.measurement.length()));
+                        ui.end_row();
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    let has_points = !self.measurement.points.is_empty();
+                    if ui.add_enabled(has_points, Button::new("Undo")).clicked() {
+                        self.measurement.undo();
+                    }
+                    if ui.add_enabled(has_points, Button::new("Clear")).clicked() {
+                        self.measurement.clear();
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::measure::{Measurement, Metric};
+
+    #[test]
+    fn test_planar_measurement() {
+        let mut measurement = Measurement::default();
+        for point in [[0., 0.], [3., 4.], [3., 0.]] {
+            measurement.add(point);
+        }
+
+        let segments = measurement.segments();
+        assert_eq!(segments[0].length, 5.);
+        assert_eq!(segments[1].bearing, 180.);
+        assert_eq!(measurement.length(), 9.);
+        assert_eq!(measurement.area(), None);
+
+        measurement.add([0., 0.]);
+        assert!(measurement.is_closed);
+        assert_eq!(measurement.length(), 12.);
+        assert_eq!(measurement.area(), Some(6.));
+        assert_eq!(measurement.segments()[2].bearing, 270.);
+
+        measurement.undo();
+        assert!(!measurement.is_closed);
+        assert_eq!(measurement.points.len(), 3);
+    }
+
+    #[test]
+    fn test_geodesic_measurement() {
+        let mut measurement = Measurement {
+            srid: Some(8307.),
+            ..Default::default()
+        };
+        assert_eq!(measurement.metric(), Metric::Geodesic);
+
+        for point in [[0., 0.], [1., 0.], [1., 1.], [0., 1.], [0., 0.]] {
+            measurement.add(point);
+        }
+
+        let segments = measurement.segments();
+        // One degree of longitude on the equator
+        assert!((segments[0].length - 111_319.5).abs() < 1.);
+        assert!((segments[0].bearing - 90.).abs() < 1e-9);
+        assert!(segments[1].bearing.abs() < 1e-9);
+        let area = measurement.area().unwrap();
+        assert!((area / 1e6 - 12_308.8).abs() < 1.);
+        assert_eq!(Metric::Geodesic.format_length(1500.), "1.500 km");
+    }
+
+    #[test]
+    fn test_geodesic_measurement_in_projected_display() {
+        let mut measurement = Measurement {
+            srid: Some(3857.),
+            ..Default::default()
+        };
+        assert_eq!(measurement.metric(), Metric::Geodesic);
+
+        // One degree of longitude on the equator in Web Mercator metres
+        measurement.add([0., 0.]);
+        measurement.add([111_319.49, 0.]);
+        let segments = measurement.segments();
+        assert!((segments[0].length - 111_319.5).abs() < 1.);
+        assert!((segments[0].bearing - 90.).abs() < 1e-6);
+
+        // Mercator stretches 60° north twice, the ground distance is half the planar one
+        let y = crate::crs::lookup(3857).unwrap().from_wgs84([0., 60.])[1];
+        measurement.clear();
+        measurement.add([0., y]);
+        measurement.add([200_000., y]);
+        assert!((measurement.length() - 100_000.).abs() < 500.);
+    }
+}
@@ -0,0 +1,140 @@
+use geo::{Centroid, Geodesic, GeodesicArea, Geometry, InteriorPoint, Length, LineString, Polygon};
+
+use crate::{crs, measure::Metric, render::Extent, sdo_geometry::SdoGeometry};
+
+/// Measures of a geometry, like the `SDO_GEOM` functions of the same names
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub metric: Metric,
+    /// Area of the polygons with their holes subtracted
+    pub area: f64,
+    /// Length of the lines, polygon rings are counted in `perimeter`
+    pub length: f64,
+    /// Length of all polygon rings, holes included
+    pub perimeter: f64,
+    pub centroid: Option<[f64; 2]>,
+    /// Point guaranteed to be inside the geometry, unlike the centroid
+    pub interior_point: Option<[f64; 2]>,
+    /// Minimum bounding rectangle
+    pub mbr: Option<Extent>,
+}
+
+impl Metrics {
+    /// Geodesic metrics for geographic SRIDs, planar ones in coordinate units otherwise
+    pub fn of(sdo_geometry: &SdoGeometry) -> Self {
+        let crs = sdo_geometry
+            .sdo_srid
+            .and_then(|srid| crs::lookup(srid as u32));
+        let metric = match crs.is_some_and(|crs| crs.is_geographic()) {
+            true => Metric::Geodesic,
+            false => Metric::Planar,
+        };
+        Self::with_metric(sdo_geometry, metric)
+    }
+
+    pub fn with_metric(sdo_geometry: &SdoGeometry, metric: Metric) -> Self {
+        let shape = sdo_geometry.to_geo();
+        let point = |p: geo::Point| [p.x(), p.y()];
+
+        let (area, length, perimeter) = match metric {
+            // Arcs are measured exactly in the plane
+            Metric::Planar => {
+                let measures = sdo_geometry.planar_measures();
+                (measures.area, measures.length, measures.perimeter)
+            }
+            // Geodesic measures use the densified shape
+            Metric::Geodesic => {
+                let (mut lines, mut polygons) = (vec![], vec![]);
+                if let Some(shape) = &shape {
+                    collect_parts(shape, &mut lines, &mut polygons);
+                }
+                let ring_length = |polygon: &Polygon| -> f64 {
+                    std::iter::once(polygon.exterior())
+                        .chain(polygon.interiors())
+                        .map(|ring| Geodesic.length(ring))
+                        .sum()
+                };
+                (
+                    polygons.iter().map(|p| p.geodesic_area_unsigned()).sum(),
+                    lines.iter().map(|l| Geodesic.length(*l)).sum(),
+                    polygons.iter().map(|p| ring_length(p)).sum(),
+                )
+            }
+        };
+
+        Self {
+            metric,
+            area,
+            length,
+            perimeter,
+            centroid: shape.as_ref().and_then(|s| s.centroid()).map(point),
+            interior_point: shape.as_ref().and_then(|s| s.interior_point()).map(point),
+            mbr: shape.as_ref().and_then(|s| Extent::of_geometries([s])),
+        }
+    }
+}
+
+/// Lines and polygons of a geometry, collections are flattened
+fn collect_parts<'a>(
+    geometry: &'a Geometry,
+    lines: &mut Vec<&'a LineString>,
+    polygons: &mut Vec<&'a Polygon>,
+) {
+    match geometry {
+        Geometry::LineString(line) => lines.push(line),
+        Geometry::MultiLineString(multi) => lines.extend(multi.iter()),
+        Geometry::Polygon(polygon) => polygons.push(polygon),
+        Geometry::MultiPolygon(multi) => polygons.extend(multi.iter()),
+        Geometry::GeometryCollection(collection) => {
+            for geometry in collection {
+                collect_parts(geometry, lines, polygons);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{measure::Metric, metrics::Metrics, sdo_geometry::SdoGeometry};
+
+    #[test]
+    fn test_planar_metrics_of_polygon_with_hole() {
+        let sdo_geometry = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 1., 11., 2003., 3.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10., 10., 0., 10., 0., 0., 2., 2., 4., 4.],
+        };
+
+        let metrics = Metrics::of(&sdo_geometry);
+
+        assert_eq!(metrics.metric, Metric::Planar);
+        assert!((metrics.area - 96.).abs() < 1e-9);
+        assert!((metrics.perimeter - 48.).abs() < 1e-9);
+        assert_eq!(metrics.length, 0.);
+        let mbr = metrics.mbr.unwrap();
+        assert_eq!((mbr.min, mbr.max), ([0., 0.], [10., 10.]));
+        let [x, y] = metrics.interior_point.unwrap();
+        assert!(!(2. ..=4.).contains(&x) || !(2. ..=4.).contains(&y));
+    }
+
+    #[test]
+    fn test_geodesic_metrics_of_line() {
+        // One degree along the equator is about 111.3 km on WGS 84
+        let sdo_geometry = SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: Some(8307.),
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 1.],
+            sdo_ordinates: vec![0., 0., 1., 0.],
+        };
+
+        let metrics = Metrics::of(&sdo_geometry);
+
+        assert_eq!(metrics.metric, Metric::Geodesic);
+        assert!((metrics.length - 111_319.49).abs() < 1.);
+        assert_eq!(metrics.centroid, Some([0.5, 0.]));
+    }
+}
@@ -0,0 +1,539 @@
+use std::f64::consts::FRAC_PI_2;
+
+use egui::{Color32, Context, DragValue, Grid, RichText, Window};
+use geo::{
+    algorithm::buffer::{BufferStyle, LineCap, LineJoin},
+    unary_union, BooleanOps, Buffer, ConvexHull, Geometry, GeometryCollection, MultiPolygon,
+};
+
+use crate::{
+    crs,
+    feature::{attribute, display, Feature},
+    layers::Layers,
+    query::{self, Query},
+    sdo_geometry::SdoGeometry,
+    selection::Selection,
+    style::{self, LayerStyle},
+    undo::{Change, UndoStack},
+};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum OperationError {
+    #[error("Select at least {0} geometries")]
+    TooFewGeometries(usize),
+    #[error("{0} needs polygons, geometry {1} of the selection is not one")]
+    NotPolygonal(&'static str, usize),
+    #[error("The result is empty")]
+    Empty,
+    #[error("The result is not a valid SDO_GEOMETRY: {0}")]
+    Invalid(String),
+    #[error("Geometry {0} of the selection cannot be reprojected to the display CRS")]
+    NotReprojectable(usize),
+    #[error("Geometry {0} of the selection has no shape to operate on")]
+    NoShape(usize),
+    #[error("The selection mixes SRIDs {0} and {1}, choose a display CRS to combine them")]
+    MixedSrids(String, String),
+}
+
+/// Operation run on the selected geometries
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Operation {
+    #[default]
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+    Buffer,
+    ConvexHull,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 6] = [
+        Operation::Union,
+        Operation::Intersection,
+        Operation::Difference,
+        Operation::SymmetricDifference,
+        Operation::Buffer,
+        Operation::ConvexHull,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Union => "Union",
+            Operation::Intersection => "Intersection",
+            Operation::Difference => "Difference",
+            Operation::SymmetricDifference => "Symmetric difference",
+            Operation::Buffer => "Buffer",
+            Operation::ConvexHull => "Convex hull",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Operation::Union => "Area covered by any of the polygons",
+            Operation::Intersection => "Area covered by all of the polygons",
+            Operation::Difference => "First selected polygon without the others",
+            Operation::SymmetricDifference => "Area covered by an odd number of the polygons",
+            Operation::Buffer => "Area within a distance of each geometry",
+            Operation::ConvexHull => "Smallest convex polygon around all geometries",
+        }
+    }
+
+    /// Least number of selected geometries the operation needs
+    fn minimum(&self) -> usize {
+        match self {
+            Operation::Union | Operation::Buffer | Operation::ConvexHull => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Operation and buffer parameters chosen in the operations window
+pub struct OperationSettings {
+    pub operation: Operation,
+    /// Buffer distance in coordinate units, negative values shrink polygons
+    pub distance: f64,
+    /// Segments approximating a quarter circle of the buffer
+    pub segments: u32,
+    pub message: RichText,
+}
+
+impl Default for OperationSettings {
+    fn default() -> Self {
+        Self {
+            operation: Operation::default(),
+            distance: 1.,
+            segments: 8,
+            message: RichText::new(""),
+        }
+    }
+}
+
+impl OperationSettings {
+    /// Runs the operation, buffers give one result per geometry, the others a single one
+    pub fn apply(&self, shapes: &[&Geometry]) -> Result<Vec<Geometry>, OperationError> {
+        let operation = self.operation;
+        if shapes.len() < operation.minimum() {
+            return Err(OperationError::TooFewGeometries(operation.minimum()));
+        }
+
+        let polygons = || {
+            shapes
+                .iter()
+                .enumerate()
+                .map(|(n, shape)| {
+                    polygons_of(shape).ok_or(OperationError::NotPolygonal(operation.label(), n + 1))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let results = match operation {
+            Operation::Union => vec![unary_union(&polygons()?)],
+            Operation::Intersection => {
+                let polygons = polygons()?;
+                vec![polygons[1..]
+                    .iter()
+                    .fold(polygons[0].clone(), |a, b| a.intersection(b))]
+            }
+            Operation::Difference => {
+                let polygons = polygons()?;
+                vec![polygons[0].difference(&unary_union(&polygons[1..]))]
+            }
+            Operation::SymmetricDifference => {
+                let polygons = polygons()?;
+                vec![polygons[1..]
+                    .iter()
+                    .fold(polygons[0].clone(), |a, b| a.xor(b))]
+            }
+            Operation::Buffer => {
+                let angle = FRAC_PI_2 / self.segments.max(1) as f64;
+                shapes
+                    .iter()
+                    .map(|shape| {
+                        let style = BufferStyle::new(self.distance)
+                            .line_join(LineJoin::Round(angle))
+                            .line_cap(LineCap::Round(angle));
+                        shape.buffer_with_style(style)
+                    })
+                    .collect()
+            }
+            Operation::ConvexHull => {
+                let all = GeometryCollection::new_from(shapes.iter().copied().cloned().collect());
+                return Ok(vec![Geometry::Polygon(all.convex_hull())]);
+            }
+        };
+
+        let results: Vec<Geometry> = results
+            .into_iter()
+            .filter(|result| !result.0.is_empty())
+            .map(|mut result| match result.0.len() {
+                1 => Geometry::Polygon(result.0.remove(0)),
+                _ => Geometry::MultiPolygon(result),
+            })
+            .collect();
+
+        match results.is_empty() {
+            true => Err(OperationError::Empty),
+            false => Ok(results),
+        }
+    }
+}
+
+/// Polygons of a shape, `None` when it has anything else
+fn polygons_of(shape: &Geometry) -> Option<MultiPolygon> {
+    match shape {
+        Geometry::Polygon(polygon) => Some(MultiPolygon::new(vec![polygon.clone()])),
+        Geometry::MultiPolygon(polygons) => Some(polygons.clone()),
+        Geometry::Rect(rect) => Some(MultiPolygon::new(vec![rect.to_polygon()])),
+        Geometry::Triangle(triangle) => Some(MultiPolygon::new(vec![triangle.to_polygon()])),
+        Geometry::GeometryCollection(collection) => {
+            let mut polygons = vec![];
+            for geometry in collection {
+                polygons.extend(polygons_of(geometry)?);
+            }
+            Some(MultiPolygon::new(polygons))
+        }
+        _ => None,
+    }
+}
+
+/// SRID of the result: the display SRID when every geometry can be reprojected to it,
+/// otherwise the native SRID all of them share
+pub fn result_srid(
+    display_srid: Option<u32>,
+    geometries: &[&query::Geometry],
+) -> Result<Option<f32>, OperationError> {
+    if let Some(srid) = display_srid {
+        return match geometries.iter().position(|g| !g.is_reprojectable()) {
+            Some(n) => Err(OperationError::NotReprojectable(n + 1)),
+            None => Ok(Some(srid as f32)),
+        };
+    }
+
+    let first = geometries.first().and_then(|g| g.sdo_geometry.sdo_srid);
+    match geometries
+        .iter()
+        .map(|g| g.sdo_geometry.sdo_srid)
+        .find(|srid| *srid != first)
+    {
+        Some(other) => Err(OperationError::MixedSrids(
+            crs::describe(first),
+            crs::describe(other),
+        )),
+        None => Ok(first),
+    }
+}
+
+/// Drawable shapes of the selected geometries, numbered like the selection in errors
+pub fn selected_shapes<'a>(
+    geometries: &[&'a query::Geometry],
+) -> Result<Vec<&'a Geometry>, OperationError> {
+    geometries
+        .iter()
+        .enumerate()
+        .map(|(n, g)| g.shape().ok_or(OperationError::NoShape(n + 1)))
+        .collect()
+}
+
+/// Encodes the results and checks that Oracle would accept them
+pub fn to_sdo_geometries(
+    results: &[Geometry],
+    srid: Option<f32>,
+) -> Result<Vec<SdoGeometry>, OperationError> {
+    results
+        .iter()
+        .map(|result| {
+            let sdo_geometry = SdoGeometry::from_geo(result, srid);
+            let errors = sdo_geometry.validation_errors();
+            match errors.is_empty() {
+                true => Ok(sdo_geometry),
+                false => Err(OperationError::Invalid(errors.join(", "))),
+            }
+        })
+        .collect()
+}
+
+/// Query that selects the geometries with the given attributes as text from `dual`,
+/// so the layer can be run again in Oracle
+pub fn derived_sql(features: &[Feature], attributes: &[&str]) -> String {
+    features
+        .iter()
+        .map(|f| {
+            let columns = attributes
+                .iter()
+                .map(|name| {
+                    let value = attribute(&f.attributes, name).map_or(String::new(), display);
+                    format!(", '{}' AS {name}", value.replace('\'', "''"))
+                })
+                .collect::<String>();
+            format!(
+                "SELECT {} AS GEOMETRY{columns} FROM dual",
+                f.geometry.to_sql()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\nUNION ALL\n")
+}
+
+pub struct OperationWindow<'a> {
+    pub settings: &'a mut OperationSettings,
+    pub layers: &'a mut Layers,
+    pub selection: &'a Selection,
+    pub undo: &'a mut UndoStack,
+}
+
+impl<'a> OperationWindow<'a> {
+    pub fn new(
+        settings: &'a mut OperationSettings,
+        layers: &'a mut Layers,
+        selection: &'a Selection,
+        undo: &'a mut UndoStack,
+    ) -> Self {
+        Self {
+            settings,
+            layers,
+            selection,
+            undo,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Geometry operations")
+            .open(is_active)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let count = self.selection.geometries(self.layers).count();
+                ui.label(format!("{count} geometries selected"));
+                ui.separator();
+
+                for operation in Operation::ALL {
+                    ui.radio_value(&mut self.settings.operation, operation, operation.label())
+                        .on_hover_text(operation.description());
+                }
+
+                let geometries: Vec<&query::Geometry> = self
+                    .selection
+                    .geometries(self.layers)
+                    .map(|(_, g)| g)
+                    .collect();
+                let srid = result_srid(self.layers.display_srid, &geometries);
+
+                if self.settings.operation == Operation::Buffer {
+                    let unit = srid
+                        .as_ref()
+                        .ok()
+                        .copied()
+                        .flatten()
+                        .and_then(|srid| crs::lookup(srid as u32))
+                        .map_or("coordinate units", |crs| crs.unit());
+                    Grid::new("buffer_settings").num_columns(2).show(ui, |ui| {
+                        ui.label("Distance:");
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut self.settings.distance).speed(0.1));
+                            ui.label(unit);
+                        });
+                        ui.end_row();
+
+                        ui.label("Segments:");
+                        ui.add(DragValue::new(&mut self.settings.segments).clamp_range(1..=90))
+                            .on_hover_text("Segments per quarter circle of rounded corners");
+                        ui.end_row();
+                    });
+                }
+
+                ui.separator();
+                if ui
+                    .add_enabled(count > 0, egui::Button::new("Create layer"))
+                    .on_hover_text("Add the result as a new layer")
+                    .clicked()
+                {
+                    let created = srid.and_then(|srid| self.create_layer(srid));
+                    self.settings.message = match created {
+                        Ok(name) => RichText::new(format!("Created {name}")).color(Color32::GREEN),
+                        Err(e) => RichText::new(e.to_string()).color(Color32::RED),
+                    };
+                }
+
+                ui.label(self.settings.message.clone());
+            });
+    }
+
+    fn create_layer(&mut self, srid: Option<f32>) -> Result<String, OperationError> {
+        let geometries: Vec<&query::Geometry> = self
+            .selection
+            .geometries(self.layers)
+            .map(|(_, g)| g)
+            .collect();
+        let shapes = selected_shapes(&geometries)?;
+        let results = self.settings.apply(&shapes)?;
+        let sdo_geometries = to_sdo_geometries(&results, srid)?;
+
+        let operation = self.settings.operation.label();
+        let name = self
+            .layers
+            .unique_name(&operation.to_lowercase().replace(' ', "_"));
+        let style = LayerStyle::with_color(style::next_color(
+            self.layers.queries().map(|q| q.style.stroke.color),
+        ));
+        let features: Vec<Feature> = sdo_geometries.into_iter().map(Feature::from).collect();
+        let query = Query::new(&name, derived_sql(&features, &[]), vec![], style, features);
+        self.undo.push(
+            format!("Add layer {name}"),
+            Change::RemoveLayer(name.clone()),
+        );
+        self.layers.push(name.clone(), query);
+
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use geo::{Area, Geometry, LineString, Point, Polygon};
+
+    use crate::{
+        feature::{Attributes, Feature},
+        operation::{
+            derived_sql, result_srid, selected_shapes, to_sdo_geometries, Operation,
+            OperationError, OperationSettings,
+        },
+        query,
+        sdo_geometry::SdoGeometry,
+    };
+
+    fn square(min: f64, size: f64) -> Geometry {
+        let max = min + size;
+        Geometry::Polygon(Polygon::new(
+            LineString::from(vec![(min, min), (max, min), (max, max), (min, max)]),
+            vec![],
+        ))
+    }
+
+    fn apply(operation: Operation, shapes: &[&Geometry]) -> Result<Vec<Geometry>, OperationError> {
+        OperationSettings {
+            operation,
+            ..Default::default()
+        }
+        .apply(shapes)
+    }
+
+    #[test]
+    fn test_boolean_operations() {
+        let (a, b) = (square(0., 2.), square(1., 2.));
+        let area = |operation| -> f64 {
+            let results = apply(operation, &[&a, &b]).unwrap();
+            assert_eq!(results.len(), 1);
+            results[0].unsigned_area()
+        };
+
+        assert!((area(Operation::Union) - 7.).abs() < 1e-9);
+        assert!((area(Operation::Intersection) - 1.).abs() < 1e-9);
+        assert!((area(Operation::Difference) - 3.).abs() < 1e-9);
+        assert!((area(Operation::SymmetricDifference) - 6.).abs() < 1e-9);
+        assert!((area(Operation::ConvexHull) - 8.).abs() < 1e-9);
+
+        let far = square(10., 1.);
+        assert_eq!(
+            apply(Operation::Intersection, &[&a, &far]),
+            Err(OperationError::Empty)
+        );
+        assert_eq!(
+            apply(Operation::Intersection, &[&a]),
+            Err(OperationError::TooFewGeometries(2))
+        );
+        let point = Geometry::Point(Point::new(0., 0.));
+        assert_eq!(
+            apply(Operation::Union, &[&a, &point]),
+            Err(OperationError::NotPolygonal("Union", 2))
+        );
+    }
+
+    #[test]
+    fn test_buffer_is_valid_sdo_geometry() {
+        let point = Geometry::Point(Point::new(5., 5.));
+        let line = Geometry::LineString(LineString::from(vec![(0., 0.), (10., 0.)]));
+        let settings = OperationSettings {
+            operation: Operation::Buffer,
+            distance: 2.,
+            segments: 16,
+            ..Default::default()
+        };
+
+        let results = settings.apply(&[&point, &line]).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!((results[0].unsigned_area() - PI * 4.).abs() < 0.05);
+        assert!((results[1].unsigned_area() - (40. + PI * 4.)).abs() < 0.05);
+
+        let sdo_geometries = to_sdo_geometries(&results, Some(2180.)).unwrap();
+        assert!(sdo_geometries
+            .iter()
+            .all(|g| g.sdo_gtype == 2003. && g.sdo_srid == Some(2180.)));
+        let mut features: Vec<Feature> = sdo_geometries.into_iter().map(Feature::from).collect();
+        let sql = derived_sql(&features, &[]);
+        assert!(sql
+            .starts_with("SELECT SDO_GEOMETRY(2003, 2180, NULL, SDO_ELEM_INFO_ARRAY(1, 1003, 1)"));
+        assert_eq!(sql.matches("UNION ALL").count(), 1);
+
+        features[0].attributes.insert("NOTE".into(), "it's".into());
+        let sql = derived_sql(&features[..1], &["NOTE"]);
+        assert!(sql.ends_with(" AS GEOMETRY, 'it''s' AS NOTE FROM dual"));
+    }
+
+    #[test]
+    fn test_result_srid() {
+        let geometry = |srid| {
+            let point = Geometry::Point(Point::new(1., 2.));
+            let sdo_geometry = SdoGeometry::from_geo(&point, srid);
+            query::Geometry::new("g".into(), sdo_geometry, Attributes::new())
+        };
+        let (wgs84, oracle_wgs84, bng) = (
+            geometry(Some(4326.)),
+            geometry(Some(8307.)),
+            geometry(Some(27700.)),
+        );
+        let mut unknown = geometry(Some(1234.));
+
+        assert_eq!(result_srid(None, &[&wgs84, &wgs84]), Ok(Some(4326.)));
+        assert!(matches!(
+            result_srid(None, &[&wgs84, &oracle_wgs84]),
+            Err(OperationError::MixedSrids(..))
+        ));
+
+        let mut displayed = [wgs84, bng];
+        for geometry in &mut displayed {
+            geometry.set_display_srid(Some(3857));
+        }
+        let [wgs84, bng] = &displayed;
+        assert_eq!(result_srid(Some(3857), &[wgs84, bng]), Ok(Some(3857.)));
+
+        unknown.set_display_srid(Some(3857));
+        assert_eq!(
+            result_srid(Some(3857), &[wgs84, &unknown]),
+            Err(OperationError::NotReprojectable(2))
+        );
+    }
+
+    #[test]
+    fn test_selected_shapes() {
+        let point = SdoGeometry::from_geo(&Geometry::Point(Point::new(1., 2.)), None);
+        let point = query::Geometry::new("point".into(), point, Attributes::new());
+        let empty = SdoGeometry {
+            sdo_gtype: 2001.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![],
+            sdo_ordinates: vec![],
+        };
+        let empty = query::Geometry::new("empty".into(), empty, Attributes::new());
+
+        assert_eq!(selected_shapes(&[&point]).unwrap().len(), 1);
+        // The shapeless geometry is reported rather than left out of the numbering
+        assert_eq!(
+            selected_shapes(&[&point, &empty, &point]),
+            Err(OperationError::NoShape(2))
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use egui::{Align2, Color32, FontId, Painter, Pos2, Shape, Stroke};
+use geo::{Distance, Geodesic, Point};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crs,
+    render::{ticks, Canvas, Extent, ScreenTransform},
+    style::Dash,
+    svg::format_tick,
+};
+
+/// Space between the map frame and the scale bar or the north arrow in pixels
+const MARGIN: f64 = 12.;
+/// Widest scale bar in pixels
+const MAX_SCALE_BAR_WIDTH: f64 = 150.;
+const SCALE_BAR_HEIGHT: f64 = 5.;
+const TEXT_SIZE: f64 = 11.;
+/// Length of the north arrow in pixels
+const ARROW_SIZE: f64 = 26.;
+/// Axis spans from which projected coordinates are labelled in kilometres
+const KILOMETRE_SPAN: f64 = 10000.;
+/// Space for the labels of the y axis in pixels
+const AXES_MARGIN_LEFT: f64 = 70.;
+/// Space for the labels of the x axis in pixels
+const AXES_MARGIN_BOTTOM: f64 = 30.;
+/// Space above and right of the map frame in pixels
+const AXES_MARGIN: f64 = 10.;
+const TICK_LENGTH: f64 = 4.;
+/// Axis labels are smaller than other text, so that stroked ones fit into the margin
+const AXIS_TEXT_SIZE: f64 = 8.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// How the values of the plot axes are written
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum AxisFormat {
+    /// Degrees, minutes and seconds for geographic SRIDs, metres for projected ones
+    #[default]
+    Auto,
+    Plain,
+    /// Degrees, minutes and seconds with the hemisphere
+    Dms,
+    /// Metres, or kilometres for wide views
+    Projected,
+}
+
+impl AxisFormat {
+    pub const ALL: [AxisFormat; 4] = [
+        AxisFormat::Auto,
+        AxisFormat::Plain,
+        AxisFormat::Dms,
+        AxisFormat::Projected,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AxisFormat::Auto => "Automatic",
+            AxisFormat::Plain => "Plain numbers",
+            AxisFormat::Dms => "Degrees, minutes, seconds",
+            AxisFormat::Projected => "Metres",
+        }
+    }
+
+    /// The format used for coordinates in the SRID, `Auto` becomes a concrete format
+    pub fn resolve(self, srid: Option<f32>) -> Self {
+        if self != AxisFormat::Auto {
+            return self;
+        }
+        match srid.and_then(|srid| crs::lookup(srid as u32)) {
+            Some(crs) if crs.is_geographic() => AxisFormat::Dms,
+            Some(_) => AxisFormat::Projected,
+            None => AxisFormat::Plain,
+        }
+    }
+
+    /// Axis label of a value, `span` is the visible range of the axis
+    pub fn format(&self, value: f64, axis: Axis, span: f64) -> String {
+        match self {
+            AxisFormat::Auto | AxisFormat::Plain => format_tick(value),
+            AxisFormat::Dms => format_dms(value, axis),
+            AxisFormat::Projected if span >= KILOMETRE_SPAN => {
+                format!("{} km", format_tick(value / 1000.))
+            }
+            AxisFormat::Projected => format!("{} m", format_tick(value)),
+        }
+    }
+}
+
+/// Degrees with as many minutes and seconds as the value needs, e.g. `12°30'N`
+pub fn format_dms(value: f64, axis: Axis) -> String {
+    let hemisphere = match (axis, value.total_cmp(&0.)) {
+        (_, std::cmp::Ordering::Equal) => "",
+        (Axis::X, std::cmp::Ordering::Greater) => "E",
+        (Axis::X, std::cmp::Ordering::Less) => "W",
+        (Axis::Y, std::cmp::Ordering::Greater) => "N",
+        (Axis::Y, std::cmp::Ordering::Less) => "S",
+    };
+    // Hundredths of a second, so that rounding never gives 60 seconds
+    let total = (value.abs() * 360000.).round() as i64;
+    let (degrees, minutes, hundredths) = (total / 360000, total / 6000 % 60, total % 6000);
+
+    match (minutes, hundredths) {
+        (0, 0) => format!("{degrees}°{hemisphere}"),
+        (_, 0) => format!("{degrees}°{minutes:02}'{hemisphere}"),
+        (_, h) if h % 100 == 0 => format!("{degrees}°{minutes:02}'{:02}\"{hemisphere}", h / 100),
+        (_, h) => format!(
+            "{degrees}°{minutes:02}'{:05.2}\"{hemisphere}",
+            h as f64 / 100.
+        ),
+    }
+}
+
+/// Metres on the ground per coordinate unit across the middle of the view
+fn metres_per_unit(srid: Option<f32>, extent: &Extent) -> Option<f64> {
+    let crs = crs::lookup(srid? as u32)?;
+    let [x, y] = [
+        (extent.min[0] + extent.max[0]) / 2.,
+        (extent.min[1] + extent.max[1]) / 2.,
+    ];
+    let step = extent.width() / 100.;
+    let a = crs.to_wgs84([x - step / 2., y]);
+    let b = crs.to_wgs84([x + step / 2., y]);
+    let metres = Geodesic.distance(Point::from(a), Point::from(b)) / step;
+
+    (metres.is_finite() && metres > 0.).then_some(metres)
+}
+
+/// Scale bar with a round length that fits the current zoom
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleBar {
+    /// Length on the screen
+    pub pixels: f64,
+    pub label: String,
+}
+
+impl ScaleBar {
+    /// Ground lengths for known SRIDs, coordinate units for the others
+    pub fn new(transform: &ScreenTransform, srid: Option<f32>, max_pixels: f64) -> Option<Self> {
+        let ground_per_unit = metres_per_unit(srid, &transform.extent);
+        let per_unit = ground_per_unit.unwrap_or(1.);
+        let max_length = max_pixels / transform.scale * per_unit;
+        if !max_length.is_finite() || max_length <= 0. {
+            return None;
+        }
+
+        // Largest 1, 2 or 5 times a power of ten that fits
+        let magnitude = 10f64.powf(max_length.log10().floor());
+        let length = [5., 2., 1.]
+            .into_iter()
+            .map(|m| m * magnitude)
+            .find(|l| *l <= max_length)
+            .unwrap_or(magnitude);
+
+        let label = match ground_per_unit {
+            None => format_tick(length),
+            Some(_) if length >= 1000. => format!("{} km", format_tick(length / 1000.)),
+            Some(_) if length >= 1. => format!("{} m", format_tick(length)),
+            Some(_) => format!("{} cm", format_tick(length * 100.)),
+        };
+
+        Some(Self {
+            pixels: length / per_unit * transform.scale,
+            label,
+        })
+    }
+}
+
+/// Angle of north from the top of the map in radians, clockwise, zero for unknown SRIDs
+pub fn north_angle(srid: Option<f32>, extent: &Extent) -> f64 {
+    let Some(crs) = srid.and_then(|srid| crs::lookup(srid as u32)) else {
+        return 0.;
+    };
+    let center = [
+        (extent.min[0] + extent.max[0]) / 2.,
+        (extent.min[1] + extent.max[1]) / 2.,
+    ];
+    let [lon, lat] = crs.to_wgs84(center);
+    let north = crs.from_wgs84([lon, (lat + 0.01).min(90.)]);
+    let angle = (north[0] - center[0]).atan2(north[1] - center[1]);
+
+    if angle.is_finite() {
+        angle
+    } else {
+        0.
+    }
+}
+
+/// Draws a scale bar into the bottom left corner of the map
+pub fn draw_scale_bar(
+    canvas: &mut dyn Canvas,
+    transform: &ScreenTransform,
+    srid: Option<f32>,
+    color: Color32,
+) {
+    let max_pixels = MAX_SCALE_BAR_WIDTH.min(transform.size[0] / 3.);
+    let Some(bar) = ScaleBar::new(transform, srid, max_pixels) else {
+        return;
+    };
+    let left = transform.origin[0] + MARGIN;
+    let bottom = transform.origin[1] + transform.size[1] - MARGIN;
+    let middle = left + bar.pixels / 2.;
+    let right = left + bar.pixels;
+    let top = bottom - SCALE_BAR_HEIGHT;
+    let stroke = Stroke::new(1., color);
+
+    // Alternating halves like on printed maps
+    let half = |from: f64, to: f64| vec![[from, top], [to, top], [to, bottom], [from, bottom]];
+    canvas.polygon(&[half(left, middle)], color, stroke, Dash::Solid);
+    canvas.polygon(
+        &[half(middle, right)],
+        Color32::TRANSPARENT,
+        stroke,
+        Dash::Solid,
+    );
+    canvas.text(
+        [middle, top - TEXT_SIZE / 2. - 3.],
+        &bar.label,
+        TEXT_SIZE,
+        color,
+    );
+}
+
+/// Draws an arrow pointing north into the bottom right corner of the map
+pub fn draw_north_arrow(
+    canvas: &mut dyn Canvas,
+    transform: &ScreenTransform,
+    srid: Option<f32>,
+    color: Color32,
+) {
+    let angle = north_angle(srid, &transform.extent);
+    let center = [
+        transform.origin[0] + transform.size[0] - MARGIN - ARROW_SIZE / 2.,
+        transform.origin[1] + transform.size[1] - MARGIN - ARROW_SIZE / 2.,
+    ];
+    // Screen y points down
+    let (sin, cos) = angle.sin_cos();
+    let at = |along: f64, across: f64| {
+        [
+            center[0] + along * sin + across * cos,
+            center[1] - along * cos + across * sin,
+        ]
+    };
+    let half = ARROW_SIZE / 2.;
+    let stroke = Stroke::new(1., color);
+
+    let (tip, tail) = (at(half - TEXT_SIZE, 0.), at(-half, 0.));
+    let (left, right) = (at(-half - 2., -half / 2.), at(-half - 2., half / 2.));
+    canvas.polygon(&[vec![tip, left, tail]], color, stroke, Dash::Solid);
+    canvas.polygon(
+        &[vec![tip, tail, right]],
+        Color32::TRANSPARENT,
+        stroke,
+        Dash::Solid,
+    );
+    canvas.text(at(half - TEXT_SIZE / 2. + 2., 0.), "N", TEXT_SIZE, color);
+}
+
+/// Transform of an exported map, leaving room for the axis labels when they are drawn
+pub fn map_transform(
+    extent: Extent,
+    width: u32,
+    height: u32,
+    include_axes: bool,
+) -> ScreenTransform {
+    let (width, height) = (width as f64, height as f64);
+    if include_axes {
+        ScreenTransform::new(
+            extent,
+            [AXES_MARGIN_LEFT, AXES_MARGIN],
+            [
+                (width - AXES_MARGIN_LEFT - AXES_MARGIN).max(1.),
+                (height - AXES_MARGIN_BOTTOM - AXES_MARGIN).max(1.),
+            ],
+        )
+    } else {
+        ScreenTransform::new(extent, [0., 0.], [width, height])
+    }
+}
+
+/// Ticks of the x and y axes with their pixel positions and labels
+pub fn axis_ticks(transform: &ScreenTransform, format: AxisFormat) -> [Vec<(f64, String)>; 2] {
+    let extent = transform.extent;
+    let [w, h] = transform.size;
+    let x = ticks(extent.min[0], extent.max[0], (w / 100.).max(2.) as usize)
+        .into_iter()
+        .map(|tick| {
+            let [tx, _] = transform.to_screen([tick, extent.min[1]]);
+            (tx, format.format(tick, Axis::X, extent.width()))
+        })
+        .collect();
+    let y = ticks(extent.min[1], extent.max[1], (h / 60.).max(2.) as usize)
+        .into_iter()
+        .map(|tick| {
+            let [_, ty] = transform.to_screen([extent.min[0], tick]);
+            (ty, format.format(tick, Axis::Y, extent.height()))
+        })
+        .collect();
+
+    [x, y]
+}
+
+/// Draws a frame around the map with labelled ticks below and left of it
+pub fn draw_axes(
+    canvas: &mut dyn Canvas,
+    transform: &ScreenTransform,
+    format: AxisFormat,
+    color: Color32,
+) {
+    let [x, y] = transform.origin;
+    let [w, h] = transform.size;
+    let stroke = Stroke::new(1., color);
+    let frame = vec![[x, y], [x + w, y], [x + w, y + h], [x, y + h]];
+    canvas.polygon(&[frame], Color32::TRANSPARENT, stroke, Dash::Solid);
+
+    let [x_ticks, y_ticks] = axis_ticks(transform, format);
+    for (tx, label) in x_ticks {
+        let bottom = y + h;
+        canvas.line(
+            &[[tx, bottom], [tx, bottom + TICK_LENGTH]],
+            stroke,
+            Dash::Solid,
+        );
+        canvas.text([tx, bottom + 12.], &label, AXIS_TEXT_SIZE, color);
+    }
+    for (ty, label) in y_ticks {
+        canvas.line(&[[x - TICK_LENGTH, ty], [x, ty]], stroke, Dash::Solid);
+        // Labels end next to the tick, the width is that of the stroked glyphs
+        let width = label.chars().count() as f64 * AXIS_TEXT_SIZE - AXIS_TEXT_SIZE / 3.;
+        canvas.text([x - 6. - width / 2., ty], &label, AXIS_TEXT_SIZE, color);
+    }
+}
+
+/// Scale bar, north arrow and axis labels of the plot
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Overlay {
+    pub axis_format: AxisFormat,
+    pub scale_bar: bool,
+    pub north_arrow: bool,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            axis_format: AxisFormat::Auto,
+            scale_bar: true,
+            north_arrow: false,
+        }
+    }
+}
+
+impl Overlay {
+    pub fn draw(
+        &self,
+        canvas: &mut dyn Canvas,
+        transform: &ScreenTransform,
+        srid: Option<f32>,
+        color: Color32,
+    ) {
+        if self.scale_bar {
+            draw_scale_bar(canvas, transform, srid, color);
+        }
+        if self.north_arrow {
+            draw_north_arrow(canvas, transform, srid, color);
+        }
+    }
+}
+
+/// Draws exporter shapes on the plot, polygons are filled as convex shapes without holes
+pub struct PainterCanvas<'a> {
+    pub painter: &'a Painter,
+}
+
+fn pos([x, y]: [f64; 2]) -> Pos2 {
+    Pos2::new(x as f32, y as f32)
+}
+
+impl Canvas for PainterCanvas<'_> {
+    fn polygon(&mut self, rings: &[Vec<[f64; 2]>], fill: Color32, stroke: Stroke, _dash: Dash) {
+        if let Some(exterior) = rings.first() {
+            let points = exterior.iter().copied().map(pos).collect();
+            self.painter
+                .add(Shape::convex_polygon(points, fill, stroke));
+        }
+    }
+
+    fn line(&mut self, points: &[[f64; 2]], stroke: Stroke, _dash: Dash) {
+        let points = points.iter().copied().map(pos).collect();
+        self.painter.add(Shape::line(points, stroke));
+    }
+
+    fn point(&mut self, point: [f64; 2], radius: f32, color: Color32) {
+        self.painter.circle_filled(pos(point), radius, color);
+    }
+
+    fn text(&mut self, center: [f64; 2], text: &str, size: f64, color: Color32) {
+        self.painter.text(
+            pos(center),
+            Align2::CENTER_CENTER,
+            text,
+            FontId::proportional(size as f32),
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        overlay::{format_dms, north_angle, Axis, AxisFormat, ScaleBar},
+        render::{Extent, ScreenTransform},
+    };
+
+    #[test]
+    fn test_axis_formats() {
+        assert_eq!(format_dms(12.5, Axis::Y), "12°30'N");
+        assert_eq!(format_dms(-0.25, Axis::X), "0°15'W");
+        assert_eq!(format_dms(10.504166666, Axis::X), "10°30'15\"E");
+        assert_eq!(format_dms(-33.0000139, Axis::Y), "33°00'00.05\"S");
+        assert_eq!(format_dms(0., Axis::X), "0°");
+        // Rounding carries into the minutes instead of giving 60 seconds
+        assert_eq!(format_dms(1.9999999999, Axis::Y), "2°N");
+
+        assert_eq!(AxisFormat::Auto.resolve(Some(8307.)), AxisFormat::Dms);
+        assert_eq!(
+            AxisFormat::Auto.resolve(Some(27700.)),
+            AxisFormat::Projected
+        );
+        assert_eq!(AxisFormat::Auto.resolve(None), AxisFormat::Plain);
+        assert_eq!(AxisFormat::Dms.resolve(None), AxisFormat::Dms);
+
+        let projected = AxisFormat::Projected;
+        assert_eq!(projected.format(530000., Axis::X, 50000.), "530 km");
+        assert_eq!(projected.format(2500., Axis::X, 1000.), "2500 m");
+    }
+
+    #[test]
+    fn test_scale_bar() {
+        // 1000 coordinate units on 1000 pixels
+        let extent = Extent::new([0., 0.], [1000., 1000.]);
+        let transform = ScreenTransform::new(extent, [0., 0.], [1000., 1000.]);
+
+        let bar = ScaleBar::new(&transform, None, 150.).unwrap();
+        assert_eq!(bar.label, "100");
+        assert_eq!(bar.pixels, 100.);
+
+        // Along the central meridian of the UTM zone the scale factor is 0.9996
+        let extent = Extent::new([500000., 0.], [501000., 1000.]);
+        let transform = ScreenTransform::new(extent, [0., 0.], [1000., 1000.]);
+        let bar = ScaleBar::new(&transform, Some(32633.), 150.).unwrap();
+        assert_eq!(bar.label, "100 m");
+        assert!((bar.pixels - 100.).abs() < 1.);
+
+        // A degree of longitude is about 111 km at the equator
+        let extent = Extent::new([0., -5.], [10., 5.]);
+        let transform = ScreenTransform::new(extent, [0., 0.], [1000., 1000.]);
+        let bar = ScaleBar::new(&transform, Some(4326.), 150.).unwrap();
+        assert_eq!(bar.label, "100 km");
+        assert!((bar.pixels - 89.8).abs() < 0.5, "{}", bar.pixels);
+    }
+
+    #[test]
+    fn test_north_angle() {
+        let geographic = Extent::new([0., 40.], [1., 41.]);
+        assert_eq!(north_angle(Some(4326.), &geographic), 0.);
+
+        // East of the central meridian grid north is east of true north, so north points left
+        let utm = Extent::new([700000., 4500000.], [710000., 4510000.]);
+        let angle = north_angle(Some(32633.), &utm).to_degrees();
+        assert!(angle < -1. && angle > -3., "{angle}");
+    }
+}
@@ -1,13 +1,65 @@
-use egui::{ahash::HashMap, Button, Color32, Context, Layout, RichText, Stroke, Window};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::Instant,
+};
 
-use crate::{api::GeometryApi, sdo_geometry::SdoGeometry};
+use chrono::{DateTime, Local};
+use egui::{Button, CollapsingHeader, Color32, Context, Grid, Layout, RichText, Window};
+use geo::CoordsIter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-const COLORS: [Color32; 4] = [Color32::RED, Color32::BLUE, Color32::GREEN, Color32::YELLOW];
+use crate::{
+    api::{Fetched, GeometryApi, GeometryApiError},
+    crs::Reprojection,
+    expression::Expr,
+    feature::{attribute, display, Attributes, Feature},
+    history::{HistoryEntry, QueryHistory, QueryLibrary, SavedQuery},
+    label::{self, LabelStyle},
+    layers::Layers,
+    lod::{self, LodCache, Simplified},
+    metrics::Metrics,
+    render::{Extent, FillCache},
+    sdo_geometry::SdoGeometry,
+    style::{self, LayerStyle},
+    theme::Theme,
+    triangulation::Triangulation,
+    undo::{Change, QueryResult, UndoStack},
+};
+
+/// Value for a `:name` placeholder in the SQL query
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Bind {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryTab {
+    Editor,
+    History,
+    Library,
+}
 
 pub struct InputQuery {
     pub sql: String,
     pub name: String,
+    pub binds: Vec<Bind>,
     pub message: RichText,
+    pub tab: QueryTab,
+    pub history_search: String,
+    pub library_search: String,
+    /// Folder and description used when saving the query to the library
+    pub library_folder: String,
+    pub library_description: String,
+    /// Layer that is replaced on submit instead of creating a new one
+    pub editing: Option<String>,
 }
 
 impl Default for InputQuery {
@@ -21,7 +73,14 @@ FROM\n\
 "
             .into(),
             name: "".into(),
+            binds: vec![],
             message: RichText::new(""),
+            tab: QueryTab::Editor,
+            history_search: "".into(),
+            library_search: "".into(),
+            library_folder: "".into(),
+            library_description: "".into(),
+            editing: None,
         }
     }
 }
@@ -30,25 +89,53 @@ impl InputQuery {
     pub fn clear(&mut self) {
         self.sql = String::from("");
         self.name = String::from("");
+        self.binds.clear();
+        self.editing = None;
+    }
+
+    /// Opens the layer in the editor, submitting the query updates the layer in place
+    pub fn edit(&mut self, name: &str, query: &Query) {
+        self.name = name.to_string();
+        self.sql = query.sql.clone();
+        self.binds = query.binds.clone();
+        self.editing = Some(name.to_string());
+        self.tab = QueryTab::Editor;
+        self.message = RichText::new("");
     }
 }
 
+/// Action picked from the history or the library, applied after the list is drawn
+enum Recall {
+    Load(String, String, Vec<Bind>),
+    Run(String, String, Vec<Bind>),
+    Delete(String, String),
+}
+
 pub struct QueryWindow<'a> {
-    pub queries: &'a mut HashMap<String, Query>,
+    pub layers: &'a mut Layers,
     pub input_query: &'a mut InputQuery,
     pub api: &'a GeometryApi,
+    pub history: &'a mut QueryHistory,
+    pub library: &'a mut QueryLibrary,
+    pub undo: &'a mut UndoStack,
 }
 
 impl<'a> QueryWindow<'a> {
     pub fn new(
-        queries: &'a mut HashMap<String, Query>,
+        layers: &'a mut Layers,
         input_query: &'a mut InputQuery,
         api: &'a GeometryApi,
+        history: &'a mut QueryHistory,
+        library: &'a mut QueryLibrary,
+        undo: &'a mut UndoStack,
     ) -> Self {
         Self {
-            queries,
+            layers,
             input_query,
             api,
+            history,
+            library,
+            undo,
         }
     }
 
@@ -58,55 +145,301 @@ impl<'a> QueryWindow<'a> {
             .resizable(true)
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label("Enter a query name: ");
-                    ui.text_edit_singleline(&mut self.input_query.name);
+                    let tab = &mut self.input_query.tab;
+                    ui.selectable_value(tab, QueryTab::Editor, "Editor");
+                    ui.selectable_value(tab, QueryTab::History, "History");
+                    ui.selectable_value(tab, QueryTab::Library, "Library");
                 });
 
-                ui.label("Enter a SQL query that contains only geometry column. ");
-                ui.label(
-                    RichText::new("NOTE: Do not put any semicolons in the query!")
-                        .color(Color32::LIGHT_YELLOW),
-                );
+                ui.separator();
 
-                // from https://github.com/emilk/egui/blob/master/crates/egui_demo_lib/src/demo/code_editor.rs
-                let theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx());
-
-                let mut layouter = |ui: &egui::Ui, string: &str, wrap_width: f32| {
-                    let mut layout_job = egui_extras::syntax_highlighting::highlight(
-                        ui.ctx(),
-                        &theme,
-                        string,
-                        "sql",
-                    );
-                    layout_job.wrap.max_width = wrap_width;
-                    ui.fonts(|f| f.layout_job(layout_job))
+                let recall = match self.input_query.tab {
+                    QueryTab::Editor => {
+                        self.editor(ui);
+                        None
+                    }
+                    QueryTab::History => self.history_list(ui),
+                    QueryTab::Library => self.library_list(ui),
                 };
 
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    ui.add(
-                        egui::TextEdit::multiline(&mut self.input_query.sql)
-                            .font(egui::TextStyle::Monospace) // for cursor height
-                            .code_editor()
-                            .desired_rows(10)
-                            .lock_focus(true)
-                            .desired_width(f32::INFINITY)
-                            .layouter(&mut layouter),
-                    );
-                });
+                if let Some(recall) = recall {
+                    self.recall(recall);
+                }
 
                 ui.horizontal(|ui| {
                     ui.label("Message:");
                     ui.label(self.input_query.message.clone());
                 });
+            });
+    }
 
-                ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
-                    let submit_button = ui.add(Button::new("Submit query"));
+    fn editor(&mut self, ui: &mut egui::Ui) {
+        if let Some(editing) = &self.input_query.editing {
+            let mut cancel = false;
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!("Editing layer {editing}")).color(Color32::LIGHT_BLUE),
+                );
+                cancel = ui.button("Cancel").clicked();
+            });
 
-                    if submit_button.clicked() {
-                        self.send_query();
-                    }
+            if cancel {
+                self.input_query.editing = None;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Enter a query name: ");
+            ui.text_edit_singleline(&mut self.input_query.name);
+        });
+
+        ui.label("Enter a SQL query that contains only geometry column. ");
+        ui.label(
+            RichText::new("NOTE: Do not put any semicolons in the query!")
+                .color(Color32::LIGHT_YELLOW),
+        );
+
+        // from https://github.com/emilk/egui/blob/master/crates/egui_demo_lib/src/demo/code_editor.rs
+        let theme = egui_extras::syntax_highlighting::CodeTheme::from_memory(ui.ctx());
+
+        let mut layouter = |ui: &egui::Ui, string: &str, wrap_width: f32| {
+            let mut layout_job =
+                egui_extras::syntax_highlighting::highlight(ui.ctx(), &theme, string, "sql");
+            layout_job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(layout_job))
+        };
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.input_query.sql)
+                    .font(egui::TextStyle::Monospace) // for cursor height
+                    .code_editor()
+                    .desired_rows(10)
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY)
+                    .layouter(&mut layouter),
+            );
+        });
+
+        self.binds_editor(ui);
+        self.library_editor(ui);
+
+        ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+            let label = match self.input_query.editing {
+                Some(_) => "Update layer",
+                None => "Submit query",
+            };
+            let submit_button = ui.add(Button::new(label));
+
+            if submit_button.clicked() {
+                self.send_query();
+            }
+        });
+    }
+
+    fn library_editor(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Save to library", |ui| {
+            Grid::new("library_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Folder:");
+                ui.text_edit_singleline(&mut self.input_query.library_folder);
+                ui.end_row();
+
+                ui.label("Description:");
+                ui.text_edit_singleline(&mut self.input_query.library_description);
+                ui.end_row();
+            });
+
+            if ui.button("Save").clicked() {
+                if self.input_query.name.is_empty() {
+                    self.input_query.message =
+                        RichText::new("Query must have a name!").color(Color32::RED);
+                    return;
+                }
+
+                self.library.upsert(SavedQuery {
+                    name: self.input_query.name.clone(),
+                    folder: self.input_query.library_folder.trim().to_string(),
+                    description: self.input_query.library_description.clone(),
+                    sql: self.input_query.sql.clone(),
+                    binds: self.input_query.binds.clone(),
                 });
+                self.library.save();
+                self.input_query.message =
+                    RichText::new("Saved to the library").color(Color32::GREEN);
+            }
+        });
+    }
+
+    fn history_list(&mut self, ui: &mut egui::Ui) -> Option<Recall> {
+        let mut recall = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.input_query.history_search);
+            if ui.button("Clear history").clicked() {
+                self.history.entries.clear();
+                self.history.save();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .max_height(400.)
+            .show(ui, |ui| {
+                Grid::new("history_grid")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for entry in self.history.search(&self.input_query.history_search) {
+                            ui.label(entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string());
+                            ui.label(&entry.name)
+                                .on_hover_text(format!("{}\n\n{}", entry.api_url, entry.sql));
+                            match &entry.error {
+                                Some(e) => ui.label(RichText::new(e).color(Color32::RED)),
+                                None => ui.label(format!(
+                                    "{} rows in {} ms",
+                                    entry.row_count, entry.duration_ms
+                                )),
+                            };
+                            if ui
+                                .button("Load")
+                                .on_hover_text("Open in the editor")
+                                .clicked()
+                            {
+                                recall = Some(Recall::Load(
+                                    entry.name.clone(),
+                                    entry.sql.clone(),
+                                    entry.binds.clone(),
+                                ));
+                            }
+                            if ui
+                                .button("Run")
+                                .on_hover_text("Run into a new layer")
+                                .clicked()
+                            {
+                                recall = Some(Recall::Run(
+                                    entry.name.clone(),
+                                    entry.sql.clone(),
+                                    entry.binds.clone(),
+                                ));
+                            }
+                            ui.end_row();
+                        }
+                    });
             });
+
+        recall
+    }
+
+    fn library_list(&mut self, ui: &mut egui::Ui) -> Option<Recall> {
+        let mut recall = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.input_query.library_search);
+        });
+
+        if self.library.queries.is_empty() {
+            ui.label("Save queries from the editor to see them here.");
+        }
+
+        egui::ScrollArea::vertical()
+            .max_height(400.)
+            .show(ui, |ui| {
+                for folder in self.library.folders() {
+                    let title = if folder.is_empty() {
+                        "Unsorted"
+                    } else {
+                        folder
+                    };
+                    CollapsingHeader::new(title)
+                        .id_source(("library_folder", folder))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for saved in self
+                                .library
+                                .search(folder, &self.input_query.library_search)
+                            {
+                                ui.horizontal(|ui| {
+                                    ui.label(&saved.name).on_hover_text(&saved.sql);
+                                    if ui.button("Load").clicked() {
+                                        recall = Some(Recall::Load(
+                                            saved.name.clone(),
+                                            saved.sql.clone(),
+                                            saved.binds.clone(),
+                                        ));
+                                    }
+                                    if ui.button("Run").clicked() {
+                                        recall = Some(Recall::Run(
+                                            saved.name.clone(),
+                                            saved.sql.clone(),
+                                            saved.binds.clone(),
+                                        ));
+                                    }
+                                    if ui
+                                        .button("🗑")
+                                        .on_hover_text("Remove from the library")
+                                        .clicked()
+                                    {
+                                        recall = Some(Recall::Delete(
+                                            saved.folder.clone(),
+                                            saved.name.clone(),
+                                        ));
+                                    }
+                                });
+                                if !saved.description.is_empty() {
+                                    ui.label(RichText::new(&saved.description).weak());
+                                }
+                            }
+                        });
+                }
+            });
+
+        recall
+    }
+
+    fn recall(&mut self, recall: Recall) {
+        match recall {
+            Recall::Load(name, sql, binds) => {
+                self.input_query.name = name;
+                self.input_query.sql = sql;
+                self.input_query.binds = binds;
+                self.input_query.tab = QueryTab::Editor;
+            }
+            Recall::Run(name, sql, binds) => {
+                let name = self.layers.unique_name(&name);
+                self.run_query(name, sql, binds);
+            }
+            Recall::Delete(folder, name) => {
+                self.library.remove(&folder, &name);
+                self.library.save();
+            }
+        }
+    }
+
+    fn binds_editor(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Bind variables", |ui| {
+            ui.label("Use `:name` in the query to refer to a bind variable.");
+            let mut to_remove: Option<usize> = None;
+
+            Grid::new("binds_grid").num_columns(3).show(ui, |ui| {
+                for (n, bind) in self.input_query.binds.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut bind.name).hint_text("name"));
+                    ui.add(egui::TextEdit::singleline(&mut bind.value).hint_text("value"));
+                    if ui.button("🗑").on_hover_text("Remove bind").clicked() {
+                        to_remove = Some(n);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(n) = to_remove {
+                self.input_query.binds.remove(n);
+            }
+
+            if ui.button("Add bind").clicked() {
+                self.input_query.binds.push(Bind::default());
+            }
+        });
     }
 
     pub fn send_query(&mut self) {
@@ -116,35 +449,93 @@ impl<'a> QueryWindow<'a> {
             return;
         }
 
-        if self.queries.contains_key(&self.input_query.name) {
+        let is_renamed = self.input_query.editing.as_ref() != Some(&self.input_query.name);
+        if is_renamed && self.layers.contains(&self.input_query.name) {
             self.input_query.message =
                 RichText::new(String::from("This name already exists!")).color(Color32::RED);
             return;
         }
 
-        let data = self.api.fetch_geometries(&self.input_query.sql);
+        if let Some(editing) = self.input_query.editing.clone() {
+            self.update_layer(&editing);
+            return;
+        }
+
+        self.run_query(
+            self.input_query.name.clone(),
+            self.input_query.sql.clone(),
+            self.input_query.binds.clone(),
+        );
+    }
+
+    /// Runs the edited query and puts the result into the existing layer
+    fn update_layer(&mut self, original: &str) {
+        let name = self.input_query.name.clone();
+        let sql = self.input_query.sql.clone();
+        let binds = self.input_query.binds.clone();
+
+        match fetch_recorded(self.api, self.history, &name, &sql, &binds) {
+            Ok(fetched) => {
+                let Some(layer) = self.layers.get_mut(original) else {
+                    self.input_query.message =
+                        RichText::new(format!("Layer {original} no longer exists"))
+                            .color(Color32::RED);
+                    self.input_query.editing = None;
+                    return;
+                };
+
+                let previous = QueryResult::of(&layer.query);
+                layer.query.sql = sql;
+                layer.query.binds = binds;
+                layer.query.is_derived = false;
+                layer.query.stale_since = fetched.stale_since;
+                layer.query.refresh(&name, fetched.features);
+                self.layers.rename(original, &name);
+                self.undo.push(
+                    format!("Update layer {name}"),
+                    Change::Several(vec![
+                        Change::Rename {
+                            from: name.clone(),
+                            to: original.to_string(),
+                        },
+                        Change::Query {
+                            layer: original.to_string(),
+                            result: Box::new(previous),
+                        },
+                    ]),
+                );
+                self.input_query.editing = None;
+
+                self.input_query.message =
+                    fetched_message("Successfully updated the layer", fetched.stale_since);
+            }
+            Err(e) => {
+                self.input_query.message =
+                    RichText::new(format!("An error occured while sending the query: {e}"))
+                        .color(Color32::RED);
+            }
+        }
+    }
+
+    /// Fetches the query into a new layer and records it in the history
+    fn run_query(&mut self, name: String, sql: String, binds: Vec<Bind>) {
+        let data = fetch_recorded(self.api, self.history, &name, &sql, &binds);
 
         match data {
-            Ok(data) => {
-                self.queries.insert(
-                    self.input_query.name.clone(),
-                    Query {
-                        sql: self.input_query.sql.clone(),
-                        stroke: Stroke::new(1., COLORS[self.queries.len() % 4]),
-                        geometries: data
-                            .into_iter()
-                            .enumerate()
-                            .map(|(n, g)| Geometry {
-                                name: format!("{}_{}", self.input_query.name.clone(), n),
-                                sdo_geometry: g,
-                                is_active: true,
-                            })
-                            .collect::<Vec<_>>(),
-                    },
+            Ok(fetched) => {
+                let style = LayerStyle::with_color(style::next_color(
+                    self.layers.queries().map(|q| q.style.stroke.color),
+                ));
+                let mut query = Query::new(&name, sql, binds, style, fetched.features);
+                query.stale_since = fetched.stale_since;
+                self.undo.push(
+                    format!("Add layer {name}"),
+                    Change::RemoveLayer(name.clone()),
                 );
+                self.layers.push(name, query);
 
                 self.input_query.message =
-                    RichText::new("Successfully fetched data").color(Color32::GREEN);
+                    fetched_message("Successfully fetched data", fetched.stale_since);
             }
             Err(e) => {
                 self.input_query.message =
@@ -155,14 +546,582 @@ impl<'a> QueryWindow<'a> {
     }
 }
 
+/// Success message, or a warning when the backend was unreachable and the cache answered
+pub fn fetched_message(message: &str, stale_since: Option<DateTime<Local>>) -> RichText {
+    match stale_since {
+        Some(time) => RichText::new(format!(
+            "Backend unreachable, showing cached data from {}",
+            time.format("%Y-%m-%d %H:%M:%S")
+        ))
+        .color(Color32::YELLOW),
+        None => RichText::new(message).color(Color32::GREEN),
+    }
+}
+
+/// Fetches features and records the run in the query history
+pub fn fetch_recorded(
+    api: &GeometryApi,
+    history: &mut QueryHistory,
+    name: &str,
+    sql: &str,
+    binds: &[Bind],
+) -> Result<Fetched, GeometryApiError> {
+    let started = Instant::now();
+    let data = api.fetch(sql, binds);
+
+    history.push(HistoryEntry::new(
+        &api.api_url,
+        name,
+        sql,
+        binds,
+        started.elapsed(),
+        data.as_ref()
+            .map(|fetched| fetched.features.len())
+            .map_err(|e| e.to_string()),
+    ));
+    history.save();
+
+    data
+}
+
 pub struct Geometry {
     pub name: String,
+    /// Create a new `Geometry` instead of changing this, the drawable shape is cached
     pub sdo_geometry: SdoGeometry,
+    pub attributes: Attributes,
+    /// Set it with `Query::set_active` so the fills of the layer are redrawn
     pub is_active: bool,
+    /// SRID the shape is reprojected to, see `set_display_srid`
+    display_srid: Option<u32>,
+    is_reprojectable: OnceLock<bool>,
+    shape: OnceLock<Option<geo::Geometry>>,
+    extent: OnceLock<Option<Extent>>,
+    /// Simplified shapes for the zoom bands drawn last
+    lod: LodCache,
+    triangulation: OnceLock<Triangulation>,
+    label_point: OnceLock<Option<[f64; 2]>>,
+    metrics: OnceLock<Metrics>,
+    validation_errors: OnceLock<Vec<String>>,
+}
+
+impl Geometry {
+    pub fn new(name: String, sdo_geometry: SdoGeometry, attributes: Attributes) -> Self {
+        Self {
+            name,
+            sdo_geometry,
+            attributes,
+            is_active: true,
+            display_srid: None,
+            is_reprojectable: OnceLock::new(),
+            shape: OnceLock::new(),
+            extent: OnceLock::new(),
+            lod: LodCache::default(),
+            triangulation: OnceLock::new(),
+            label_point: OnceLock::new(),
+            metrics: OnceLock::new(),
+            validation_errors: OnceLock::new(),
+        }
+    }
+
+    /// Reprojects the drawable shape, see `is_reprojectable`
+    pub fn set_display_srid(&mut self, srid: Option<u32>) {
+        if self.display_srid != srid {
+            self.display_srid = srid;
+            self.reset_display();
+        }
+    }
+
+    /// Forgets everything converted to the display SRID, it is converted again on next use
+    pub fn reset_display(&mut self) {
+        self.is_reprojectable = OnceLock::new();
+        self.shape = OnceLock::new();
+        self.extent = OnceLock::new();
+        self.lod = LodCache::default();
+        self.triangulation = OnceLock::new();
+        self.label_point = OnceLock::new();
+    }
+
+    /// Whether the geometry can be drawn in the display SRID, missing and unknown SRIDs cannot
+    pub fn is_reprojectable(&self) -> bool {
+        *self.is_reprojectable.get_or_init(|| {
+            let Some(to) = self.display_srid else {
+                return true;
+            };
+            self.sdo_geometry
+                .sdo_srid
+                .is_some_and(|from| Reprojection::new(from as u32, to).is_ok())
+        })
+    }
+
+    /// Conversion from the SRID of the geometry to the display SRID, when they differ
+    pub fn reprojection(&self) -> Option<Reprojection> {
+        let from = self.sdo_geometry.sdo_srid? as u32;
+        Reprojection::new(from, self.display_srid?)
+            .ok()
+            .filter(|r| !r.is_identity())
+    }
+
+    /// The geometry in display coordinates
+    pub fn display_sdo_geometry(&self) -> Cow<'_, SdoGeometry> {
+        self.reprojection()
+            .and_then(|r| r.sdo_geometry(&self.sdo_geometry))
+            .map_or(Cow::Borrowed(&self.sdo_geometry), Cow::Owned)
+    }
+
+    /// Drawable shape in display coordinates, converted on first use.
+    ///
+    /// There is none when the geometry cannot be reprojected, rather than one in other units
+    pub fn shape(&self) -> Option<&geo::Geometry> {
+        self.shape
+            .get_or_init(|| {
+                if !self.is_reprojectable() {
+                    return None;
+                }
+                let mut shape = self.sdo_geometry.to_geo()?;
+                if let Some(reprojection) = self.reprojection() {
+                    reprojection.geometry(&mut shape);
+                }
+                Some(shape)
+            })
+            .as_ref()
+    }
+
+    /// Bounding box of the shape, computed on first use
+    pub fn extent(&self) -> Option<Extent> {
+        *self
+            .extent
+            .get_or_init(|| Extent::of_geometries(self.shape()))
+    }
+
+    /// Shape simplified for a zoom band, `None` when the geometry is small enough to draw in full
+    pub fn simplified(&self, band: i32) -> Option<Arc<Simplified>> {
+        let shape = self.shape()?;
+        if shape.coords_count() < lod::MIN_SIMPLIFIED_VERTICES {
+            return None;
+        }
+        Some(
+            self.lod
+                .get_or_insert(band, || Simplified::new(shape, band)),
+        )
+    }
+
+    /// Triangles filling the polygons, computed on first use
+    pub fn triangulation(&self) -> &Triangulation {
+        self.triangulation.get_or_init(|| {
+            self.shape()
+                .map(Triangulation::of_geometry)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Position of the label, computed on first use
+    pub fn label_point(&self) -> Option<[f64; 2]> {
+        *self
+            .label_point
+            .get_or_init(|| self.shape().and_then(label::anchor))
+    }
+
+    /// Metrics of the geometry in its own SRID, computed on first use
+    pub fn metrics(&self) -> &Metrics {
+        self.metrics.get_or_init(|| Metrics::of(&self.sdo_geometry))
+    }
+
+    /// Reasons the geometry is invalid, checked on first use
+    pub fn validation_errors(&self) -> &[String] {
+        self.validation_errors
+            .get_or_init(|| self.sdo_geometry.validation_errors())
+    }
+
+    /// Value of a name in filter expressions, attributes win over `name`, `area`,
+    /// `length`, `perimeter` and `valid`
+    pub fn value(&self, name: &str) -> Option<Value> {
+        if let Some(value) = attribute(&self.attributes, name) {
+            return Some(value.clone());
+        }
+        let number = |n: f64| serde_json::Number::from_f64(n).map(Value::Number);
+        match name.to_ascii_lowercase().as_str() {
+            "name" => Some(Value::String(self.name.clone())),
+            "area" => number(self.metrics().area),
+            "length" => number(self.metrics().length),
+            "perimeter" => number(self.metrics().perimeter),
+            "valid" => Some(Value::Bool(self.validation_errors().is_empty())),
+            _ => None,
+        }
+    }
+
+    /// Whether the name or an attribute value contains the text, ignoring the case
+    pub fn contains_text(&self, text: &str) -> bool {
+        let text = text.trim().to_lowercase();
+        text.is_empty()
+            || self.name.to_lowercase().contains(&text)
+            || self
+                .attributes
+                .values()
+                .any(|value| display(value).to_lowercase().contains(&text))
+    }
 }
 
 pub struct Query {
     pub sql: String,
-    pub stroke: Stroke,
+    pub binds: Vec<Bind>,
+    pub style: LayerStyle,
+    pub theme: Theme,
+    pub label: LabelStyle,
+    /// Expression geometries have to match to be drawn and listed, empty shows all
+    pub filter: String,
+    /// When the cached response the geometries come from was fetched, `None` when they are fresh
+    pub stale_since: Option<DateTime<Local>>,
+    /// Theme colour of every geometry, updated by `apply_theme`
+    theme_colors: Vec<Option<Color32>>,
+    /// Whether every geometry matches the filter, updated by `apply_filter`
+    filter_matches: Vec<bool>,
+    /// Created from other layers, the SQL only rebuilds the geometries so it is not run again
+    pub is_derived: bool,
+    /// Changes whenever the geometries are replaced, see `generation`
+    generation: u64,
+    /// Changes whenever the shown geometries, their theme colours or their display SRID change
+    revision: u64,
+    /// Fills drawn on the plot, see `render::fill_mesh`
+    fills: FillCache,
     pub geometries: Vec<Geometry>,
 }
+
+impl Query {
+    /// Creates a layer where every geometry is named after the layer and its row number
+    pub fn new(
+        name: &str,
+        sql: String,
+        binds: Vec<Bind>,
+        style: LayerStyle,
+        data: Vec<Feature>,
+    ) -> Self {
+        let mut query = Self {
+            sql,
+            binds,
+            style,
+            theme: Theme::Single,
+            label: LabelStyle::default(),
+            filter: String::new(),
+            stale_since: None,
+            theme_colors: vec![],
+            filter_matches: vec![],
+            is_derived: false,
+            generation: 0,
+            revision: 0,
+            fills: FillCache::default(),
+            geometries: vec![],
+        };
+        query.refresh(name, data);
+
+        query
+    }
+
+    /// Identifies the current geometries, no other layer or refresh gets the same number.
+    ///
+    /// Anything that refers to rows by their index can check it to notice that they changed
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Identifies which geometries are shown and in which colours and display SRID
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn fill_cache(&self) -> &FillCache {
+        &self.fills
+    }
+
+    /// Checks or unchecks a geometry
+    pub fn set_active(&mut self, index: usize, is_active: bool) {
+        if let Some(geometry) = self.geometries.get_mut(index) {
+            geometry.is_active = is_active;
+            self.revision = next_generation();
+        }
+    }
+
+    /// Reprojects the geometries whose display SRID differs, see `Geometry::set_display_srid`
+    pub fn set_display_srid(&mut self, srid: Option<u32>) {
+        if self.geometries.iter().any(|g| g.display_srid != srid) {
+            for geometry in &mut self.geometries {
+                geometry.set_display_srid(srid);
+            }
+            self.revision = next_generation();
+        }
+    }
+
+    /// Converts every geometry to the display SRID again on next use
+    pub fn reset_display(&mut self) {
+        for geometry in &mut self.geometries {
+            geometry.reset_display();
+        }
+        self.revision = next_generation();
+    }
+
+    /// Whether the geometry matches the filter, an invalid filter matches everything
+    pub fn matches_filter(&self, index: usize) -> bool {
+        self.filter_matches.get(index).copied().unwrap_or(true)
+    }
+
+    /// Whether the geometry is checked and matches the filter
+    pub fn is_shown(&self, index: usize) -> bool {
+        self.geometries.get(index).is_some_and(|g| g.is_active) && self.matches_filter(index)
+    }
+
+    /// Checked geometries that match the filter
+    pub fn active_geometries(&self) -> impl DoubleEndedIterator<Item = &Geometry> {
+        self.geometries
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| self.is_shown(*n))
+            .map(|(_, g)| g)
+    }
+
+    /// Bounding box of the active geometries
+    pub fn extent(&self) -> Option<Extent> {
+        self.active_geometries()
+            .filter_map(Geometry::extent)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Active geometries with the style they are drawn with
+    pub fn active_styled(&self) -> impl Iterator<Item = (&Geometry, LayerStyle)> {
+        self.geometries
+            .iter()
+            .enumerate()
+            .filter(|(n, _)| self.is_shown(*n))
+            .map(|(n, g)| (g, self.geometry_style(n)))
+    }
+
+    pub fn geometry_style(&self, index: usize) -> LayerStyle {
+        match self.theme_colors.get(index).copied().flatten() {
+            Some(color) => self.style.themed(color),
+            None => self.style,
+        }
+    }
+
+    /// Recomputes geometry colours after the theme or the geometries change
+    pub fn apply_theme(&mut self) {
+        self.theme_colors = self.theme.colors(&self.geometries);
+        self.revision = next_generation();
+    }
+
+    /// Re-evaluates the filter after it or the geometries change
+    pub fn apply_filter(&mut self) {
+        self.filter_matches = match self.filter.trim() {
+            "" => vec![],
+            filter => match filter.parse::<Expr>() {
+                Ok(expr) => self
+                    .geometries
+                    .iter()
+                    .map(|g| expr.matches(&|name| g.value(name)))
+                    .collect(),
+                Err(_) => vec![],
+            },
+        };
+        self.revision = next_generation();
+    }
+
+    /// Attribute names of all geometries in the order they first appear
+    pub fn attribute_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for geometry in &self.geometries {
+            for name in geometry.attributes.keys() {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+
+        names
+    }
+
+    /// Swaps the shape of one geometry, its name, attributes and visibility are kept
+    pub fn replace_geometry(&mut self, index: usize, sdo_geometry: SdoGeometry) {
+        let Some(geometry) = self.geometries.get_mut(index) else {
+            return;
+        };
+        *geometry = Geometry {
+            is_active: geometry.is_active,
+            ..Geometry::new(
+                std::mem::take(&mut geometry.name),
+                sdo_geometry,
+                std::mem::take(&mut geometry.attributes),
+            )
+        };
+        self.generation = next_generation();
+        self.apply_theme();
+        self.apply_filter();
+    }
+
+    /// Replaces geometries with freshly fetched ones.
+    ///
+    /// A geometry that is equal to a previous one keeps its visibility, the row
+    /// with the same number is checked first. New geometries are visible.
+    pub fn refresh(&mut self, name: &str, data: Vec<Feature>) {
+        let previous = std::mem::take(&mut self.geometries);
+        let mut matched = vec![false; previous.len()];
+        let mut by_shape: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (i, p) in previous.iter().enumerate() {
+            by_shape
+                .entry(shape_hash(&p.sdo_geometry))
+                .or_default()
+                .push(i);
+        }
+
+        self.geometries = data
+            .into_iter()
+            .enumerate()
+            .map(|(n, feature)| {
+                let g = feature.geometry;
+                let same_row = previous
+                    .get(n)
+                    .filter(|p| !matched[n] && p.sdo_geometry == g)
+                    .map(|_| n);
+                let found = same_row.or_else(|| {
+                    by_shape
+                        .get(&shape_hash(&g))?
+                        .iter()
+                        .copied()
+                        .find(|i| !matched[*i] && previous[*i].sdo_geometry == g)
+                });
+
+                let is_active = match found {
+                    Some(i) => {
+                        matched[i] = true;
+                        previous[i].is_active
+                    }
+                    None => true,
+                };
+
+                Geometry {
+                    is_active,
+                    ..Geometry::new(format!("{}_{}", name, n), g, feature.attributes)
+                }
+            })
+            .collect();
+        self.generation = next_generation();
+        self.apply_theme();
+        self.apply_filter();
+    }
+}
+
+fn next_generation() -> u64 {
+    static GENERATION: AtomicU64 = AtomicU64::new(1);
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Hash of the decoded values, equal geometries always get the same one
+fn shape_hash(geometry: &SdoGeometry) -> u64 {
+    // Adding zero turns -0.0 into 0.0, which compare as equal
+    let bits = |value: f64| (value + 0.).to_bits();
+    let mut hasher = DefaultHasher::new();
+    bits(geometry.sdo_gtype as f64).hash(&mut hasher);
+    geometry
+        .sdo_srid
+        .map(|srid| bits(srid as f64))
+        .hash(&mut hasher);
+    geometry
+        .sdo_point
+        .map(|p| [p.x, p.y, p.z].map(|v| v.map(bits)))
+        .hash(&mut hasher);
+    for value in &geometry.sdo_elem_info {
+        bits(*value as f64).hash(&mut hasher);
+    }
+    for value in &geometry.sdo_ordinates {
+        bits(*value).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Stroke};
+    use serde_json::json;
+
+    use crate::{feature::Feature, query::Query, sdo_geometry::SdoGeometry, style::LayerStyle};
+
+    fn point(x: f64) -> Feature {
+        Feature::from(SdoGeometry {
+            sdo_gtype: 2001.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1., 1.],
+            sdo_ordinates: vec![x, 0.],
+        })
+    }
+
+    #[test]
+    fn test_refresh_keeps_visibility_of_matching_geometries() {
+        let mut query = Query::new(
+            "points",
+            "select geom from points".into(),
+            vec![],
+            LayerStyle::from(Stroke::new(3., Color32::BLUE)),
+            vec![point(0.), point(1.), point(2.)],
+        );
+        query.geometries[1].is_active = false;
+        query.geometries[2].is_active = false;
+        let generation = query.generation();
+
+        // Row 0 is gone, so the hidden points move up and a new one appears
+        query.refresh("renamed", vec![point(1.), point(2.), point(3.)]);
+        assert_ne!(query.generation(), generation);
+
+        assert_eq!(query.style.stroke, Stroke::new(3., Color32::BLUE));
+        assert_eq!(
+            query
+                .geometries
+                .iter()
+                .map(|g| (g.name.as_str(), g.is_active))
+                .collect::<Vec<_>>(),
+            vec![
+                ("renamed_0", false),
+                ("renamed_1", false),
+                ("renamed_2", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_hides_geometries() {
+        let square = |size: f64, class: &str| Feature {
+            geometry: SdoGeometry {
+                sdo_gtype: 2003.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 1003., 3.],
+                sdo_ordinates: vec![0., 0., size, size],
+            },
+            attributes: serde_json::from_value(json!({ "CLASS": class })).unwrap(),
+        };
+        let mut query = Query::new(
+            "parcels",
+            String::new(),
+            vec![],
+            LayerStyle::default(),
+            vec![square(20., "RES"), square(5., "RES"), square(20., "COM")],
+        );
+        fn shown(query: &Query) -> Vec<&str> {
+            query.active_geometries().map(|g| g.name.as_str()).collect()
+        }
+
+        query.filter = "area > 100 and class = 'RES'".into();
+        query.apply_filter();
+        assert_eq!(shown(&query), vec!["parcels_0"]);
+
+        // The filter is kept when the layer is refreshed
+        query.refresh("parcels", vec![square(5., "RES"), square(20., "RES")]);
+        assert_eq!(shown(&query), vec!["parcels_1"]);
+        assert!(!query.is_shown(0));
+
+        // An invalid filter hides nothing
+        query.filter = "area >".into();
+        query.apply_filter();
+        assert_eq!(shown(&query).len(), 2);
+
+        assert!(query.geometries[0].contains_text("res"));
+        assert!(query.geometries[0].contains_text("PARCELS_0"));
+        assert!(!query.geometries[0].contains_text("com"));
+    }
+}
@@ -0,0 +1,328 @@
+use std::path::Path;
+
+use egui::{Color32, Stroke};
+use tiny_skia::{
+    FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke as SkiaStroke, StrokeDash,
+    Transform,
+};
+
+use crate::{
+    overlay::{self, AxisFormat},
+    query::Query,
+    render::{draw_layers, Canvas, Extent},
+    style::Dash,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum RasterError {
+    #[error("Image size {0}x{1} is not valid")]
+    InvalidSize(u32, u32),
+    #[error("Failed to encode PNG: {0}")]
+    Encoding(String),
+}
+
+/// Renders layers into a PNG image without any window or GPU.
+///
+/// Output only depends on the layers, the extent and the size,
+/// so the same input always produces the same bytes.
+pub struct PngRenderer {
+    pub extent: Extent,
+    pub width: u32,
+    pub height: u32,
+    pub background: Color32,
+    pub include_axes: bool,
+    pub axis_format: AxisFormat,
+    pub include_scale_bar: bool,
+    pub include_north_arrow: bool,
+    /// SRID of the coordinates, used for ground distances, north and `AxisFormat::Auto`
+    pub srid: Option<f32>,
+}
+
+impl PngRenderer {
+    pub fn new(extent: Extent, width: u32, height: u32) -> Self {
+        Self {
+            extent,
+            width,
+            height,
+            background: Color32::WHITE,
+            include_axes: false,
+            axis_format: AxisFormat::Auto,
+            include_scale_bar: false,
+            include_north_arrow: false,
+            srid: None,
+        }
+    }
+
+    pub fn render<'a>(
+        &self,
+        layers: impl IntoIterator<Item = (&'a str, &'a Query)>,
+    ) -> Result<Pixmap, RasterError> {
+        let mut pixmap = Pixmap::new(self.width, self.height)
+            .ok_or(RasterError::InvalidSize(self.width, self.height))?;
+        pixmap.fill(skia_color(self.background));
+
+        let transform =
+            overlay::map_transform(self.extent, self.width, self.height, self.include_axes);
+        let mut canvas = PixmapCanvas {
+            pixmap: &mut pixmap,
+        };
+        draw_layers(&mut canvas, &transform, layers);
+        // Labels are drawn with strokes, the PNG has no fonts
+        if self.include_axes {
+            // Geometries reaching into the margins are covered like the SVG clips them
+            let [x, y] = transform.origin;
+            let [w, h] = transform.size;
+            let (width, height) = (self.width as f64, self.height as f64);
+            for [left, top, right, bottom] in [
+                [0., 0., width, y],
+                [0., y + h, width, height],
+                [0., y, x, y + h],
+                [x + w, y, width, y + h],
+            ] {
+                let margin = vec![[left, top], [right, top], [right, bottom], [left, bottom]];
+                canvas.polygon(&[margin], self.background, Stroke::NONE, Dash::Solid);
+            }
+            let format = self.axis_format.resolve(self.srid);
+            overlay::draw_axes(&mut canvas, &transform, format, Color32::BLACK);
+        }
+        if self.include_scale_bar {
+            overlay::draw_scale_bar(&mut canvas, &transform, self.srid, Color32::BLACK);
+        }
+        if self.include_north_arrow {
+            overlay::draw_north_arrow(&mut canvas, &transform, self.srid, Color32::BLACK);
+        }
+
+        Ok(pixmap)
+    }
+
+    pub fn encode<'a>(
+        &self,
+        layers: impl IntoIterator<Item = (&'a str, &'a Query)>,
+    ) -> Result<Vec<u8>, RasterError> {
+        self.render(layers)?
+            .encode_png()
+            .map_err(|e| RasterError::Encoding(e.to_string()))
+    }
+
+    pub fn export<'a>(
+        &self,
+        layers: impl IntoIterator<Item = (&'a str, &'a Query)>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), RasterError> {
+        self.render(layers)?
+            .save_png(path)
+            .map_err(|e| RasterError::Encoding(e.to_string()))
+    }
+}
+
+struct PixmapCanvas<'a> {
+    pixmap: &'a mut Pixmap,
+}
+
+impl PixmapCanvas<'_> {
+    fn stroke(&mut self, builder: PathBuilder, stroke: Stroke, dash: Dash) {
+        if stroke.is_empty() {
+            return;
+        }
+
+        if let Some(path) = builder.finish() {
+            let skia_stroke = SkiaStroke {
+                width: stroke.width,
+                line_cap: LineCap::Round,
+                line_join: LineJoin::Round,
+                dash: dash
+                    .pattern(stroke.width)
+                    .and_then(|pattern| StrokeDash::new(pattern.to_vec(), 0.)),
+                ..Default::default()
+            };
+            self.pixmap.stroke_path(
+                &path,
+                &paint(stroke.color),
+                &skia_stroke,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+}
+
+impl Canvas for PixmapCanvas<'_> {
+    fn polygon(&mut self, rings: &[Vec<[f64; 2]>], fill: Color32, stroke: Stroke, dash: Dash) {
+        let mut builder = PathBuilder::new();
+        for ring in rings {
+            push_path(&mut builder, ring, true);
+        }
+
+        if fill.a() > 0 {
+            if let Some(path) = builder.clone().finish() {
+                self.pixmap.fill_path(
+                    &path,
+                    &paint(fill),
+                    FillRule::EvenOdd,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+
+        self.stroke(builder, stroke, dash);
+    }
+
+    fn line(&mut self, points: &[[f64; 2]], stroke: Stroke, dash: Dash) {
+        let mut builder = PathBuilder::new();
+        push_path(&mut builder, points, false);
+        self.stroke(builder, stroke, dash);
+    }
+
+    fn point(&mut self, point: [f64; 2], radius: f32, color: Color32) {
+        if let Some(path) = PathBuilder::from_circle(point[0] as f32, point[1] as f32, radius) {
+            self.pixmap.fill_path(
+                &path,
+                &paint(color),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+}
+
+fn push_path(builder: &mut PathBuilder, points: &[[f64; 2]], closed: bool) {
+    for (i, [x, y]) in points.iter().enumerate() {
+        if i == 0 {
+            builder.move_to(*x as f32, *y as f32);
+        } else {
+            builder.line_to(*x as f32, *y as f32);
+        }
+    }
+
+    if closed && points.len() > 2 {
+        builder.close();
+    }
+}
+
+fn skia_color(color: Color32) -> tiny_skia::Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    tiny_skia::Color::from_rgba8(r, g, b, a)
+}
+
+fn paint(color: Color32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(skia_color(color));
+    paint.anti_alias = true;
+    paint
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Stroke};
+
+    use crate::{
+        feature::Feature,
+        query::Query,
+        raster::{PngRenderer, RasterError},
+        render::Extent,
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+    };
+
+    fn query(sdo_geometry: SdoGeometry) -> Query {
+        Query::new(
+            "test",
+            String::new(),
+            vec![],
+            LayerStyle::from(Stroke::new(2., Color32::RED)),
+            vec![Feature::from(sdo_geometry)],
+        )
+    }
+
+    fn line() -> SdoGeometry {
+        SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 1.],
+            sdo_ordinates: vec![0., 5., 10., 5.],
+        }
+    }
+
+    #[test]
+    fn test_render_line_pixels() {
+        let query = query(line());
+        let renderer = PngRenderer::new(Extent::new([0., 0.], [10., 10.]), 20, 20);
+        let pixmap = renderer.render([("roads", &query)]).unwrap();
+
+        let center = pixmap.pixel(10, 10).unwrap();
+        assert_eq!(
+            (center.red(), center.green(), center.blue(), center.alpha()),
+            (255, 0, 0, 255)
+        );
+
+        let corner = pixmap.pixel(0, 0).unwrap();
+        assert_eq!(
+            (corner.red(), corner.green(), corner.blue(), corner.alpha()),
+            (255, 255, 255, 255)
+        );
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let query = query(SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 4.],
+            sdo_ordinates: vec![8., 7., 10., 9., 8., 11.],
+        });
+        let renderer = PngRenderer::new(Extent::new([5., 6.], [11., 12.]), 64, 48);
+
+        let first = renderer.encode([("circles", &query)]).unwrap();
+        let second = renderer.encode([("circles", &query)]).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_render_invalid_size() {
+        let query = query(line());
+        let renderer = PngRenderer::new(Extent::new([0., 0.], [10., 10.]), 0, 10);
+
+        assert!(matches!(
+            renderer.render([("roads", &query)]),
+            Err(RasterError::InvalidSize(0, 10))
+        ));
+    }
+
+    #[test]
+    fn test_render_scale_bar() {
+        let query = query(line());
+        let mut renderer = PngRenderer::new(Extent::new([0., 0.], [100., 100.]), 200, 200);
+        let plain = renderer.render([("roads", &query)]).unwrap();
+        renderer.include_scale_bar = true;
+        let with_bar = renderer.render([("roads", &query)]).unwrap();
+
+        // The filled half of the bar starts next to the bottom left corner
+        let pixel = with_bar.pixel(15, 185).unwrap();
+        assert_eq!((pixel.red(), pixel.green(), pixel.blue()), (0, 0, 0));
+        assert_ne!(plain.data(), with_bar.data());
+    }
+
+    #[test]
+    fn test_render_axes() {
+        let query = query(line());
+        let mut renderer = PngRenderer::new(Extent::new([0., 0.], [10., 10.]), 200, 200);
+        renderer.include_axes = true;
+        let pixmap = renderer.render([("roads", &query)]).unwrap();
+
+        let is_white = |x: u32, y: u32| {
+            let pixel = pixmap.pixel(x, y).unwrap();
+            (pixel.red(), pixel.green(), pixel.blue()) == (255, 255, 255)
+        };
+        // The frame starts after the margin of the y labels
+        assert!(!is_white(70, 100));
+        assert!(is_white(100, 5));
+        // Tick labels are drawn with strokes left of the frame and below it
+        assert!((0..64).any(|x| (0..200).any(|y| !is_white(x, y))));
+        assert!((70..190).any(|x| (175..200).any(|y| !is_white(x, y))));
+    }
+}
@@ -0,0 +1,641 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use egui::{Color32, Mesh, Pos2, Shape, Stroke};
+use egui_plot::{Line, PlotBounds, PlotPoints, PlotUi, Points, Polygon};
+use geo::{BoundingRect, Geometry};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lod::Viewport,
+    query::Query,
+    style::{Dash, LayerStyle},
+};
+
+/// Cross and plus markers stay readable with hairline strokes
+const MARKER_STROKE_WIDTH: f32 = 1.5;
+/// Span of the view around a single point when there is nothing else to scale it by
+pub const POINT_SPAN: f64 = 1.0;
+
+/// Rectangle in data coordinates that is mapped onto a drawing surface
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Extent {
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+}
+
+impl Extent {
+    pub fn new(min: [f64; 2], max: [f64; 2]) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_plot_bounds(bounds: &PlotBounds) -> Self {
+        Self::new(bounds.min(), bounds.max())
+    }
+
+    pub fn of_geometries<'a>(geometries: impl IntoIterator<Item = &'a Geometry>) -> Option<Self> {
+        geometries
+            .into_iter()
+            .filter_map(|g| g.bounding_rect())
+            .map(|r| Self::new([r.min().x, r.min().y], [r.max().x, r.max().y]))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub fn union(&self, other: &Extent) -> Self {
+        Self::new(
+            [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        )
+    }
+
+    /// Whether the extents overlap or touch
+    pub fn intersects(&self, other: &Extent) -> bool {
+        self.min[0] <= other.max[0]
+            && other.min[0] <= self.max[0]
+            && self.min[1] <= other.max[1]
+            && other.min[1] <= self.max[1]
+    }
+
+    pub fn contains(&self, other: &Extent) -> bool {
+        self.min[0] <= other.min[0]
+            && self.min[1] <= other.min[1]
+            && other.max[0] <= self.max[0]
+            && other.max[1] <= self.max[1]
+    }
+
+    /// Grows the extent on every side by a fraction of its size
+    pub fn with_margin(&self, fraction: f64) -> Self {
+        // A straight line gets the margin of its length on both axes
+        let longest = self.width().max(self.height());
+        let margin = |side: f64| if side > 0. { side } else { longest } * fraction;
+        let dx = margin(self.width());
+        let dy = margin(self.height());
+        Self::new(
+            [self.min[0] - dx, self.min[1] - dy],
+            [self.max[0] + dx, self.max[1] + dy],
+        )
+    }
+
+    /// A single point becomes a square of `span` around it, other extents are kept
+    pub fn with_point_span(&self, span: f64) -> Self {
+        if self.width() > 0. || self.height() > 0. {
+            return *self;
+        }
+        let half = span / 2.;
+        Self::new(
+            [self.min[0] - half, self.min[1] - half],
+            [self.max[0] + half, self.max[1] + half],
+        )
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max[0] - self.min[0]
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max[1] - self.min[1]
+    }
+}
+
+/// Maps data coordinates onto a pixel area with the y axis pointing down.
+///
+/// The scale is the same for both axes, like the plot's `data_aspect(1.)`,
+/// so the extent is centered inside the area.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenTransform {
+    pub extent: Extent,
+    pub origin: [f64; 2],
+    pub size: [f64; 2],
+    pub scale: f64,
+}
+
+impl ScreenTransform {
+    pub fn new(extent: Extent, origin: [f64; 2], size: [f64; 2]) -> Self {
+        let scale_x = size[0] / extent.width().max(f64::EPSILON);
+        let scale_y = size[1] / extent.height().max(f64::EPSILON);
+        let scale = scale_x.min(scale_y);
+        let center = [
+            (extent.min[0] + extent.max[0]) / 2.,
+            (extent.min[1] + extent.max[1]) / 2.,
+        ];
+        let half = [size[0] / scale / 2., size[1] / scale / 2.];
+
+        Self {
+            extent: Extent::new(
+                [center[0] - half[0], center[1] - half[1]],
+                [center[0] + half[0], center[1] + half[1]],
+            ),
+            origin,
+            size,
+            scale,
+        }
+    }
+
+    pub fn to_screen(&self, point: [f64; 2]) -> [f64; 2] {
+        [
+            self.origin[0] + (point[0] - self.extent.min[0]) * self.scale,
+            self.origin[1] + (self.extent.max[1] - point[1]) * self.scale,
+        ]
+    }
+}
+
+/// Picks "nice" tick positions (1, 2 or 5 times a power of ten) between `min` and `max`
+pub fn ticks(min: f64, max: f64, max_count: usize) -> Vec<f64> {
+    let range = max - min;
+    if range <= 0. || !range.is_finite() || max_count == 0 {
+        return vec![];
+    }
+
+    let rough_step = range / max_count as f64;
+    let magnitude = 10f64.powf(rough_step.log10().floor());
+    let step = [1., 2., 5., 10.]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|s| *s >= rough_step)
+        .unwrap_or(10. * magnitude);
+
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+/// Surface that exporters draw onto, all coordinates are in pixels
+pub trait Canvas {
+    fn begin_layer(&mut self, _name: &str) {}
+
+    fn end_layer(&mut self) {}
+
+    /// The first ring is the exterior, the rest are holes
+    fn polygon(&mut self, rings: &[Vec<[f64; 2]>], fill: Color32, stroke: Stroke, dash: Dash);
+
+    fn line(&mut self, points: &[[f64; 2]], stroke: Stroke, dash: Dash);
+
+    fn point(&mut self, point: [f64; 2], radius: f32, color: Color32);
+
+    /// Text centered on a pixel position, `size` is the height of digits.
+    ///
+    /// Canvases without fonts draw digits, units, degrees and hemispheres with strokes.
+    fn text(&mut self, center: [f64; 2], text: &str, size: f64, color: Color32) {
+        stroke_text(self, center, text, size, color);
+    }
+}
+
+/// Strokes of a glyph on a 4 by 6 grid with y pointing up, unknown characters are blank
+fn glyph(c: char) -> &'static [&'static [(f64, f64)]] {
+    match c {
+        '0' => &[&[(0., 0.), (4., 0.), (4., 6.), (0., 6.), (0., 0.)]],
+        '1' => &[&[(1., 5.), (2., 6.), (2., 0.)], &[(1., 0.), (3., 0.)]],
+        '2' => &[&[(0., 6.), (4., 6.), (4., 3.), (0., 3.), (0., 0.), (4., 0.)]],
+        '3' => &[
+            &[(0., 6.), (4., 6.), (4., 0.), (0., 0.)],
+            &[(0., 3.), (4., 3.)],
+        ],
+        '4' => &[&[(0., 6.), (0., 3.), (4., 3.)], &[(4., 6.), (4., 0.)]],
+        '5' => &[&[(4., 6.), (0., 6.), (0., 3.), (4., 3.), (4., 0.), (0., 0.)]],
+        '6' => &[&[(4., 6.), (0., 6.), (0., 0.), (4., 0.), (4., 3.), (0., 3.)]],
+        '7' => &[&[(0., 6.), (4., 6.), (4., 0.)]],
+        '8' => &[
+            &[(0., 0.), (4., 0.), (4., 6.), (0., 6.), (0., 0.)],
+            &[(0., 3.), (4., 3.)],
+        ],
+        '9' => &[&[(4., 3.), (0., 3.), (0., 6.), (4., 6.), (4., 0.), (0., 0.)]],
+        '.' => &[&[(1.5, 0.), (2.5, 0.)]],
+        '-' => &[&[(0., 3.), (4., 3.)]],
+        '°' => &[&[(1., 4.), (3., 4.), (3., 6.), (1., 6.), (1., 4.)]],
+        '\'' => &[&[(2., 6.), (2., 4.)]],
+        '"' => &[&[(1., 6.), (1., 4.)], &[(3., 6.), (3., 4.)]],
+        'c' => &[&[(4., 4.), (0., 4.), (0., 0.), (4., 0.)]],
+        'k' => &[&[(0., 6.), (0., 0.)], &[(4., 4.), (0., 2.), (4., 0.)]],
+        'm' => &[
+            &[(0., 0.), (0., 4.), (4., 4.), (4., 0.)],
+            &[(2., 4.), (2., 0.)],
+        ],
+        'E' => &[
+            &[(4., 6.), (0., 6.), (0., 0.), (4., 0.)],
+            &[(0., 3.), (3., 3.)],
+        ],
+        'N' => &[&[(0., 0.), (0., 6.), (4., 0.), (4., 6.)]],
+        'S' => &[&[(4., 6.), (0., 6.), (0., 3.), (4., 3.), (4., 0.), (0., 0.)]],
+        'W' => &[&[(0., 6.), (1., 0.), (2., 4.), (3., 0.), (4., 6.)]],
+        _ => &[],
+    }
+}
+
+fn stroke_text<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    center: [f64; 2],
+    text: &str,
+    size: f64,
+    color: Color32,
+) {
+    const GLYPH_WIDTH: f64 = 4.;
+    const ADVANCE: f64 = 6.;
+    let scale = size / 6.;
+    let width = (text.chars().count() as f64 * ADVANCE - (ADVANCE - GLYPH_WIDTH)) * scale;
+    let stroke = Stroke::new((size / 8.).max(1.) as f32, color);
+    let left = center[0] - width / 2.;
+    let baseline = center[1] + size / 2.;
+
+    for (n, c) in text.chars().enumerate() {
+        let x = left + n as f64 * ADVANCE * scale;
+        for strokes in glyph(c) {
+            let points = strokes
+                .iter()
+                .map(|(gx, gy)| [x + gx * scale, baseline - gy * scale])
+                .collect::<Vec<_>>();
+            canvas.line(&points, stroke, Dash::Solid);
+        }
+    }
+}
+
+/// Walks active geometries of the layers in the given order,
+/// the same way the interactive plot does
+pub fn draw_layers<'a>(
+    canvas: &mut dyn Canvas,
+    transform: &ScreenTransform,
+    layers: impl IntoIterator<Item = (&'a str, &'a Query)>,
+) {
+    for (name, query) in layers {
+        canvas.begin_layer(name);
+        for (geometry, style) in query.active_styled() {
+            if let Some(shape) = geometry.shape() {
+                draw_geometry(canvas, transform, shape, &style);
+            }
+        }
+        canvas.end_layer();
+    }
+}
+
+/// Draws the point marker of the style centered on a pixel position
+pub fn draw_marker(canvas: &mut dyn Canvas, center: [f64; 2], style: &LayerStyle) {
+    let color = style.stroke.color;
+    if let Some(outline) = style.marker.outline(center, style.marker_size) {
+        canvas.polygon(&[outline], color, Stroke::NONE, Dash::Solid);
+        return;
+    }
+
+    let segments = style.marker.segments(center, style.marker_size);
+    if segments.is_empty() {
+        canvas.point(center, style.marker_size, color);
+    }
+    for segment in segments {
+        canvas.line(
+            &segment,
+            Stroke::new(style.stroke.width.max(MARKER_STROKE_WIDTH), color),
+            Dash::Solid,
+        );
+    }
+}
+
+pub fn draw_geometry(
+    canvas: &mut dyn Canvas,
+    transform: &ScreenTransform,
+    geometry: &Geometry,
+    style: &LayerStyle,
+) {
+    let screen = |coords: &mut dyn Iterator<Item = geo::Coord>| {
+        coords
+            .map(|c| transform.to_screen([c.x, c.y]))
+            .collect::<Vec<_>>()
+    };
+    let (stroke, dash) = (style.stroke, style.dash);
+
+    match geometry {
+        Geometry::Point(point) => {
+            draw_marker(canvas, transform.to_screen([point.x(), point.y()]), style)
+        }
+        Geometry::MultiPoint(points) => {
+            for point in points {
+                draw_marker(canvas, transform.to_screen([point.x(), point.y()]), style);
+            }
+        }
+        Geometry::Line(line) => canvas.line(
+            &screen(&mut [line.start, line.end].into_iter()),
+            stroke,
+            dash,
+        ),
+        Geometry::LineString(line) => {
+            canvas.line(&screen(&mut line.coords().copied()), stroke, dash)
+        }
+        Geometry::MultiLineString(lines) => {
+            for line in lines {
+                canvas.line(&screen(&mut line.coords().copied()), stroke, dash);
+            }
+        }
+        Geometry::Polygon(polygon) => {
+            let rings = std::iter::once(polygon.exterior())
+                .chain(polygon.interiors())
+                .map(|ring| screen(&mut ring.coords().copied()))
+                .collect::<Vec<_>>();
+            canvas.polygon(&rings, style.fill_color(), stroke, dash);
+        }
+        Geometry::MultiPolygon(polygons) => {
+            for polygon in polygons {
+                draw_geometry(
+                    canvas,
+                    transform,
+                    &Geometry::Polygon(polygon.clone()),
+                    style,
+                );
+            }
+        }
+        Geometry::GeometryCollection(collection) => {
+            for geometry in collection {
+                draw_geometry(canvas, transform, geometry, style);
+            }
+        }
+        Geometry::Rect(rect) => draw_geometry(
+            canvas,
+            transform,
+            &Geometry::Polygon(rect.to_polygon()),
+            style,
+        ),
+        Geometry::Triangle(triangle) => draw_geometry(
+            canvas,
+            transform,
+            &Geometry::Polygon(triangle.to_polygon()),
+            style,
+        ),
+    }
+}
+
+/// Fills of a layer in data coordinates, relative to `origin` so they fit in `f32`
+struct DataMesh {
+    key: u64,
+    origin: [f64; 2],
+    mesh: Mesh,
+}
+
+/// Fill mesh of a layer for the zoom band it was built for
+#[derive(Default)]
+pub struct FillCache {
+    mesh: Mutex<Option<DataMesh>>,
+}
+
+impl FillCache {
+    fn with<R>(
+        &self,
+        key: u64,
+        build: impl FnOnce() -> DataMesh,
+        f: impl FnOnce(&DataMesh) -> R,
+    ) -> R {
+        let mut mesh = self.mesh.lock().unwrap_or_else(|e| e.into_inner());
+        match mesh.as_ref() {
+            Some(cached) if cached.key == key => f(cached),
+            _ => f(mesh.insert(build())),
+        }
+    }
+}
+
+/// Identifies the fills of a layer, the same on every frame until it is zoomed into another band
+fn fill_key(viewport: &Viewport, query: &Query) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    viewport.band.hash(&mut hasher);
+    query.generation().hash(&mut hasher);
+    query.revision().hash(&mut hasher);
+    query.style.fill_color().hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// Fills of the active polygons of a layer as one mesh in screen coordinates
+///
+/// Polygons are simplified for the zoom band of the viewport. The triangles are kept in data
+/// coordinates until the band or the fills of the layer change, only the transform is applied
+/// on every frame.
+pub fn fill_mesh(transform: &ScreenTransform, viewport: &Viewport, query: &Query) -> Mesh {
+    let key = fill_key(viewport, query);
+    let build = || {
+        let origin = query.extent().map_or([0., 0.], |e| e.min);
+        let mut mesh = Mesh::default();
+        for (geometry, style) in query.active_styled() {
+            let color = style.fill_color();
+            if color.a() == 0 {
+                continue;
+            }
+            if let Some(drawn) = viewport.simplified(geometry) {
+                drawn.triangulation().append_to(&mut mesh, color, |p| {
+                    pos([p[0] - origin[0], p[1] - origin[1]])
+                });
+            }
+        }
+        DataMesh { key, origin, mesh }
+    };
+
+    query.fill_cache().with(key, build, |data| {
+        let [x, y] = transform.to_screen(data.origin);
+        let scale = transform.scale as f32;
+        let mut mesh = data.mesh.clone();
+        for vertex in &mut mesh.vertices {
+            vertex.pos = Pos2::new(
+                x as f32 + vertex.pos.x * scale,
+                y as f32 - vertex.pos.y * scale,
+            );
+        }
+        mesh
+    })
+}
+
+fn pos([x, y]: [f64; 2]) -> Pos2 {
+    Pos2::new(x as f32, y as f32)
+}
+
+/// Outlines, lines and markers of the interactive plot as egui shapes
+#[derive(Default)]
+struct ShapeCanvas {
+    shapes: Vec<Shape>,
+}
+
+impl Canvas for ShapeCanvas {
+    fn polygon(&mut self, rings: &[Vec<[f64; 2]>], fill: Color32, stroke: Stroke, dash: Dash) {
+        // Polygon fills come from the triangulation, only the convex markers are filled here
+        if let Some(exterior) = rings.first().filter(|_| fill.a() > 0) {
+            let points = exterior.iter().copied().map(pos).collect();
+            self.shapes
+                .push(Shape::convex_polygon(points, fill, Stroke::NONE));
+        }
+        for ring in rings {
+            self.line(ring, stroke, dash);
+        }
+    }
+
+    fn line(&mut self, points: &[[f64; 2]], stroke: Stroke, dash: Dash) {
+        let points: Vec<Pos2> = points.iter().copied().map(pos).collect();
+        match dash.pattern(stroke.width) {
+            None => self.shapes.push(Shape::line(points, stroke)),
+            Some([dash, gap]) => self
+                .shapes
+                .extend(Shape::dashed_line(&points, stroke, dash, gap)),
+        }
+    }
+
+    fn point(&mut self, point: [f64; 2], radius: f32, color: Color32) {
+        self.shapes
+            .push(Shape::circle_filled(pos(point), radius, color));
+    }
+}
+
+/// Shapes of a layer on the interactive plot, its fills below its outlines and markers
+pub fn layer_shapes(transform: &ScreenTransform, viewport: &Viewport, query: &Query) -> Vec<Shape> {
+    let mut canvas = ShapeCanvas::default();
+    canvas
+        .shapes
+        .push(Shape::mesh(fill_mesh(transform, viewport, query)));
+    for (geometry, style) in query.active_styled() {
+        if let Some(drawn) = viewport.drawn(geometry) {
+            let style = LayerStyle {
+                fill_opacity: 0.,
+                ..style
+            };
+            draw_geometry(&mut canvas, transform, drawn.shape(), &style);
+        }
+    }
+
+    canvas.shapes
+}
+
+/// Draws a geometry on the interactive plot
+pub fn plot_geometry(plot_ui: &mut PlotUi, name: &str, geometry: &Geometry, style: &LayerStyle) {
+    let line_style = style.dash.line_style(style.stroke.width);
+    let points = |points: Vec<[f64; 2]>| {
+        Points::new(points)
+            .shape(style.marker.shape())
+            .filled(true)
+            .radius(style.marker_size)
+            .color(style.stroke.color)
+            .name(name)
+    };
+
+    match geometry {
+        Geometry::Point(point) => plot_ui.points(points(vec![[point.x(), point.y()]])),
+        Geometry::MultiPoint(multi_point) => {
+            plot_ui.points(points(multi_point.iter().map(|p| [p.x(), p.y()]).collect()))
+        }
+        Geometry::Line(line) => plot_ui.line(
+            Line::new(vec![[line.start.x, line.start.y], [line.end.x, line.end.y]])
+                .stroke(style.stroke)
+                .style(line_style)
+                .name(name),
+        ),
+        Geometry::LineString(line) => plot_ui.line(
+            Line::new(line.coords().map(|c| [c.x, c.y]).collect::<PlotPoints>())
+                .stroke(style.stroke)
+                .style(line_style)
+                .name(name),
+        ),
+        Geometry::MultiLineString(lines) => {
+            for line in lines {
+                plot_geometry(plot_ui, name, &Geometry::LineString(line.clone()), style);
+            }
+        }
+        Geometry::Polygon(polygon) => {
+            // Polygons are outlined, a fill is drawn from the triangulation, see `fill_mesh`
+            for ring in std::iter::once(polygon.exterior()).chain(polygon.interiors()) {
+                plot_ui.polygon(
+                    Polygon::new(ring.coords().map(|c| [c.x, c.y]).collect::<PlotPoints>())
+                        .fill_color(Color32::TRANSPARENT)
+                        .stroke(style.stroke)
+                        .style(line_style)
+                        .name(name),
+                );
+            }
+        }
+        Geometry::MultiPolygon(polygons) => {
+            for polygon in polygons {
+                plot_geometry(plot_ui, name, &Geometry::Polygon(polygon.clone()), style);
+            }
+        }
+        Geometry::GeometryCollection(collection) => {
+            for geometry in collection {
+                plot_geometry(plot_ui, name, geometry, style);
+            }
+        }
+        Geometry::Rect(rect) => {
+            plot_geometry(plot_ui, name, &Geometry::Polygon(rect.to_polygon()), style)
+        }
+        Geometry::Triangle(triangle) => plot_geometry(
+            plot_ui,
+            name,
+            &Geometry::Polygon(triangle.to_polygon()),
+            style,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Shape;
+
+    use crate::{
+        feature::Feature,
+        lod::Viewport,
+        query::Query,
+        render::{fill_mesh, layer_shapes, ticks, Extent, ScreenTransform},
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+    };
+
+    #[test]
+    fn test_ticks() {
+        assert_eq!(ticks(0., 10., 5), vec![0., 2., 4., 6., 8., 10.]);
+        assert_eq!(ticks(3., 47., 4), vec![20., 40.]);
+        assert!(ticks(1., 1., 5).is_empty());
+    }
+
+    #[test]
+    fn test_screen_transform_keeps_aspect() {
+        let transform =
+            ScreenTransform::new(Extent::new([0., 0.], [10., 10.]), [0., 0.], [200., 100.]);
+
+        assert_eq!(transform.scale, 10.);
+        assert_eq!(transform.to_screen([5., 5.]), [100., 50.]);
+        assert_eq!(transform.to_screen([0., 10.]), [50., 0.]);
+    }
+
+    #[test]
+    fn test_layer_shapes_fill_below_outline() {
+        let square = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 1.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10., 10., 0., 10., 0., 0.],
+        };
+        let mut query = Query::new(
+            "a",
+            String::new(),
+            vec![],
+            LayerStyle::default(),
+            vec![Feature::from(square)],
+        );
+        query.style.fill_opacity = 0.5;
+        let extent = Extent::new([0., 0.], [10., 10.]);
+        let transform = ScreenTransform::new(extent, [0., 0.], [100., 100.]);
+        let viewport = Viewport::new(extent, 0.1);
+
+        let shapes = layer_shapes(&transform, &viewport, &query);
+        assert!(matches!(&shapes[0], Shape::Mesh(mesh) if mesh.indices.len() == 6));
+        assert!(matches!(shapes[1], Shape::Path(_)));
+
+        // The cached mesh is kept until the fills change
+        let mesh = fill_mesh(&transform, &viewport, &query);
+        query.set_active(0, false);
+        assert!(fill_mesh(&transform, &viewport, &query).is_empty());
+        query.set_active(0, true);
+        assert_eq!(
+            fill_mesh(&transform, &viewport, &query).vertices,
+            mesh.vertices
+        );
+
+        // Panning moves the cached triangles on the screen
+        let panned =
+            ScreenTransform::new(Extent::new([-5., 0.], [5., 10.]), [0., 0.], [100., 100.]);
+        let moved = fill_mesh(&panned, &viewport, &query);
+        for (moved, vertex) in moved.vertices.iter().zip(&mesh.vertices) {
+            assert_eq!(moved.pos, vertex.pos + egui::vec2(50., 0.));
+        }
+    }
+}
@@ -1,8 +1,17 @@
-use std::f64::consts::TAU;
+use std::f64::consts::{PI, TAU};
 
-use egui::{remap, Color32, Pos2, Stroke};
-use egui_plot::{Line, PlotPoints, Polygon};
-use serde::Deserialize;
+use geo::{
+    orient::{Direction, Orient},
+    relate::IntersectionMatrix,
+    Coord, Geometry, GeometryCollection, LineString, MultiLineString, MultiPoint, MultiPolygon,
+    Point, Polygon, Relate, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+/// Most arguments Oracle accepts in a function or constructor call
+const MAX_SQL_ARGUMENTS: usize = 999;
+/// Amount of segments used to approximate a full circle
+const CIRCLE_SEGMENTS: usize = 512;
 
 // https://www.reddit.com/r/learnrust/comments/lfw6uy/comment/gmqqhg2/?utm_source=share&utm_medium=web3x&utm_name=web3xcss&utm_term=1&utm_content=share_button
 fn collect_array<T, I, const N: usize>(itr: I) -> [T; N]
@@ -18,87 +27,725 @@ where
     res
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SdoPoint {
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub z: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SdoGeometry {
     pub sdo_gtype: f32,
     pub sdo_srid: Option<f32>,
-    pub sdo_point: Option<f32>,
+    pub sdo_point: Option<SdoPoint>,
     pub sdo_elem_info: Vec<f32>,
     pub sdo_ordinates: Vec<f64>,
 }
 
+/// One triplet of the `SDO_ELEM_INFO` array
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdoElement {
+    pub offset: usize,
+    pub etype: i32,
+    pub interpretation: i32,
+}
+
 impl SdoGeometry {
+    /// Encodes a 2D `geo` geometry with Oracle's element info triplets.
+    ///
+    /// Polygon rings are reoriented the way Oracle expects them:
+    /// exterior rings counterclockwise and interior rings clockwise.
+    pub fn from_geo(geometry: &Geometry, srid: Option<f32>) -> Self {
+        let geometry_type = match geometry {
+            Geometry::Point(_) => 1,
+            Geometry::Line(_) | Geometry::LineString(_) => 2,
+            Geometry::Polygon(_) | Geometry::Rect(_) | Geometry::Triangle(_) => 3,
+            Geometry::GeometryCollection(_) => 4,
+            Geometry::MultiPoint(_) => 5,
+            Geometry::MultiLineString(_) => 6,
+            Geometry::MultiPolygon(_) => 7,
+        };
+
+        let mut sdo_geometry = Self {
+            sdo_gtype: (2000 + geometry_type) as f32,
+            sdo_srid: srid,
+            sdo_point: None,
+            sdo_elem_info: vec![],
+            sdo_ordinates: vec![],
+        };
+
+        // Oracle stores a single point in SDO_POINT and leaves both arrays empty
+        if let Geometry::Point(point) = geometry {
+            sdo_geometry.sdo_point = Some(SdoPoint {
+                x: Some(point.x()),
+                y: Some(point.y()),
+                z: None,
+            });
+            return sdo_geometry;
+        }
+
+        sdo_geometry.push_geometry(geometry);
+        sdo_geometry
+    }
+
+    fn push_geometry(&mut self, geometry: &Geometry) {
+        match geometry {
+            Geometry::Point(point) => self.push_element(1, 1, [point.0]),
+            Geometry::MultiPoint(points) => {
+                self.push_element(1, points.len() as i32, points.iter().map(|p| p.0))
+            }
+            Geometry::Line(line) => self.push_element(2, 1, [line.start, line.end]),
+            Geometry::LineString(line) => self.push_element(2, 1, line.coords().copied()),
+            Geometry::MultiLineString(lines) => {
+                for line in lines {
+                    self.push_element(2, 1, line.coords().copied());
+                }
+            }
+            Geometry::Polygon(polygon) => {
+                let polygon = polygon.orient(Direction::Default);
+                self.push_element(1003, 1, polygon.exterior().coords().copied());
+                for interior in polygon.interiors() {
+                    self.push_element(2003, 1, interior.coords().copied());
+                }
+            }
+            Geometry::MultiPolygon(polygons) => {
+                for polygon in polygons {
+                    self.push_geometry(&Geometry::Polygon(polygon.clone()));
+                }
+            }
+            Geometry::Rect(rect) => self.push_geometry(&Geometry::Polygon(rect.to_polygon())),
+            Geometry::Triangle(triangle) => {
+                self.push_geometry(&Geometry::Polygon(triangle.to_polygon()))
+            }
+            Geometry::GeometryCollection(collection) => {
+                for geometry in collection {
+                    self.push_geometry(geometry);
+                }
+            }
+        }
+    }
+
+    fn push_element(
+        &mut self,
+        etype: i32,
+        interpretation: i32,
+        coords: impl IntoIterator<Item = Coord>,
+    ) {
+        self.sdo_elem_info.extend([
+            (self.sdo_ordinates.len() + 1) as f32,
+            etype as f32,
+            interpretation as f32,
+        ]);
+        for coord in coords {
+            self.sdo_ordinates.extend([coord.x, coord.y]);
+        }
+    }
+
+    /// `SDO_GEOMETRY` constructor that creates this geometry in SQL
+    pub fn to_sql(&self) -> String {
+        let srid = self.sdo_srid.map_or("NULL".into(), |srid| srid.to_string());
+        let point = match self.sdo_point {
+            Some(SdoPoint { x, y, z }) => format!(
+                "SDO_POINT_TYPE({}, {}, {})",
+                sql_number(x),
+                sql_number(y),
+                sql_number(z)
+            ),
+            None => "NULL".into(),
+        };
+
+        format!(
+            "SDO_GEOMETRY({}, {srid}, {point}, {}, {})",
+            self.sdo_gtype,
+            sql_array(
+                "SDO_ELEM_INFO_ARRAY",
+                &self
+                    .sdo_elem_info
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+            ),
+            sql_array(
+                "SDO_ORDINATE_ARRAY",
+                &self
+                    .sdo_ordinates
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+            ),
+        )
+    }
+
+    /// Checks the encoding of the geometry and then the decoded shape itself,
+    /// similar to `SDO_GEOM.VALIDATE_GEOMETRY_WITH_CONTEXT`
+    pub fn validation_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        let dimensions = self.sdo_gtype as i32 / 1000;
+
+        if !(2..=4).contains(&dimensions) {
+            errors.push(format!(
+                "SDO_GTYPE {} has invalid dimensions",
+                self.sdo_gtype
+            ));
+        }
+
+        if !(1..=7).contains(&self.geometry_type()) {
+            errors.push(format!("SDO_GTYPE {} has unknown type", self.sdo_gtype));
+        }
+
+        if !self.sdo_elem_info.len().is_multiple_of(3) {
+            errors.push(String::from("SDO_ELEM_INFO length is not a multiple of 3"));
+        }
+
+        if !self.sdo_ordinates.len().is_multiple_of(self.dimensions()) {
+            errors.push(format!(
+                "SDO_ORDINATES length is not a multiple of {} dimensions",
+                self.dimensions()
+            ));
+        }
+
+        let mut previous_offset = 0;
+        for element in self.elements() {
+            if element.offset <= previous_offset
+                || element.offset > self.sdo_ordinates.len()
+                || (element.offset - 1) % self.dimensions() != 0
+            {
+                errors.push(format!("Element offset {} is invalid", element.offset));
+            }
+            previous_offset = element.offset;
+
+            if !matches!(element.etype, 0..=4 | 1003 | 2003 | 1005 | 2005) {
+                errors.push(format!("Element type {} is unknown", element.etype));
+            }
+        }
+
+        if self.sdo_elem_info.is_empty()
+            && !matches!(
+                self.sdo_point,
+                Some(SdoPoint {
+                    x: Some(_),
+                    y: Some(_),
+                    ..
+                })
+            )
+        {
+            errors.push(String::from("Geometry has neither elements nor SDO_POINT"));
+        }
+
+        if errors.is_empty() {
+            match self.to_geo() {
+                Some(geometry) => {
+                    errors.extend(geometry.validation_errors().iter().map(|e| e.to_string()))
+                }
+                None => errors.push(String::from("Geometry has no drawable elements")),
+            }
+        }
+
+        errors
+    }
+
+    /// Number of dimensions encoded in the first digit of `SDO_GTYPE`
+    pub fn dimensions(&self) -> usize {
+        ((self.sdo_gtype as i32 / 1000) as usize).max(2)
+    }
+
+    /// Geometry type encoded in the last two digits of `SDO_GTYPE`
+    pub fn geometry_type(&self) -> i32 {
+        self.sdo_gtype as i32 % 100
+    }
+
+    pub fn elements(&self) -> Vec<SdoElement> {
+        self.sdo_elem_info
+            .chunks_exact(3)
+            .map(|c| SdoElement {
+                offset: c[0] as usize,
+                etype: c[1] as i32,
+                interpretation: c[2] as i32,
+            })
+            .collect()
+    }
+
     fn create_coordinates(&self) -> Vec<[f64; 2]> {
         self.sdo_ordinates
-            .chunks(2)
-            .map(|c| c.to_owned())
-            .map(|c| collect_array(c.into_iter()))
+            .chunks(self.dimensions())
+            .map(|c| collect_array(c.iter().copied()))
             .collect::<Vec<_>>()
     }
 
+    pub fn is_point(&self) -> bool {
+        matches!(self.geometry_type(), 1 | 5)
+    }
+
     pub fn is_polygon(&self) -> bool {
-        self.sdo_gtype as i32 == 2003 && self.sdo_elem_info == vec![1., 3., 1.]
+        matches!(self.geometry_type(), 3 | 7)
     }
 
-    pub fn create_polygon(&self, stroke: Stroke) -> Option<Polygon> {
-        if !self.is_polygon() {
-            return None;
+    pub fn is_circle(&self) -> bool {
+        self.is_polygon()
+            && self
+                .elements()
+                .iter()
+                .any(|e| matches!(e.etype, 3 | 1003 | 2003) && e.interpretation == 4)
+    }
+
+    pub fn is_line(&self) -> bool {
+        matches!(self.geometry_type(), 2 | 6)
+    }
+
+    /// Decodes the element info array into a `geo` geometry.
+    ///
+    /// Arcs and circles are densified into straight segments,
+    /// so the result can be drawn by anything that understands plain coordinates.
+    pub fn to_geo(&self) -> Option<Geometry> {
+        let elements = self.elements();
+
+        if elements.is_empty() {
+            return match self.sdo_point {
+                Some(SdoPoint {
+                    x: Some(x),
+                    y: Some(y),
+                    ..
+                }) => Some(Geometry::Point(Point::new(x, y))),
+                _ => None,
+            };
+        }
+
+        let coordinates = self.create_coordinates();
+        let mut points: Vec<Point> = vec![];
+        let mut lines: Vec<LineString> = vec![];
+        let mut polygons: Vec<Polygon> = vec![];
+
+        for part in self.parts(&coordinates) {
+            match part {
+                Part::Points(coords) => {
+                    points.extend(coords.iter().map(|c| Point::new(c[0], c[1])))
+                }
+                Part::Path { etype, subelements } => {
+                    let line = match (etype, subelements.as_slice()) {
+                        (3 | 1003 | 2003, [(coords, interpretation)]) => {
+                            ring(coords, *interpretation)
+                        }
+                        _ => LineString::new(joined(&subelements)),
+                    };
+                    match etype {
+                        2 | 4 => lines.push(line),
+                        _ => push_ring(&mut polygons, etype, line),
+                    }
+                }
+            }
         }
 
+        let mut parts: Vec<Geometry> = vec![];
+        let kinds = [!points.is_empty(), !lines.is_empty(), !polygons.is_empty()]
+            .iter()
+            .filter(|k| **k)
+            .count();
+
+        if kinds > 1 {
+            parts.extend(points.into_iter().map(Geometry::Point));
+            parts.extend(lines.into_iter().map(Geometry::LineString));
+            parts.extend(polygons.into_iter().map(Geometry::Polygon));
+            return Some(Geometry::GeometryCollection(GeometryCollection::new_from(
+                parts,
+            )));
+        }
+
+        if points.len() == 1 {
+            return points.pop().map(Geometry::Point);
+        }
+        if points.len() > 1 {
+            return Some(Geometry::MultiPoint(MultiPoint::new(points)));
+        }
+        if lines.len() == 1 {
+            return lines.pop().map(Geometry::LineString);
+        }
+        if lines.len() > 1 {
+            return Some(Geometry::MultiLineString(MultiLineString::new(lines)));
+        }
+        if polygons.len() == 1 {
+            return polygons.pop().map(Geometry::Polygon);
+        }
+        if polygons.len() > 1 {
+            return Some(Geometry::MultiPolygon(MultiPolygon::new(polygons)));
+        }
+
+        None
+    }
+
+    /// Exact planar length of the lines, perimeter of all rings and area of the polygons
+    /// with holes subtracted, arcs and circles are measured without densifying them
+    pub fn planar_measures(&self) -> PlanarMeasures {
         let coordinates = self.create_coordinates();
+        let mut measures = PlanarMeasures::default();
 
-        let polygon = Polygon::new(coordinates)
-            // egui plot cannot handle concave plots, therefore turning the filling off
-            .fill_color(Color32::TRANSPARENT)
-            .stroke(stroke);
+        for part in self.parts(&coordinates) {
+            let Part::Path { etype, subelements } = part else {
+                continue;
+            };
+            let (length, area) = match (etype, subelements.as_slice()) {
+                (3 | 1003 | 2003, [(coords, interpretation)]) => {
+                    ring_measures(coords, *interpretation)
+                }
+                _ => subelements
+                    .iter()
+                    .map(|(coords, interpretation)| path_measures(coords, *interpretation))
+                    .fold((0., 0.), |a, b| (a.0 + b.0, a.1 + b.1)),
+            };
+            match etype {
+                2 | 4 => measures.length += length,
+                2003 | 2005 => {
+                    measures.perimeter += length;
+                    measures.area -= area.abs();
+                }
+                _ => {
+                    measures.perimeter += length;
+                    measures.area += area.abs();
+                }
+            }
+        }
 
-        Some(polygon)
+        measures
     }
 
-    pub fn is_circle(&self) -> bool {
-        self.sdo_gtype as i32 == 2003 && self.sdo_elem_info == vec![1., 1003., 4.]
+    /// DE-9IM matrix of the relationship with a geometry in the same SRID,
+    /// `None` when either geometry has nothing to compare
+    pub fn relate(&self, other: &SdoGeometry) -> Option<IntersectionMatrix> {
+        Some(self.to_geo()?.relate(&other.to_geo()?))
+    }
+
+    pub fn intersects(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_intersects())
     }
 
-    pub fn create_circle(&self, stroke: Stroke) -> Option<Line> {
-        if !self.is_circle() {
-            return None;
+    pub fn contains(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_contains())
+    }
+
+    pub fn within(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_within())
+    }
+
+    pub fn touches(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_touches())
+    }
+
+    pub fn overlaps(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_overlaps())
+    }
+
+    /// Geometries without any shape are disjoint from everything
+    pub fn disjoint(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_none_or(|m| m.is_disjoint())
+    }
+
+    /// Topological equality, the same point set regardless of vertex order or encoding
+    pub fn equals(&self, other: &SdoGeometry) -> bool {
+        self.relate(other).is_some_and(|m| m.is_equal_topo())
+    }
+
+    /// Splits the coordinates by element, compound elements keep one path per subelement
+    fn parts<'a>(&self, coordinates: &'a [[f64; 2]]) -> Vec<Part<'a>> {
+        let elements = self.elements();
+        let dimensions = self.dimensions();
+        // Offsets in SDO_ELEM_INFO point to ordinates and start from 1
+        let start_of = |e: &SdoElement| e.offset.saturating_sub(1) / dimensions;
+        let end_of = |i: usize| {
+            elements
+                .get(i + 1)
+                .map(start_of)
+                .unwrap_or(coordinates.len())
+                .min(coordinates.len())
+        };
+
+        let mut parts = vec![];
+        let mut i = 0;
+        while i < elements.len() {
+            let element = elements[i];
+            let start = start_of(&element).min(coordinates.len());
+
+            match element.etype {
+                1 => {
+                    let coords = &coordinates[start..end_of(i)];
+                    let count = (element.interpretation.max(0) as usize).min(coords.len());
+                    parts.push(Part::Points(&coords[..count]));
+                }
+                2 | 3 | 1003 | 2003 => parts.push(Part::Path {
+                    etype: element.etype,
+                    subelements: vec![(&coordinates[start..end_of(i)], element.interpretation)],
+                }),
+                4 | 1005 | 2005 => {
+                    let count = element.interpretation.max(0) as usize;
+                    let last = (i + count).min(elements.len() - 1);
+                    let subelements = elements
+                        .iter()
+                        .enumerate()
+                        .take(last + 1)
+                        .skip(i + 1)
+                        .map(|(sub, sub_element)| {
+                            let sub_start = start_of(sub_element).min(coordinates.len());
+                            // Subelements share their last vertex with the start of the next one
+                            let sub_end = if sub < last {
+                                (end_of(sub) + 1).min(coordinates.len())
+                            } else {
+                                end_of(sub)
+                            };
+                            (&coordinates[sub_start..sub_end], sub_element.interpretation)
+                        })
+                        .collect();
+                    parts.push(Part::Path {
+                        etype: element.etype,
+                        subelements,
+                    });
+
+                    i += count;
+                }
+                _ => {}
+            }
+
+            i += 1;
         }
 
-        let radius = (self.sdo_ordinates[5] - self.sdo_ordinates[1]) / 2.;
-        let center = Pos2::new(
-            (self.sdo_ordinates[1] + radius) as f32,
-            (self.sdo_ordinates[2] - radius) as f32,
-        );
-        let n = 512;
-        let circle_points: PlotPoints = (0..=n)
-            .map(|i| {
-                let t = remap(i as f64, 0.0..=(n as f64), 0.0..=TAU);
-                let r = radius;
-                [r * t.cos() + center.y as f64, r * t.sin() + center.x as f64]
-            })
-            .collect();
-        Some(Line::new(circle_points).stroke(stroke))
-        // .name("circle")
+        parts
     }
+}
 
-    pub fn is_line(&self) -> bool {
-        self.sdo_gtype as i32 == 2002 && self.sdo_elem_info == vec![1., 2., 1.]
+/// Sums of the exact planar measures of a geometry, see `SdoGeometry::planar_measures`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlanarMeasures {
+    pub length: f64,
+    pub perimeter: f64,
+    pub area: f64,
+}
+
+/// Coordinates of one element of the element info array
+enum Part<'a> {
+    Points(&'a [[f64; 2]]),
+    /// Line or ring with the coordinates and interpretation of each subelement
+    Path {
+        etype: i32,
+        subelements: Vec<(&'a [[f64; 2]], i32)>,
+    },
+}
+
+/// Interior rings are attached to the last exterior ring that was found
+fn push_ring(polygons: &mut Vec<Polygon>, etype: i32, ring: LineString) {
+    match (etype, polygons.last_mut()) {
+        (2003 | 2005, Some(polygon)) => polygon.interiors_push(ring),
+        _ => polygons.push(Polygon::new(ring, vec![])),
     }
+}
 
-    pub fn create_line(&self, stroke: Stroke) -> Option<Line> {
-        if !self.is_line() {
-            return None;
+fn path(coordinates: &[[f64; 2]], interpretation: i32) -> Vec<Coord> {
+    match interpretation {
+        2 => {
+            let mut coords: Vec<Coord> = vec![];
+            for arc_points in coordinates.windows(3).step_by(2) {
+                let arc = arc(arc_points[0], arc_points[1], arc_points[2]);
+                if !coords.is_empty() {
+                    coords.pop();
+                }
+                coords.extend(arc);
+            }
+            coords
         }
+        _ => coordinates
+            .iter()
+            .map(|c| Coord { x: c[0], y: c[1] })
+            .collect(),
+    }
+}
 
-        let coordinates = self.create_coordinates();
-        Some(Line::new(coordinates).stroke(stroke))
+/// Paths of the subelements of a compound element without their shared vertices
+fn joined(subelements: &[(&[[f64; 2]], i32)]) -> Vec<Coord> {
+    let mut coords: Vec<Coord> = vec![];
+    for (coordinates, interpretation) in subelements {
+        let sub_path = path(coordinates, *interpretation);
+        if coords.last().is_some() && coords.last() == sub_path.first() {
+            coords.pop();
+        }
+        coords.extend(sub_path);
+    }
+    coords
+}
+
+fn ring(coordinates: &[[f64; 2]], interpretation: i32) -> LineString {
+    match interpretation {
+        // Optimized rectangle is stored as lower-left and upper-right corners
+        3 if coordinates.len() >= 2 => {
+            let [min, max] = [coordinates[0], coordinates[1]];
+            LineString::from(vec![
+                (min[0], min[1]),
+                (max[0], min[1]),
+                (max[0], max[1]),
+                (min[0], max[1]),
+                (min[0], min[1]),
+            ])
+        }
+        // Circle is described by three distinct points on its circumference
+        4 if coordinates.len() >= 3 => {
+            match circumcircle(coordinates[0], coordinates[1], coordinates[2]) {
+                Some((center, radius)) => LineString::new(
+                    (0..=CIRCLE_SEGMENTS)
+                        .map(|i| {
+                            let t = TAU * i as f64 / CIRCLE_SEGMENTS as f64;
+                            Coord {
+                                x: center[0] + radius * t.cos(),
+                                y: center[1] + radius * t.sin(),
+                            }
+                        })
+                        .collect(),
+                ),
+                None => LineString::new(path(coordinates, 1)),
+            }
+        }
+        _ => LineString::new(path(coordinates, interpretation)),
+    }
+}
+
+/// Length and signed shoelace area of a path, counterclockwise paths are positive
+fn path_measures(coordinates: &[[f64; 2]], interpretation: i32) -> (f64, f64) {
+    let straight = |a: [f64; 2], b: [f64; 2]| {
+        (
+            (b[0] - a[0]).hypot(b[1] - a[1]),
+            (a[0] * b[1] - b[0] * a[1]) / 2.,
+        )
+    };
+    let pieces: Vec<(f64, f64)> = match interpretation {
+        2 => coordinates
+            .windows(3)
+            .step_by(2)
+            .map(|w| match arc_sweep(w[0], w[1], w[2]) {
+                // The segment between the chord and the arc is added to the chord's area
+                Some((_, radius, _, sweep)) => {
+                    let (_, chord) = straight(w[0], w[2]);
+                    (
+                        radius * sweep.abs(),
+                        chord + radius * radius / 2. * (sweep - sweep.sin()),
+                    )
+                }
+                None => straight(w[0], w[2]),
+            })
+            .collect(),
+        _ => coordinates
+            .windows(2)
+            .map(|w| straight(w[0], w[1]))
+            .collect(),
+    };
+    pieces
+        .into_iter()
+        .fold((0., 0.), |a, b| (a.0 + b.0, a.1 + b.1))
+}
+
+/// Length and signed area of a ring, see `ring` for the interpretations
+fn ring_measures(coordinates: &[[f64; 2]], interpretation: i32) -> (f64, f64) {
+    match interpretation {
+        3 if coordinates.len() >= 2 => {
+            let [min, max] = [coordinates[0], coordinates[1]];
+            let (width, height) = ((max[0] - min[0]).abs(), (max[1] - min[1]).abs());
+            (2. * (width + height), width * height)
+        }
+        4 if coordinates.len() >= 3 => {
+            match circumcircle(coordinates[0], coordinates[1], coordinates[2]) {
+                Some((_, radius)) => (TAU * radius, PI * radius * radius),
+                None => path_measures(coordinates, 1),
+            }
+        }
+        _ => path_measures(coordinates, interpretation),
+    }
+}
+
+/// Varray constructor, longer arrays are put together from chunks in their order
+/// because Oracle rejects calls with more than `MAX_SQL_ARGUMENTS` (ORA-00939)
+fn sql_array(name: &str, values: &[String]) -> String {
+    if values.is_empty() {
+        return "NULL".into();
+    }
+    if values.len() <= MAX_SQL_ARGUMENTS {
+        return format!("{name}({})", values.join(", "));
     }
+
+    let chunks = values
+        .chunks(MAX_SQL_ARGUMENTS)
+        .enumerate()
+        .map(|(part, chunk)| {
+            format!(
+                "SELECT {part} part, ROWNUM n, COLUMN_VALUE v FROM TABLE({name}({}))",
+                chunk.join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    format!("CAST(MULTISET(SELECT v FROM ({chunks}) ORDER BY part, n) AS {name})")
+}
+
+fn sql_number(value: Option<f64>) -> String {
+    value.map_or("NULL".into(), |v| v.to_string())
+}
+
+/// Center and radius of the circle through three points, `None` when they are collinear
+pub fn circumcircle(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> Option<([f64; 2], f64)> {
+    let d = 2. * (a[0] * (b[1] - c[1]) + b[0] * (c[1] - a[1]) + c[0] * (a[1] - b[1]));
+    if d.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let a2 = a[0] * a[0] + a[1] * a[1];
+    let b2 = b[0] * b[0] + b[1] * b[1];
+    let c2 = c[0] * c[0] + c[1] * c[1];
+    let center = [
+        (a2 * (b[1] - c[1]) + b2 * (c[1] - a[1]) + c2 * (a[1] - b[1])) / d,
+        (a2 * (c[0] - b[0]) + b2 * (a[0] - c[0]) + c2 * (b[0] - a[0])) / d,
+    ];
+    let radius = ((a[0] - center[0]).powi(2) + (a[1] - center[1]).powi(2)).sqrt();
+
+    Some((center, radius))
+}
+
+/// Center, radius, start angle and signed sweep of the arc going from `a` through `b` to `c`,
+/// `None` when the points are collinear
+fn arc_sweep(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> Option<([f64; 2], f64, f64, f64)> {
+    let (center, radius) = circumcircle(a, b, c)?;
+
+    let angle = |p: [f64; 2]| (p[1] - center[1]).atan2(p[0] - center[0]);
+    let ccw = |from: f64, to: f64| (to - from).rem_euclid(TAU);
+
+    let (start, middle, end) = (angle(a), angle(b), angle(c));
+    // The arc goes counterclockwise when the middle point is met before the end point
+    let sweep = if ccw(start, middle) <= ccw(start, end) {
+        ccw(start, end)
+    } else {
+        -ccw(end, start)
+    };
+
+    Some((center, radius, start, sweep))
+}
+
+/// Densifies a circular arc going from `a` through `b` to `c`
+fn arc(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> Vec<Coord> {
+    let Some((center, radius, start, sweep)) = arc_sweep(a, b, c) else {
+        return path(&[a, c], 1);
+    };
+
+    let segments = ((CIRCLE_SEGMENTS as f64 * sweep.abs() / TAU).ceil() as usize).max(2);
+    (0..=segments)
+        .map(|i| {
+            let t = start + sweep * i as f64 / segments as f64;
+            Coord {
+                x: center[0] + radius * t.cos(),
+                y: center[1] + radius * t.sin(),
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f64::consts::PI;
 
-    use crate::sdo_geometry::SdoGeometry;
+    use geo::{Geometry, LineString};
+
+    use crate::sdo_geometry::{SdoGeometry, SdoPoint};
 
     #[test]
     fn test_is_polygon() {
@@ -153,4 +800,251 @@ mod tests {
 
         assert!(sdo_object.is_circle());
     }
+
+    #[test]
+    fn test_polygon_with_hole_to_geo() {
+        let sdo_object = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 1., 11., 2003., 3.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10., 10., 0., 10., 0., 0., 2., 2., 4., 4.],
+        };
+
+        let Some(Geometry::Polygon(polygon)) = sdo_object.to_geo() else {
+            panic!("Expected a polygon");
+        };
+
+        assert_eq!(polygon.exterior().0.len(), 5);
+        assert_eq!(
+            polygon.interiors(),
+            &[LineString::from(vec![
+                (2., 2.),
+                (4., 2.),
+                (4., 4.),
+                (2., 4.),
+                (2., 2.)
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_circle_to_geo() {
+        let sdo_object = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 4.],
+            sdo_ordinates: vec![8., 7., 10., 9., 8., 11.],
+        };
+
+        let Some(Geometry::Polygon(polygon)) = sdo_object.to_geo() else {
+            panic!("Expected a polygon");
+        };
+
+        for coord in polygon.exterior().coords() {
+            let distance = ((coord.x - 8.).powi(2) + (coord.y - 9.).powi(2)).sqrt();
+            assert!((distance - 2.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_point_to_geo() {
+        let sdo_object = SdoGeometry {
+            sdo_gtype: 2001.,
+            sdo_srid: None,
+            sdo_point: Some(SdoPoint {
+                x: Some(12.),
+                y: Some(14.),
+                z: None,
+            }),
+            sdo_elem_info: vec![],
+            sdo_ordinates: vec![],
+        };
+
+        assert_eq!(sdo_object.to_geo(), Some(geo::Point::new(12., 14.).into()));
+    }
+
+    #[test]
+    fn test_from_geo_orients_polygon() {
+        // Clockwise exterior and counterclockwise hole
+        let polygon = geo::Polygon::new(
+            LineString::from(vec![(0., 0.), (0., 10.), (10., 10.), (10., 0.), (0., 0.)]),
+            vec![LineString::from(vec![
+                (2., 2.),
+                (4., 2.),
+                (4., 4.),
+                (2., 4.),
+                (2., 2.),
+            ])],
+        );
+
+        let sdo_object = SdoGeometry::from_geo(&polygon.into(), Some(8307.));
+
+        assert_eq!(sdo_object.sdo_gtype, 2003.);
+        assert_eq!(sdo_object.sdo_srid, Some(8307.));
+        assert_eq!(
+            sdo_object.sdo_elem_info,
+            vec![1., 1003., 1., 11., 2003., 1.]
+        );
+        assert_eq!(
+            &sdo_object.sdo_ordinates[..10],
+            &[0., 0., 10., 0., 10., 10., 0., 10., 0., 0.]
+        );
+        assert_eq!(
+            &sdo_object.sdo_ordinates[10..],
+            &[2., 2., 2., 4., 4., 4., 4., 2., 2., 2.]
+        );
+        assert!(sdo_object.validation_errors().is_empty());
+    }
+
+    #[test]
+    fn test_from_geo_point() {
+        let sdo_object = SdoGeometry::from_geo(&geo::Point::new(1., 2.).into(), None);
+
+        assert_eq!(sdo_object.sdo_gtype, 2001.);
+        assert!(sdo_object.sdo_elem_info.is_empty());
+        assert_eq!(sdo_object.to_geo(), Some(geo::Point::new(1., 2.).into()));
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let point = SdoGeometry::from_geo(&geo::Point::new(1.5, 2.).into(), Some(8307.));
+        assert_eq!(
+            point.to_sql(),
+            "SDO_GEOMETRY(2001, 8307, SDO_POINT_TYPE(1.5, 2, NULL), NULL, NULL)"
+        );
+
+        let rectangle = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3.],
+            sdo_ordinates: vec![0., 0., 10., 5.],
+        };
+        assert_eq!(
+            rectangle.to_sql(),
+            "SDO_GEOMETRY(2003, NULL, NULL, SDO_ELEM_INFO_ARRAY(1, 1003, 3), \
+SDO_ORDINATE_ARRAY(0, 0, 10, 5))"
+        );
+    }
+
+    #[test]
+    fn test_to_sql_splits_long_arrays() {
+        let line = geo::LineString::from((0..1000).map(|i| (i as f64, 0.)).collect::<Vec<_>>());
+        let sql = SdoGeometry::from_geo(&line.into(), None).to_sql();
+
+        assert_eq!(sql.matches("SDO_ORDINATE_ARRAY(").count(), 3);
+        assert!(sql.contains("CAST(MULTISET(SELECT v FROM (SELECT 0 part, ROWNUM n"));
+        assert!(sql.ends_with("ORDER BY part, n) AS SDO_ORDINATE_ARRAY))"));
+        // No constructor gets more than 999 arguments
+        for call in sql.split("SDO_ORDINATE_ARRAY(").skip(1) {
+            let arguments = &call[..call.find(')').unwrap()];
+            assert!(arguments.matches(", ").count() < 999);
+        }
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let sdo_object = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 1., 5.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10.],
+        };
+
+        let errors = sdo_object.validation_errors();
+
+        assert!(errors.contains(&String::from("SDO_ELEM_INFO length is not a multiple of 3")));
+        assert!(errors.contains(&String::from(
+            "SDO_ORDINATES length is not a multiple of 2 dimensions"
+        )));
+    }
+
+    #[test]
+    fn test_arc_line_to_geo() {
+        let sdo_object = SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 2.],
+            sdo_ordinates: vec![-1., 0., 0., 1., 1., 0.],
+        };
+
+        let Some(Geometry::LineString(line)) = sdo_object.to_geo() else {
+            panic!("Expected a line");
+        };
+
+        let first = line.0.first().unwrap();
+        let last = line.0.last().unwrap();
+        assert!((first.x + 1.).abs() < 1e-9 && first.y.abs() < 1e-9);
+        assert!((last.x - 1.).abs() < 1e-9 && last.y.abs() < 1e-9);
+        // The arc must go over the top through (0, 1)
+        assert!(line.0.iter().all(|c| c.y >= -1e-9));
+    }
+
+    #[test]
+    fn test_planar_measures_of_arcs_and_circles() {
+        let arc_line = SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 2.],
+            sdo_ordinates: vec![-1., 0., 0., 1., 1., 0.],
+        };
+        assert!((arc_line.planar_measures().length - PI).abs() < 1e-12);
+
+        let circle = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 4.],
+            sdo_ordinates: vec![8., 7., 10., 9., 8., 11.],
+        };
+        let measures = circle.planar_measures();
+        assert!((measures.area - 4. * PI).abs() < 1e-12);
+        assert!((measures.perimeter - 4. * PI).abs() < 1e-12);
+
+        // Half disc closed by its diameter, as a compound ring of an arc and a line
+        let half_disc = SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1005., 2., 1., 2., 2., 5., 2., 1.],
+            sdo_ordinates: vec![1., 0., 0., 1., -1., 0., 1., 0.],
+        };
+        let measures = half_disc.planar_measures();
+        assert!((measures.area - PI / 2.).abs() < 1e-12);
+        assert!((measures.perimeter - (PI + 2.)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let rectangle = |min: [f64; 2], max: [f64; 2]| SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3.],
+            sdo_ordinates: vec![min[0], min[1], max[0], max[1]],
+        };
+        let parcel = rectangle([0., 0.], [10., 10.]);
+        let inside = rectangle([2., 2.], [4., 4.]);
+        let neighbour = rectangle([10., 0.], [20., 10.]);
+        let across = rectangle([8., 2.], [12., 4.]);
+        let far = rectangle([40., 40.], [42., 42.]);
+        // Same square as a plain polygon starting from another corner
+        let same = SdoGeometry {
+            sdo_elem_info: vec![1., 1003., 1.],
+            sdo_ordinates: vec![10., 10., 0., 10., 0., 0., 10., 0., 10., 10.],
+            ..parcel.clone()
+        };
+
+        assert!(parcel.intersects(&inside) && parcel.contains(&inside));
+        assert!(inside.within(&parcel) && !parcel.within(&inside));
+        assert!(parcel.touches(&neighbour) && !parcel.overlaps(&neighbour));
+        assert!(parcel.overlaps(&across) && !parcel.touches(&across));
+        assert!(parcel.disjoint(&far) && !parcel.intersects(&far));
+        assert!(parcel.equals(&same) && !parcel.equals(&inside));
+    }
 }
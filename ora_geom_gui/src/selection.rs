@@ -0,0 +1,198 @@
+use egui::{Color32, Stroke};
+use geo::{Distance, Euclidean, Point};
+
+use crate::{layers::Layers, query::Geometry, render::Extent, style::LayerStyle};
+
+/// Outline colour of selected geometries
+pub const HIGHLIGHT: Color32 = Color32::from_rgb(255, 200, 0);
+/// Distance in pixels within which a click hits a line or a point
+pub const HIT_TOLERANCE: f64 = 5.;
+
+/// A geometry by its layer name and row number
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GeometryRef {
+    pub layer: String,
+    pub index: usize,
+}
+
+impl GeometryRef {
+    pub fn new(layer: &str, index: usize) -> Self {
+        Self {
+            layer: layer.to_string(),
+            index,
+        }
+    }
+
+    pub fn resolve<'a>(&self, layers: &'a Layers) -> Option<&'a Geometry> {
+        layers.get(&self.layer)?.query.geometries.get(self.index)
+    }
+}
+
+/// Selected geometries in the order they were selected
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub items: Vec<GeometryRef>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn contains(&self, item: &GeometryRef) -> bool {
+        self.items.contains(item)
+    }
+
+    /// Replaces the selection with one geometry
+    pub fn select(&mut self, item: GeometryRef) {
+        self.items = vec![item];
+    }
+
+    /// Adds the geometry, or removes it when it is already selected
+    pub fn toggle(&mut self, item: GeometryRef) {
+        match self.items.iter().position(|i| *i == item) {
+            Some(n) => {
+                self.items.remove(n);
+            }
+            None => self.items.push(item),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Drops geometries of deleted layers and rows
+    pub fn retain_existing(&mut self, layers: &Layers) {
+        self.items.retain(|item| item.resolve(layers).is_some());
+    }
+
+    /// Selected geometries that still exist
+    pub fn geometries<'a>(
+        &'a self,
+        layers: &'a Layers,
+    ) -> impl Iterator<Item = (&'a GeometryRef, &'a Geometry)> {
+        self.items
+            .iter()
+            .filter_map(|item| Some((item, item.resolve(layers)?)))
+    }
+
+    pub fn extent(&self, layers: &Layers) -> Option<Extent> {
+        self.geometries(layers)
+            .filter_map(|(_, g)| g.extent())
+            .reduce(|a, b| a.union(&b))
+    }
+}
+
+/// Same style with a highlighted outline and larger markers
+pub fn highlighted(style: &LayerStyle) -> LayerStyle {
+    LayerStyle {
+        stroke: Stroke::new(style.stroke.width + 2., HIGHLIGHT),
+        fill_opacity: 0.,
+        marker_size: style.marker_size + 2.,
+        ..*style
+    }
+}
+
+/// Topmost drawn geometry within `tolerance` of the point, polygons are hit anywhere inside
+pub fn hit_test(layers: &Layers, point: [f64; 2], tolerance: f64) -> Option<GeometryRef> {
+    let point = Point::new(point[0], point[1]);
+    layers
+        .layers
+        .iter()
+        .filter(|layer| layers.is_drawn(layer))
+        .find_map(|layer| {
+            let query = &layer.query;
+            let index = query.geometries.iter().enumerate().rposition(|(n, g)| {
+                query.is_shown(n)
+                    && g.shape()
+                        .is_some_and(|shape| Euclidean.distance(&point, shape) <= tolerance)
+            })?;
+            Some(GeometryRef::new(&layer.name, index))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        feature::Feature,
+        layers::Layers,
+        query::Query,
+        render::Extent,
+        sdo_geometry::SdoGeometry,
+        selection::{hit_test, GeometryRef, Selection},
+        style::LayerStyle,
+    };
+
+    fn square(x: f64) -> Feature {
+        Feature::from(SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3.],
+            sdo_ordinates: vec![x, 0., x + 2., 2.],
+        })
+    }
+
+    fn layers() -> Layers {
+        let mut layers = Layers::default();
+        for name in ["bottom", "top"] {
+            let query = Query::new(
+                name,
+                String::new(),
+                vec![],
+                LayerStyle::default(),
+                vec![square(0.), square(1.)],
+            );
+            layers.push(name.into(), query);
+        }
+        layers
+    }
+
+    #[test]
+    fn test_hit_test_picks_topmost_geometry() {
+        let mut layers = layers();
+
+        assert_eq!(
+            hit_test(&layers, [1.5, 1.], 0.1),
+            Some(GeometryRef::new("top", 1))
+        );
+        assert_eq!(
+            hit_test(&layers, [0.5, 1.], 0.1),
+            Some(GeometryRef::new("top", 0))
+        );
+        assert_eq!(hit_test(&layers, [3.5, 1.], 0.1), None);
+        assert_eq!(
+            hit_test(&layers, [3.5, 1.], 1.),
+            Some(GeometryRef::new("top", 1))
+        );
+
+        layers.get_mut("top").unwrap().is_visible = false;
+        assert_eq!(
+            hit_test(&layers, [1.5, 1.], 0.1),
+            Some(GeometryRef::new("bottom", 1))
+        );
+    }
+
+    #[test]
+    fn test_selection_extent() {
+        let mut layers = layers();
+        let mut selection = Selection::default();
+        selection.select(GeometryRef::new("top", 0));
+        selection.toggle(GeometryRef::new("bottom", 1));
+
+        assert_eq!(
+            selection.extent(&layers),
+            Some(Extent::new([0., 0.], [3., 2.]))
+        );
+        assert_eq!(
+            layers.get("top").unwrap().query.extent(),
+            Some(Extent::new([0., 0.], [3., 2.]))
+        );
+
+        selection.toggle(GeometryRef::new("top", 0));
+        layers.remove("bottom");
+        selection.retain_existing(&layers);
+        assert!(selection.is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+use geo::CoordsIter;
+
+use crate::{layers::Layers, render::Extent};
+
+/// Distance in pixels within which the pointer snaps to a vertex
+pub const SNAP_TOLERANCE: f64 = 8.;
+
+/// Vertex of the drawn geometries or of `extra` that is closest to the point within `tolerance`
+pub fn snap_to_vertex(
+    layers: &Layers,
+    extra: &[[f64; 2]],
+    point: [f64; 2],
+    tolerance: f64,
+) -> Option<[f64; 2]> {
+    let near = |extent: &Extent| {
+        (0..2)
+            .all(|n| extent.min[n] - tolerance <= point[n] && point[n] <= extent.max[n] + tolerance)
+    };
+
+    let drawn = layers
+        .drawn()
+        .flat_map(|(_, query)| query.active_geometries())
+        .filter(|g| g.extent().is_some_and(|e| near(&e)))
+        .filter_map(|g| g.shape())
+        .flat_map(|shape| shape.coords_iter().map(|c| [c.x, c.y]));
+
+    let distance = |v: &[f64; 2]| (v[0] - point[0]).hypot(v[1] - point[1]);
+    extra
+        .iter()
+        .copied()
+        .chain(drawn)
+        .filter(|v| distance(v) <= tolerance)
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        feature::Feature, layers::Layers, query::Query, sdo_geometry::SdoGeometry,
+        snap::snap_to_vertex, style::LayerStyle,
+    };
+
+    #[test]
+    fn test_snap_to_nearest_vertex() {
+        let mut layers = Layers::default();
+        let line = Feature::from(SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 1.],
+            sdo_ordinates: vec![0., 0., 10., 0., 10., 10.],
+        });
+        let query = Query::new(
+            "lines",
+            String::new(),
+            vec![],
+            LayerStyle::default(),
+            vec![line],
+        );
+        layers.push("lines".into(), query);
+
+        assert_eq!(
+            snap_to_vertex(&layers, &[], [9.5, 0.2], 1.),
+            Some([10., 0.])
+        );
+        assert_eq!(snap_to_vertex(&layers, &[], [5., 0.], 1.), None);
+        assert_eq!(
+            snap_to_vertex(&layers, &[[5., 0.5]], [5., 0.], 1.),
+            Some([5., 0.5])
+        );
+
+        layers.get_mut("lines").unwrap().is_visible = false;
+        assert_eq!(snap_to_vertex(&layers, &[], [9.5, 0.2], 1.), None);
+    }
+}
@@ -0,0 +1,296 @@
+use egui::{Color32, ComboBox, DragValue, Grid, Slider, Stroke, Ui};
+use egui_plot::{LineStyle, MarkerShape};
+use serde::{Deserialize, Serialize};
+
+/// Default layer colours, distinguishable on both light and dark backgrounds
+pub const PALETTE: [Color32; 12] = [
+    Color32::from_rgb(78, 121, 167),
+    Color32::from_rgb(242, 142, 43),
+    Color32::from_rgb(225, 87, 89),
+    Color32::from_rgb(118, 183, 178),
+    Color32::from_rgb(89, 161, 79),
+    Color32::from_rgb(237, 201, 72),
+    Color32::from_rgb(176, 122, 161),
+    Color32::from_rgb(255, 157, 167),
+    Color32::from_rgb(156, 117, 95),
+    Color32::from_rgb(186, 176, 172),
+    Color32::from_rgb(23, 190, 207),
+    Color32::from_rgb(188, 189, 34),
+];
+
+/// Picks the palette colour used by the fewest layers, earlier colours win ties
+pub fn next_color(used: impl IntoIterator<Item = Color32>) -> Color32 {
+    let mut counts = [0usize; PALETTE.len()];
+    for color in used {
+        if let Some(n) = PALETTE.iter().position(|c| *c == color) {
+            counts[n] += 1;
+        }
+    }
+
+    let min = counts.iter().min().copied().unwrap_or_default();
+    let n = counts.iter().position(|c| *c == min).unwrap_or_default();
+    PALETTE[n]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dash {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl Dash {
+    pub const ALL: [Dash; 3] = [Dash::Solid, Dash::Dashed, Dash::Dotted];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Dash::Solid => "Solid",
+            Dash::Dashed => "Dashed",
+            Dash::Dotted => "Dotted",
+        }
+    }
+
+    /// Lengths of dashes and gaps in pixels, `None` for a solid line
+    pub fn pattern(&self, width: f32) -> Option<[f32; 2]> {
+        let width = width.max(1.);
+        match self {
+            Dash::Solid => None,
+            Dash::Dashed => Some([6. * width, 4. * width]),
+            Dash::Dotted => Some([width, 2. * width]),
+        }
+    }
+
+    pub fn line_style(&self, width: f32) -> LineStyle {
+        let width = width.max(1.);
+        match self {
+            Dash::Solid => LineStyle::Solid,
+            Dash::Dashed => LineStyle::Dashed { length: 6. * width },
+            Dash::Dotted => LineStyle::Dotted {
+                spacing: 3. * width,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Marker {
+    #[default]
+    Circle,
+    Square,
+    Diamond,
+    Triangle,
+    Cross,
+    Plus,
+}
+
+impl Marker {
+    pub const ALL: [Marker; 6] = [
+        Marker::Circle,
+        Marker::Square,
+        Marker::Diamond,
+        Marker::Triangle,
+        Marker::Cross,
+        Marker::Plus,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Marker::Circle => "Circle",
+            Marker::Square => "Square",
+            Marker::Diamond => "Diamond",
+            Marker::Triangle => "Triangle",
+            Marker::Cross => "Cross",
+            Marker::Plus => "Plus",
+        }
+    }
+
+    pub fn shape(&self) -> MarkerShape {
+        match self {
+            Marker::Circle => MarkerShape::Circle,
+            Marker::Square => MarkerShape::Square,
+            Marker::Diamond => MarkerShape::Diamond,
+            Marker::Triangle => MarkerShape::Up,
+            Marker::Cross => MarkerShape::Cross,
+            Marker::Plus => MarkerShape::Plus,
+        }
+    }
+
+    /// Outline in pixels with the y axis pointing down, `None` for circles and strokes
+    pub fn outline(&self, center: [f64; 2], size: f32) -> Option<Vec<[f64; 2]>> {
+        let [x, y] = center;
+        let r = size as f64;
+        match self {
+            Marker::Square => Some(vec![
+                [x - r, y - r],
+                [x + r, y - r],
+                [x + r, y + r],
+                [x - r, y + r],
+            ]),
+            Marker::Diamond => Some(vec![[x, y - r], [x + r, y], [x, y + r], [x - r, y]]),
+            Marker::Triangle => {
+                let half = r * 3f64.sqrt() / 2.;
+                Some(vec![
+                    [x, y - r],
+                    [x + half, y + r / 2.],
+                    [x - half, y + r / 2.],
+                ])
+            }
+            Marker::Circle | Marker::Cross | Marker::Plus => None,
+        }
+    }
+
+    /// Segments in pixels for markers drawn as strokes
+    pub fn segments(&self, center: [f64; 2], size: f32) -> Vec<[[f64; 2]; 2]> {
+        let [x, y] = center;
+        let r = size as f64;
+        match self {
+            Marker::Cross => {
+                let d = r / 2f64.sqrt();
+                vec![
+                    [[x - d, y - d], [x + d, y + d]],
+                    [[x - d, y + d], [x + d, y - d]],
+                ]
+            }
+            Marker::Plus => vec![[[x - r, y], [x + r, y]], [[x, y - r], [x, y + r]]],
+            _ => vec![],
+        }
+    }
+}
+
+/// How a layer is drawn on the plot and in exports
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LayerStyle {
+    pub stroke: Stroke,
+    pub dash: Dash,
+    pub fill: Color32,
+    /// Opacity of the fill between 0 and 1, no fill by default
+    pub fill_opacity: f32,
+    pub marker: Marker,
+    /// Marker radius in pixels
+    pub marker_size: f32,
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        Self::with_color(PALETTE[0])
+    }
+}
+
+impl From<Stroke> for LayerStyle {
+    fn from(stroke: Stroke) -> Self {
+        Self {
+            stroke,
+            ..Self::with_color(stroke.color)
+        }
+    }
+}
+
+impl LayerStyle {
+    pub fn with_color(color: Color32) -> Self {
+        Self {
+            stroke: Stroke::new(1., color),
+            dash: Dash::Solid,
+            fill: color,
+            fill_opacity: 0.,
+            marker: Marker::Circle,
+            marker_size: 3.,
+        }
+    }
+
+    /// Same style with the stroke and fill in a theme colour
+    pub fn themed(&self, color: Color32) -> Self {
+        Self {
+            stroke: Stroke::new(self.stroke.width, color),
+            fill: color,
+            ..*self
+        }
+    }
+
+    pub fn fill_color(&self) -> Color32 {
+        let [r, g, b, _] = self.fill.to_srgba_unmultiplied();
+        Color32::from_rgba_unmultiplied(r, g, b, (self.fill_opacity.clamp(0., 1.) * 255.) as u8)
+    }
+}
+
+/// Editor for a layer style, similar to `egui::stroke_ui`
+pub fn style_ui(ui: &mut Ui, style: &mut LayerStyle, id: &str) {
+    Grid::new(("layer_style", id))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Stroke:");
+            ui.horizontal(|ui| {
+                ui.add(
+                    DragValue::new(&mut style.stroke.width)
+                        .speed(0.1)
+                        .clamp_range(0.0..=10.0),
+                )
+                .on_hover_text("Width");
+                ui.color_edit_button_srgba(&mut style.stroke.color);
+            });
+            ui.end_row();
+
+            ui.label("Dash:");
+            ComboBox::from_id_source(("layer_dash", id))
+                .selected_text(style.dash.label())
+                .show_ui(ui, |ui| {
+                    for dash in Dash::ALL {
+                        ui.selectable_value(&mut style.dash, dash, dash.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Fill:");
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgba(&mut style.fill);
+                ui.add(Slider::new(&mut style.fill_opacity, 0.0..=1.0).text("opacity"));
+            });
+            ui.end_row();
+
+            ui.label("Marker:");
+            ui.horizontal(|ui| {
+                ComboBox::from_id_source(("layer_marker", id))
+                    .selected_text(style.marker.label())
+                    .show_ui(ui, |ui| {
+                        for marker in Marker::ALL {
+                            ui.selectable_value(&mut style.marker, marker, marker.label());
+                        }
+                    });
+                ui.add(
+                    DragValue::new(&mut style.marker_size)
+                        .speed(0.1)
+                        .clamp_range(1.0..=20.0),
+                )
+                .on_hover_text("Size");
+            });
+            ui.end_row();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Stroke};
+
+    use crate::style::{next_color, LayerStyle, PALETTE};
+
+    #[test]
+    fn test_next_color_avoids_used_colors() {
+        assert_eq!(next_color([]), PALETTE[0]);
+        // A deleted layer leaves a gap that is filled first
+        assert_eq!(next_color([PALETTE[0], PALETTE[2]]), PALETTE[1]);
+        assert_eq!(next_color(PALETTE), PALETTE[0]);
+        assert_eq!(next_color(PALETTE.iter().copied().skip(1)), PALETTE[0]);
+    }
+
+    #[test]
+    fn test_fill_color_opacity() {
+        let style = LayerStyle {
+            fill_opacity: 0.5,
+            ..LayerStyle::from(Stroke::new(2., Color32::RED))
+        };
+
+        assert_eq!(style.stroke.width, 2.);
+        assert_eq!(style.fill_color().to_srgba_unmultiplied(), [255, 0, 0, 127]);
+        assert_eq!(LayerStyle::default().fill_color().a(), 0);
+    }
+}
@@ -0,0 +1,460 @@
+use std::{fmt::Write, fs, io, path::Path};
+
+use egui::{Color32, Stroke};
+
+use crate::{
+    overlay::{self, AxisFormat},
+    query::Query,
+    render::{draw_layers, draw_marker, Canvas, Extent, ScreenTransform},
+    style::Dash,
+};
+
+const LEGEND_PADDING: f64 = 8.;
+const LEGEND_ROW_HEIGHT: f64 = 18.;
+const FONT: &str = r#"font-family="sans-serif" font-size="11""#;
+
+/// Writes layers into an SVG document using the same geometries and strokes as the plot
+pub struct SvgExporter {
+    pub extent: Extent,
+    pub width: u32,
+    pub height: u32,
+    pub include_axes: bool,
+    pub include_legend: bool,
+    pub axis_format: AxisFormat,
+    pub include_scale_bar: bool,
+    pub include_north_arrow: bool,
+    /// SRID of the coordinates, used for ground distances, north and `AxisFormat::Auto`
+    pub srid: Option<f32>,
+}
+
+impl SvgExporter {
+    pub fn new(extent: Extent) -> Self {
+        Self {
+            extent,
+            width: 1280,
+            height: 720,
+            include_axes: false,
+            include_legend: false,
+            axis_format: AxisFormat::Auto,
+            include_scale_bar: false,
+            include_north_arrow: false,
+            srid: None,
+        }
+    }
+
+    pub fn export<'a>(
+        &self,
+        layers: impl IntoIterator<Item = (&'a str, &'a Query)>,
+        path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        fs::write(path, self.render(layers))
+    }
+
+    pub fn render<'a>(&self, layers: impl IntoIterator<Item = (&'a str, &'a Query)>) -> String {
+        let layers = layers.into_iter().collect::<Vec<_>>();
+        let (width, height) = (self.width as f64, self.height as f64);
+        let transform = self.transform();
+        let [x, y] = transform.origin;
+        let [w, h] = transform.size;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            self.width, self.height, self.width, self.height
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect x="0" y="0" width="{width}" height="{height}" fill="white"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<clipPath id="map"><rect x="{x:.2}" y="{y:.2}" width="{w:.2}" height="{h:.2}"/></clipPath>"#
+        );
+        let _ = writeln!(svg, r#"<g clip-path="url(#map)">"#);
+        let mut canvas = SvgCanvas { svg };
+        draw_layers(&mut canvas, &transform, layers.iter().copied());
+        let _ = writeln!(canvas.svg, "</g>");
+        if self.include_scale_bar {
+            overlay::draw_scale_bar(&mut canvas, &transform, self.srid, Color32::BLACK);
+        }
+        if self.include_north_arrow {
+            overlay::draw_north_arrow(&mut canvas, &transform, self.srid, Color32::BLACK);
+        }
+        let mut svg = canvas.svg;
+
+        if self.include_axes {
+            self.write_axes(&mut svg, &transform);
+        }
+
+        if self.include_legend {
+            self.write_legend(&mut svg, &transform, &layers);
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn transform(&self) -> ScreenTransform {
+        overlay::map_transform(self.extent, self.width, self.height, self.include_axes)
+    }
+
+    fn write_axes(&self, svg: &mut String, transform: &ScreenTransform) {
+        let [x, y] = transform.origin;
+        let [w, h] = transform.size;
+        let format = self.axis_format.resolve(self.srid);
+
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{x:.2}" y="{y:.2}" width="{w:.2}" height="{h:.2}" fill="none" stroke="black" stroke-width="1"/>"#
+        );
+
+        let [x_ticks, y_ticks] = overlay::axis_ticks(transform, format);
+        for (tx, label) in x_ticks {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{tx:.2}" y1="{:.2}" x2="{tx:.2}" y2="{:.2}" stroke="black" stroke-width="1"/>"#,
+                y + h,
+                y + h + 4.
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{tx:.2}" y="{:.2}" text-anchor="middle" {FONT}>{label}</text>"#,
+                y + h + 16.
+            );
+        }
+
+        for (ty, label) in y_ticks {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{ty:.2}" x2="{x:.2}" y2="{ty:.2}" stroke="black" stroke-width="1"/>"#,
+                x - 4.
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" text-anchor="end" {FONT}>{label}</text>"#,
+                x - 6.,
+                ty + 4.
+            );
+        }
+    }
+
+    fn write_legend(
+        &self,
+        svg: &mut String,
+        transform: &ScreenTransform,
+        layers: &[(&str, &Query)],
+    ) {
+        if layers.is_empty() {
+            return;
+        }
+
+        let longest = layers
+            .iter()
+            .map(|(n, _)| n.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = 40. + longest as f64 * 7.;
+        let height = LEGEND_PADDING * 2. + layers.len() as f64 * LEGEND_ROW_HEIGHT;
+        let x = transform.origin[0] + transform.size[0] - width - LEGEND_PADDING;
+        let y = transform.origin[1] + LEGEND_PADDING;
+
+        let _ = writeln!(
+            svg,
+            r#"<g><rect x="{x:.2}" y="{y:.2}" width="{width:.2}" height="{height:.2}" fill="white" fill-opacity="0.8" stroke="black" stroke-width="0.5"/>"#
+        );
+        for (row, (name, query)) in layers.iter().enumerate() {
+            let cy = y + LEGEND_PADDING + (row as f64 + 0.5) * LEGEND_ROW_HEIGHT;
+            let sx = x + LEGEND_PADDING;
+            let style = &query.style;
+            let stroke = stroke_attributes(style.stroke, style.dash);
+            let geometries = || query.active_geometries().map(|g| &g.sdo_geometry);
+
+            if geometries().any(|g| g.is_polygon()) {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{sx:.2}" y="{:.2}" width="20" height="10" {} {stroke}/>"#,
+                    cy - 5.,
+                    fill_attributes(style.fill_color())
+                );
+            } else if geometries().any(|g| g.is_line()) {
+                let _ = writeln!(
+                    svg,
+                    r#"<line x1="{sx:.2}" y1="{cy:.2}" x2="{:.2}" y2="{cy:.2}" {stroke}/>"#,
+                    sx + 20.
+                );
+            } else {
+                let mut canvas = SvgCanvas::default();
+                draw_marker(&mut canvas, [sx + 10., cy], style);
+                svg.push_str(&canvas.svg);
+            }
+
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" {FONT}>{}</text>"#,
+                sx + 28.,
+                cy + 4.,
+                escape(name)
+            );
+        }
+        let _ = writeln!(svg, "</g>");
+    }
+}
+
+/// Collects SVG elements for the layers
+#[derive(Default)]
+struct SvgCanvas {
+    svg: String,
+}
+
+impl Canvas for SvgCanvas {
+    fn begin_layer(&mut self, name: &str) {
+        let _ = writeln!(self.svg, "<g><title>{}</title>", escape(name));
+    }
+
+    fn end_layer(&mut self) {
+        let _ = writeln!(self.svg, "</g>");
+    }
+
+    fn polygon(&mut self, rings: &[Vec<[f64; 2]>], fill: Color32, stroke: Stroke, dash: Dash) {
+        // Holes are written into the same path and cut out by the even-odd rule
+        let data = rings
+            .iter()
+            .map(|ring| path_data(ring, true))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.path(&data, fill, stroke, dash);
+    }
+
+    fn line(&mut self, points: &[[f64; 2]], stroke: Stroke, dash: Dash) {
+        self.path(
+            &path_data(points, false),
+            Color32::TRANSPARENT,
+            stroke,
+            dash,
+        );
+    }
+
+    fn point(&mut self, point: [f64; 2], radius: f32, color: Color32) {
+        let [x, y] = point;
+        let _ = writeln!(
+            self.svg,
+            r#"<circle cx="{x:.2}" cy="{y:.2}" r="{radius}" {}/>"#,
+            fill_attributes(color)
+        );
+    }
+
+    fn text(&mut self, center: [f64; 2], text: &str, size: f64, color: Color32) {
+        let [x, y] = center;
+        let _ = writeln!(
+            self.svg,
+            r#"<text x="{x:.2}" y="{y:.2}" text-anchor="middle" dominant-baseline="central" font-family="sans-serif" font-size="{size}" {}>{}</text>"#,
+            fill_attributes(color),
+            escape(text)
+        );
+    }
+}
+
+impl SvgCanvas {
+    fn path(&mut self, data: &str, fill: Color32, stroke: Stroke, dash: Dash) {
+        if data.is_empty() {
+            return;
+        }
+
+        let _ = writeln!(
+            self.svg,
+            r#"<path d="{data}" fill-rule="evenodd" {} {} stroke-linejoin="round" stroke-linecap="round"/>"#,
+            fill_attributes(fill),
+            stroke_attributes(stroke, dash)
+        );
+    }
+}
+
+fn path_data(points: &[[f64; 2]], closed: bool) -> String {
+    let mut data = String::new();
+    for (i, [x, y]) in points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+        let _ = write!(data, "{command}{x:.2} {y:.2} ");
+    }
+
+    if closed && !data.is_empty() {
+        data.push('Z');
+    }
+
+    data.trim_end().to_string()
+}
+
+fn fill_attributes(color: Color32) -> String {
+    if color.a() == 0 {
+        return String::from(r#"fill="none""#);
+    }
+
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!(
+        r#"fill="rgb({r},{g},{b})" fill-opacity="{:.3}""#,
+        a as f32 / 255.
+    )
+}
+
+fn stroke_attributes(stroke: Stroke, dash: Dash) -> String {
+    if stroke.is_empty() {
+        return String::from(r#"stroke="none""#);
+    }
+
+    let [r, g, b, a] = stroke.color.to_srgba_unmultiplied();
+    let mut attributes = format!(
+        r#"stroke="rgb({r},{g},{b})" stroke-opacity="{:.3}" stroke-width="{}""#,
+        a as f32 / 255.,
+        stroke.width
+    );
+    if let Some([on, off]) = dash.pattern(stroke.width) {
+        let _ = write!(attributes, r#" stroke-dasharray="{on} {off}""#);
+    }
+
+    attributes
+}
+
+/// Formats a tick value without floating point noise like `0.30000000000000004`
+pub fn format_tick(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+    if rounded == 0. {
+        return String::from("0");
+    }
+
+    rounded.to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Stroke};
+
+    use crate::{
+        feature::Feature,
+        query::Query,
+        render::Extent,
+        sdo_geometry::SdoGeometry,
+        style::{Dash, LayerStyle, Marker},
+        svg::SvgExporter,
+    };
+
+    fn query(geometries: Vec<SdoGeometry>) -> Query {
+        Query::new(
+            "test",
+            String::new(),
+            vec![],
+            LayerStyle::from(Stroke::new(2., Color32::RED)),
+            geometries.into_iter().map(Feature::from).collect(),
+        )
+    }
+
+    #[test]
+    fn test_render_polygon_with_hole() {
+        let query = query(vec![SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3., 5., 2003., 3.],
+            sdo_ordinates: vec![0., 0., 10., 10., 2., 2., 4., 4.],
+        }]);
+
+        let mut exporter = SvgExporter::new(Extent::new([0., 0.], [10., 10.]));
+        exporter.width = 100;
+        exporter.height = 100;
+        let svg = exporter.render([("parcels", &query)]);
+
+        assert!(svg.contains(
+            r#"<path d="M0.00 100.00 L100.00 100.00 L100.00 0.00 L0.00 0.00 L0.00 100.00 Z M20.00 80.00 L40.00 80.00 L40.00 60.00 L20.00 60.00 L20.00 80.00 Z" fill-rule="evenodd" fill="none" stroke="rgb(255,0,0)" stroke-opacity="1.000" stroke-width="2""#
+        ));
+    }
+
+    #[test]
+    fn test_render_skips_inactive_geometries() {
+        let mut query = query(vec![SdoGeometry {
+            sdo_gtype: 2002.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 2., 1.],
+            sdo_ordinates: vec![0., 0., 10., 10.],
+        }]);
+        query.geometries[0].is_active = false;
+
+        let svg = SvgExporter::new(Extent::new([0., 0.], [10., 10.])).render([("roads", &query)]);
+
+        assert!(!svg.contains("<path"));
+    }
+
+    #[test]
+    fn test_render_points_axes_and_legend() {
+        let query = query(vec![SdoGeometry {
+            sdo_gtype: 2005.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1., 2.],
+            sdo_ordinates: vec![2., 2., 8., 8.],
+        }]);
+
+        let mut exporter = SvgExporter::new(Extent::new([0., 0.], [10., 10.]));
+        exporter.include_axes = true;
+        exporter.include_legend = true;
+        let svg = exporter.render([("wells & pumps", &query)]);
+
+        // Two markers and one legend symbol
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert!(svg.contains("wells &amp; pumps"));
+        assert!(svg.contains(">10</text>"));
+    }
+
+    #[test]
+    fn test_render_dashed_fill_and_square_markers() {
+        let mut polygon = query(vec![SdoGeometry {
+            sdo_gtype: 2003.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1003., 3.],
+            sdo_ordinates: vec![0., 0., 10., 10.],
+        }]);
+        polygon.style.dash = Dash::Dashed;
+        polygon.style.fill = Color32::BLUE;
+        polygon.style.fill_opacity = 0.5;
+
+        let mut points = query(vec![SdoGeometry {
+            sdo_gtype: 2005.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1., 2.],
+            sdo_ordinates: vec![2., 2., 8., 8.],
+        }]);
+        points.style.marker = Marker::Square;
+
+        let svg = SvgExporter::new(Extent::new([0., 0.], [10., 10.]))
+            .render([("parcels", &polygon), ("wells", &points)]);
+
+        assert!(svg.contains(r#"stroke-dasharray="12 8""#));
+        assert!(svg.contains(r#"fill="rgb(0,0,255)" fill-opacity="0.498""#));
+        assert!(!svg.contains("<circle"));
+    }
+
+    #[test]
+    fn test_render_dms_axes_scale_bar_and_north_arrow() {
+        let query = query(vec![]);
+
+        let mut exporter = SvgExporter::new(Extent::new([10., 45.], [11., 46.]));
+        exporter.include_axes = true;
+        exporter.include_scale_bar = true;
+        exporter.include_north_arrow = true;
+        exporter.srid = Some(8307.);
+        let svg = exporter.render([("empty", &query)]);
+
+        assert!(svg.contains(">10°12'E</text>"));
+        assert!(svg.contains(">45°30'N</text>"));
+        assert!(svg.contains(" km</text>"));
+        assert!(svg.contains(">N</text>"));
+    }
+}
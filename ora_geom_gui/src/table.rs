@@ -0,0 +1,516 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use egui::{Align, Color32, ComboBox, Context, RichText, Window};
+use egui_extras::{Column, TableBuilder};
+use serde_json::Value;
+
+use crate::{
+    feature::{as_number, attribute, display},
+    layers::Layers,
+    query::Geometry,
+    selection::{GeometryRef, Selection},
+};
+
+const ROW_HEIGHT: f32 = 18.;
+
+/// Column the attribute table is sorted by
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SortColumn {
+    /// Order the rows were returned in
+    #[default]
+    Row,
+    Name,
+    Area,
+    Perimeter,
+    Length,
+    /// Number of validation errors
+    Validity,
+    Attribute(String),
+}
+
+impl SortColumn {
+    fn compare(&self, a: &Geometry, b: &Geometry) -> Ordering {
+        match self {
+            SortColumn::Row => Ordering::Equal,
+            SortColumn::Name => a.name.cmp(&b.name),
+            SortColumn::Area => a.metrics().area.total_cmp(&b.metrics().area),
+            SortColumn::Perimeter => a.metrics().perimeter.total_cmp(&b.metrics().perimeter),
+            SortColumn::Length => a.metrics().length.total_cmp(&b.metrics().length),
+            SortColumn::Validity => a
+                .validation_errors()
+                .len()
+                .cmp(&b.validation_errors().len()),
+            SortColumn::Attribute(name) => compare_values(
+                attribute(&a.attributes, name),
+                attribute(&b.attributes, name),
+            ),
+        }
+    }
+}
+
+/// Numbers are compared by value, other values by their text, missing values and nulls go last
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => match (as_number(a), as_number(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            _ => display(a).cmp(&display(b)),
+        },
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Listed rows and the geometries, sort and search they were computed for
+struct CachedRows {
+    generation: u64,
+    sort: SortColumn,
+    is_descending: bool,
+    search: String,
+    rows: Rc<[usize]>,
+}
+
+/// Geometries of one layer with their metrics and attributes as sortable columns
+pub struct AttributeTable {
+    pub layer: String,
+    pub sort: SortColumn,
+    pub is_descending: bool,
+    /// Text the name or an attribute value has to contain, ignoring the case
+    pub search: String,
+    pub csv_path: String,
+    pub message: RichText,
+    /// Selected geometry the table was last scrolled to
+    scrolled_to: Option<GeometryRef>,
+    cached_rows: Option<CachedRows>,
+}
+
+impl Default for AttributeTable {
+    fn default() -> Self {
+        Self {
+            layer: String::new(),
+            sort: SortColumn::Row,
+            is_descending: false,
+            search: String::new(),
+            csv_path: "attributes.csv".into(),
+            message: RichText::new(""),
+            scrolled_to: None,
+            cached_rows: None,
+        }
+    }
+}
+
+impl AttributeTable {
+    pub fn open(&mut self, layer: &str) {
+        if self.layer != layer {
+            self.layer = layer.to_string();
+            self.sort = SortColumn::Row;
+            self.is_descending = false;
+            self.search.clear();
+            self.scrolled_to = None;
+        }
+    }
+
+    /// Sorts by the column, or reverses the order when it is already sorted by it
+    pub fn sort_by(&mut self, column: SortColumn) {
+        if self.sort == column {
+            self.is_descending = !self.is_descending;
+        } else {
+            self.sort = column;
+            self.is_descending = false;
+        }
+    }
+
+    /// Indices of the listed geometries in the order they are listed, ties keep the row order.
+    ///
+    /// They are only searched and sorted again when the sort, the search or the
+    /// `generation` of the geometries changes
+    pub fn rows(&mut self, geometries: &[Geometry], generation: u64) -> Rc<[usize]> {
+        let is_current = self.cached_rows.as_ref().is_some_and(|cached| {
+            cached.generation == generation
+                && cached.sort == self.sort
+                && cached.is_descending == self.is_descending
+                && cached.search == self.search
+        });
+        if !is_current {
+            self.cached_rows = Some(CachedRows {
+                generation,
+                sort: self.sort.clone(),
+                is_descending: self.is_descending,
+                search: self.search.clone(),
+                rows: self.sorted_rows(geometries).into(),
+            });
+        }
+        self.cached_rows
+            .as_ref()
+            .map_or_else(|| Rc::from([]), |cached| cached.rows.clone())
+    }
+
+    fn sorted_rows(&self, geometries: &[Geometry]) -> Vec<usize> {
+        let mut rows: Vec<usize> = (0..geometries.len())
+            .filter(|&n| geometries[n].contains_text(&self.search))
+            .collect();
+        rows.sort_by(|&a, &b| {
+            let ordering = self.sort.compare(&geometries[a], &geometries[b]);
+            match self.is_descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+            .then(a.cmp(&b))
+        });
+        rows
+    }
+
+    /// Listed rows as CSV with unformatted metrics, in the order they are listed
+    pub fn to_csv(geometries: &[Geometry], rows: &[usize], attributes: &[String]) -> String {
+        let mut header = ["#", "Name", "Area", "Perimeter", "Length", "Errors"]
+            .map(String::from)
+            .to_vec();
+        header.extend(attributes.iter().cloned());
+
+        let mut lines = vec![header];
+        for &n in rows {
+            let geometry = &geometries[n];
+            let metrics = geometry.metrics();
+            let is_polygon = geometry.sdo_geometry.is_polygon();
+            let measure = |is_shown: bool, value: f64| match is_shown {
+                true => value.to_string(),
+                false => String::new(),
+            };
+            let mut line = vec![
+                (n + 1).to_string(),
+                geometry.name.clone(),
+                measure(is_polygon, metrics.area),
+                measure(is_polygon, metrics.perimeter),
+                measure(metrics.length > 0., metrics.length),
+                geometry.validation_errors().join("; "),
+            ];
+            line.extend(attributes.iter().map(|name| {
+                attribute(&geometry.attributes, name)
+                    .filter(|v| !v.is_null())
+                    .map(display)
+                    .unwrap_or_default()
+            }));
+            lines.push(line);
+        }
+
+        lines
+            .iter()
+            .map(|line| {
+                let fields: Vec<String> = line.iter().map(|f| csv_field(f)).collect();
+                fields.join(",") + "\r\n"
+            })
+            .collect()
+    }
+
+    fn export(&mut self, geometries: &[Geometry], rows: &[usize], attributes: &[String]) {
+        let csv = Self::to_csv(geometries, rows, attributes);
+        self.message = match std::fs::write(&self.csv_path, csv) {
+            Ok(()) => RichText::new(format!("Exported to {}", self.csv_path)).color(Color32::GREEN),
+            Err(e) => RichText::new(format!("Failed to export the table: {e}")).color(Color32::RED),
+        };
+    }
+}
+
+pub struct AttributeTableWindow<'a> {
+    pub table: &'a mut AttributeTable,
+    pub layers: &'a Layers,
+    /// Clicking a row selects its geometry, selecting one on the map scrolls to its row
+    pub selection: &'a mut Selection,
+}
+
+impl<'a> AttributeTableWindow<'a> {
+    pub fn new(
+        table: &'a mut AttributeTable,
+        layers: &'a Layers,
+        selection: &'a mut Selection,
+    ) -> Self {
+        Self {
+            table,
+            layers,
+            selection,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Attribute table")
+            .open(is_active)
+            .resizable(true)
+            .default_size([640., 360.])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ComboBox::from_label("Layer")
+                        .selected_text(&self.table.layer)
+                        .show_ui(ui, |ui| {
+                            for layer in &self.layers.layers {
+                                if ui
+                                    .selectable_label(self.table.layer == layer.name, &layer.name)
+                                    .clicked()
+                                {
+                                    self.table.open(&layer.name);
+                                }
+                            }
+                        });
+                    ui.label("Search:");
+                    ui.text_edit_singleline(&mut self.table.search)
+                        .on_hover_text("Lists rows whose name or an attribute contains the text");
+                });
+
+                let Some(layer) = self.layers.get(&self.table.layer) else {
+                    ui.label("Choose a layer to list its geometries.");
+                    return;
+                };
+                let geometries = &layer.query.geometries;
+                let attributes = layer.query.attribute_names();
+                let rows = self.table.rows(geometries, layer.query.generation());
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} of {} rows", rows.len(), geometries.len()));
+                    ui.separator();
+                    ui.label("CSV file:");
+                    ui.text_edit_singleline(&mut self.table.csv_path);
+                    if ui
+                        .button("Export CSV")
+                        .on_hover_text("Writes the listed rows in their order")
+                        .clicked()
+                    {
+                        self.table.export(geometries, &rows, &attributes);
+                    }
+                });
+                ui.label(self.table.message.clone());
+
+                // Scrolls to the geometry selected last when it changed, on the map or here
+                let selected = self
+                    .selection
+                    .items
+                    .iter()
+                    .rev()
+                    .find(|item| item.layer == layer.name)
+                    .cloned();
+                let mut scroll_to = None;
+                if selected != self.table.scrolled_to {
+                    scroll_to = selected
+                        .as_ref()
+                        .and_then(|item| rows.iter().position(|&n| n == item.index));
+                    self.table.scrolled_to = selected;
+                }
+
+                let mut columns = vec![
+                    (SortColumn::Row, String::from("#")),
+                    (SortColumn::Name, String::from("Name")),
+                    (SortColumn::Area, String::from("Area")),
+                    (SortColumn::Perimeter, String::from("Perimeter")),
+                    (SortColumn::Length, String::from("Length")),
+                    (SortColumn::Validity, String::from("Validity")),
+                ];
+                columns.extend(
+                    attributes
+                        .iter()
+                        .map(|name| (SortColumn::Attribute(name.clone()), name.clone())),
+                );
+
+                let mut clicked: Option<SortColumn> = None;
+                let mut clicked_row: Option<(usize, bool)> = None;
+                ui.separator();
+                let mut builder = TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(true)
+                    .columns(Column::auto().at_least(40.), columns.len());
+                if let Some(row) = scroll_to {
+                    builder = builder.scroll_to_row(row, Some(Align::Center));
+                }
+                builder
+                    .header(ROW_HEIGHT + 4., |mut header| {
+                        for (column, title) in &columns {
+                            header.col(|ui| {
+                                let arrow =
+                                    match (self.table.sort == *column, self.table.is_descending) {
+                                        (true, false) => " ⏶",
+                                        (true, true) => " ⏷",
+                                        (false, _) => "",
+                                    };
+                                if ui
+                                    .button(format!("{title}{arrow}"))
+                                    .on_hover_text("Sort by this column")
+                                    .clicked()
+                                {
+                                    clicked = Some(column.clone());
+                                }
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(ROW_HEIGHT, rows.len(), |n, mut row| {
+                            let index = rows[n];
+                            let geometry = &geometries[index];
+                            let metrics = geometry.metrics();
+                            let metric = metrics.metric;
+                            let is_selected = self
+                                .selection
+                                .contains(&GeometryRef::new(&layer.name, index));
+                            for text in [(index + 1).to_string(), geometry.name.clone()] {
+                                row.col(|ui| {
+                                    let response = ui
+                                        .selectable_label(is_selected, text)
+                                        .on_hover_text("Click to select, shift-click to add");
+                                    if response.clicked() {
+                                        clicked_row =
+                                            Some((index, ui.input(|i| i.modifiers.shift)));
+                                    }
+                                });
+                            }
+                            for (is_shown, text) in [
+                                (
+                                    geometry.sdo_geometry.is_polygon(),
+                                    metric.format_area(metrics.area),
+                                ),
+                                (
+                                    geometry.sdo_geometry.is_polygon(),
+                                    metric.format_length(metrics.perimeter),
+                                ),
+                                (metrics.length > 0., metric.format_length(metrics.length)),
+                            ] {
+                                row.col(|ui| {
+                                    if is_shown {
+                                        ui.label(text);
+                                    }
+                                });
+                            }
+                            row.col(|ui| {
+                                let errors = geometry.validation_errors();
+                                if errors.is_empty() {
+                                    ui.label(RichText::new("Valid").color(Color32::GREEN));
+                                } else {
+                                    ui.label(
+                                        RichText::new(format!("{} errors", errors.len()))
+                                            .color(Color32::RED),
+                                    )
+                                    .on_hover_text(errors.join("\n"));
+                                }
+                            });
+                            for name in &attributes {
+                                row.col(|ui| {
+                                    if let Some(value) = attribute(&geometry.attributes, name) {
+                                        ui.label(display(value));
+                                    }
+                                });
+                            }
+                        });
+                    });
+
+                if let Some(column) = clicked {
+                    self.table.sort_by(column);
+                }
+                if let Some((index, is_added)) = clicked_row {
+                    let item = GeometryRef::new(&layer.name, index);
+                    match is_added {
+                        true => self.selection.toggle(item),
+                        false => self.selection.select(item),
+                    }
+                    // The clicked row is already in view
+                    self.table.scrolled_to = self
+                        .selection
+                        .items
+                        .iter()
+                        .rev()
+                        .find(|item| item.layer == layer.name)
+                        .cloned();
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        feature::Attributes,
+        query::Geometry,
+        sdo_geometry::SdoGeometry,
+        table::{AttributeTable, SortColumn},
+    };
+
+    fn square(size: f64, attributes: serde_json::Value) -> Geometry {
+        let attributes: Attributes = serde_json::from_value(attributes).unwrap();
+        Geometry::new(
+            String::new(),
+            SdoGeometry {
+                sdo_gtype: 2003.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 1003., 3.],
+                sdo_ordinates: vec![0., 0., size, size],
+            },
+            attributes,
+        )
+    }
+
+    #[test]
+    fn test_rows_sorted_by_metric_and_attribute() {
+        let geometries = vec![
+            square(3., json!({"NAME": "b", "POP": 20})),
+            square(1., json!({"NAME": "c", "POP": null})),
+            square(2., json!({"NAME": "a", "POP": "100"})),
+        ];
+        let mut table = AttributeTable::default();
+        assert_eq!(table.rows(&geometries, 0).to_vec(), vec![0, 1, 2]);
+
+        table.sort_by(SortColumn::Area);
+        assert_eq!(table.rows(&geometries, 0).to_vec(), vec![1, 2, 0]);
+        table.sort_by(SortColumn::Area);
+        assert_eq!(table.rows(&geometries, 0).to_vec(), vec![0, 2, 1]);
+
+        // Numeric strings sort as numbers and nulls go last
+        table.sort_by(SortColumn::Attribute(String::from("pop")));
+        assert_eq!(table.rows(&geometries, 0).to_vec(), vec![0, 2, 1]);
+        table.sort_by(SortColumn::Attribute(String::from("NAME")));
+        assert_eq!(table.rows(&geometries, 0).to_vec(), vec![2, 0, 1]);
+
+        // The order is kept until the geometries change
+        let reversed: Vec<Geometry> = geometries.into_iter().rev().collect();
+        assert_eq!(table.rows(&reversed, 0).to_vec(), vec![2, 0, 1]);
+        assert_eq!(table.rows(&reversed, 1).to_vec(), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn test_search_and_csv_export() {
+        let geometries = vec![
+            square(1., json!({"NAME": "Main, north", "NOTE": "say \"hi\""})),
+            square(2., json!({"NAME": "Harbour", "NOTE": null})),
+        ];
+        let attributes = vec![String::from("NAME"), String::from("NOTE")];
+        let mut table = AttributeTable {
+            search: String::from("NORTH"),
+            ..Default::default()
+        };
+        let rows = table.rows(&geometries, 0);
+        assert_eq!(rows.to_vec(), vec![0]);
+
+        let csv = AttributeTable::to_csv(&geometries, &rows, &attributes);
+        assert_eq!(
+            csv,
+            "#,Name,Area,Perimeter,Length,Errors,NAME,NOTE\r\n\
+             1,,1,4,,,\"Main, north\",\"say \"\"hi\"\"\"\r\n"
+        );
+
+        table.search.clear();
+        let rows = table.rows(&geometries, 0);
+        assert_eq!(
+            AttributeTable::to_csv(&geometries, &rows, &attributes)
+                .lines()
+                .count(),
+            3
+        );
+    }
+}
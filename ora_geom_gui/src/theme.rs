@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+
+use egui::{Color32, ComboBox, DragValue, Grid, RichText, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    expression::Expr,
+    feature::{as_number, attribute, display},
+    query::Geometry,
+    style::PALETTE,
+    svg::format_tick,
+};
+
+/// Unique values beyond this are left with the layer style
+const MAX_CATEGORIES: usize = 64;
+const MAX_CLASSES: usize = 10;
+/// Natural breaks are quadratic, larger layers are classified from an even sample
+const NATURAL_BREAKS_SAMPLE: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Classification {
+    EqualInterval,
+    Quantile,
+    NaturalBreaks,
+}
+
+impl Classification {
+    pub const ALL: [Classification; 3] = [
+        Classification::EqualInterval,
+        Classification::Quantile,
+        Classification::NaturalBreaks,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Classification::EqualInterval => "Equal interval",
+            Classification::Quantile => "Quantile",
+            Classification::NaturalBreaks => "Natural breaks (Jenks)",
+        }
+    }
+
+    /// Class bounds from the minimum to the maximum, classes that would be empty are merged
+    pub fn bounds(&self, values: &[f64], classes: usize) -> Vec<f64> {
+        let mut sorted = values
+            .iter()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+
+        let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+            return vec![];
+        };
+        let classes = classes.clamp(1, MAX_CLASSES);
+
+        let mut bounds = match self {
+            Classification::EqualInterval => (0..=classes)
+                .map(|i| min + (max - min) * i as f64 / classes as f64)
+                .collect(),
+            Classification::Quantile => {
+                let n = sorted.len();
+                std::iter::once(min)
+                    .chain((1..classes).map(|i| sorted[(i * n).div_ceil(classes) - 1]))
+                    .chain(std::iter::once(max))
+                    .collect()
+            }
+            Classification::NaturalBreaks => {
+                let step = sorted.len().div_ceil(NATURAL_BREAKS_SAMPLE);
+                let mut sample = sorted.iter().copied().step_by(step).collect::<Vec<_>>();
+                if sample.last() != Some(&max) {
+                    sample.push(max);
+                }
+                natural_breaks(&sample, classes)
+            }
+        };
+        bounds.dedup();
+
+        bounds
+    }
+}
+
+/// Jenks optimisation, the sorted values are split so that the variance inside classes is minimal
+fn natural_breaks(sorted: &[f64], classes: usize) -> Vec<f64> {
+    let n = sorted.len();
+    let k = classes.min(n);
+    let mut lower_limits = vec![vec![0usize; k + 1]; n + 1];
+    let mut variances = vec![vec![f64::INFINITY; k + 1]; n + 1];
+    for j in 1..=k {
+        lower_limits[1][j] = 1;
+        variances[1][j] = 0.;
+    }
+
+    for l in 2..=n {
+        let (mut sum, mut sum_squares, mut count) = (0., 0., 0.);
+        let mut variance = 0.;
+        for m in 1..=l {
+            let lower = l - m + 1;
+            let value = sorted[lower - 1];
+            count += 1.;
+            sum += value;
+            sum_squares += value * value;
+            variance = sum_squares - sum * sum / count;
+
+            if lower > 1 {
+                for j in 2..=k {
+                    let candidate = variance + variances[lower - 1][j - 1];
+                    if variances[l][j] >= candidate {
+                        lower_limits[l][j] = lower;
+                        variances[l][j] = candidate;
+                    }
+                }
+            }
+        }
+        lower_limits[l][1] = 1;
+        variances[l][1] = variance;
+    }
+
+    let mut bounds = vec![0.; k + 1];
+    bounds[0] = sorted[0];
+    bounds[k] = sorted[n - 1];
+    let mut end = n;
+    for class in (2..=k).rev() {
+        let lower = lower_limits[end][class];
+        bounds[class - 1] = sorted[lower - 2];
+        end = lower - 1;
+    }
+
+    bounds
+}
+
+/// Index of the class that contains the value, the lowest bound belongs to the first class
+pub fn class_of(bounds: &[f64], value: f64) -> Option<usize> {
+    let (first, last) = (bounds.first()?, bounds.last()?);
+    if value < *first || value > *last {
+        return None;
+    }
+
+    Some(
+        bounds
+            .iter()
+            .skip(1)
+            .position(|bound| value <= *bound)
+            .unwrap_or(0),
+    )
+}
+
+/// Colour at `t` between 0 and 1 on a linear ramp
+pub fn ramp(start: Color32, end: Color32, t: f32) -> Color32 {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t.clamp(0., 1.)).round() as u8;
+    Color32::from_rgb(
+        mix(start.r(), end.r()),
+        mix(start.g(), end.g()),
+        mix(start.b(), end.b()),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Category {
+    /// Attribute value as shown by `feature::display`
+    pub value: String,
+    pub color: Color32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Categorized {
+    pub attribute: String,
+    pub categories: Vec<Category>,
+}
+
+impl Categorized {
+    /// Gives every unique value of the attribute its own palette colour
+    pub fn classify(attribute_name: &str, geometries: &[Geometry]) -> Self {
+        let mut values = geometries
+            .iter()
+            .map(|g| attribute(&g.attributes, attribute_name).map_or(String::from("NULL"), display))
+            .collect::<Vec<_>>();
+        values.sort();
+        values.dedup();
+
+        Self {
+            attribute: attribute_name.to_string(),
+            categories: values
+                .into_iter()
+                .take(MAX_CATEGORIES)
+                .enumerate()
+                .map(|(n, value)| Category {
+                    value,
+                    color: PALETTE[n % PALETTE.len()],
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Graduated {
+    pub attribute: String,
+    pub classification: Classification,
+    pub classes: usize,
+    pub start: Color32,
+    pub end: Color32,
+    /// Computed by `classify`, stored so that the legend does not change on refresh
+    pub bounds: Vec<f64>,
+}
+
+impl Graduated {
+    pub fn new(attribute: &str) -> Self {
+        Self {
+            attribute: attribute.to_string(),
+            classification: Classification::EqualInterval,
+            classes: 5,
+            start: Color32::from_rgb(255, 255, 204),
+            end: Color32::from_rgb(189, 0, 38),
+            bounds: vec![],
+        }
+    }
+
+    pub fn classify(&mut self, geometries: &[Geometry]) {
+        let values = geometries
+            .iter()
+            .filter_map(|g| attribute(&g.attributes, &self.attribute).and_then(as_number))
+            .collect::<Vec<_>>();
+        self.bounds = self.classification.bounds(&values, self.classes);
+    }
+
+    pub fn class_color(&self, class: usize) -> Color32 {
+        let count = self.bounds.len().saturating_sub(1);
+        if count <= 1 {
+            return self.end;
+        }
+
+        ramp(self.start, self.end, class as f32 / (count - 1) as f32)
+    }
+}
+
+/// Colours the geometry of the first expression that matches it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub expression: String,
+    pub color: Color32,
+}
+
+/// Attribute-driven colouring of a layer, geometries without a match keep the layer style
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Single,
+    Categorized(Categorized),
+    Graduated(Graduated),
+    Rules(Vec<Rule>),
+}
+
+impl Theme {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Single => "Single style",
+            Theme::Categorized(_) => "Categorized",
+            Theme::Graduated(_) => "Graduated",
+            Theme::Rules(_) => "Rule-based",
+        }
+    }
+
+    /// Colour of every geometry, `None` when the layer style is used
+    pub fn colors(&self, geometries: &[Geometry]) -> Vec<Option<Color32>> {
+        match self {
+            Theme::Single => vec![None; geometries.len()],
+            Theme::Categorized(categorized) => {
+                let mut colors = HashMap::new();
+                for category in categorized.categories.iter().rev() {
+                    colors.insert(category.value.as_str(), category.color);
+                }
+                geometries
+                    .iter()
+                    .map(|g| {
+                        let value = attribute(&g.attributes, &categorized.attribute)
+                            .map_or(String::from("NULL"), display);
+                        colors.get(value.as_str()).copied()
+                    })
+                    .collect()
+            }
+            Theme::Graduated(graduated) => geometries
+                .iter()
+                .map(|g| {
+                    let value =
+                        attribute(&g.attributes, &graduated.attribute).and_then(as_number)?;
+                    class_of(&graduated.bounds, value).map(|class| graduated.class_color(class))
+                })
+                .collect(),
+            Theme::Rules(rules) => {
+                let parsed = rules
+                    .iter()
+                    .filter_map(|rule| Some((rule.expression.parse::<Expr>().ok()?, rule.color)))
+                    .collect::<Vec<_>>();
+                geometries
+                    .iter()
+                    .map(|g| {
+                        let lookup = |name: &str| attribute(&g.attributes, name).cloned();
+                        parsed
+                            .iter()
+                            .find(|(expr, _)| expr.matches(&lookup))
+                            .map(|(_, color)| *color)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn legend(&self) -> Vec<(String, Color32)> {
+        match self {
+            Theme::Single => vec![],
+            Theme::Categorized(categorized) => categorized
+                .categories
+                .iter()
+                .map(|c| (c.value.clone(), c.color))
+                .collect(),
+            Theme::Graduated(graduated) => graduated
+                .bounds
+                .windows(2)
+                .enumerate()
+                .map(|(class, bounds)| {
+                    let label = format!("{} – {}", format_tick(bounds[0]), format_tick(bounds[1]));
+                    (label, graduated.class_color(class))
+                })
+                .collect(),
+            Theme::Rules(rules) => rules
+                .iter()
+                .map(|r| (r.expression.clone(), r.color))
+                .collect(),
+        }
+    }
+}
+
+/// Editor for the theme of a layer, returns true when the geometry colours have to be updated
+pub fn theme_ui(
+    ui: &mut Ui,
+    theme: &mut Theme,
+    attributes: &[String],
+    geometries: &[Geometry],
+    id: &str,
+) -> bool {
+    let before = theme.clone();
+    let first_attribute = attributes.first().cloned().unwrap_or_default();
+
+    if attributes.is_empty() {
+        ui.label(
+            RichText::new("Select more columns than the geometry to style by attributes.")
+                .color(Color32::LIGHT_YELLOW),
+        );
+    }
+
+    ComboBox::from_id_source(("theme_kind", id))
+        .selected_text(theme.label())
+        .show_ui(ui, |ui| {
+            if ui
+                .selectable_label(matches!(theme, Theme::Single), "Single style")
+                .clicked()
+            {
+                *theme = Theme::Single;
+            }
+            if ui
+                .selectable_label(matches!(theme, Theme::Categorized(_)), "Categorized")
+                .clicked()
+                && !matches!(theme, Theme::Categorized(_))
+            {
+                *theme = Theme::Categorized(Categorized::classify(&first_attribute, geometries));
+            }
+            if ui
+                .selectable_label(matches!(theme, Theme::Graduated(_)), "Graduated")
+                .clicked()
+                && !matches!(theme, Theme::Graduated(_))
+            {
+                // The first attribute with numbers is the most likely choice
+                let numeric = attributes
+                    .iter()
+                    .find(|name| {
+                        geometries
+                            .iter()
+                            .any(|g| attribute(&g.attributes, name).and_then(as_number).is_some())
+                    })
+                    .unwrap_or(&first_attribute);
+                let mut graduated = Graduated::new(numeric);
+                graduated.classify(geometries);
+                *theme = Theme::Graduated(graduated);
+            }
+            if ui
+                .selectable_label(matches!(theme, Theme::Rules(_)), "Rule-based")
+                .clicked()
+                && !matches!(theme, Theme::Rules(_))
+            {
+                *theme = Theme::Rules(vec![]);
+            }
+        });
+
+    match theme {
+        Theme::Single => {}
+        Theme::Categorized(categorized) => {
+            let mut attribute_name = categorized.attribute.clone();
+            attribute_combo(ui, &mut attribute_name, attributes, id);
+            if attribute_name != categorized.attribute || ui.button("Reclassify").clicked() {
+                *categorized = Categorized::classify(&attribute_name, geometries);
+            }
+
+            Grid::new(("theme_categories", id))
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for category in categorized.categories.iter_mut() {
+                        ui.color_edit_button_srgba(&mut category.color);
+                        ui.label(&category.value);
+                        ui.end_row();
+                    }
+                });
+        }
+        Theme::Graduated(graduated) => {
+            let settings = (
+                graduated.attribute.clone(),
+                graduated.classification,
+                graduated.classes,
+            );
+
+            attribute_combo(ui, &mut graduated.attribute, attributes, id);
+            ComboBox::from_id_source(("theme_classification", id))
+                .selected_text(graduated.classification.label())
+                .show_ui(ui, |ui| {
+                    for classification in Classification::ALL {
+                        ui.selectable_value(
+                            &mut graduated.classification,
+                            classification,
+                            classification.label(),
+                        );
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("Classes:");
+                ui.add(DragValue::new(&mut graduated.classes).clamp_range(2..=MAX_CLASSES));
+                ui.label("Ramp:");
+                ui.color_edit_button_srgba(&mut graduated.start);
+                ui.color_edit_button_srgba(&mut graduated.end);
+            });
+
+            let changed = settings
+                != (
+                    graduated.attribute.clone(),
+                    graduated.classification,
+                    graduated.classes,
+                );
+            if changed || ui.button("Reclassify").clicked() {
+                graduated.classify(geometries);
+            }
+        }
+        Theme::Rules(rules) => {
+            let mut to_remove = None;
+            for (n, rule) in rules.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgba(&mut rule.color);
+                    ui.add(
+                        egui::TextEdit::singleline(&mut rule.expression)
+                            .hint_text("area > 100 and class = 'RES'"),
+                    );
+                    if ui.button("🗑").on_hover_text("Remove rule").clicked() {
+                        to_remove = Some(n);
+                    }
+                });
+                if let Err(e) = rule.expression.parse::<Expr>() {
+                    ui.label(RichText::new(e.to_string()).color(Color32::RED));
+                }
+            }
+
+            if let Some(n) = to_remove {
+                rules.remove(n);
+            }
+
+            if ui.button("Add rule").clicked() {
+                rules.push(Rule {
+                    expression: String::new(),
+                    color: PALETTE[rules.len() % PALETTE.len()],
+                });
+            }
+        }
+    }
+
+    *theme != before
+}
+
+fn attribute_combo(ui: &mut Ui, selected: &mut String, attributes: &[String], id: &str) {
+    ComboBox::from_id_source(("theme_attribute", id))
+        .selected_text(selected.as_str())
+        .show_ui(ui, |ui| {
+            for name in attributes {
+                ui.selectable_value(selected, name.clone(), name);
+            }
+        });
+}
+
+/// Colour swatches with labels of a themed layer
+pub fn legend_ui(ui: &mut Ui, theme: &Theme) {
+    for (label, color) in theme.legend() {
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(16., 10.), egui::Sense::hover());
+            ui.painter().rect_filled(rect, 2., color);
+            ui.label(label);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+    use serde_json::json;
+
+    use crate::{
+        feature::Attributes,
+        query::Geometry,
+        sdo_geometry::SdoGeometry,
+        theme::{class_of, ramp, Categorized, Classification, Rule, Theme},
+    };
+
+    fn geometry(attributes: serde_json::Value) -> Geometry {
+        let attributes: Attributes = serde_json::from_value(attributes).unwrap();
+        Geometry::new(
+            String::new(),
+            SdoGeometry {
+                sdo_gtype: 2001.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 1., 1.],
+                sdo_ordinates: vec![0., 0.],
+            },
+            attributes,
+        )
+    }
+
+    #[test]
+    fn test_classification_bounds() {
+        let values = [1., 2., 3., 10., 11., 12., 30., 31., 32.];
+
+        assert_eq!(
+            Classification::EqualInterval.bounds(&values, 2),
+            vec![1., 16.5, 32.]
+        );
+        assert_eq!(
+            Classification::Quantile.bounds(&values, 3),
+            vec![1., 3., 12., 32.]
+        );
+        assert_eq!(
+            Classification::NaturalBreaks.bounds(&values, 3),
+            vec![1., 3., 12., 32.]
+        );
+        assert_eq!(Classification::Quantile.bounds(&[5., 5., 5.], 4), vec![5.]);
+        assert!(Classification::NaturalBreaks.bounds(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn test_class_of() {
+        let bounds = [1., 3., 12., 32.];
+
+        assert_eq!(class_of(&bounds, 1.), Some(0));
+        assert_eq!(class_of(&bounds, 3.), Some(0));
+        assert_eq!(class_of(&bounds, 3.5), Some(1));
+        assert_eq!(class_of(&bounds, 32.), Some(2));
+        assert_eq!(class_of(&bounds, 40.), None);
+        assert_eq!(class_of(&[5.], 5.), Some(0));
+    }
+
+    #[test]
+    fn test_ramp() {
+        assert_eq!(
+            ramp(Color32::BLACK, Color32::WHITE, 0.5),
+            Color32::from_gray(128)
+        );
+        assert_eq!(ramp(Color32::BLACK, Color32::WHITE, 2.), Color32::WHITE);
+    }
+
+    #[test]
+    fn test_categorized_and_rule_colors() {
+        let geometries = vec![
+            geometry(json!({"CLASS": "RES", "AREA": 50})),
+            geometry(json!({"CLASS": "COM", "AREA": 500})),
+            geometry(json!({"CLASS": null, "AREA": 150})),
+        ];
+
+        let categorized = Categorized::classify("class", &geometries);
+        assert_eq!(
+            categorized
+                .categories
+                .iter()
+                .map(|c| c.value.as_str())
+                .collect::<Vec<_>>(),
+            vec!["COM", "NULL", "RES"]
+        );
+        let colors = Theme::Categorized(categorized.clone()).colors(&geometries);
+        assert_eq!(colors[0], Some(categorized.categories[2].color));
+
+        let rules = Theme::Rules(vec![
+            Rule {
+                expression: "area > 100 and class = 'COM'".into(),
+                color: Color32::RED,
+            },
+            Rule {
+                expression: "not valid (".into(),
+                color: Color32::GREEN,
+            },
+            Rule {
+                expression: "area > 100".into(),
+                color: Color32::BLUE,
+            },
+        ]);
+        assert_eq!(
+            rules.colors(&geometries),
+            vec![None, Some(Color32::RED), Some(Color32::BLUE)]
+        );
+    }
+}
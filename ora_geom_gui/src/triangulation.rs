@@ -0,0 +1,149 @@
+use egui::{epaint::Vertex, Color32, Mesh, Pos2};
+use geo::{Geometry, Polygon, TriangulateEarcut};
+
+/// Triangles filling the polygons of a geometry, in data coordinates.
+///
+/// egui can only fill convex paths, so concave polygons and holes are
+/// drawn as a mesh of these triangles.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Triangulation {
+    pub vertices: Vec<[f64; 2]>,
+    /// Every three indices into `vertices` make a triangle
+    pub indices: Vec<u32>,
+}
+
+impl Triangulation {
+    /// Triangulates polygons with ear clipping, other geometry types have no fill
+    pub fn of_geometry(geometry: &Geometry) -> Self {
+        let mut triangulation = Self::default();
+        triangulation.push_geometry(geometry);
+        triangulation
+    }
+
+    fn push_geometry(&mut self, geometry: &Geometry) {
+        match geometry {
+            Geometry::Polygon(polygon) => self.push_polygon(polygon),
+            Geometry::MultiPolygon(polygons) => {
+                for polygon in polygons {
+                    self.push_polygon(polygon);
+                }
+            }
+            Geometry::Rect(rect) => self.push_polygon(&rect.to_polygon()),
+            Geometry::Triangle(triangle) => self.push_polygon(&triangle.to_polygon()),
+            Geometry::GeometryCollection(collection) => {
+                for geometry in collection {
+                    self.push_geometry(geometry);
+                }
+            }
+            Geometry::Point(_)
+            | Geometry::MultiPoint(_)
+            | Geometry::Line(_)
+            | Geometry::LineString(_)
+            | Geometry::MultiLineString(_) => {}
+        }
+    }
+
+    fn push_polygon(&mut self, polygon: &Polygon) {
+        let raw = polygon.earcut_triangles_raw();
+        let offset = self.vertices.len() as u32;
+
+        self.vertices
+            .extend(raw.vertices.chunks_exact(2).map(|c| [c[0], c[1]]));
+        self.indices
+            .extend(raw.triangle_indices.iter().map(|i| offset + *i as u32));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Sum of the triangle areas, equals the polygon area when the triangulation is right
+    pub fn area(&self) -> f64 {
+        self.indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|n| self.vertices[t[n] as usize]);
+                ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.
+            })
+            .sum()
+    }
+
+    /// Adds the triangles to a mesh, `to_screen` maps data coordinates onto the screen
+    pub fn append_to(&self, mesh: &mut Mesh, color: Color32, to_screen: impl Fn([f64; 2]) -> Pos2) {
+        let offset = mesh.vertices.len() as u32;
+
+        mesh.vertices.extend(self.vertices.iter().map(|v| Vertex {
+            pos: to_screen(*v),
+            uv: egui::epaint::WHITE_UV,
+            color,
+        }));
+        mesh.indices.extend(self.indices.iter().map(|i| offset + i));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Mesh, Pos2};
+    use geo::{polygon, Area, Geometry};
+
+    use crate::triangulation::Triangulation;
+
+    #[test]
+    fn test_concave_polygon() {
+        let l_shape = polygon![
+            (x: 0., y: 0.),
+            (x: 4., y: 0.),
+            (x: 4., y: 1.),
+            (x: 1., y: 1.),
+            (x: 1., y: 3.),
+            (x: 0., y: 3.),
+        ];
+        let triangulation = Triangulation::of_geometry(&Geometry::Polygon(l_shape.clone()));
+
+        assert_eq!(triangulation.indices.len(), 4 * 3);
+        assert!((triangulation.area() - l_shape.unsigned_area()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_polygon_with_hole() {
+        let with_hole = polygon!(
+            exterior: [
+                (x: 0., y: 0.),
+                (x: 10., y: 0.),
+                (x: 10., y: 10.),
+                (x: 0., y: 10.),
+            ],
+            interiors: [[
+                (x: 2., y: 2.),
+                (x: 2., y: 4.),
+                (x: 4., y: 4.),
+                (x: 4., y: 2.),
+            ]],
+        );
+        let triangulation = Triangulation::of_geometry(&Geometry::Polygon(with_hole));
+
+        assert!((triangulation.area() - 96.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_append_to_mesh() {
+        let square = polygon![
+            (x: 0., y: 0.),
+            (x: 1., y: 0.),
+            (x: 1., y: 1.),
+            (x: 0., y: 1.),
+        ];
+        let triangulation = Triangulation::of_geometry(&Geometry::Polygon(square));
+        let mut mesh = Mesh::default();
+        triangulation.append_to(&mut mesh, Color32::RED, |[x, y]| {
+            Pos2::new(x as f32, y as f32)
+        });
+        triangulation.append_to(&mut mesh, Color32::RED, |[x, y]| {
+            Pos2::new(x as f32 + 2., y as f32)
+        });
+
+        assert_eq!(mesh.indices.len(), 12);
+        assert!(mesh.indices[6..].iter().all(|i| *i >= 4));
+        assert!(mesh.is_valid());
+    }
+}
@@ -0,0 +1,586 @@
+use chrono::{DateTime, Local};
+
+use crate::{
+    editor::{Editor, Sketch},
+    feature::Feature,
+    label::LabelStyle,
+    layers::{Layer, LayerGroup, Layers, Solo},
+    query::{Bind, Query},
+    sdo_geometry::SdoGeometry,
+    style::LayerStyle,
+    theme::Theme,
+};
+
+const MAX_CHANGES: usize = 100;
+/// Seconds within which repeated changes with the same key are merged, like dragging a slider
+const MERGE_SECONDS: f64 = 1.;
+
+/// Order of the layers with their groups
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrangement {
+    layers: Vec<(String, String)>,
+    groups: Vec<String>,
+}
+
+impl Arrangement {
+    pub fn of(layers: &Layers) -> Self {
+        Self {
+            layers: layers
+                .layers
+                .iter()
+                .map(|l| (l.name.clone(), l.group.clone()))
+                .collect(),
+            groups: layers.groups.iter().map(|g| g.name.clone()).collect(),
+        }
+    }
+
+    /// Layers that are not arranged keep their order below the arranged ones
+    fn apply(self, layers: &mut Layers) -> Self {
+        let previous = Self::of(layers);
+
+        let mut remaining = std::mem::take(&mut layers.layers);
+        for (name, group) in self.layers {
+            if let Some(n) = remaining.iter().position(|l| l.name == name) {
+                let mut layer = remaining.remove(n);
+                layer.group = group;
+                layers.layers.push(layer);
+            }
+        }
+        layers.layers.extend(remaining);
+
+        let is_visible = |name: &str| layers.group(name).is_none_or(|g| g.is_visible);
+        layers.groups = self
+            .groups
+            .into_iter()
+            .map(|name| LayerGroup {
+                is_visible: is_visible(&name),
+                name,
+            })
+            .collect();
+
+        previous
+    }
+}
+
+/// Visibility of every layer, group and geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visibility {
+    layers: Vec<(String, bool, Vec<bool>)>,
+    groups: Vec<(String, bool)>,
+    solo: Option<Solo>,
+}
+
+impl Visibility {
+    pub fn of(layers: &Layers) -> Self {
+        Self {
+            layers: layers
+                .layers
+                .iter()
+                .map(|l| {
+                    let active = l.query.geometries.iter().map(|g| g.is_active).collect();
+                    (l.name.clone(), l.is_visible, active)
+                })
+                .collect(),
+            groups: layers
+                .groups
+                .iter()
+                .map(|g| (g.name.clone(), g.is_visible))
+                .collect(),
+            solo: layers.solo.clone(),
+        }
+    }
+
+    fn apply(self, layers: &mut Layers) -> Self {
+        let previous = Self::of(layers);
+
+        for (name, is_visible, active) in self.layers {
+            if let Some(layer) = layers.get_mut(&name) {
+                layer.is_visible = is_visible;
+                for (n, is_active) in active.into_iter().enumerate() {
+                    layer.query.set_active(n, is_active);
+                }
+            }
+        }
+        for (name, is_visible) in self.groups {
+            if let Some(group) = layers.group_mut(&name) {
+                group.is_visible = is_visible;
+            }
+        }
+        layers.solo = self.solo;
+
+        previous
+    }
+}
+
+/// What running the query of a layer replaces
+pub struct QueryResult {
+    sql: String,
+    binds: Vec<Bind>,
+    features: Vec<Feature>,
+    stale_since: Option<DateTime<Local>>,
+    is_derived: bool,
+}
+
+impl QueryResult {
+    pub fn of(query: &Query) -> Self {
+        Self {
+            sql: query.sql.clone(),
+            binds: query.binds.clone(),
+            features: query
+                .geometries
+                .iter()
+                .map(|g| Feature {
+                    geometry: g.sdo_geometry.clone(),
+                    attributes: g.attributes.clone(),
+                })
+                .collect(),
+            stale_since: query.stale_since,
+            is_derived: query.is_derived,
+        }
+    }
+
+    fn apply(self, name: &str, query: &mut Query) -> Self {
+        let previous = Self::of(query);
+        query.sql = self.sql;
+        query.binds = self.binds;
+        query.stale_since = self.stale_since;
+        query.is_derived = self.is_derived;
+        query.refresh(name, self.features);
+
+        previous
+    }
+}
+
+/// A reversible change, applying it returns the change that reverts it
+pub enum Change {
+    InsertLayer(usize, Box<Layer>),
+    RemoveLayer(String),
+    Rename {
+        from: String,
+        to: String,
+    },
+    Arrange(Arrangement),
+    Style {
+        layer: String,
+        style: LayerStyle,
+        theme: Theme,
+        label: LabelStyle,
+    },
+    Visibility(Visibility),
+    Geometry {
+        layer: String,
+        index: usize,
+        sdo_geometry: SdoGeometry,
+        /// Geometry the row has to hold, it was refetched or replaced otherwise
+        edited: SdoGeometry,
+    },
+    Query {
+        layer: String,
+        result: Box<QueryResult>,
+    },
+    Sketch(Option<Sketch>),
+    /// Changes applied in order, reverted in the opposite order
+    Several(Vec<Change>),
+}
+
+impl Change {
+    /// Changes of layers or rows that no longer exist do nothing
+    pub fn apply(self, layers: &mut Layers, editor: &mut Editor) -> Change {
+        match self {
+            Change::InsertLayer(index, layer) => {
+                let name = layer.name.clone();
+                layers.layers.insert(index.min(layers.len()), *layer);
+                Change::RemoveLayer(name)
+            }
+            Change::RemoveLayer(name) => match layers.position(&name).zip(layers.remove(&name)) {
+                Some((n, layer)) => Change::InsertLayer(n, Box::new(layer)),
+                None => Change::RemoveLayer(name),
+            },
+            Change::Rename { from, to } => {
+                layers.rename(&from, &to);
+                Change::Rename { from: to, to: from }
+            }
+            Change::Arrange(arrangement) => Change::Arrange(arrangement.apply(layers)),
+            Change::Style {
+                layer,
+                style,
+                theme,
+                label,
+            } => match layers.get_mut(&layer) {
+                Some(l) => {
+                    let previous = Change::Style {
+                        layer,
+                        style: std::mem::replace(&mut l.query.style, style),
+                        theme: std::mem::replace(&mut l.query.theme, theme),
+                        label: std::mem::replace(&mut l.query.label, label),
+                    };
+                    l.query.apply_theme();
+                    previous
+                }
+                None => Change::Style {
+                    layer,
+                    style,
+                    theme,
+                    label,
+                },
+            },
+            Change::Visibility(visibility) => Change::Visibility(visibility.apply(layers)),
+            Change::Geometry {
+                layer,
+                index,
+                sdo_geometry,
+                edited,
+            } => {
+                let query = layers.get_mut(&layer).map(|l| &mut l.query).filter(|q| {
+                    q.geometries
+                        .get(index)
+                        .is_some_and(|g| g.sdo_geometry == edited)
+                });
+                match query {
+                    Some(query) => {
+                        query.replace_geometry(index, sdo_geometry.clone());
+                        Change::Geometry {
+                            layer,
+                            index,
+                            sdo_geometry: edited,
+                            edited: sdo_geometry,
+                        }
+                    }
+                    None => Change::Geometry {
+                        layer,
+                        index,
+                        sdo_geometry,
+                        edited,
+                    },
+                }
+            }
+            Change::Query { layer, result } => match layers.get_mut(&layer) {
+                Some(l) => Change::Query {
+                    result: Box::new(result.apply(&layer, &mut l.query)),
+                    layer,
+                },
+                None => Change::Query { layer, result },
+            },
+            Change::Sketch(sketch) => Change::Sketch(editor.set_sketch(sketch)),
+            Change::Several(changes) => {
+                let mut reverts: Vec<Change> = changes
+                    .into_iter()
+                    .map(|change| change.apply(layers, editor))
+                    .collect();
+                reverts.reverse();
+                Change::Several(reverts)
+            }
+        }
+    }
+}
+
+struct Entry {
+    label: String,
+    change: Change,
+    /// Key and time of the last change merged into this one
+    merge: Option<(String, f64)>,
+}
+
+/// Operations that can be undone and redone, each stored as the change that reverts it
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl UndoStack {
+    /// Records an operation by the change that reverts it, redoing is no longer possible
+    pub fn push(&mut self, label: impl Into<String>, revert: Change) {
+        self.push_entry(Entry {
+            label: label.into(),
+            change: revert,
+            merge: None,
+        });
+    }
+
+    /// Records an operation like `push`, or merges it into the last one with the same key
+    pub fn push_merged(&mut self, label: impl Into<String>, key: &str, revert: Change, time: f64) {
+        if let Some((last_key, last_time)) = self.undo.last_mut().and_then(|e| e.merge.as_mut()) {
+            if last_key == key && time - *last_time < MERGE_SECONDS {
+                *last_time = time;
+                self.redo.clear();
+                return;
+            }
+        }
+
+        self.push_entry(Entry {
+            label: label.into(),
+            change: revert,
+            merge: Some((key.to_string(), time)),
+        });
+    }
+
+    /// Records a change of the layer order or groups, `before` is the arrangement it replaced
+    pub fn push_arrangement(&mut self, before: Arrangement, layers: &Layers) {
+        let now = Arrangement::of(layers);
+        if before == now {
+            return;
+        }
+        let label = match before.groups == now.groups {
+            true => "Reorder layers",
+            false => "Change groups",
+        };
+        self.push(label, Change::Arrange(before));
+    }
+
+    /// Records a change of the visibility, `before` is the visibility it replaced
+    pub fn push_visibility(&mut self, before: Visibility, layers: &Layers) {
+        if before != Visibility::of(layers) {
+            self.push("Change visibility", Change::Visibility(before));
+        }
+    }
+
+    fn push_entry(&mut self, entry: Entry) {
+        self.undo.push(entry);
+        if self.undo.len() > MAX_CHANGES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, layers: &mut Layers, editor: &mut Editor) {
+        if let Some(entry) = self.undo.pop() {
+            self.redo.push(Entry {
+                change: entry.change.apply(layers, editor),
+                merge: None,
+                ..entry
+            });
+        }
+    }
+
+    pub fn redo(&mut self, layers: &mut Layers, editor: &mut Editor) {
+        if let Some(entry) = self.redo.pop() {
+            self.undo.push(Entry {
+                change: entry.change.apply(layers, editor),
+                merge: None,
+                ..entry
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Labels of the operations that can be undone, the oldest first
+    pub fn done(&self) -> impl Iterator<Item = &str> {
+        self.undo.iter().map(|e| e.label.as_str())
+    }
+
+    /// Labels of the operations that can be redone, the next one first
+    pub fn undone(&self) -> impl Iterator<Item = &str> {
+        self.redo.iter().rev().map(|e| e.label.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        editor::Editor,
+        feature::Feature,
+        layers::Layers,
+        query::Query,
+        sdo_geometry::SdoGeometry,
+        style::LayerStyle,
+        undo::{Arrangement, Change, QueryResult, UndoStack, Visibility},
+    };
+
+    fn layers(names: &[&str]) -> Layers {
+        let mut layers = Layers::default();
+        for name in names {
+            let point = Feature::from(SdoGeometry {
+                sdo_gtype: 2001.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 1., 1.],
+                sdo_ordinates: vec![0., 0.],
+            });
+            let query = Query::new(
+                name,
+                String::new(),
+                vec![],
+                LayerStyle::default(),
+                vec![point],
+            );
+            layers.push(name.to_string(), query);
+        }
+        layers
+    }
+
+    fn names(layers: &Layers) -> Vec<&str> {
+        layers.layers.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_undo_and_redo_layer_removal() {
+        let mut layers = layers(&["a", "b", "c"]);
+        let mut editor = Editor::default();
+        let mut undo = UndoStack::default();
+
+        let n = layers.position("b").unwrap();
+        let layer = layers.remove("b").unwrap();
+        undo.push("Delete layer b", Change::InsertLayer(n, Box::new(layer)));
+        assert_eq!(names(&layers), vec!["c", "a"]);
+
+        undo.undo(&mut layers, &mut editor);
+        assert_eq!(names(&layers), vec!["c", "b", "a"]);
+        assert!(!undo.can_undo());
+        assert_eq!(undo.undone().collect::<Vec<_>>(), vec!["Delete layer b"]);
+
+        undo.redo(&mut layers, &mut editor);
+        assert_eq!(names(&layers), vec!["c", "a"]);
+        assert!(undo.can_undo());
+        assert!(!undo.can_redo());
+    }
+
+    #[test]
+    fn test_panel_changes() {
+        let mut layers = layers(&["a", "b"]);
+        let mut editor = Editor::default();
+        let mut undo = UndoStack::default();
+
+        let before = Arrangement::of(&layers);
+        layers.move_layer(0, 2, "");
+        undo.push_arrangement(before, &layers);
+        let before = Visibility::of(&layers);
+        layers.get_mut("a").unwrap().is_visible = false;
+        undo.push_visibility(before, &layers);
+        // Nothing changed
+        undo.push_visibility(Visibility::of(&layers), &layers);
+        assert_eq!(
+            undo.done().collect::<Vec<_>>(),
+            vec!["Reorder layers", "Change visibility"]
+        );
+
+        // Dragging a slider changes the style on every frame
+        for (n, width) in [2., 3., 4.].into_iter().enumerate() {
+            let query = &mut layers.get_mut("b").unwrap().query;
+            let revert = Change::Style {
+                layer: "b".into(),
+                style: query.style,
+                theme: query.theme.clone(),
+                label: query.label.clone(),
+            };
+            query.style.stroke.width = width;
+            undo.push_merged("Style of b", "style b", revert, n as f64 * 0.1);
+        }
+        assert_eq!(undo.done().count(), 3);
+
+        undo.undo(&mut layers, &mut editor);
+        assert_eq!(layers.get("b").unwrap().query.style.stroke.width, 1.);
+        undo.undo(&mut layers, &mut editor);
+        undo.undo(&mut layers, &mut editor);
+        assert!(layers.get("a").unwrap().is_visible);
+        assert_eq!(names(&layers), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_undo_geometry_edit_after_refresh() {
+        let mut layers = layers(&["a"]);
+        let mut editor = Editor::default();
+        let mut undo = UndoStack::default();
+        let point = |x: f64| SdoGeometry {
+            sdo_gtype: 2001.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1., 1.],
+            sdo_ordinates: vec![x, 0.],
+        };
+        let first = |layers: &Layers| {
+            layers.get("a").unwrap().query.geometries[0]
+                .sdo_geometry
+                .clone()
+        };
+
+        let edit = |layers: &mut Layers, undo: &mut UndoStack| {
+            let query = &mut layers.get_mut("a").unwrap().query;
+            query.replace_geometry(0, point(5.));
+            undo.push(
+                "Edit geometry of a",
+                Change::Geometry {
+                    layer: "a".into(),
+                    index: 0,
+                    sdo_geometry: point(0.),
+                    edited: point(5.),
+                },
+            );
+        };
+
+        edit(&mut layers, &mut undo);
+        undo.undo(&mut layers, &mut editor);
+        assert_eq!(first(&layers), point(0.));
+        undo.redo(&mut layers, &mut editor);
+        assert_eq!(first(&layers), point(5.));
+
+        // The refetched first row is another feature, it is left alone
+        let mut undo = UndoStack::default();
+        edit(&mut layers, &mut undo);
+        let rows = vec![Feature::from(point(7.)), Feature::from(point(5.))];
+        layers.get_mut("a").unwrap().query.refresh("a", rows);
+        undo.undo(&mut layers, &mut editor);
+        assert_eq!(first(&layers), point(7.));
+        assert_eq!(
+            layers.get("a").unwrap().query.geometries[1].sdo_geometry,
+            point(5.)
+        );
+    }
+
+    #[test]
+    fn test_undo_query_update() {
+        let mut layers = layers(&["a"]);
+        let mut editor = Editor::default();
+        let mut undo = UndoStack::default();
+
+        let layer = layers.get_mut("a").unwrap();
+        let previous = QueryResult::of(&layer.query);
+        layer.query.sql = "select geom from b".into();
+        let point = Feature::from(SdoGeometry {
+            sdo_gtype: 2001.,
+            sdo_srid: None,
+            sdo_point: None,
+            sdo_elem_info: vec![1., 1., 1.],
+            sdo_ordinates: vec![1., 1.],
+        });
+        layer.query.refresh("b", vec![point.clone(), point]);
+        layers.rename("a", "b");
+        undo.push(
+            "Update layer b",
+            Change::Several(vec![
+                Change::Rename {
+                    from: "b".into(),
+                    to: "a".into(),
+                },
+                Change::Query {
+                    layer: "a".into(),
+                    result: Box::new(previous),
+                },
+            ]),
+        );
+
+        undo.undo(&mut layers, &mut editor);
+        let query = &layers.get("a").unwrap().query;
+        assert!(query.sql.is_empty());
+        assert_eq!(query.geometries.len(), 1);
+        assert_eq!(query.geometries[0].name, "a_0");
+
+        undo.redo(&mut layers, &mut editor);
+        assert!(layers.get("a").is_none());
+        let query = &layers.get("b").unwrap().query;
+        assert_eq!(query.sql, "select geom from b");
+        assert_eq!(query.geometries.len(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::render::{Extent, POINT_SPAN};
+
+/// Space around a zoomed layer, geometry or selection as a fraction of its size
+pub const ZOOM_MARGIN: f64 = 0.05;
+/// Seconds a panned or zoomed view has to stay still before it is remembered
+const SETTLE_SECONDS: f64 = 0.5;
+const MAX_VIEWS: usize = 100;
+
+/// What the next observed view is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    /// A requested view, remembered right away
+    Jump,
+    /// A view from the history, replaces the current one
+    Revisit,
+}
+
+/// Views of the plot with back and forward navigation like a web browser
+#[derive(Debug, Default)]
+pub struct ViewHistory {
+    back: Vec<Extent>,
+    forward: Vec<Extent>,
+    current: Option<Extent>,
+    /// A changed view and the time it was first seen
+    changed: Option<(Extent, f64)>,
+    /// View that the plot switches to on the next frame
+    requested: Option<Extent>,
+    expect: Option<Expect>,
+}
+
+impl ViewHistory {
+    /// Shows the extent with a margin around it, a single point keeps the current scale
+    pub fn zoom_to(&mut self, extent: Extent) {
+        let span = self
+            .current
+            .map_or(POINT_SPAN, |view| view.width().min(view.height()));
+        self.set_view(extent.with_margin(ZOOM_MARGIN).with_point_span(span));
+    }
+
+    pub fn set_view(&mut self, view: Extent) {
+        self.requested = Some(view);
+        self.expect = Some(Expect::Jump);
+    }
+
+    pub fn take_requested(&mut self) -> Option<Extent> {
+        self.requested.take()
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    pub fn go_back(&mut self) {
+        if let Some(view) = self.back.pop() {
+            self.forward.extend(self.current);
+            self.revisit(view);
+        }
+    }
+
+    pub fn go_forward(&mut self) {
+        if let Some(view) = self.forward.pop() {
+            self.back.extend(self.current);
+            self.revisit(view);
+        }
+    }
+
+    fn revisit(&mut self, view: Extent) {
+        self.current = Some(view);
+        self.requested = Some(view);
+        self.expect = Some(Expect::Revisit);
+    }
+
+    /// Records the view drawn at `time` seconds, returns `true` while a change is settling
+    pub fn observe(&mut self, view: Extent, time: f64) -> bool {
+        match self.expect.take() {
+            Some(Expect::Jump) => {
+                self.remember(view);
+                return false;
+            }
+            Some(Expect::Revisit) => {
+                self.current = Some(view);
+                self.changed = None;
+                return false;
+            }
+            None => {}
+        }
+
+        let Some(current) = self.current else {
+            self.current = Some(view);
+            return false;
+        };
+        if same_view(&current, &view) {
+            self.changed = None;
+            return false;
+        }
+
+        match self.changed {
+            Some((changed, since)) if same_view(&changed, &view) => {
+                if time - since < SETTLE_SECONDS {
+                    return true;
+                }
+                self.remember(view);
+                false
+            }
+            _ => {
+                self.changed = Some((view, time));
+                true
+            }
+        }
+    }
+
+    fn remember(&mut self, view: Extent) {
+        if let Some(current) = self.current.filter(|c| !same_view(c, &view)) {
+            self.back.push(current);
+            if self.back.len() > MAX_VIEWS {
+                self.back.remove(0);
+            }
+            self.forward.clear();
+        }
+        self.current = Some(view);
+        self.changed = None;
+    }
+}
+
+/// Views are equal when they differ by less than a thousandth of their size
+fn same_view(a: &Extent, b: &Extent) -> bool {
+    let tolerance = a.width().max(a.height()).abs() * 1e-3;
+    (0..2).all(|n| {
+        (a.min[n] - b.min[n]).abs() <= tolerance && (a.max[n] - b.max[n]).abs() <= tolerance
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        render::Extent,
+        view::{ViewHistory, SETTLE_SECONDS},
+    };
+
+    fn view(x: f64) -> Extent {
+        Extent::new([x, 0.], [x + 10., 10.])
+    }
+
+    #[test]
+    fn test_panned_views_are_remembered_once_settled() {
+        let mut history = ViewHistory::default();
+        assert!(!history.observe(view(0.), 0.));
+
+        // Dragging changes the view every frame
+        assert!(history.observe(view(1.), 1.));
+        assert!(history.observe(view(2.), 1.1));
+        assert!(history.observe(view(2.), 1.2));
+        assert!(!history.can_go_back());
+        assert!(!history.observe(view(2.), 1.1 + SETTLE_SECONDS));
+        assert!(history.can_go_back());
+
+        history.go_back();
+        assert_eq!(history.take_requested(), Some(view(0.)));
+        history.observe(view(0.), 3.);
+        assert!(!history.can_go_back());
+        assert!(history.can_go_forward());
+
+        history.go_forward();
+        assert_eq!(history.take_requested(), Some(view(2.)));
+        history.observe(view(2.), 4.);
+        assert!(history.can_go_back());
+        assert!(!history.can_go_forward());
+    }
+
+    #[test]
+    fn test_zoom_to_clears_forward_views() {
+        let mut history = ViewHistory::default();
+        history.observe(view(0.), 0.);
+        history.zoom_to(view(50.));
+        let requested = history.take_requested().unwrap();
+        assert!(requested.width() > 10.);
+
+        // The plot keeps the aspect ratio, so the drawn view differs from the requested one
+        history.observe(view(49.), 0.1);
+        history.go_back();
+        history.observe(view(0.), 0.2);
+        assert!(history.can_go_forward());
+
+        history.zoom_to(view(100.));
+        history.observe(view(100.), 0.3);
+        assert!(!history.can_go_forward());
+        history.go_back();
+        assert_eq!(history.take_requested(), Some(view(0.)));
+    }
+
+    #[test]
+    fn test_zoom_to_point_and_line_keeps_a_usable_view() {
+        let mut history = ViewHistory::default();
+        history.observe(view(0.), 0.);
+
+        history.zoom_to(Extent::new([5., 5.], [5., 5.]));
+        let requested = history.take_requested().unwrap();
+        assert_eq!(requested, Extent::new([0., 0.], [10., 10.]));
+
+        history.zoom_to(Extent::new([0., 5.], [100., 5.]));
+        let requested = history.take_requested().unwrap();
+        assert!((requested.height() - 10.).abs() < 1e-9);
+        assert!((requested.width() - 110.).abs() < 1e-9);
+    }
+}
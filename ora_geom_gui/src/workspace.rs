@@ -0,0 +1,555 @@
+use std::{fs, io, path::Path};
+
+use egui::{Button, Color32, Context, Layout, RichText, Stroke, Window};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::GeometryApi,
+    feature::Feature,
+    geometry_viewer::GeometryViewerConfig,
+    label::LabelStyle,
+    layers::{Layer, LayerGroup, Layers, Solo},
+    query::{Bind, Query},
+    render::Extent,
+    style::LayerStyle,
+    theme::Theme,
+    undo::UndoStack,
+    view::ViewHistory,
+};
+
+const WORKSPACE_VERSION: u32 = 3;
+const MAX_RECENT_WORKSPACES: usize = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WorkspaceError {
+    #[error("Failed to access the workspace file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Workspace file is not valid: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Workspace version {0} is not supported")]
+    UnsupportedVersion(u32),
+}
+
+/// Everything needed to bring the viewer back to the same state
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Workspace {
+    pub version: u32,
+    /// Layers in the order of the side panel, the first one is drawn on top
+    pub layers: Vec<WorkspaceLayer>,
+    #[serde(default)]
+    pub groups: Vec<LayerGroup>,
+    #[serde(default)]
+    pub solo: Option<Solo>,
+    /// SRID of the view, the layers are stored in their own SRIDs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_srid: Option<u32>,
+    pub view: Option<Extent>,
+}
+
+fn is_visible_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WorkspaceLayer {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub group: String,
+    #[serde(default = "is_visible_default")]
+    pub is_visible: bool,
+    pub sql: String,
+    #[serde(default)]
+    pub binds: Vec<Bind>,
+    #[serde(default)]
+    pub style: LayerStyle,
+    /// Only version 1 workspaces have a stroke instead of a style
+    #[serde(default, skip_serializing)]
+    pub stroke: Option<Stroke>,
+    /// Visibility of geometries by their row number
+    #[serde(default)]
+    pub visibility: Vec<bool>,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub label: LabelStyle,
+    /// Filter expression, geometries that do not match it are hidden
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub filter: String,
+    /// Geometries and attributes from the last query, allows to open the workspace without the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometries: Option<Vec<Feature>>,
+    /// Created from other layers, restored from `geometries` instead of the query
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_derived: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestoreMode {
+    /// Query every layer again and fall back to cached geometries on failure
+    Requery,
+    /// Use cached geometries and query only the layers without them
+    Cached,
+}
+
+impl Workspace {
+    pub fn from_layers(layers: &Layers, view: Option<Extent>, include_geometries: bool) -> Self {
+        Self {
+            version: WORKSPACE_VERSION,
+            layers: layers
+                .layers
+                .iter()
+                .map(|layer| {
+                    let query = &layer.query;
+                    WorkspaceLayer {
+                        name: layer.name.clone(),
+                        group: layer.group.clone(),
+                        is_visible: layer.is_visible,
+                        sql: query.sql.clone(),
+                        binds: query.binds.clone(),
+                        style: query.style,
+                        stroke: None,
+                        visibility: query.geometries.iter().map(|g| g.is_active).collect(),
+                        theme: query.theme.clone(),
+                        label: query.label.clone(),
+                        filter: query.filter.clone(),
+                        is_derived: query.is_derived,
+                        geometries: (include_geometries || query.is_derived).then(|| {
+                            query
+                                .geometries
+                                .iter()
+                                .map(|g| Feature {
+                                    geometry: g.sdo_geometry.clone(),
+                                    attributes: g.attributes.clone(),
+                                })
+                                .collect()
+                        }),
+                    }
+                })
+                .collect(),
+            groups: layers.groups.clone(),
+            solo: layers.solo.clone(),
+            display_srid: layers.display_srid,
+            view,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WorkspaceError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WorkspaceError> {
+        let workspace: Workspace = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if workspace.version > WORKSPACE_VERSION {
+            return Err(WorkspaceError::UnsupportedVersion(workspace.version));
+        }
+
+        Ok(workspace)
+    }
+
+    /// Creates layers in the saved order, failed layers are reported as messages
+    pub fn restore(self, api: &GeometryApi, mode: RestoreMode) -> (Layers, Vec<String>) {
+        let mut layers = Layers {
+            layers: vec![],
+            groups: self.groups,
+            solo: self.solo,
+            display_srid: self.display_srid,
+        };
+        let mut errors = vec![];
+
+        for layer in self.layers {
+            let mut stale_since = None;
+            let data = match (mode, layer.geometries) {
+                (RestoreMode::Cached, Some(geometries)) => geometries,
+                (_, Some(geometries)) if layer.is_derived => geometries,
+                (_, cached) => match api.fetch(&layer.sql, &layer.binds) {
+                    Ok(fetched) => {
+                        stale_since = fetched.stale_since;
+                        fetched.features
+                    }
+                    Err(e) => match cached {
+                        Some(geometries) => {
+                            errors.push(format!("{}: using cached geometries, {e}", layer.name));
+                            geometries
+                        }
+                        None => {
+                            errors.push(format!("{}: {e}", layer.name));
+                            continue;
+                        }
+                    },
+                },
+            };
+
+            let style = layer.stroke.map(LayerStyle::from).unwrap_or(layer.style);
+            let mut query = Query::new(&layer.name, layer.sql, layer.binds, style, data);
+            for (n, is_active) in layer.visibility.into_iter().enumerate() {
+                query.set_active(n, is_active);
+            }
+            query.theme = layer.theme;
+            query.label = layer.label;
+            query.filter = layer.filter;
+            query.is_derived = layer.is_derived;
+            query.stale_since = stale_since;
+            query.apply_theme();
+            query.apply_filter();
+            layers.layers.push(Layer {
+                name: layer.name,
+                group: layer.group,
+                is_visible: layer.is_visible,
+                query,
+            });
+        }
+
+        // Groups may be missing from hand written files
+        for layer in &layers.layers {
+            if layers.group(&layer.group).is_none() && !layer.group.is_empty() {
+                layers.groups.push(LayerGroup {
+                    name: layer.group.clone(),
+                    is_visible: true,
+                });
+            }
+        }
+
+        (layers, errors)
+    }
+}
+
+pub struct WorkspaceSettings {
+    pub path: String,
+    pub include_geometries: bool,
+    pub restore_mode: RestoreMode,
+    pub message: RichText,
+}
+
+impl Default for WorkspaceSettings {
+    fn default() -> Self {
+        Self {
+            path: "workspace.json".into(),
+            include_geometries: true,
+            restore_mode: RestoreMode::Requery,
+            message: RichText::new(""),
+        }
+    }
+}
+
+pub struct WorkspaceWindow<'a> {
+    pub settings: &'a mut WorkspaceSettings,
+    pub layers: &'a mut Layers,
+    pub config: &'a mut GeometryViewerConfig,
+    pub plot_bounds: Option<Extent>,
+    pub view: &'a mut ViewHistory,
+    pub undo: &'a mut UndoStack,
+}
+
+impl<'a> WorkspaceWindow<'a> {
+    pub fn new(
+        settings: &'a mut WorkspaceSettings,
+        layers: &'a mut Layers,
+        config: &'a mut GeometryViewerConfig,
+        plot_bounds: Option<Extent>,
+        view: &'a mut ViewHistory,
+        undo: &'a mut UndoStack,
+    ) -> Self {
+        Self {
+            settings,
+            layers,
+            config,
+            plot_bounds,
+            view,
+            undo,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, is_active: &'a mut bool) {
+        Window::new("Workspace")
+            .open(is_active)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File path:");
+                    ui.text_edit_singleline(&mut self.settings.path);
+                });
+
+                ui.checkbox(
+                    &mut self.settings.include_geometries,
+                    "Store geometries for offline viewing",
+                );
+
+                ui.horizontal(|ui| {
+                    ui.label("On open:");
+                    ui.radio_value(
+                        &mut self.settings.restore_mode,
+                        RestoreMode::Requery,
+                        "Query layers again",
+                    );
+                    ui.radio_value(
+                        &mut self.settings.restore_mode,
+                        RestoreMode::Cached,
+                        "Use stored geometries",
+                    );
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Message:");
+                    ui.label(self.settings.message.clone());
+                });
+
+                ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                    if ui.add(Button::new("Open")).clicked() {
+                        let path = self.settings.path.clone();
+                        self.open(&path);
+                    }
+
+                    if ui.add(Button::new("Save")).clicked() {
+                        self.save();
+                    }
+                });
+            });
+    }
+
+    pub fn save(&mut self) {
+        let workspace = Workspace::from_layers(
+            self.layers,
+            self.plot_bounds,
+            self.settings.include_geometries,
+        );
+
+        self.settings.message = match workspace.save(&self.settings.path) {
+            Ok(()) => {
+                self.remember(&self.settings.path.clone());
+                RichText::new(format!("Saved to {}", self.settings.path)).color(Color32::GREEN)
+            }
+            Err(e) => RichText::new(format!("Failed to save: {e}")).color(Color32::RED),
+        };
+    }
+
+    pub fn open(&mut self, path: &str) {
+        self.settings.path = path.to_string();
+
+        let workspace = match Workspace::open(path) {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                self.settings.message =
+                    RichText::new(format!("Failed to open: {e}")).color(Color32::RED);
+                return;
+            }
+        };
+
+        if let Some(view) = workspace.view {
+            self.view.set_view(view);
+        }
+        let (layers, errors) = workspace.restore(&self.config.api, self.settings.restore_mode);
+        *self.layers = layers;
+        // Recorded changes refer to the layers that were replaced
+        self.undo.clear();
+        self.remember(path);
+
+        self.settings.message = if errors.is_empty() {
+            RichText::new(format!("Opened {path}")).color(Color32::GREEN)
+        } else {
+            RichText::new(errors.join("\n")).color(Color32::LIGHT_YELLOW)
+        };
+    }
+
+    /// Moves the path to the top of the recent workspaces
+    fn remember(&mut self, path: &str) {
+        let recent = &mut self.config.recent_workspaces;
+        recent.retain(|p| p != path);
+        recent.insert(0, path.to_string());
+        recent.truncate(MAX_RECENT_WORKSPACES);
+        self.config.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Stroke};
+    use serde_json::json;
+
+    use crate::{
+        api::{unreachable_url, GeometryApi},
+        cache::CacheSettings,
+        feature::Feature,
+        layers::{Layers, Solo},
+        query::{Bind, Query},
+        render::Extent,
+        sdo_geometry::SdoGeometry,
+        style::{Dash, LayerStyle},
+        theme::{Categorized, Theme},
+        workspace::{RestoreMode, Workspace},
+    };
+
+    fn layers(queries: Vec<(&str, Query)>) -> Layers {
+        let mut layers = Layers::default();
+        for (name, query) in queries.into_iter().rev() {
+            layers.push(name.to_string(), query);
+        }
+        layers
+    }
+
+    fn unreachable_api() -> GeometryApi {
+        GeometryApi {
+            api_url: unreachable_url(),
+            cache: CacheSettings {
+                is_enabled: false,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn line(class: &str) -> Feature {
+        Feature {
+            geometry: SdoGeometry {
+                sdo_gtype: 2002.,
+                sdo_srid: None,
+                sdo_point: None,
+                sdo_elem_info: vec![1., 2., 1.],
+                sdo_ordinates: vec![0., 0., 10., 10.],
+            },
+            attributes: serde_json::from_value(json!({ "CLASS": class })).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_restore_from_cached_geometries() {
+        let mut query = Query::new(
+            "roads",
+            "select geom from roads where class = :class".into(),
+            vec![Bind {
+                name: "class".into(),
+                value: "A".into(),
+            }],
+            LayerStyle {
+                fill_opacity: 0.4,
+                dash: Dash::Dashed,
+                ..LayerStyle::from(Stroke::new(2., Color32::BLUE))
+            },
+            vec![line("A"), line("B")],
+        );
+        query.geometries[1].is_active = false;
+        query.theme = Theme::Categorized(Categorized::classify("CLASS", &query.geometries));
+        query.apply_theme();
+        query.filter = "class = 'A'".into();
+        query.apply_filter();
+
+        let empty = Query::new(
+            "empty",
+            "select geom from empty".into(),
+            vec![],
+            LayerStyle::default(),
+            vec![],
+        );
+        let mut layers = layers(vec![("empty", empty), ("roads", query)]);
+        layers.add_group("streets");
+        layers.set_group(1, "streets");
+        layers.get_mut("empty").unwrap().is_visible = false;
+        layers.solo = Some(Solo::Group("streets".into()));
+
+        let view = Some(Extent::new([0., 0.], [5., 5.]));
+        let workspace = Workspace::from_layers(&layers, view, true);
+        let json = serde_json::to_string(&workspace).unwrap();
+        let workspace: Workspace = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(workspace.view, view);
+
+        let api = unreachable_api();
+        let (restored, errors) = workspace.restore(&api, RestoreMode::Cached);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            restored
+                .layers
+                .iter()
+                .map(|l| l.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["empty", "roads"]
+        );
+        assert!(!restored.layers[0].is_visible);
+        assert_eq!(restored.layers[1].group, "streets");
+        assert_eq!(restored.groups, layers.groups);
+        assert_eq!(restored.solo, layers.solo);
+
+        let query = &layers.layers[1].query;
+        let restored = &restored.layers[1].query;
+        assert_eq!(restored.sql, query.sql);
+        assert_eq!(restored.binds, query.binds);
+        assert_eq!(restored.style, query.style);
+        assert_eq!(
+            restored
+                .geometries
+                .iter()
+                .map(|g| g.is_active)
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert_eq!(restored.theme, query.theme);
+        assert_eq!(restored.filter, query.filter);
+        assert!(restored.matches_filter(0) && !restored.matches_filter(1));
+        assert_eq!(restored.geometry_style(1), query.geometry_style(1));
+        assert_ne!(restored.geometry_style(1), query.style);
+    }
+
+    #[test]
+    fn test_restore_without_cache_reports_errors() {
+        let query = Query::new(
+            "roads",
+            "select geom from roads".into(),
+            vec![],
+            LayerStyle::from(Stroke::new(1., Color32::RED)),
+            vec![line("A")],
+        );
+        let workspace = Workspace::from_layers(&layers(vec![("roads", query)]), None, false);
+
+        let api = unreachable_api();
+        let (layers, errors) = workspace.restore(&api, RestoreMode::Cached);
+
+        assert!(layers.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("roads: "));
+    }
+
+    #[test]
+    fn test_derived_layer_keeps_its_geometries() {
+        let mut query = Query::new(
+            "roads_vs_streets",
+            "select 'Added' as status from dual".into(),
+            vec![],
+            LayerStyle::default(),
+            vec![line("A")],
+        );
+        query.is_derived = true;
+        let workspace = Workspace::from_layers(&layers(vec![("diff", query)]), None, false);
+
+        let api = unreachable_api();
+        let (layers, errors) = workspace.restore(&api, RestoreMode::Requery);
+
+        assert!(errors.is_empty());
+        let query = &layers.layers[0].query;
+        assert!(query.is_derived);
+        assert_eq!(query.geometries[0].attributes["CLASS"], "A");
+    }
+
+    #[test]
+    fn test_open_version_1_stroke() {
+        let json = r#"{
+            "version": 1,
+            "layers": [{
+                "name": "roads",
+                "sql": "select geom from roads",
+                "stroke": {"width": 3.0, "color": [0, 0, 255, 255]},
+                "geometries": []
+            }],
+            "view": null
+        }"#;
+        let workspace: Workspace = serde_json::from_str(json).unwrap();
+
+        let api = unreachable_api();
+        let (layers, errors) = workspace.restore(&api, RestoreMode::Cached);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            layers.layers[0].query.style.stroke,
+            Stroke::new(3., Color32::from_rgb(0, 0, 255))
+        );
+    }
+}